@@ -3,9 +3,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build_server(true)
         .build_client(true)
         .file_descriptor_set_path("proto/class_descriptor.bin")
-        .compile_protos(&["proto/class.proto"], &["proto"])?;
+        .compile_protos(
+            &["proto/class.proto", "proto_ext/diagram_ext.proto"],
+            &["proto", "proto_ext"],
+        )?;
 
     println!("cargo:rerun-if-changed=proto/class.proto");
+    println!("cargo:rerun-if-changed=proto_ext/diagram_ext.proto");
 
     Ok(())
 }
@@ -0,0 +1,421 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use subtle::ConstantTimeEq;
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+use crate::config::{AuthMode, ServerConfig};
+use crate::store::{AccountStore, ApiKeyRecord, StoreError, StoreResult, UserRecord};
+
+// APIキーの形式: edea_<キーID>_<秘密部分>
+const API_KEY_PREFIX: &str = "edea_";
+
+// パスワードの最小の長さ
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+// 署名付きトークンのヘッダ（JWT、HMAC-SHA256）
+const TOKEN_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+// 認証・利用者の管理のエラー
+#[derive(Debug)]
+pub enum AuthError {
+    // 資格情報が無い・正しくない
+    Unauthenticated(String),
+    // 認証されているが権限が無い
+    Forbidden(String),
+    Invalid(String),
+    AlreadyExists(String),
+    NotFound(String),
+    Hash(String),
+    Store(StoreError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Unauthenticated(message)
+            | AuthError::Forbidden(message)
+            | AuthError::Invalid(message)
+            | AuthError::AlreadyExists(message)
+            | AuthError::NotFound(message) => write!(f, "{}", message),
+            AuthError::Hash(message) => write!(f, "Failed to hash password: {}", message),
+            AuthError::Store(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<StoreError> for AuthError {
+    fn from(e: StoreError) -> Self {
+        AuthError::Store(e)
+    }
+}
+
+impl From<AuthError> for Status {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::Unauthenticated(message) => Status::unauthenticated(message),
+            AuthError::Forbidden(message) => Status::permission_denied(message),
+            AuthError::Invalid(message) => Status::invalid_argument(message),
+            AuthError::AlreadyExists(message) => Status::already_exists(message),
+            AuthError::NotFound(message) => Status::not_found(message),
+            AuthError::Hash(_) => Status::internal(e.to_string()),
+            AuthError::Store(e) => e.into(),
+        }
+    }
+}
+
+// 認証された利用者（インターセプタがリクエストのextensionsに入れる）
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user_name: String,
+    pub admin: bool,
+    pub created_at: i64,
+}
+
+impl From<&UserRecord> for Principal {
+    fn from(user: &UserRecord) -> Self {
+        Self {
+            user_name: user.user_name.clone(),
+            admin: user.admin,
+            created_at: user.created_at,
+        }
+    }
+}
+
+// トークンの内容
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    // 利用者名
+    sub: String,
+    // 発行・期限（UNIX秒）
+    iat: i64,
+    exp: i64,
+}
+
+// パスワード・APIキー・トークンによる認証
+#[derive(Debug)]
+pub struct Authenticator {
+    store: Arc<dyn AccountStore>,
+    // トークンの署名鍵
+    key: Vec<u8>,
+    mode: AuthMode,
+    // トークンの有効期間（秒）
+    token_lifetime: i64,
+}
+
+impl Authenticator {
+    // 署名鍵を読み込み（無い場合は作成して保存）、設定された管理者が存在しない場合は作成する
+    pub fn open(store: Arc<dyn AccountStore>, config: &ServerConfig) -> Result<Self, AuthError> {
+        let key = match &config.token_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => signing_key(store.as_ref())?,
+        };
+        let auth = Self {
+            store,
+            key,
+            mode: config.auth_mode,
+            token_lifetime: config.token_lifetime,
+        };
+        // 最初のログインだけ時間がかからないように、照合用のハッシュを先に作っておく
+        dummy_password_hash();
+
+        if let Some(password) = &config.admin_password {
+            if auth.store.get_user(&config.admin_user)?.is_none() {
+                auth.insert_user(&config.admin_user, password, true)?;
+                println!("Created administrator {}", config.admin_user);
+            }
+        }
+        // 利用者がいないと誰も呼び出せないため起動しない
+        if auth.mode == AuthMode::Required && auth.store.user_count()? == 0 {
+            return Err(AuthError::Invalid(
+                "No users exist; set EDEA_ADMIN_PASSWORD to create an administrator or EDEA_AUTH=off to disable authentication"
+                    .to_string(),
+            ));
+        }
+        Ok(auth)
+    }
+
+    // パスワードを確認してトークンと期限を発行する
+    // 利用者が存在しない場合も照合を行い、応答時間から利用者名が分からないようにする
+    // Argon2の計算に時間がかかるため、非同期のランタイムからはspawn_blockingで呼ぶ
+    pub fn login(
+        &self,
+        user_name: &str,
+        password: &str,
+    ) -> Result<(String, i64, Principal), AuthError> {
+        let invalid = || AuthError::Unauthenticated("Invalid user name or password".to_string());
+        let user = self.store.get_user(user_name)?;
+        let password_hash = match &user {
+            Some(user) => user.password_hash.as_str(),
+            None => dummy_password_hash(),
+        };
+        let hash = PasswordHash::new(password_hash).map_err(|e| AuthError::Hash(e.to_string()))?;
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        let user = user.filter(|_| verified).ok_or_else(invalid)?;
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = Claims {
+            sub: user.user_name.clone(),
+            iat: now,
+            exp: now + self.token_lifetime,
+        };
+        Ok((self.sign(&claims), claims.exp, Principal::from(&user)))
+    }
+
+    // authorizationメタデータ（Bearer <トークンまたはAPIキー>）の利用者
+    // 資格情報が無い場合はNone、正しくない場合はErr
+    pub fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<Principal>, AuthError> {
+        let Some(value) = metadata.get("authorization") else {
+            return Ok(None);
+        };
+        let credential = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(|| {
+                AuthError::Unauthenticated("Authorization must be a Bearer credential".to_string())
+            })?;
+
+        let user = match credential.strip_prefix(API_KEY_PREFIX) {
+            Some(api_key) => self.verify_api_key(api_key)?,
+            None => self.verify_token(credential)?,
+        };
+        Ok(Some(Principal::from(&user)))
+    }
+
+    // 資格情報が必要な呼び出しの利用者
+    pub fn require(&self, metadata: &MetadataMap) -> Result<Principal, AuthError> {
+        self.authenticate(metadata)?
+            .ok_or_else(|| AuthError::Unauthenticated("Credentials are required".to_string()))
+    }
+
+    // 管理者が利用者を作成する（パスワードのハッシュ化はloginと同様に時間がかかる）
+    pub fn create_user(
+        &self,
+        principal: &Principal,
+        user_name: &str,
+        password: &str,
+        admin: bool,
+    ) -> Result<Principal, AuthError> {
+        if !principal.admin {
+            return Err(AuthError::Forbidden(
+                "Only administrators can create users".to_string(),
+            ));
+        }
+        let user = self.insert_user(user_name, password, admin)?;
+        println!("User {} created by {}", user_name, principal.user_name);
+        Ok(Principal::from(&user))
+    }
+
+    // APIキーを作成し、キーの情報とキーの値を返す（値は保存しない）
+    pub fn create_api_key(
+        &self,
+        principal: &Principal,
+        name: &str,
+    ) -> Result<(ApiKeyRecord, String), AuthError> {
+        let key_id: String = random_bytes(8)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let secret = URL_SAFE_NO_PAD.encode(random_bytes(32));
+        let record = ApiKeyRecord {
+            key_id: key_id.clone(),
+            user_name: principal.user_name.clone(),
+            name: name.to_string(),
+            secret_hash: secret_hash(&secret),
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.store.put_api_key(record.clone())?;
+        println!("API key {} created for {}", key_id, principal.user_name);
+        Ok((record, format!("{}{}_{}", API_KEY_PREFIX, key_id, secret)))
+    }
+
+    pub fn api_keys(&self, principal: &Principal) -> Result<Vec<ApiKeyRecord>, AuthError> {
+        Ok(self.store.api_keys(&principal.user_name)?)
+    }
+
+    // 利用者自身のAPIキーを削除する
+    pub fn revoke_api_key(&self, principal: &Principal, key_id: &str) -> Result<(), AuthError> {
+        if !self.store.delete_api_key(&principal.user_name, key_id)? {
+            return Err(AuthError::NotFound(format!(
+                "API key not found: {}",
+                key_id
+            )));
+        }
+        println!("API key {} revoked by {}", key_id, principal.user_name);
+        Ok(())
+    }
+
+    fn insert_user(
+        &self,
+        user_name: &str,
+        password: &str,
+        admin: bool,
+    ) -> Result<UserRecord, AuthError> {
+        if user_name.trim().is_empty() {
+            return Err(AuthError::Invalid("User name is required".to_string()));
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(AuthError::Invalid(format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            )));
+        }
+
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| AuthError::Hash(e.to_string()))?
+            .to_string();
+        let user = UserRecord {
+            user_name: user_name.to_string(),
+            password_hash,
+            admin,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        if !self.store.create_user(user.clone())? {
+            return Err(AuthError::AlreadyExists(format!(
+                "User already exists: {}",
+                user_name
+            )));
+        }
+        Ok(user)
+    }
+
+    // <キーID>_<秘密部分>
+    fn verify_api_key(&self, api_key: &str) -> Result<UserRecord, AuthError> {
+        let invalid = || AuthError::Unauthenticated("Invalid API key".to_string());
+        let (key_id, secret) = api_key.split_once('_').ok_or_else(invalid)?;
+        let record = self.store.get_api_key(key_id)?.ok_or_else(invalid)?;
+        // 比較にかかる時間から秘密部分が推測されないようにする
+        let matched: bool = record
+            .secret_hash
+            .as_bytes()
+            .ct_eq(secret_hash(secret).as_bytes())
+            .into();
+        if !matched {
+            return Err(invalid());
+        }
+        self.store
+            .get_user(&record.user_name)?
+            .ok_or_else(|| AuthError::Unauthenticated("User no longer exists".to_string()))
+    }
+
+    fn verify_token(&self, token: &str) -> Result<UserRecord, AuthError> {
+        let invalid = || AuthError::Unauthenticated("Invalid token".to_string());
+        let (signing_input, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        let mut mac = self.mac();
+        mac.update(signing_input.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+        let (header, payload) = signing_input.split_once('.').ok_or_else(invalid)?;
+        if URL_SAFE_NO_PAD.decode(header).ok().as_deref() != Some(TOKEN_HEADER.as_bytes()) {
+            return Err(invalid());
+        }
+        let claims: Claims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|payload| serde_json::from_slice(&payload).ok())
+            .ok_or_else(invalid)?;
+        if claims.exp <= chrono::Utc::now().timestamp() {
+            return Err(AuthError::Unauthenticated("Token has expired".to_string()));
+        }
+
+        self.store
+            .get_user(&claims.sub)?
+            .ok_or_else(|| AuthError::Unauthenticated("User no longer exists".to_string()))
+    }
+
+    // <ヘッダ>.<内容>.<署名>（それぞれBase64URL）
+    fn sign(&self, claims: &Claims) -> String {
+        let payload = serde_json::to_vec(claims).expect("claims are always serializable");
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(TOKEN_HEADER),
+            URL_SAFE_NO_PAD.encode(payload)
+        );
+        let mut mac = self.mac();
+        mac.update(signing_input.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}", signing_input, signature)
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+}
+
+// 全てのRPCの前に資格情報を確認し、利用者をextensionsに入れる
+// 資格情報が無い呼び出しは認証が必要な設定の場合だけ拒否する
+#[derive(Debug, Clone)]
+pub struct AuthInterceptor {
+    auth: Arc<Authenticator>,
+}
+
+impl AuthInterceptor {
+    pub fn new(auth: Arc<Authenticator>) -> Self {
+        Self { auth }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        match self.auth.authenticate(request.metadata())? {
+            Some(principal) => {
+                request.extensions_mut().insert(principal);
+            }
+            None if self.auth.mode == AuthMode::Required => {
+                return Err(Status::unauthenticated("Credentials are required"));
+            }
+            None => {}
+        }
+        Ok(request)
+    }
+}
+
+// ストアに保存した署名鍵（無い場合は作成して保存する）
+fn signing_key(store: &dyn AccountStore) -> StoreResult<Vec<u8>> {
+    if let Some(key) = store.signing_key()? {
+        if let Ok(key) = STANDARD.decode(key) {
+            return Ok(key);
+        }
+    }
+    let key = random_bytes(32);
+    store.set_signing_key(&STANDARD.encode(&key))?;
+    Ok(key)
+}
+
+// 存在しない利用者のログインで照合するハッシュ（パスワードは誰も知らない乱数）
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(&random_bytes(32), &salt)
+            .expect("hashing a random password cannot fail")
+            .to_string()
+    })
+}
+
+fn secret_hash(secret: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
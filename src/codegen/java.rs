@@ -0,0 +1,465 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::{
+    class_identifier, identifier, parameter_names, upper_camel, Cardinality, GeneratedSource,
+    Model, TypeTable, VisibilityTable,
+};
+use crate::model::{is_abstract_class, Visibility};
+use crate::server::class::{Class, File, Method, Variable};
+
+const INDENT: &str = "    ";
+
+const TYPES: TypeTable = TypeTable {
+    names: &[
+        // 大文字で書かれたラッパークラスはそのまま
+        ("Integer", "Integer"),
+        ("Long", "Long"),
+        ("Short", "Short"),
+        ("Byte", "Byte"),
+        ("Character", "Character"),
+        ("Float", "Float"),
+        ("Double", "Double"),
+        ("Boolean", "Boolean"),
+        ("int", "int"),
+        ("integer", "int"),
+        ("long", "long"),
+        ("short", "short"),
+        ("byte", "byte"),
+        ("char", "char"),
+        ("character", "char"),
+        ("float", "float"),
+        ("double", "double"),
+        ("number", "double"),
+        ("bool", "boolean"),
+        ("boolean", "boolean"),
+        ("string", "String"),
+        ("str", "String"),
+        ("any", "Object"),
+        ("object", "Object"),
+        ("void", "void"),
+        ("list", "List"),
+        ("array", "List"),
+        ("set", "Set"),
+        ("map", "Map"),
+        ("dict", "Map"),
+        ("date", "Date"),
+    ],
+    boxed: &[
+        ("int", "Integer"),
+        ("long", "Long"),
+        ("short", "Short"),
+        ("byte", "Byte"),
+        ("char", "Character"),
+        ("float", "Float"),
+        ("double", "Double"),
+        ("boolean", "Boolean"),
+    ],
+    array: "{}[]",
+    unknown: "Object",
+};
+
+const VISIBILITY: VisibilityTable = VisibilityTable {
+    public: "public ",
+    private: "private ",
+    protected: "protected ",
+    package: "",
+};
+
+// java.utilのうち、型に現れたらimportするクラス
+const JAVA_UTIL: [&str; 10] = [
+    "ArrayList",
+    "Collection",
+    "Date",
+    "HashMap",
+    "HashSet",
+    "List",
+    "Map",
+    "Optional",
+    "Set",
+    "UUID",
+];
+
+// 識別子に使えない予約語
+const KEYWORDS: [&str; 53] = [
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+// ファイルのクラスごとにJavaのソースファイル（Name.java）を生成
+// 実現の関係先になっているクラスはinterface、抽象メソッドを持つクラスはabstract class とする
+// 継承はextends、実現はimplements、関連・集約・コンポジションは関係先のフィールドにする
+// （関係先の多重度の上限が2以上のものはList）
+pub fn generate(file: &File) -> Vec<GeneratedSource> {
+    let model = Model::new(file, class_identifier);
+    model
+        .classes()
+        .map(|class| {
+            let writer = ClassWriter {
+                model: &model,
+                class,
+                is_interface: model.is_interface(class),
+                imports: BTreeSet::new(),
+            };
+            GeneratedSource {
+                path: format!("{}.java", model.base_name(class)),
+                content: writer.write(),
+            }
+        })
+        .collect()
+}
+
+// 1クラス分のソースを組み立てる
+struct ClassWriter<'a> {
+    model: &'a Model<'a>,
+    class: &'a Class,
+    is_interface: bool,
+    imports: BTreeSet<&'static str>,
+}
+
+impl ClassWriter<'_> {
+    fn write(mut self) -> String {
+        let model = self.model;
+        let class = self.class;
+        let mut body = String::new();
+        let mut comments = Vec::new();
+
+        // 親クラス・インターフェース
+        let parents = model.parents(class);
+        for id in &parents.unresolved {
+            comments.push(format!("unresolved relation target: {}", id));
+        }
+        for parent in &parents.ignored {
+            comments.push(format!(
+                "multiple inheritance is not supported: {}",
+                model.name(parent)
+            ));
+        }
+        let interfaces: Vec<&str> = parents
+            .interfaces
+            .iter()
+            .map(|parent| model.name(parent))
+            .collect();
+        let (extends, implements) = if self.is_interface {
+            (interfaces, Vec::new())
+        } else {
+            let superclass = parents.superclass.map(|parent| model.name(parent));
+            (superclass.into_iter().collect(), interfaces)
+        };
+
+        // 属性（インターフェースはインスタンスのフィールドを持てないため、getterにする）
+        let mut methods = Vec::new();
+        for attribute in &class.attributes {
+            if self.is_interface {
+                methods.push(getter(attribute));
+            } else {
+                let line = self.field(attribute);
+                let _ = writeln!(body, "{}{}", INDENT, line);
+            }
+        }
+
+        // 関連・集約・コンポジションのフィールド
+        if !self.is_interface {
+            for field in model.relation_fields(class) {
+                let target = model.base_name(field.target);
+                let name = name(&field.name);
+                let line = if field.cardinality == Cardinality::Many {
+                    self.imports.insert("List");
+                    self.imports.insert("ArrayList");
+                    format!("private List<{}> {} = new ArrayList<>();", target, name)
+                } else {
+                    format!("private {} {};", target, name)
+                };
+                let _ = writeln!(body, "{}{}", INDENT, line);
+            }
+        }
+
+        methods.extend(class.methods.iter().cloned());
+        for method in &methods {
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            body.push_str(&self.method(method, parents.superclass));
+        }
+
+        // 親クラスに引数の無いコンストラクタが無い場合は、同じ引数で親を呼び出すコンストラクタを作る
+        if let Some(parent) = parents.superclass {
+            if model.constructors(class).is_empty() && model.requires_arguments(parent) {
+                for constructor in model.constructors(parent) {
+                    if !body.is_empty() {
+                        body.push('\n');
+                    }
+                    body.push_str(&self.super_constructor(constructor));
+                }
+            }
+        }
+
+        // 具象クラスでは継承した抽象メソッド・インターフェースの属性のgetterのスタブも生成する
+        if !self.is_interface && !is_abstract_class(class) {
+            let inherited = model.inherited_members(class);
+            let getters = inherited.attributes.into_iter().map(getter);
+            for method in inherited.methods.into_iter().chain(getters) {
+                let method = Method {
+                    is_abstract: Some(false),
+                    ..method
+                };
+                if !body.is_empty() {
+                    body.push('\n');
+                }
+                let _ = writeln!(body, "{}@Override", INDENT);
+                body.push_str(&self.method(&method, None));
+            }
+        }
+
+        // ソース全体
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "// Generated by EDEA from \"{}\"",
+            model.file.name.replace(['\r', '\n'], " ")
+        );
+        out.push('\n');
+        if !self.imports.is_empty() {
+            for import in &self.imports {
+                let _ = writeln!(out, "import java.util.{};", import);
+            }
+            out.push('\n');
+        }
+        for comment in comments {
+            let _ = writeln!(out, "// {}", comment.replace(['\r', '\n'], " "));
+        }
+
+        let keyword = if self.is_interface {
+            "interface"
+        } else if is_abstract_class(class) {
+            "abstract class"
+        } else {
+            "class"
+        };
+        let _ = write!(out, "public {} {}", keyword, model.name(class));
+        if !extends.is_empty() {
+            let _ = write!(out, " extends {}", extends.join(", "));
+        }
+        if !implements.is_empty() {
+            let _ = write!(out, " implements {}", implements.join(", "));
+        }
+        out.push_str(" {\n");
+        out.push_str(&body);
+        out.push_str("}\n");
+        out
+    }
+
+    // private String name;
+    fn field(&mut self, attribute: &Variable) -> String {
+        // 可視性が指定されていないフィールドはprivate
+        let visibility = attribute
+            .visibility
+            .map_or(Visibility::Private, Visibility::from_i32);
+        format!(
+            "{}{}{} {};",
+            VISIBILITY.modifier(visibility),
+            if attribute.is_static.unwrap_or(false) {
+                "static "
+            } else {
+                ""
+            },
+            self.type_name(&attribute.r#type, "Object"),
+            name(&attribute.name)
+        )
+    }
+
+    // メソッドのスタブ（抽象メソッド以外は未実装の例外を投げる）
+    fn method(&mut self, method: &Method, superclass: Option<&Class>) -> String {
+        let is_static = method.is_static.unwrap_or(false);
+        let is_abstract = method.is_abstract.unwrap_or(false) && !is_static;
+        let is_constructor = self.model.is_constructor(self.class, method);
+        let parameters = self.parameters(method);
+
+        let mut signature = String::new();
+        if !self.is_interface {
+            signature.push_str(VISIBILITY.modifier(Visibility::from_i32(method.visibility)));
+        }
+        if is_static {
+            signature.push_str("static ");
+        }
+        if is_constructor {
+            let _ = write!(
+                signature,
+                "{}({})",
+                self.model.base_name(self.class),
+                parameters
+            );
+        } else {
+            if is_abstract && !self.is_interface {
+                signature.push_str("abstract ");
+            }
+            let return_type = self.type_name(&method.return_type, "void");
+            let _ = write!(
+                signature,
+                "{} {}({})",
+                return_type,
+                name(&method.name),
+                parameters
+            );
+        }
+
+        if is_abstract || (self.is_interface && !is_static) {
+            format!("{}{};\n", INDENT, signature)
+        } else if is_constructor {
+            // 親クラスの引数が必要なコンストラクタを呼び出す
+            let super_call = superclass
+                .and_then(|parent| self.model.super_arguments(parent, method))
+                .filter(|arguments| !arguments.is_empty())
+                .map(|arguments| {
+                    let arguments: Vec<String> =
+                        arguments.iter().map(|argument| name(argument)).collect();
+                    format!(
+                        "{indent}{indent}super({});\n",
+                        arguments.join(", "),
+                        indent = INDENT
+                    )
+                })
+                .unwrap_or_default();
+            format!("{}{} {{\n{}{}}}\n", INDENT, signature, super_call, INDENT)
+        } else {
+            format!(
+                "{indent}{} {{\n{indent}{indent}throw new UnsupportedOperationException(\"Not implemented\");\n{indent}}}\n",
+                signature,
+                indent = INDENT
+            )
+        }
+    }
+
+    // 親クラスのコンストラクタを呼び出すだけのコンストラクタ
+    fn super_constructor(&mut self, constructor: &Method) -> String {
+        let parameters = self.parameters(constructor);
+        let arguments = parameter_names(constructor)
+            .iter()
+            .map(|parameter| name(parameter))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{indent}{}{}({}) {{\n{indent}{indent}super({});\n{indent}}}\n",
+            VISIBILITY.modifier(Visibility::from_i32(constructor.visibility)),
+            self.model.base_name(self.class),
+            parameters,
+            arguments,
+            indent = INDENT
+        )
+    }
+
+    // 引数の並び（int count, String name）
+    fn parameters(&mut self, method: &Method) -> String {
+        method
+            .parameters
+            .iter()
+            .zip(parameter_names(method))
+            .map(|(parameter, parameter_name)| {
+                format!(
+                    "{} {}",
+                    self.type_name(&parameter.r#type, "Object"),
+                    name(&parameter_name)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // 図の型名をJavaの型名に変換し、必要なimportを記録する
+    fn type_name(&mut self, text: &str, default: &str) -> String {
+        if text.trim().is_empty() {
+            return default.to_string();
+        }
+        let model = self.model;
+        let mapped = TYPES.map(text, &mut |name| match model.class_named(name) {
+            Some(class) => model.base_name(class).to_string(),
+            None => name.to_string(),
+        });
+        for word in mapped.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            if let Some(name) = JAVA_UTIL.iter().find(|name| **name == word) {
+                self.imports.insert(name);
+            }
+        }
+        mapped
+    }
+}
+
+// 属性のgetter（getName、booleanの場合はisName）
+fn getter(attribute: &Variable) -> Method {
+    let type_name = attribute.r#type.trim();
+    let boolean = TYPES.map(type_name, &mut |name| name.to_string()) == "boolean";
+    let prefix = if boolean { "is" } else { "get" };
+
+    Method {
+        name: format!("{}{}", prefix, upper_camel(&identifier(&attribute.name))),
+        return_type: if type_name.is_empty() {
+            "Object".to_string()
+        } else {
+            type_name.to_string()
+        },
+        visibility: Visibility::Public.to_i32(),
+        is_abstract: Some(true),
+        ..Default::default()
+    }
+}
+
+// 予約語と同じ名前は末尾に _ を付ける
+fn name(text: &str) -> String {
+    let name = identifier(text);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
@@ -0,0 +1,572 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Cursor, Write};
+
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::model::{display_name, RelationKind, Visibility};
+use crate::server::class::{Class, File, Method, RelationInfo, Variable};
+use crate::server::diagram_ext::CodeLanguage;
+
+pub mod java;
+pub mod rust;
+pub mod typescript;
+
+// 生成したソースファイル（pathはアーカイブ内の相対パス）
+#[derive(Debug, Clone)]
+pub struct GeneratedSource {
+    pub path: String,
+    pub content: String,
+}
+
+// 保存されたファイルから指定した言語のソースファイルを生成
+pub fn generate(file: &File, language: CodeLanguage) -> Vec<GeneratedSource> {
+    match language {
+        CodeLanguage::Java => java::generate(file),
+        CodeLanguage::Typescript => typescript::generate(file),
+        CodeLanguage::Rust => rust::generate(file),
+    }
+}
+
+// まとめてダウンロードする場合のアーカイブ名
+pub fn archive_name(file: &File, language: CodeLanguage) -> String {
+    let suffix = match language {
+        CodeLanguage::Java => "java",
+        CodeLanguage::Typescript => "typescript",
+        CodeLanguage::Rust => "rust",
+    };
+    format!("{}-{}.zip", crate::export::file_stem(file), suffix)
+}
+
+// 生成したソースファイルをzipにまとめる
+pub fn to_zip(sources: &[GeneratedSource]) -> Result<Vec<u8>, String> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    for source in sources {
+        writer
+            .start_file(source.path.as_str(), options)
+            .map_err(|e| format!("Failed to add {}: {}", source.path, e))?;
+        writer
+            .write_all(source.content.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", source.path, e))?;
+    }
+
+    let cursor = writer
+        .finish()
+        .map_err(|e| format!("Failed to finish zip: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+// 図の型名から言語の型名への対応表
+// 型名は完全一致、次に小文字で一致するものを探し、見つからない型名はresolveに任せる
+pub(crate) struct TypeTable {
+    // 図の型名と言語の型名（{} を含む場合は型引数を埋め込む書式）
+    pub names: &'static [(&'static str, &'static str)],
+    // 型引数の中で使う型名（Javaのプリミティブ型のラッパークラスなど）
+    pub boxed: &'static [(&'static str, &'static str)],
+    // 配列（T[]）の書式（{} が要素の型）
+    pub array: &'static str,
+    // 型引数を書かずに使われたコレクションの要素の型
+    pub unknown: &'static str,
+}
+
+impl TypeTable {
+    // 型名を変換（List<string> → List<String>、int[] → Vec<i32> など）
+    pub fn map(&self, text: &str, resolve: &mut dyn FnMut(&str) -> String) -> String {
+        self.map_type(text.trim(), false, resolve)
+    }
+
+    fn map_type(&self, text: &str, boxed: bool, resolve: &mut dyn FnMut(&str) -> String) -> String {
+        if let Some(element) = text.strip_suffix("[]") {
+            let element = self.map_type(element.trim(), false, resolve);
+            return self.array.replace("{}", &element);
+        }
+        if let (Some(open), true) = (text.find('<'), text.ends_with('>')) {
+            let arguments = split_arguments(&text[open + 1..text.len() - 1])
+                .into_iter()
+                .map(|argument| self.map_type(argument.trim(), true, resolve))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let base = text[..open].trim();
+            return match self.lookup(base) {
+                Some(format) if format.contains("{}") => format.replace("{}", &arguments),
+                Some(name) => format!("{}<{}>", name, arguments),
+                None => format!("{}<{}>", resolve(base), arguments),
+            };
+        }
+
+        let Some(name) = self.lookup(text) else {
+            return resolve(text);
+        };
+        if boxed {
+            if let Some((_, wrapper)) = self.boxed.iter().find(|(from, _)| *from == name) {
+                return wrapper.to_string();
+            }
+        }
+        // 型引数の無いコレクション（List など）
+        name.replace("{}", self.unknown)
+    }
+
+    fn lookup(&self, text: &str) -> Option<&'static str> {
+        let lower = text.to_lowercase();
+        self.names
+            .iter()
+            .find(|(from, _)| *from == text)
+            .or_else(|| self.names.iter().find(|(from, _)| *from == lower))
+            .map(|(_, to)| *to)
+    }
+}
+
+// 可視性ごとの修飾子
+pub(crate) struct VisibilityTable {
+    pub public: &'static str,
+    pub private: &'static str,
+    pub protected: &'static str,
+    pub package: &'static str,
+}
+
+impl VisibilityTable {
+    pub fn modifier(&self, visibility: Visibility) -> &'static str {
+        match visibility {
+            Visibility::Public => self.public,
+            Visibility::Private => self.private,
+            Visibility::Protected => self.protected,
+            Visibility::Package => self.package,
+        }
+    }
+}
+
+// 関連のフィールドの持ち方（関係先の多重度による）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cardinality {
+    // 多重度が無い、または下限が1以上で上限が1
+    One,
+    // 0..1
+    Optional,
+    // 上限が2以上（*を含む）
+    Many,
+}
+
+// 関連・集約・コンポジションから作るフィールド
+pub(crate) struct RelationField<'a> {
+    // ロール名、無い場合は関係先のクラス名の先頭を小文字にした名前（Manyは末尾にs）
+    pub name: String,
+    pub target: &'a Class,
+    pub kind: RelationKind,
+    pub cardinality: Cardinality,
+}
+
+// 親クラス・インターフェース
+#[derive(Default)]
+pub(crate) struct Parents<'a> {
+    // 継承するクラス（インターフェースの場合は常にNone）
+    pub superclass: Option<&'a Class>,
+    // 実現・継承するインターフェース（インターフェースの場合は継承する全て）
+    pub interfaces: Vec<&'a Class>,
+    // 単一継承の言語で表せない2つ目以降の親クラス
+    pub ignored: Vec<&'a Class>,
+    // ファイルに無いクラスを指す関係先のID
+    pub unresolved: Vec<&'a str>,
+}
+
+// 親から継承し、まだ実装されていない抽象メンバ
+#[derive(Default)]
+pub(crate) struct InheritedMembers<'a> {
+    // 抽象メソッド（インターフェースのメソッドは公開にする）
+    pub methods: Vec<Method>,
+    // インターフェースの属性
+    pub attributes: Vec<&'a Variable>,
+}
+
+// 言語によらないコード生成の前処理
+// 実現の関係先になっているクラスをインターフェースとみなし、クラス名を言語の識別子にする
+pub(crate) struct Model<'a> {
+    pub file: &'a File,
+    classes: HashMap<&'a str, &'a Class>,
+    // クラスIDから生成するクラス名（型引数を含む）
+    names: HashMap<&'a str, String>,
+    // 図の表示名（型引数を除く）からクラス
+    by_name: HashMap<&'a str, &'a Class>,
+    interfaces: HashSet<&'a str>,
+}
+
+impl<'a> Model<'a> {
+    // naming はクラス名（型引数を除く）を言語の識別子にする関数
+    // 名前が重複する場合は2つ目以降に番号を付ける
+    pub fn new(file: &'a File, naming: fn(&str) -> String) -> Self {
+        let mut classes = HashMap::new();
+        let mut names = HashMap::new();
+        let mut by_name = HashMap::new();
+        let mut used = HashSet::new();
+        for class in &file.classes {
+            if classes.contains_key(class.id.as_str()) {
+                continue;
+            }
+            classes.insert(class.id.as_str(), class);
+
+            let display = display_name(class);
+            let (base, parameters) = match display.find('<') {
+                Some(index) => (&display[..index], &display[index..]),
+                None => (display, ""),
+            };
+            by_name.entry(base.trim()).or_insert(class);
+            let base = naming(base);
+            let mut unique = base.clone();
+            let mut count = 1;
+            while !used.insert(unique.clone()) {
+                count += 1;
+                unique = format!("{}{}", base, count);
+            }
+            names.insert(class.id.as_str(), format!("{}{}", unique, parameters));
+        }
+
+        let interfaces = file
+            .classes
+            .iter()
+            .flat_map(relations)
+            .filter(|relation| {
+                RelationKind::from_i32(relation.relation) == RelationKind::Realization
+            })
+            .map(|relation| relation.target_class_id.as_str())
+            .collect();
+
+        Self {
+            file,
+            classes,
+            names,
+            by_name,
+            interfaces,
+        }
+    }
+
+    // 生成するクラス（IDが重複している場合は最初のクラスのみ）
+    pub fn classes(&self) -> impl Iterator<Item = &'a Class> + '_ {
+        self.file
+            .classes
+            .iter()
+            .filter(|class| std::ptr::eq(self.classes[class.id.as_str()], *class))
+    }
+
+    // 型引数を含むクラス名（Box<T>）
+    pub fn name(&self, class: &Class) -> &str {
+        &self.names[class.id.as_str()]
+    }
+
+    // 型引数を除いたクラス名（Box）
+    pub fn base_name(&self, class: &Class) -> &str {
+        let name = self.name(class);
+        name.split('<').next().unwrap_or(name)
+    }
+
+    pub fn is_interface(&self, class: &Class) -> bool {
+        self.interfaces.contains(class.id.as_str())
+    }
+
+    // 型名として書かれたクラス（図の表示名で探す）
+    pub fn class_named(&self, name: &str) -> Option<&'a Class> {
+        self.by_name.get(name.trim()).copied()
+    }
+
+    pub fn parents(&self, class: &'a Class) -> Parents<'a> {
+        let mut parents = Parents::default();
+        let is_interface = self.is_interface(class);
+        for relation in relations(class) {
+            if !matches!(
+                RelationKind::from_i32(relation.relation),
+                RelationKind::Inheritance | RelationKind::Realization
+            ) {
+                continue;
+            }
+            let Some(parent) = self.classes.get(relation.target_class_id.as_str()) else {
+                parents.unresolved.push(&relation.target_class_id);
+                continue;
+            };
+            if is_interface || self.is_interface(parent) {
+                parents.interfaces.push(parent);
+            } else if parents.superclass.is_none() {
+                parents.superclass = Some(parent);
+            } else {
+                parents.ignored.push(parent);
+            }
+        }
+        parents
+    }
+
+    // 継承・実現で辿れる全ての親（近い順）
+    pub fn ancestors(&self, class: &'a Class) -> Vec<&'a Class> {
+        let mut ancestors = Vec::new();
+        let mut visited = HashSet::from([class.id.as_str()]);
+        let mut queue = VecDeque::from([class]);
+        while let Some(current) = queue.pop_front() {
+            for relation in relations(current) {
+                if !matches!(
+                    RelationKind::from_i32(relation.relation),
+                    RelationKind::Inheritance | RelationKind::Realization
+                ) {
+                    continue;
+                }
+                let Some(parent) = self.classes.get(relation.target_class_id.as_str()) else {
+                    continue;
+                };
+                if visited.insert(parent.id.as_str()) {
+                    ancestors.push(*parent);
+                    queue.push_back(parent);
+                }
+            }
+        }
+        ancestors
+    }
+
+    // 具象クラスで実装が必要な、親の抽象メソッドとインターフェースの属性
+    // 名前と引数の数が同じメソッドを実装済みとみなす
+    pub fn inherited_members(&self, class: &'a Class) -> InheritedMembers<'a> {
+        let key = |method: &Method| (identifier(&method.name), method.parameters.len());
+        let mut implemented: HashSet<(String, usize)> = class.methods.iter().map(key).collect();
+        let mut members = InheritedMembers::default();
+        let mut abstract_methods = Vec::new();
+
+        for parent in self.ancestors(class) {
+            let is_interface = self.is_interface(parent);
+            for method in &parent.methods {
+                if method.is_static.unwrap_or(false) || self.is_constructor(parent, method) {
+                    continue;
+                }
+                if is_interface || method.is_abstract.unwrap_or(false) {
+                    abstract_methods.push(Method {
+                        visibility: if is_interface {
+                            Visibility::Public.to_i32()
+                        } else {
+                            method.visibility
+                        },
+                        ..method.clone()
+                    });
+                } else {
+                    implemented.insert(key(method));
+                }
+            }
+            if is_interface {
+                members.attributes.extend(&parent.attributes);
+            }
+        }
+
+        members.methods = abstract_methods
+            .into_iter()
+            .filter(|method| implemented.insert(key(method)))
+            .map(|method| Method {
+                is_abstract: Some(false),
+                is_static: Some(false),
+                ..method
+            })
+            .collect();
+        members
+    }
+
+    // クラス名と同じ名前で戻り値の型が無いメソッドはコンストラクタ
+    pub fn is_constructor(&self, class: &Class, method: &Method) -> bool {
+        let display = display_name(class);
+        let base = display.split('<').next().unwrap_or(display);
+        !self.is_interface(class)
+            && method.return_type.trim().is_empty()
+            && (method.name.trim() == base.trim() || method.name.trim() == self.base_name(class))
+    }
+
+    pub fn constructors<'c>(&self, class: &'c Class) -> Vec<&'c Method> {
+        class
+            .methods
+            .iter()
+            .filter(|method| self.is_constructor(class, method))
+            .collect()
+    }
+
+    // 親クラスのコンストラクタが全て引数を必要とするか
+    pub fn requires_arguments(&self, parent: &Class) -> bool {
+        let constructors = self.constructors(parent);
+        !constructors.is_empty()
+            && constructors
+                .iter()
+                .all(|constructor| !constructor.parameters.is_empty())
+    }
+
+    // 子のコンストラクタから親のコンストラクタに渡す引数の名前
+    // 親に引数の無いコンストラクタがある場合は空、同じ名前の引数で全て渡せるコンストラクタが無い場合はNone
+    pub fn super_arguments(&self, parent: &Class, constructor: &Method) -> Option<Vec<String>> {
+        if !self.requires_arguments(parent) {
+            return Some(Vec::new());
+        }
+        let own = parameter_names(constructor);
+        self.constructors(parent)
+            .into_iter()
+            .map(parameter_names)
+            .find(|names| names.iter().all(|name| own.contains(name)))
+    }
+
+    // 関連・集約・コンポジションのフィールド（属性と同じ名前になるものは作らない）
+    pub fn relation_fields(&self, class: &Class) -> Vec<RelationField<'a>> {
+        let mut field_names: HashSet<String> = class
+            .attributes
+            .iter()
+            .map(|attribute| identifier(&attribute.name))
+            .collect();
+        let mut fields = Vec::new();
+
+        for relation in relations(class) {
+            let kind = RelationKind::from_i32(relation.relation);
+            if !matches!(
+                kind,
+                RelationKind::Association | RelationKind::Aggregation | RelationKind::Composition
+            ) {
+                continue;
+            }
+            let Some(target) = self.classes.get(relation.target_class_id.as_str()) else {
+                continue;
+            };
+
+            let cardinality = match &relation.multiplicity_c {
+                Some(multiplicity) if multiplicity.upper.is_none_or(|upper| upper > 1) => {
+                    Cardinality::Many
+                }
+                Some(multiplicity) if multiplicity.lower == 0 => Cardinality::Optional,
+                _ => Cardinality::One,
+            };
+            let name = match relation
+                .role_name_c
+                .as_deref()
+                .filter(|role| !role.trim().is_empty())
+            {
+                Some(role) => identifier(role),
+                None if cardinality == Cardinality::Many => {
+                    format!("{}s", lower_camel(self.base_name(target)))
+                }
+                None => lower_camel(self.base_name(target)),
+            };
+            if !field_names.insert(name.clone()) {
+                continue;
+            }
+
+            fields.push(RelationField {
+                name,
+                target,
+                kind,
+                cardinality,
+            });
+        }
+        fields
+    }
+}
+
+pub(crate) fn relations(class: &Class) -> impl Iterator<Item = &RelationInfo> {
+    class
+        .relations
+        .iter()
+        .flat_map(|relations| &relations.relation_infos)
+}
+
+// 引数名（名前が無い引数はarg0, arg1, ...）
+pub(crate) fn parameter_names(method: &Method) -> Vec<String> {
+    method
+        .parameters
+        .iter()
+        .enumerate()
+        .map(|(index, parameter)| {
+            if parameter.name.trim().is_empty() {
+                format!("arg{}", index)
+            } else {
+                identifier(&parameter.name)
+            }
+        })
+        .collect()
+}
+
+// 型引数をカンマで区切る（入れ子の<>の中のカンマは区切らない）
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// 名前を識別子に変換（使えない文字は _ に置き換え、数字で始まる場合は _ を付ける）
+pub(crate) fn identifier(name: &str) -> String {
+    let mut result: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+        result.insert(0, '_');
+    }
+    result
+}
+
+// クラス名（Owner Person → OwnerPerson）
+pub(crate) fn class_identifier(name: &str) -> String {
+    let name: String = name
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(upper_camel)
+        .collect();
+    let name = identifier(&name);
+    // Rustでは型名に使えない
+    if name == "Self" {
+        "Self_".to_string()
+    } else {
+        name
+    }
+}
+
+// 先頭を小文字にした名前（関連のフィールド名に使う）
+pub(crate) fn lower_camel(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// 先頭を大文字にした名前（getterの名前に使う）
+pub(crate) fn upper_camel(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// スネークケースの名前（orderItems → order_items、HTTPServer → http_server）
+pub(crate) fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = identifier(name).chars().collect();
+    let mut result = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let after_lower =
+                index > 0 && (chars[index - 1].is_lowercase() || chars[index - 1].is_ascii_digit());
+            let before_lower = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            let after_upper = index > 0 && chars[index - 1].is_uppercase();
+            if after_lower || (after_upper && before_lower) {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
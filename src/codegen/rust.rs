@@ -0,0 +1,390 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use super::{
+    class_identifier, parameter_names, snake_case, Cardinality, GeneratedSource, Model, TypeTable,
+    VisibilityTable,
+};
+use crate::model::{RelationKind, Visibility};
+use crate::server::class::{Class, File, Method, Variable};
+
+const INDENT: &str = "    ";
+
+const TYPES: TypeTable = TypeTable {
+    names: &[
+        ("int", "i32"),
+        ("integer", "i32"),
+        ("long", "i64"),
+        ("short", "i16"),
+        ("byte", "u8"),
+        ("char", "char"),
+        ("character", "char"),
+        ("float", "f32"),
+        ("double", "f64"),
+        ("number", "f64"),
+        ("bool", "bool"),
+        ("boolean", "bool"),
+        ("string", "String"),
+        ("str", "String"),
+        ("any", "Box<dyn std::any::Any>"),
+        ("object", "Box<dyn std::any::Any>"),
+        ("void", "()"),
+        ("list", "Vec<{}>"),
+        ("array", "Vec<{}>"),
+        ("vec", "Vec<{}>"),
+        ("set", "HashSet<{}>"),
+        ("map", "HashMap<{}>"),
+        ("dict", "HashMap<{}>"),
+        ("optional", "Option<{}>"),
+    ],
+    boxed: &[],
+    array: "Vec<{}>",
+    unknown: "()",
+};
+
+// Rustには継承が無いため、protected・packageはクレート内に公開する
+const VISIBILITY: VisibilityTable = VisibilityTable {
+    public: "pub ",
+    private: "",
+    protected: "pub(crate) ",
+    package: "pub(crate) ",
+};
+
+// 識別子に使えない予約語（r# を付ける）
+const KEYWORDS: [&str; 48] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+// ファイルの全クラスから1つのRustのソースファイル（lib.rs）を生成
+// 実現の関係先になっているクラスはtrait、それ以外はstructとメソッドのimplにする
+// 継承は親のstructをフィールドとして持ち、実現・継承したtraitはimplのスタブを作る
+// コンポジションは関係先を所有（T、Option<Box<T>>、Vec<T>）、関連・集約はRcで共有する
+pub fn generate(file: &File) -> Vec<GeneratedSource> {
+    let model = Model::new(file, class_identifier);
+    let mut writer = Writer {
+        model: &model,
+        uses: BTreeSet::new(),
+    };
+
+    let mut items = Vec::new();
+    for class in model.classes() {
+        if model.is_interface(class) {
+            items.push(writer.write_trait(class));
+        } else {
+            items.push(writer.write_struct(class));
+        }
+    }
+
+    let mut out = format!(
+        "// Generated by EDEA from \"{}\"\n\n",
+        file.name.replace(['\r', '\n'], " ")
+    );
+    out.push_str("#![allow(dead_code, unused_variables)]\n\n");
+    if !writer.uses.is_empty() {
+        for path in &writer.uses {
+            let _ = writeln!(out, "use {};", path);
+        }
+        out.push('\n');
+    }
+    out.push_str(&items.join("\n"));
+
+    vec![GeneratedSource {
+        path: "lib.rs".to_string(),
+        content: out,
+    }]
+}
+
+struct Writer<'a> {
+    model: &'a Model<'a>,
+    // ソース先頭のuse（std::rc::Rc など）
+    uses: BTreeSet<&'static str>,
+}
+
+impl Writer<'_> {
+    // pub trait Name: Parent { fn method(&self) -> T; }
+    // 属性は同じ名前のgetterにする
+    fn write_trait(&mut self, class: &Class) -> String {
+        let model = self.model;
+        let parents = model.parents(class);
+
+        let mut out = unresolved_comments(&parents.unresolved);
+        let _ = write!(out, "pub trait {}", model.name(class));
+        if !parents.interfaces.is_empty() {
+            let supertraits: Vec<&str> = parents
+                .interfaces
+                .iter()
+                .map(|parent| model.name(parent))
+                .collect();
+            let _ = write!(out, ": {}", supertraits.join(" + "));
+        }
+        out.push_str(" {\n");
+
+        let mut names = HashSet::new();
+        for method in trait_methods(class) {
+            let signature = self.signature(class, &method, &mut names);
+            // dyn Traitとして使えるよう、静的メソッドはSelf: Sizedの場合に限る
+            let bound = if method.is_static.unwrap_or(false) {
+                " where Self: Sized"
+            } else {
+                ""
+            };
+            let _ = writeln!(out, "{}{}{};", INDENT, signature, bound);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // pub struct Name { ... } と impl Name { ... }、実装するtraitごとの impl Trait for Name
+    fn write_struct(&mut self, class: &Class) -> String {
+        let model = self.model;
+        let parents = model.parents(class);
+        let name = model.name(class);
+        let generics = name.find('<').map_or("", |index| &name[index..]);
+
+        let mut out = unresolved_comments(&parents.unresolved);
+        let mut field_names = HashSet::new();
+        let mut fields = String::new();
+
+        // 継承は親のstructを持つ（単一継承でない場合も全て持つ）
+        for parent in parents.superclass.iter().chain(&parents.ignored) {
+            let field = field_name(model.base_name(parent));
+            field_names.insert(field.clone());
+            let _ = writeln!(fields, "{}pub {}: {},", INDENT, field, model.name(parent));
+        }
+
+        let mut static_attributes = Vec::new();
+        for attribute in &class.attributes {
+            // 静的な属性は関連関数にする
+            if attribute.is_static.unwrap_or(false) {
+                static_attributes.push(attribute);
+                continue;
+            }
+            let field = field_name(&attribute.name);
+            if !field_names.insert(field.clone()) {
+                continue;
+            }
+            let visibility = attribute
+                .visibility
+                .map_or(Visibility::Private, Visibility::from_i32);
+            let type_name = self.type_name(&attribute.r#type, "()");
+            let _ = writeln!(
+                fields,
+                "{}{}{}: {},",
+                INDENT,
+                VISIBILITY.modifier(visibility),
+                field,
+                type_name
+            );
+        }
+
+        for field in model.relation_fields(class) {
+            let name = field_name(&field.name);
+            if !field_names.insert(name.clone()) {
+                continue;
+            }
+            let target = self.relation_target(field.target, field.kind);
+            let type_name = match (field.kind, field.cardinality) {
+                (RelationKind::Composition, Cardinality::Optional)
+                    if !target.starts_with("Box<") =>
+                {
+                    format!("Option<Box<{}>>", target)
+                }
+                (_, Cardinality::One) => target,
+                (_, Cardinality::Optional) => format!("Option<{}>", target),
+                (_, Cardinality::Many) => format!("Vec<{}>", target),
+            };
+            let _ = writeln!(fields, "{}{}: {},", INDENT, name, type_name);
+        }
+
+        if fields.is_empty() {
+            let _ = writeln!(out, "pub struct {} {{}}", name);
+        } else {
+            let _ = write!(out, "pub struct {} {{\n{}}}\n", name, fields);
+        }
+
+        // 固有のメソッド
+        let mut names = HashSet::new();
+        let mut methods = Vec::new();
+        for attribute in static_attributes {
+            let method = Method {
+                name: attribute.name.clone(),
+                return_type: attribute.r#type.clone(),
+                visibility: attribute.visibility.unwrap_or(Visibility::Public.to_i32()),
+                is_static: Some(true),
+                ..Default::default()
+            };
+            let signature = self.signature(class, &method, &mut names);
+            methods.push(stub(&signature));
+        }
+        for method in &class.methods {
+            let signature = self.signature(class, method, &mut names);
+            methods.push(stub(&signature));
+        }
+        if !methods.is_empty() {
+            let _ = write!(
+                out,
+                "\nimpl{} {} {{\n{}}}\n",
+                generics,
+                name,
+                methods.join("\n")
+            );
+        }
+
+        // 実現・継承したtrait
+        for parent in model.ancestors(class) {
+            if !model.is_interface(parent) {
+                continue;
+            }
+            let mut names = HashSet::new();
+            let mut methods = Vec::new();
+            for method in trait_methods(parent) {
+                let signature = self.signature(parent, &method, &mut names);
+                methods.push(stub(&signature));
+            }
+            let _ = write!(
+                out,
+                "\nimpl{} {} for {} {{\n{}}}\n",
+                generics,
+                model.name(parent),
+                name,
+                methods.join("\n")
+            );
+        }
+        out
+    }
+
+    // 関連の関係先の型（traitはdyn、コンポジション以外はRcで共有する）
+    fn relation_target(&mut self, target: &Class, kind: RelationKind) -> String {
+        let name = self.model.name(target);
+        let owned = kind == RelationKind::Composition;
+        match (self.model.is_interface(target), owned) {
+            (true, true) => format!("Box<dyn {}>", name),
+            (false, true) => name.to_string(),
+            (is_interface, false) => {
+                self.uses.insert("std::rc::Rc");
+                if is_interface {
+                    format!("Rc<dyn {}>", name)
+                } else {
+                    format!("Rc<{}>", name)
+                }
+            }
+        }
+    }
+
+    // pub fn name(&self, count: i32) -> String
+    // コンストラクタはnew、同じ名前のメソッドは2つ目以降に番号を付ける
+    fn signature(&mut self, class: &Class, method: &Method, names: &mut HashSet<String>) -> String {
+        let model = self.model;
+        let is_interface = model.is_interface(class);
+        let is_constructor = model.is_constructor(class, method);
+        let base = if is_constructor {
+            "new".to_string()
+        } else {
+            field_name(&method.name)
+        };
+        let mut name = base.clone();
+        let mut count = 1;
+        while !names.insert(name.clone()) {
+            count += 1;
+            name = format!("{}_{}", base, count);
+        }
+
+        let mut parameters = Vec::new();
+        if !is_constructor && !method.is_static.unwrap_or(false) {
+            parameters.push("&self".to_string());
+        }
+        for (parameter, parameter_name) in method.parameters.iter().zip(parameter_names(method)) {
+            let type_name = self.type_name(&parameter.r#type, "()");
+            parameters.push(format!("{}: {}", field_name(&parameter_name), type_name));
+        }
+
+        let return_type = if is_constructor {
+            " -> Self".to_string()
+        } else {
+            match self.type_name(&method.return_type, "()").as_str() {
+                "()" => String::new(),
+                type_name => format!(" -> {}", type_name),
+            }
+        };
+        let visibility = if is_interface {
+            ""
+        } else {
+            VISIBILITY.modifier(Visibility::from_i32(method.visibility))
+        };
+        format!(
+            "{}fn {}({}){}",
+            visibility,
+            name,
+            parameters.join(", "),
+            return_type
+        )
+    }
+
+    // 図の型名をRustの型名に変換（traitはBox<dyn Trait>）
+    fn type_name(&mut self, text: &str, default: &str) -> String {
+        if text.trim().is_empty() {
+            return default.to_string();
+        }
+        let model = self.model;
+        let mapped = TYPES.map(text, &mut |name| match model.class_named(name) {
+            Some(class) if model.is_interface(class) => {
+                format!("Box<dyn {}>", model.base_name(class))
+            }
+            Some(class) => model.base_name(class).to_string(),
+            None => name.to_string(),
+        });
+        for word in mapped.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            match word {
+                "HashMap" => self.uses.insert("std::collections::HashMap"),
+                "HashSet" => self.uses.insert("std::collections::HashSet"),
+                _ => false,
+            };
+        }
+        mapped
+    }
+}
+
+// traitのメソッド（属性は同じ名前のgetterにする）
+fn trait_methods(class: &Class) -> Vec<Method> {
+    let getters = class.attributes.iter().map(|attribute: &Variable| Method {
+        name: attribute.name.clone(),
+        return_type: attribute.r#type.clone(),
+        is_static: attribute.is_static,
+        ..Default::default()
+    });
+    getters.chain(class.methods.iter().cloned()).collect()
+}
+
+fn stub(signature: &str) -> String {
+    format!(
+        "{indent}{} {{\n{indent}{indent}todo!()\n{indent}}}\n",
+        signature,
+        indent = INDENT
+    )
+}
+
+fn unresolved_comments(unresolved: &[&str]) -> String {
+    unresolved
+        .iter()
+        .map(|id| {
+            format!(
+                "// unresolved relation target: {}\n",
+                id.replace(['\r', '\n'], " ")
+            )
+        })
+        .collect()
+}
+
+// フィールド・メソッド・引数の名前（スネークケース、予約語はr#を付ける）
+fn field_name(name: &str) -> String {
+    let name = snake_case(name);
+    match name.as_str() {
+        "self" | "super" | "crate" => format!("{}_", name),
+        _ if KEYWORDS.contains(&name.as_str()) => format!("r#{}", name),
+        _ => name,
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::{
+    class_identifier, identifier, parameter_names, Cardinality, GeneratedSource, Model, TypeTable,
+    VisibilityTable,
+};
+use crate::model::{is_abstract_class, Visibility};
+use crate::server::class::{Class, File, Method, Variable};
+
+const INDENT: &str = "  ";
+
+const TYPES: TypeTable = TypeTable {
+    names: &[
+        ("int", "number"),
+        ("integer", "number"),
+        ("long", "number"),
+        ("short", "number"),
+        ("byte", "number"),
+        ("float", "number"),
+        ("double", "number"),
+        ("number", "number"),
+        ("bigint", "bigint"),
+        ("string", "string"),
+        ("str", "string"),
+        ("char", "string"),
+        ("character", "string"),
+        ("bool", "boolean"),
+        ("boolean", "boolean"),
+        ("any", "any"),
+        ("object", "object"),
+        ("void", "void"),
+        ("list", "{}[]"),
+        ("array", "{}[]"),
+        ("set", "Set"),
+        ("map", "Map"),
+        ("dict", "Record<{}>"),
+        ("date", "Date"),
+    ],
+    boxed: &[],
+    array: "{}[]",
+    unknown: "unknown",
+};
+
+// TypeScriptでは公開が既定のため修飾子を書かない
+const VISIBILITY: VisibilityTable = VisibilityTable {
+    public: "",
+    private: "private ",
+    protected: "protected ",
+    package: "",
+};
+
+// 引数名に使えない予約語
+const KEYWORDS: [&str; 44] = [
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "yield",
+];
+
+// ファイルのクラスごとにTypeScriptのソースファイル（Name.ts）と、全てを再エクスポートするindex.tsを生成
+// 実現の関係先になっているクラスはinterface、抽象メソッドを持つクラスはabstract class とする
+// 関連は関係先の多重度により T、T | undefined（省略可能なプロパティ）、T[] のプロパティにする
+pub fn generate(file: &File) -> Vec<GeneratedSource> {
+    let model = Model::new(file, class_identifier);
+    let mut sources: Vec<GeneratedSource> = model
+        .classes()
+        .map(|class| {
+            let writer = ClassWriter {
+                model: &model,
+                class,
+                is_interface: model.is_interface(class),
+                imports: BTreeSet::new(),
+            };
+            GeneratedSource {
+                path: format!("{}.ts", model.base_name(class)),
+                content: writer.write(),
+            }
+        })
+        .collect();
+
+    let mut index = header(file);
+    for class in model.classes() {
+        let _ = writeln!(index, "export * from \"./{}\";", model.base_name(class));
+    }
+    sources.push(GeneratedSource {
+        path: "index.ts".to_string(),
+        content: index,
+    });
+    sources
+}
+
+fn header(file: &File) -> String {
+    format!(
+        "// Generated by EDEA from \"{}\"\n\n",
+        file.name.replace(['\r', '\n'], " ")
+    )
+}
+
+// 1クラス分のソースを組み立てる
+struct ClassWriter<'a> {
+    model: &'a Model<'a>,
+    class: &'a Class,
+    is_interface: bool,
+    // 参照している他のクラス（型引数を除いたクラス名）
+    imports: BTreeSet<String>,
+}
+
+impl ClassWriter<'_> {
+    fn write(mut self) -> String {
+        let model = self.model;
+        let class = self.class;
+        let mut body = String::new();
+        let mut comments = Vec::new();
+
+        // 親クラス・インターフェース
+        let parents = model.parents(class);
+        for id in &parents.unresolved {
+            comments.push(format!("unresolved relation target: {}", id));
+        }
+        for parent in &parents.ignored {
+            comments.push(format!(
+                "multiple inheritance is not supported: {}",
+                model.name(parent)
+            ));
+        }
+        let mut interfaces = Vec::new();
+        for parent in &parents.interfaces {
+            interfaces.push(self.reference(parent));
+        }
+        let superclass = parents.superclass.map(|parent| self.reference(parent));
+        let (extends, implements) = if self.is_interface {
+            (interfaces, Vec::new())
+        } else {
+            (superclass.into_iter().collect(), interfaces)
+        };
+
+        // 属性（インターフェースは可視性を書かない）
+        for attribute in &class.attributes {
+            let line = self.property(attribute);
+            let _ = writeln!(body, "{}{}", INDENT, line);
+        }
+
+        // 関連・集約・コンポジションのプロパティ
+        if !self.is_interface {
+            for field in model.relation_fields(class) {
+                let target = self.reference(field.target);
+                let name = identifier(&field.name);
+                let line = match field.cardinality {
+                    Cardinality::One => format!("private {}!: {};", name, target),
+                    Cardinality::Optional => format!("private {}?: {};", name, target),
+                    Cardinality::Many => format!("private {}: {}[] = [];", name, target),
+                };
+                let _ = writeln!(body, "{}{}", INDENT, line);
+            }
+        }
+
+        for method in &class.methods {
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            body.push_str(&self.method(method, parents.superclass));
+        }
+
+        // 具象クラスでは継承した抽象メソッド・インターフェースのプロパティも実装する
+        if !self.is_interface && !is_abstract_class(class) {
+            let inherited = model.inherited_members(class);
+            if !inherited.attributes.is_empty() {
+                body.push('\n');
+            }
+            for attribute in inherited.attributes {
+                let line = self.property(&Variable {
+                    visibility: Some(Visibility::Public.to_i32()),
+                    ..attribute.clone()
+                });
+                let _ = writeln!(body, "{}{}", INDENT, line);
+            }
+            for method in inherited.methods {
+                if !body.is_empty() {
+                    body.push('\n');
+                }
+                body.push_str(&self.method(&method, None));
+            }
+        }
+
+        // ソース全体
+        let mut out = header(model.file);
+        let own = model.base_name(class);
+        let imports: Vec<&String> = self.imports.iter().filter(|name| *name != own).collect();
+        if !imports.is_empty() {
+            for import in imports {
+                let _ = writeln!(out, "import {{ {} }} from \"./{}\";", import, import);
+            }
+            out.push('\n');
+        }
+        for comment in comments {
+            let _ = writeln!(out, "// {}", comment.replace(['\r', '\n'], " "));
+        }
+
+        let keyword = if self.is_interface {
+            "interface"
+        } else if is_abstract_class(class) {
+            "abstract class"
+        } else {
+            "class"
+        };
+        let _ = write!(out, "export {} {}", keyword, model.name(class));
+        if !extends.is_empty() {
+            let _ = write!(out, " extends {}", extends.join(", "));
+        }
+        if !implements.is_empty() {
+            let _ = write!(out, " implements {}", implements.join(", "));
+        }
+        out.push_str(" {\n");
+        out.push_str(&body);
+        out.push_str("}\n");
+        out
+    }
+
+    // private name!: string;（静的でないプロパティは初期化していないことを ! で示す）
+    fn property(&mut self, attribute: &Variable) -> String {
+        let type_name = self.type_name(&attribute.r#type, "unknown");
+        let name = identifier(&attribute.name);
+        if self.is_interface {
+            return format!("{}: {};", name, type_name);
+        }
+
+        // 可視性が指定されていないプロパティはprivate
+        let visibility = attribute
+            .visibility
+            .map_or(Visibility::Private, Visibility::from_i32);
+        let (is_static, definite) = if attribute.is_static.unwrap_or(false) {
+            ("static ", "")
+        } else {
+            ("", "!")
+        };
+        format!(
+            "{}{}{}{}: {};",
+            VISIBILITY.modifier(visibility),
+            is_static,
+            name,
+            definite,
+            type_name
+        )
+    }
+
+    // メソッドのスタブ（抽象メソッド以外は未実装の例外を投げる）
+    fn method(&mut self, method: &Method, superclass: Option<&Class>) -> String {
+        let is_static = method.is_static.unwrap_or(false);
+        let is_abstract = method.is_abstract.unwrap_or(false) && !is_static;
+        let is_constructor = self.model.is_constructor(self.class, method);
+        let parameters = self.parameters(method);
+
+        // インターフェースは静的メンバを持てない
+        if self.is_interface && is_static {
+            return format!(
+                "{}// static {}({}) is not supported in interfaces\n",
+                INDENT,
+                identifier(&method.name),
+                parameters
+            );
+        }
+
+        let mut signature = String::new();
+        if !self.is_interface {
+            signature.push_str(VISIBILITY.modifier(Visibility::from_i32(method.visibility)));
+        }
+        if is_static {
+            signature.push_str("static ");
+        }
+        if is_constructor {
+            let _ = write!(signature, "constructor({})", parameters);
+        } else {
+            if is_abstract && !self.is_interface {
+                signature.push_str("abstract ");
+            }
+            let return_type = self.type_name(&method.return_type, "void");
+            let _ = write!(
+                signature,
+                "{}({}): {}",
+                identifier(&method.name),
+                parameters,
+                return_type
+            );
+        }
+
+        if is_abstract || self.is_interface {
+            format!("{}{};\n", INDENT, signature)
+        } else if is_constructor {
+            // 派生クラスのコンストラクタは親のコンストラクタを呼び出す必要がある
+            let super_call = match superclass {
+                Some(parent) => {
+                    let arguments = self
+                        .model
+                        .super_arguments(parent, method)
+                        .map(|arguments| {
+                            let arguments: Vec<String> =
+                                arguments.iter().map(|argument| name(argument)).collect();
+                            arguments.join(", ")
+                        })
+                        .unwrap_or_else(|| "/* TODO */".to_string());
+                    format!("{indent}{indent}super({});\n", arguments, indent = INDENT)
+                }
+                None => String::new(),
+            };
+            format!("{}{} {{\n{}{}}}\n", INDENT, signature, super_call, INDENT)
+        } else {
+            format!(
+                "{indent}{} {{\n{indent}{indent}throw new Error(\"Not implemented\");\n{indent}}}\n",
+                signature,
+                indent = INDENT
+            )
+        }
+    }
+
+    // 引数の並び（count: number, name: string）
+    fn parameters(&mut self, method: &Method) -> String {
+        method
+            .parameters
+            .iter()
+            .zip(parameter_names(method))
+            .map(|(parameter, parameter_name)| {
+                format!(
+                    "{}: {}",
+                    name(&parameter_name),
+                    self.type_name(&parameter.r#type, "unknown")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // 他のクラスへの参照（importを記録する）
+    fn reference(&mut self, class: &Class) -> String {
+        self.imports.insert(self.model.base_name(class).to_string());
+        self.model.name(class).to_string()
+    }
+
+    // 図の型名をTypeScriptの型名に変換し、参照するクラスを記録する
+    fn type_name(&mut self, text: &str, default: &str) -> String {
+        if text.trim().is_empty() {
+            return default.to_string();
+        }
+        let model = self.model;
+        let imports = &mut self.imports;
+        TYPES.map(text, &mut |name| match model.class_named(name) {
+            Some(class) => {
+                let base = model.base_name(class).to_string();
+                imports.insert(base.clone());
+                base
+            }
+            None => name.to_string(),
+        })
+    }
+}
+
+// 予約語と同じ引数名は末尾に _ を付ける
+fn name(text: &str) -> String {
+    let name = identifier(text);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
@@ -0,0 +1,156 @@
+// サーバの設定（環境変数から読み込む）
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    // メモリ上に保持し、スナップショットとWALで永続化
+    Memory,
+    // 組み込みSQLiteに保存
+    Sqlite,
+}
+
+// 保存時の検証で問題が見つかった場合の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    // 保存を拒否
+    Reject,
+    // 保存した上で警告を返す
+    Warn,
+    // 検証しない
+    Off,
+}
+
+// 他のセッションがロックしているクラスを変更する保存の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    // 保存を拒否
+    Reject,
+    // 保存した上でロックしているセッションを返す
+    Warn,
+}
+
+// gRPC・RESTの呼び出しの認証
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    // 資格情報が無い呼び出しも受け付ける（送られた資格情報は確認する）
+    // EDEA_AUTH=offを設定した場合だけ使う
+    Off,
+    // 全ての呼び出しに資格情報が必要
+    Required,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    // 永続化ディレクトリのパス
+    pub persistence_dir: String,
+    // ダイアグラムの保存先
+    pub storage_backend: StorageBackend,
+    // ファイルごとに保持する以前の版の数
+    pub history_limit: usize,
+    // 保存時の検証
+    pub validation_mode: ValidationMode,
+    // 保存時のクラスのロックの確認
+    pub lock_mode: LockMode,
+    // 呼び出しの認証
+    pub auth_mode: AuthMode,
+    // 起動時に作成する管理者（パスワードが設定されていて、利用者が存在しない場合）
+    pub admin_user: String,
+    pub admin_password: Option<String>,
+    // トークンの署名鍵（設定しない場合はストアに保存した鍵を使う）
+    pub token_secret: Option<String>,
+    // トークンの有効期間（秒）
+    pub token_lifetime: i64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            persistence_dir: "data".to_string(),
+            storage_backend: StorageBackend::Memory,
+            history_limit: 20,
+            validation_mode: ValidationMode::Reject,
+            lock_mode: LockMode::Reject,
+            auth_mode: AuthMode::Required,
+            admin_user: "admin".to_string(),
+            admin_password: None,
+            token_secret: None,
+            token_lifetime: 12 * 60 * 60,
+        }
+    }
+}
+
+impl ServerConfig {
+    // EDEA_DATA_DIR: 永続化ディレクトリ（デフォルト: data）
+    // EDEA_STORAGE: memory または sqlite（デフォルト: memory）
+    // EDEA_HISTORY_LIMIT: ファイルごとに保持する以前の版の数（デフォルト: 20）
+    // EDEA_VALIDATION: reject、warn または off（デフォルト: reject）
+    // EDEA_LOCKS: reject または warn（デフォルト: reject）
+    // EDEA_AUTH: required または off（デフォルト: required）
+    // EDEA_ADMIN_USER: 起動時に作成する管理者の名前（デフォルト: admin）
+    // EDEA_ADMIN_PASSWORD: 起動時に作成する管理者のパスワード（デフォルト: なし）
+    // EDEA_TOKEN_SECRET: トークンの署名鍵（デフォルト: ストアに保存した鍵）
+    // EDEA_TOKEN_LIFETIME: トークンの有効期間（秒、デフォルト: 43200）
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Ok(dir) = std::env::var("EDEA_DATA_DIR") {
+            config.persistence_dir = dir;
+        }
+
+        if let Ok(backend) = std::env::var("EDEA_STORAGE") {
+            config.storage_backend = match backend.to_lowercase().as_str() {
+                "memory" => StorageBackend::Memory,
+                "sqlite" => StorageBackend::Sqlite,
+                other => return Err(format!("Unknown storage backend: {}", other)),
+            };
+        }
+
+        if let Ok(limit) = std::env::var("EDEA_HISTORY_LIMIT") {
+            config.history_limit = limit
+                .parse()
+                .map_err(|e| format!("Invalid EDEA_HISTORY_LIMIT: {}", e))?;
+        }
+
+        if let Ok(mode) = std::env::var("EDEA_VALIDATION") {
+            config.validation_mode = match mode.to_lowercase().as_str() {
+                "reject" => ValidationMode::Reject,
+                "warn" => ValidationMode::Warn,
+                "off" => ValidationMode::Off,
+                other => return Err(format!("Unknown validation mode: {}", other)),
+            };
+        }
+
+        if let Ok(mode) = std::env::var("EDEA_LOCKS") {
+            config.lock_mode = match mode.to_lowercase().as_str() {
+                "reject" => LockMode::Reject,
+                "warn" => LockMode::Warn,
+                other => return Err(format!("Unknown lock mode: {}", other)),
+            };
+        }
+
+        if let Ok(mode) = std::env::var("EDEA_AUTH") {
+            config.auth_mode = match mode.to_lowercase().as_str() {
+                "off" => AuthMode::Off,
+                "required" => AuthMode::Required,
+                other => return Err(format!("Unknown auth mode: {}", other)),
+            };
+        }
+
+        if let Ok(user) = std::env::var("EDEA_ADMIN_USER") {
+            config.admin_user = user;
+        }
+        config.admin_password = std::env::var("EDEA_ADMIN_PASSWORD")
+            .ok()
+            .filter(|password| !password.is_empty());
+        config.token_secret = std::env::var("EDEA_TOKEN_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+
+        if let Ok(lifetime) = std::env::var("EDEA_TOKEN_LIFETIME") {
+            config.token_lifetime = lifetime
+                .parse()
+                .map_err(|e| format!("Invalid EDEA_TOKEN_LIFETIME: {}", e))?;
+        }
+
+        Ok(config)
+    }
+}
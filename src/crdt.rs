@@ -0,0 +1,684 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::diff::{method_signature, relations};
+use crate::server::class::{Class, File, RelationInfoList};
+use crate::server::diagram_ext::{
+    crdt_operation::Body, crdt_put::Value, CrdtDot, CrdtElement, CrdtElementKind, CrdtOperation,
+    CrdtPut, CrdtRemove, CrdtStateVector,
+};
+
+// 通常の保存で変更された内容を操作にする時のレプリカ（クライアントは使えない）
+// CRDTを作るたびに server:<UUID> とし、作り直したCRDTの操作が
+// 以前のサーバのレプリカの状態ベクトルに含まれないようにする
+pub const SERVER_REPLICA: &str = "server";
+
+// 操作の識別子（レプリカ、連番）
+type Dot = (String, u64);
+// 後勝ちの順序（lamport、レプリカ）
+type Stamp = (u64, String);
+// 要素のキー（種類、クラスID、要素のkey）
+type ElementKey = (i32, String, String);
+
+// クラス図のCRDT
+// ファイル名・クラス名・メンバ・関係はそれぞれ後勝ちのレジスタ、
+// クラス・属性・メソッド・関係の集合は追加が勝つ集合（OR-Set）で、
+// 操作をどの順に受け取っても同じ状態に収束する
+// 並びは各要素が最初に追加された操作の順とする
+#[derive(Debug, Clone)]
+pub struct CrdtDiagram {
+    // このCRDTでサーバが操作を作る時のレプリカ
+    replica_id: String,
+    // 受け取った順の全ての操作（差分の送信に使う）
+    operations: Vec<CrdtOperation>,
+    // レプリカごとの受け取り済みの連番
+    seen: HashMap<String, BTreeSet<u64>>,
+    lamport: u64,
+    name: Option<(Stamp, String)>,
+    elements: BTreeMap<ElementKey, Element>,
+    // 削除された追加の操作（削除より後に届いた追加を無視するため）
+    removed: HashSet<Dot>,
+    // この状態を反映したファイルの版
+    pub revision: u64,
+    // この状態を反映した保存済みのファイル（版番号は削除の後に再び使われるため内容で比べる）
+    pub base: Option<File>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Element {
+    // 削除されていない追加の操作（空の場合は要素が存在しない）
+    dots: BTreeSet<Dot>,
+    value: Option<(Stamp, Value)>,
+    // 最初に追加された操作（並び順に使う）
+    first: Option<Stamp>,
+}
+
+impl Element {
+    fn visible(&self) -> bool {
+        !self.dots.is_empty() && self.value.is_some()
+    }
+}
+
+impl CrdtDiagram {
+    pub fn new() -> Self {
+        Self {
+            replica_id: format!("{}:{}", SERVER_REPLICA, uuid::Uuid::new_v4()),
+            operations: Vec::new(),
+            seen: HashMap::new(),
+            lamport: 0,
+            name: None,
+            elements: BTreeMap::new(),
+            removed: HashSet::new(),
+            revision: 0,
+            base: None,
+        }
+    }
+
+    // 操作を反映（受け取り済みの場合はfalse）
+    // 操作はvalidateで検査済みであること
+    pub fn apply(&mut self, operation: CrdtOperation) -> bool {
+        let (Some(dot), Some(body)) = (&operation.dot, &operation.body) else {
+            return false;
+        };
+        if !self
+            .seen
+            .entry(dot.replica_id.clone())
+            .or_default()
+            .insert(dot.counter)
+        {
+            return false;
+        }
+        self.lamport = self.lamport.max(operation.lamport);
+
+        let id: Dot = (dot.replica_id.clone(), dot.counter);
+        let stamp: Stamp = (operation.lamport, dot.replica_id.clone());
+        match body {
+            Body::SetFileName(name) => {
+                if self
+                    .name
+                    .as_ref()
+                    .is_none_or(|(current, _)| *current < stamp)
+                {
+                    self.name = Some((stamp, name.clone()));
+                }
+            }
+            Body::Put(put) => {
+                if let (Some(element), Some(value)) = (&put.element, &put.value) {
+                    let removed = self.removed.contains(&id);
+                    let entry = self.elements.entry(element_key(element)).or_default();
+                    if !removed {
+                        entry.dots.insert(id);
+                    }
+                    if entry
+                        .value
+                        .as_ref()
+                        .is_none_or(|(current, _)| *current < stamp)
+                    {
+                        entry.value = Some((stamp.clone(), value.clone()));
+                    }
+                    if entry.first.as_ref().is_none_or(|first| stamp < *first) {
+                        entry.first = Some(stamp);
+                    }
+                }
+            }
+            Body::Remove(remove) => {
+                if let Some(element) = &remove.element {
+                    let entry = self.elements.entry(element_key(element)).or_default();
+                    for observed in &remove.observed {
+                        let observed: Dot = (observed.replica_id.clone(), observed.counter);
+                        entry.dots.remove(&observed);
+                        self.removed.insert(observed);
+                    }
+                }
+            }
+        }
+        self.operations.push(operation);
+        true
+    }
+
+    // ファイルとの違いをサーバのレプリカの操作として反映
+    pub fn update_from(&mut self, file: &File) {
+        if self.name.as_ref().map(|(_, name)| name) != Some(&file.name) {
+            self.local(Body::SetFileName(file.name.clone()));
+        }
+
+        let class_ids: HashSet<&str> = file.classes.iter().map(|class| class.id.as_str()).collect();
+        let removed_classes: Vec<String> = self
+            .visible(CrdtElementKind::Class, None)
+            .into_iter()
+            .map(|(class_id, _, _)| class_id)
+            .filter(|class_id| !class_ids.contains(class_id.as_str()))
+            .collect();
+        for class_id in removed_classes {
+            self.remove(CrdtElementKind::Class, &class_id, "");
+        }
+
+        for class in &file.classes {
+            self.put(
+                CrdtElementKind::Class,
+                &class.id,
+                Value::ClassName(class.name.clone()),
+            );
+            let members = class
+                .attributes
+                .iter()
+                .map(|attribute| Value::Attribute(attribute.clone()))
+                .chain(
+                    class
+                        .methods
+                        .iter()
+                        .map(|method| Value::Method(method.clone())),
+                )
+                .chain(
+                    relations(class)
+                        .iter()
+                        .map(|relation| Value::Relation(relation.clone())),
+                );
+            let mut wanted: HashSet<(i32, String)> = HashSet::new();
+            for value in members {
+                let kind = value_kind(&value);
+                wanted.insert((kind as i32, value_key(&value)));
+                self.put(kind, &class.id, value);
+            }
+
+            for kind in [
+                CrdtElementKind::Attribute,
+                CrdtElementKind::Method,
+                CrdtElementKind::Relation,
+            ] {
+                let stale: Vec<String> = self
+                    .visible(kind, Some(&class.id))
+                    .into_iter()
+                    .map(|(_, key, _)| key)
+                    .filter(|key| !wanted.contains(&(kind as i32, key.clone())))
+                    .collect();
+                for key in stale {
+                    self.remove(kind, &class.id, &key);
+                }
+            }
+        }
+    }
+
+    // 現在の状態のファイル（ファイルID・日時は呼び出し側で設定する）
+    // 関係先のクラスが存在しない関係は含めない
+    pub fn to_file(&self) -> File {
+        let class_ids: HashSet<String> = self
+            .visible(CrdtElementKind::Class, None)
+            .into_iter()
+            .map(|(class_id, _, _)| class_id)
+            .collect();
+
+        let classes = self
+            .visible(CrdtElementKind::Class, None)
+            .into_iter()
+            .map(|(class_id, _, value)| {
+                let mut class = Class {
+                    name: match value {
+                        Value::ClassName(name) => name.clone(),
+                        _ => String::new(),
+                    },
+                    ..Default::default()
+                };
+                for (_, _, value) in self.visible(CrdtElementKind::Attribute, Some(&class_id)) {
+                    if let Value::Attribute(attribute) = value {
+                        class.attributes.push(attribute.clone());
+                    }
+                }
+                for (_, _, value) in self.visible(CrdtElementKind::Method, Some(&class_id)) {
+                    if let Value::Method(method) = value {
+                        class.methods.push(method.clone());
+                    }
+                }
+                let relation_infos: Vec<_> = self
+                    .visible(CrdtElementKind::Relation, Some(&class_id))
+                    .into_iter()
+                    .filter_map(|(_, _, value)| match value {
+                        Value::Relation(relation)
+                            if class_ids.contains(&relation.target_class_id) =>
+                        {
+                            Some(relation.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                class.relations =
+                    (!relation_infos.is_empty()).then_some(RelationInfoList { relation_infos });
+                class.id = class_id;
+                class
+            })
+            .collect();
+
+        File {
+            name: self
+                .name
+                .as_ref()
+                .map(|(_, name)| name.clone())
+                .unwrap_or_default(),
+            classes,
+            ..Default::default()
+        }
+    }
+
+    // レプリカごとに、1から途切れずに受け取っている最後の連番
+    pub fn state_vector(&self) -> CrdtStateVector {
+        let mut entries: Vec<CrdtDot> = self
+            .seen
+            .iter()
+            .map(|(replica_id, counters)| CrdtDot {
+                replica_id: replica_id.clone(),
+                counter: contiguous(counters),
+            })
+            .filter(|dot| dot.counter > 0)
+            .collect();
+        entries.sort_by(|a, b| a.replica_id.cmp(&b.replica_id));
+        CrdtStateVector { entries }
+    }
+
+    // 状態ベクトルに含まれない操作
+    pub fn delta(&self, state_vector: &CrdtStateVector) -> Vec<CrdtOperation> {
+        let known: HashMap<&str, u64> = state_vector
+            .entries
+            .iter()
+            .map(|dot| (dot.replica_id.as_str(), dot.counter))
+            .collect();
+        self.operations
+            .iter()
+            .filter(|operation| {
+                operation.dot.as_ref().is_some_and(|dot| {
+                    dot.counter > known.get(dot.replica_id.as_str()).copied().unwrap_or(0)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    // 存在する要素（最初に追加された順）の（クラスID、key、値）
+    fn visible(
+        &self,
+        kind: CrdtElementKind,
+        class_id: Option<&str>,
+    ) -> Vec<(String, String, &Value)> {
+        let mut found: Vec<(&Stamp, String, String, &Value)> = self
+            .elements
+            .iter()
+            .filter(|((element_kind, element_class, _), element)| {
+                *element_kind == kind as i32
+                    && class_id.is_none_or(|class_id| element_class == class_id)
+                    && element.visible()
+            })
+            .filter_map(|((_, element_class, key), element)| {
+                let (_, value) = element.value.as_ref()?;
+                Some((
+                    element.first.as_ref()?,
+                    element_class.clone(),
+                    key.clone(),
+                    value,
+                ))
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(b.0));
+        found
+            .into_iter()
+            .map(|(_, class_id, key, value)| (class_id, key, value))
+            .collect()
+    }
+
+    // 値が異なる（または存在しない）場合だけ追加・変更する
+    fn put(&mut self, kind: CrdtElementKind, class_id: &str, value: Value) {
+        let key = (kind as i32, class_id.to_string(), value_key(&value));
+        if self.elements.get(&key).is_some_and(|element| {
+            element.visible() && element.value.as_ref().map(|(_, v)| v) == Some(&value)
+        }) {
+            return;
+        }
+        self.local(Body::Put(CrdtPut {
+            element: Some(CrdtElement {
+                kind: kind as i32,
+                class_id: class_id.to_string(),
+                key: key.2,
+            }),
+            value: Some(value),
+        }));
+    }
+
+    fn remove(&mut self, kind: CrdtElementKind, class_id: &str, key: &str) {
+        let observed = self
+            .elements
+            .get(&(kind as i32, class_id.to_string(), key.to_string()))
+            .map(|element| {
+                element
+                    .dots
+                    .iter()
+                    .map(|(replica_id, counter)| CrdtDot {
+                        replica_id: replica_id.clone(),
+                        counter: *counter,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.local(Body::Remove(CrdtRemove {
+            element: Some(CrdtElement {
+                kind: kind as i32,
+                class_id: class_id.to_string(),
+                key: key.to_string(),
+            }),
+            observed,
+        }));
+    }
+
+    // サーバのレプリカの操作を作成して反映
+    fn local(&mut self, body: Body) {
+        let counter = self
+            .seen
+            .get(&self.replica_id)
+            .map_or(0, |counters| counters.last().copied().unwrap_or(0))
+            + 1;
+        let operation = CrdtOperation {
+            dot: Some(CrdtDot {
+                replica_id: self.replica_id.clone(),
+                counter,
+            }),
+            lamport: self.lamport + 1,
+            body: Some(body),
+        };
+        self.apply(operation);
+    }
+}
+
+// クライアントから受け取った操作の検査
+pub fn validate(operation: &CrdtOperation) -> Result<(), String> {
+    let dot = operation.dot.as_ref().ok_or("dot is required")?;
+    if dot.replica_id.is_empty() || dot.counter == 0 {
+        return Err("dot must have a replica_id and a counter starting at 1".to_string());
+    }
+    if dot.replica_id == SERVER_REPLICA
+        || dot
+            .replica_id
+            .strip_prefix(SERVER_REPLICA)
+            .is_some_and(|rest| rest.starts_with(':'))
+    {
+        return Err(format!(
+            "replica_id \"{}\" and \"{}:*\" are reserved",
+            SERVER_REPLICA, SERVER_REPLICA
+        ));
+    }
+
+    let element = match operation.body.as_ref().ok_or("body is required")? {
+        Body::SetFileName(_) => return Ok(()),
+        Body::Put(put) => {
+            let element = put.element.as_ref().ok_or("element is required")?;
+            let value = put.value.as_ref().ok_or("value is required")?;
+            if element.kind != value_kind(value) as i32 {
+                return Err("element kind does not match the value".to_string());
+            }
+            let key = value_key(value);
+            if element.key != key {
+                return Err(format!("element key must be \"{}\"", key));
+            }
+            element
+        }
+        Body::Remove(remove) => remove.element.as_ref().ok_or("element is required")?,
+    };
+    if element.class_id.is_empty() {
+        return Err("class_id is required".to_string());
+    }
+    CrdtElementKind::try_from(element.kind).map_err(|_| "unknown element kind".to_string())?;
+    Ok(())
+}
+
+fn element_key(element: &CrdtElement) -> ElementKey {
+    (element.kind, element.class_id.clone(), element.key.clone())
+}
+
+fn value_kind(value: &Value) -> CrdtElementKind {
+    match value {
+        Value::ClassName(_) => CrdtElementKind::Class,
+        Value::Attribute(_) => CrdtElementKind::Attribute,
+        Value::Method(_) => CrdtElementKind::Method,
+        Value::Relation(_) => CrdtElementKind::Relation,
+    }
+}
+
+// 値から求める要素のkey
+fn value_key(value: &Value) -> String {
+    match value {
+        Value::ClassName(_) => String::new(),
+        Value::Attribute(attribute) => attribute.name.clone(),
+        Value::Method(method) => method_signature(method),
+        Value::Relation(relation) => format!("{}/{}", relation.target_class_id, relation.relation),
+    }
+}
+
+fn contiguous(counters: &BTreeSet<u64>) -> u64 {
+    let mut last = 0;
+    for &counter in counters {
+        if counter != last + 1 {
+            break;
+        }
+        last = counter;
+    }
+    last
+}
+
+impl Default for CrdtDiagram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ファイルIDごとのCRDT（サーバの起動後、最初の同期の時に保存されている版から作る）
+// 同期はファイルごとにロックし、他のファイルの同期を待たせない
+#[derive(Debug, Default)]
+pub struct CrdtDiagrams {
+    diagrams: Mutex<HashMap<String, Arc<AsyncMutex<CrdtDiagram>>>>,
+}
+
+impl CrdtDiagrams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // ファイルのCRDT（無い場合は空のCRDTを登録する）
+    // 同期の間はこのCRDTをロックする
+    pub fn get(&self, file_id: &str) -> Arc<AsyncMutex<CrdtDiagram>> {
+        Arc::clone(self.lock().entry(file_id.to_string()).or_default())
+    }
+
+    // 削除したファイルのCRDTを破棄する（同じIDで作り直したファイルに使わない）
+    pub fn remove(&self, file_id: &str) {
+        self.lock().remove(file_id);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<AsyncMutex<CrdtDiagram>>>> {
+        self.diagrams
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::class::Variable;
+
+    fn operation(replica_id: &str, counter: u64, lamport: u64, body: Body) -> CrdtOperation {
+        CrdtOperation {
+            dot: Some(CrdtDot {
+                replica_id: replica_id.to_string(),
+                counter,
+            }),
+            lamport,
+            body: Some(body),
+        }
+    }
+
+    fn put(kind: CrdtElementKind, class_id: &str, value: Value) -> Body {
+        Body::Put(CrdtPut {
+            element: Some(CrdtElement {
+                kind: kind as i32,
+                class_id: class_id.to_string(),
+                key: value_key(&value),
+            }),
+            value: Some(value),
+        })
+    }
+
+    fn attribute(name: &str) -> Value {
+        Value::Attribute(Variable {
+            name: name.to_string(),
+            r#type: "String".to_string(),
+            ..Default::default()
+        })
+    }
+
+    // 2つのレプリカの同時の編集
+    // a2の属性はb2で削除され、a4の属性は削除の時点で見えていないため残る
+    fn operations() -> Vec<CrdtOperation> {
+        let class = |name: &str| Value::ClassName(name.to_string());
+        vec![
+            operation("a", 1, 1, put(CrdtElementKind::Class, "c1", class("User"))),
+            operation(
+                "a",
+                2,
+                2,
+                put(CrdtElementKind::Attribute, "c1", attribute("id")),
+            ),
+            operation("b", 1, 1, put(CrdtElementKind::Class, "c2", class("Order"))),
+            operation(
+                "b",
+                2,
+                3,
+                Body::Remove(CrdtRemove {
+                    element: Some(CrdtElement {
+                        kind: CrdtElementKind::Attribute as i32,
+                        class_id: "c1".to_string(),
+                        key: "id".to_string(),
+                    }),
+                    observed: vec![CrdtDot {
+                        replica_id: "a".to_string(),
+                        counter: 2,
+                    }],
+                }),
+            ),
+            operation("a", 3, 3, Body::SetFileName("Shop".to_string())),
+            operation(
+                "b",
+                3,
+                4,
+                put(CrdtElementKind::Class, "c1", class("Account")),
+            ),
+            operation(
+                "a",
+                4,
+                3,
+                put(CrdtElementKind::Attribute, "c1", attribute("name")),
+            ),
+        ]
+    }
+
+    fn apply_all(operations: impl IntoIterator<Item = CrdtOperation>) -> CrdtDiagram {
+        let mut diagram = CrdtDiagram::new();
+        for operation in operations {
+            diagram.apply(operation);
+        }
+        diagram
+    }
+
+    #[test]
+    fn converges_regardless_of_delivery_order() {
+        let operations = operations();
+        let expected = apply_all(operations.clone()).to_file();
+
+        let mut reversed = operations.clone();
+        reversed.reverse();
+        // 削除（b2）が追加（a2）より先に届く
+        let remove_first = [3, 1, 0, 2, 6, 5, 4].map(|index| operations[index].clone());
+        // 重複して届く
+        let duplicated = operations
+            .iter()
+            .chain(operations.iter().rev())
+            .chain(operations.iter().step_by(2))
+            .cloned();
+
+        assert_eq!(apply_all(reversed).to_file(), expected);
+        assert_eq!(apply_all(remove_first).to_file(), expected);
+        assert_eq!(apply_all(duplicated).to_file(), expected);
+
+        assert_eq!(expected.name, "Shop");
+        let classes: Vec<(&str, &str)> = expected
+            .classes
+            .iter()
+            .map(|class| (class.id.as_str(), class.name.as_str()))
+            .collect();
+        assert_eq!(classes, [("c1", "Account"), ("c2", "Order")]);
+        let attributes: Vec<&str> = expected.classes[0]
+            .attributes
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+        assert_eq!(attributes, ["name"]);
+    }
+
+    #[test]
+    fn duplicate_operations_are_ignored() {
+        let operations = operations();
+        let mut diagram = CrdtDiagram::new();
+        assert!(diagram.apply(operations[0].clone()));
+        assert!(!diagram.apply(operations[0].clone()));
+    }
+
+    #[test]
+    fn delta_fills_in_missing_operations() {
+        let operations = operations();
+        let server = apply_all(operations.clone());
+        // a2を受け取らずにa3・a4を受け取ったクライアント
+        let mut client = apply_all([0, 2, 4, 6].map(|index| operations[index].clone()));
+
+        let state_vector = client.state_vector();
+        let counters: Vec<(&str, u64)> = state_vector
+            .entries
+            .iter()
+            .map(|dot| (dot.replica_id.as_str(), dot.counter))
+            .collect();
+        assert_eq!(counters, [("a", 1), ("b", 1)]);
+
+        for operation in server.delta(&state_vector) {
+            client.apply(operation);
+        }
+        assert_eq!(client.to_file(), server.to_file());
+        assert_eq!(client.state_vector(), server.state_vector());
+        assert!(server.delta(&client.state_vector()).is_empty());
+    }
+
+    #[test]
+    fn rebuilt_server_replica_is_not_hidden_by_old_state_vector() {
+        let file = apply_all(operations()).to_file();
+        let before_restart = {
+            let mut diagram = CrdtDiagram::new();
+            diagram.update_from(&file);
+            diagram
+        };
+        let mut after_restart = CrdtDiagram::new();
+        after_restart.update_from(&file);
+
+        // 再起動の前の状態ベクトルを持つクライアントにも作り直したCRDTの操作を全て送る
+        let delta = after_restart.delta(&before_restart.state_vector());
+        assert_eq!(delta.len(), after_restart.operations.len());
+        assert_eq!(after_restart.to_file(), file);
+    }
+
+    #[test]
+    fn server_replica_ids_are_reserved() {
+        let class = || {
+            put(
+                CrdtElementKind::Class,
+                "c1",
+                Value::ClassName("User".to_string()),
+            )
+        };
+        assert!(validate(&operation("client", 1, 1, class())).is_ok());
+        assert!(validate(&operation(SERVER_REPLICA, 1, 1, class())).is_err());
+        assert!(validate(&operation("server:1234", 1, 1, class())).is_err());
+    }
+}
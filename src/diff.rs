@@ -0,0 +1,614 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::model::{format_multiplicity, RelationKind, Visibility};
+use crate::server::class::{Class, File, Method, Multiplicity, RelationInfo, Variable};
+use crate::server::diagram_ext::{
+    ChangeKind, ClassChange, DiagramDiff, MemberChange, RelationChange, ValueChange,
+};
+
+// 2つのファイルの構造の差分（baseからtargetへの変更）
+// クラスはClass.id、属性は名前、メソッドは名前と引数の型、関係は関係先と種類で対応付ける
+// 版番号は呼び出し側で設定する
+pub fn diff(base: &File, target: &File) -> DiagramDiff {
+    let mut file_changes = Vec::new();
+    compare(&mut file_changes, "name", &base.name, &target.name);
+
+    let base_classes: HashMap<&str, &Class> = base
+        .classes
+        .iter()
+        .map(|class| (class.id.as_str(), class))
+        .collect();
+    let target_ids: HashSet<&str> = target
+        .classes
+        .iter()
+        .map(|class| class.id.as_str())
+        .collect();
+
+    // 変更後の順に並べ、削除されたクラスは最後に置く
+    let mut classes = Vec::new();
+    for class in &target.classes {
+        match base_classes.get(class.id.as_str()) {
+            Some(old) => {
+                if let Some(change) = diff_class(old, class) {
+                    classes.push(change);
+                }
+            }
+            None => classes.push(ClassChange {
+                kind: ChangeKind::Added as i32,
+                class_id: class.id.clone(),
+                name: class.name.clone(),
+                ..Default::default()
+            }),
+        }
+    }
+    for class in &base.classes {
+        if !target_ids.contains(class.id.as_str()) {
+            classes.push(ClassChange {
+                kind: ChangeKind::Removed as i32,
+                class_id: class.id.clone(),
+                name: class.name.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    DiagramDiff {
+        file_changes,
+        classes,
+        ..Default::default()
+    }
+}
+
+// 同じIDのクラスの差分（変更が無い場合はNone）
+fn diff_class(old: &Class, new: &Class) -> Option<ClassChange> {
+    let attributes = pair(
+        &old.attributes,
+        &new.attributes,
+        |attribute| attribute.name.clone(),
+        |attribute| attribute.name.clone(),
+    )
+    .into_iter()
+    .filter_map(|(old, new)| {
+        member_change(
+            old,
+            new,
+            |attribute| attribute.name.clone(),
+            attribute_changes,
+        )
+    })
+    .collect::<Vec<_>>();
+
+    let methods = pair(&old.methods, &new.methods, method_signature, |method| {
+        method.name.clone()
+    })
+    .into_iter()
+    .filter_map(|(old, new)| member_change(old, new, method_signature, method_changes))
+    .collect::<Vec<_>>();
+
+    let relations = pair(
+        relations(old),
+        relations(new),
+        |relation| format!("{}/{}", relation.target_class_id, relation.relation),
+        |relation| relation.target_class_id.clone(),
+    )
+    .into_iter()
+    .filter_map(|(old, new)| relation_change(old, new))
+    .collect::<Vec<_>>();
+
+    let renamed = old.name != new.name;
+    if !renamed && attributes.is_empty() && methods.is_empty() && relations.is_empty() {
+        return None;
+    }
+
+    Some(ClassChange {
+        kind: ChangeKind::Changed as i32,
+        class_id: new.id.clone(),
+        name: new.name.clone(),
+        previous_name: if renamed {
+            old.name.clone()
+        } else {
+            String::new()
+        },
+        attributes,
+        methods,
+        relations,
+    })
+}
+
+// 変更前後の要素を対応付ける（変更後の順、削除された要素は最後）
+// まずexactのキーが一致するもの、残りをlooseのキーが一致するもので対応付ける
+// 同じキーが複数ある場合は現れた順に対応付ける
+pub(crate) fn pair<'a, T>(
+    old: &'a [T],
+    new: &'a [T],
+    exact: impl Fn(&T) -> String,
+    loose: impl Fn(&T) -> String,
+) -> Vec<(Option<&'a T>, Option<&'a T>)> {
+    let mut used = vec![false; old.len()];
+    let mut matched: Vec<Option<usize>> = vec![None; new.len()];
+
+    for key in [&exact as &dyn Fn(&T) -> String, &loose] {
+        for (index, item) in new.iter().enumerate() {
+            if matched[index].is_some() {
+                continue;
+            }
+            let wanted = key(item);
+            let found = old
+                .iter()
+                .enumerate()
+                .position(|(old_index, old_item)| !used[old_index] && key(old_item) == wanted);
+            if let Some(old_index) = found {
+                used[old_index] = true;
+                matched[index] = Some(old_index);
+            }
+        }
+    }
+
+    let mut pairs: Vec<(Option<&T>, Option<&T>)> = new
+        .iter()
+        .zip(&matched)
+        .map(|(item, old_index)| (old_index.map(|old_index| &old[old_index]), Some(item)))
+        .collect();
+    for (old_index, item) in old.iter().enumerate() {
+        if !used[old_index] {
+            pairs.push((Some(item), None));
+        }
+    }
+    pairs
+}
+
+fn member_change<T>(
+    old: Option<&T>,
+    new: Option<&T>,
+    signature: impl Fn(&T) -> String,
+    changes: impl Fn(&T, &T) -> Vec<ValueChange>,
+) -> Option<MemberChange> {
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            let changes = changes(old, new);
+            (!changes.is_empty()).then(|| MemberChange {
+                kind: ChangeKind::Changed as i32,
+                signature: signature(new),
+                changes,
+            })
+        }
+        (None, Some(new)) => Some(MemberChange {
+            kind: ChangeKind::Added as i32,
+            signature: signature(new),
+            changes: Vec::new(),
+        }),
+        (Some(old), None) => Some(MemberChange {
+            kind: ChangeKind::Removed as i32,
+            signature: signature(old),
+            changes: Vec::new(),
+        }),
+        (None, None) => None,
+    }
+}
+
+fn relation_change(
+    old: Option<&RelationInfo>,
+    new: Option<&RelationInfo>,
+) -> Option<RelationChange> {
+    let (kind, relation, changes) = match (old, new) {
+        (Some(old), Some(new)) => {
+            let changes = relation_changes(old, new);
+            if changes.is_empty() {
+                return None;
+            }
+            (ChangeKind::Changed, new, changes)
+        }
+        (None, Some(new)) => (ChangeKind::Added, new, Vec::new()),
+        (Some(old), None) => (ChangeKind::Removed, old, Vec::new()),
+        (None, None) => return None,
+    };
+    Some(RelationChange {
+        kind: kind as i32,
+        target_class_id: relation.target_class_id.clone(),
+        relation: RelationKind::from_i32(relation.relation).name().to_string(),
+        changes,
+    })
+}
+
+fn attribute_changes(old: &Variable, new: &Variable) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    compare(&mut changes, "type", &old.r#type, &new.r#type);
+    compare(
+        &mut changes,
+        "visibility",
+        &optional_visibility(old.visibility),
+        &optional_visibility(new.visibility),
+    );
+    compare(
+        &mut changes,
+        "is_static",
+        &flag(old.is_static),
+        &flag(new.is_static),
+    );
+    changes
+}
+
+fn method_changes(old: &Method, new: &Method) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    compare(
+        &mut changes,
+        "return_type",
+        &old.return_type,
+        &new.return_type,
+    );
+    compare(
+        &mut changes,
+        "visibility",
+        Visibility::from_i32(old.visibility).uml_name(),
+        Visibility::from_i32(new.visibility).uml_name(),
+    );
+    compare(
+        &mut changes,
+        "is_static",
+        &flag(old.is_static),
+        &flag(new.is_static),
+    );
+    compare(
+        &mut changes,
+        "is_abstract",
+        &flag(old.is_abstract),
+        &flag(new.is_abstract),
+    );
+    compare(
+        &mut changes,
+        "parameters",
+        &parameters(&old.parameters),
+        &parameters(&new.parameters),
+    );
+    changes
+}
+
+fn relation_changes(old: &RelationInfo, new: &RelationInfo) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    compare(
+        &mut changes,
+        "relation",
+        RelationKind::from_i32(old.relation).name(),
+        RelationKind::from_i32(new.relation).name(),
+    );
+    compare(
+        &mut changes,
+        "multiplicity_p",
+        &multiplicity(&old.multiplicity_p),
+        &multiplicity(&new.multiplicity_p),
+    );
+    compare(
+        &mut changes,
+        "multiplicity_c",
+        &multiplicity(&old.multiplicity_c),
+        &multiplicity(&new.multiplicity_c),
+    );
+    compare(
+        &mut changes,
+        "role_name_p",
+        old.role_name_p.as_deref().unwrap_or_default(),
+        new.role_name_p.as_deref().unwrap_or_default(),
+    );
+    compare(
+        &mut changes,
+        "role_name_c",
+        old.role_name_c.as_deref().unwrap_or_default(),
+        new.role_name_c.as_deref().unwrap_or_default(),
+    );
+    changes
+}
+
+fn compare(changes: &mut Vec<ValueChange>, field: &str, old: &str, new: &str) {
+    if old != new {
+        changes.push(ValueChange {
+            field: field.to_string(),
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        });
+    }
+}
+
+pub(crate) fn relations(class: &Class) -> &[RelationInfo] {
+    class
+        .relations
+        .as_ref()
+        .map_or(&[], |relations| relations.relation_infos.as_slice())
+}
+
+// find(String, int)
+pub(crate) fn method_signature(method: &Method) -> String {
+    let types: Vec<&str> = method
+        .parameters
+        .iter()
+        .map(|parameter| parameter.r#type.as_str())
+        .collect();
+    format!("{}({})", method.name, types.join(", "))
+}
+
+// id: String, count: int
+pub(crate) fn parameters(parameters: &[Variable]) -> String {
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|parameter| format!("{}: {}", parameter.name, parameter.r#type))
+        .collect();
+    parameters.join(", ")
+}
+
+// 指定されていない可視性は空
+pub(crate) fn optional_visibility(visibility: Option<i32>) -> String {
+    visibility
+        .map(|visibility| Visibility::from_i32(visibility).uml_name().to_string())
+        .unwrap_or_default()
+}
+
+// 指定されていないフラグはfalseと同じとみなす
+pub(crate) fn flag(value: Option<bool>) -> String {
+    value.unwrap_or(false).to_string()
+}
+
+pub(crate) fn multiplicity(multiplicity: &Option<Multiplicity>) -> String {
+    multiplicity
+        .as_ref()
+        .map(format_multiplicity)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::class::RelationInfoList;
+
+    fn variable(name: &str, type_name: &str) -> Variable {
+        Variable {
+            name: name.to_string(),
+            r#type: type_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn method(name: &str, parameters: &[(&str, &str)], return_type: &str) -> Method {
+        Method {
+            name: name.to_string(),
+            return_type: return_type.to_string(),
+            parameters: parameters
+                .iter()
+                .map(|(name, type_name)| variable(name, type_name))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn relation(target: &str, kind: RelationKind) -> RelationInfo {
+        RelationInfo {
+            target_class_id: target.to_string(),
+            relation: kind.to_i32(),
+            ..Default::default()
+        }
+    }
+
+    fn class(id: &str, name: &str) -> Class {
+        Class {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn file(classes: Vec<Class>) -> File {
+        File {
+            name: "Shop".to_string(),
+            classes,
+            ..Default::default()
+        }
+    }
+
+    fn value(field: &str, old: &str, new: &str) -> ValueChange {
+        ValueChange {
+            field: field.to_string(),
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        }
+    }
+
+    fn kinds(diff: &DiagramDiff) -> Vec<(i32, &str)> {
+        diff.classes
+            .iter()
+            .map(|class| (class.kind, class.class_id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn same_file_has_no_changes() {
+        let mut order = class("order", "Order");
+        order.attributes.push(variable("id", "UUID"));
+        let file = file(vec![order, class("line", "OrderLine")]);
+
+        assert_eq!(diff(&file, &file), DiagramDiff::default());
+    }
+
+    #[test]
+    fn keeps_target_order_and_puts_removed_classes_last() {
+        let base = file(vec![class("a", "A"), class("b", "B"), class("c", "C")]);
+        let mut target = file(vec![
+            class("d", "D"),
+            class("c", "Renamed"),
+            class("a", "A"),
+        ]);
+        target.name = "Store".to_string();
+
+        let diff = diff(&base, &target);
+        assert_eq!(diff.file_changes, vec![value("name", "Shop", "Store")]);
+        assert_eq!(
+            kinds(&diff),
+            vec![
+                (ChangeKind::Added as i32, "d"),
+                (ChangeKind::Changed as i32, "c"),
+                (ChangeKind::Removed as i32, "b"),
+            ]
+        );
+        assert_eq!(diff.classes[1].name, "Renamed");
+        assert_eq!(diff.classes[1].previous_name, "C");
+        assert!(diff.classes[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn pairs_attributes_by_name() {
+        let mut old = class("order", "Order");
+        old.attributes = vec![variable("id", "int"), variable("note", "String")];
+        let mut new = class("order", "Order");
+        new.attributes = vec![
+            Variable {
+                visibility: Some(Visibility::Private.to_i32()),
+                is_static: Some(false),
+                ..variable("id", "UUID")
+            },
+            variable("total", "int"),
+        ];
+
+        let diff = diff(&file(vec![old]), &file(vec![new]));
+        let change = &diff.classes[0];
+        assert_eq!(change.previous_name, "");
+        assert_eq!(
+            change.attributes,
+            vec![
+                MemberChange {
+                    kind: ChangeKind::Changed as i32,
+                    signature: "id".to_string(),
+                    // 指定されていないフラグはfalseと同じ
+                    changes: vec![
+                        value("type", "int", "UUID"),
+                        value("visibility", "", "private"),
+                    ],
+                },
+                MemberChange {
+                    kind: ChangeKind::Added as i32,
+                    signature: "total".to_string(),
+                    changes: Vec::new(),
+                },
+                MemberChange {
+                    kind: ChangeKind::Removed as i32,
+                    signature: "note".to_string(),
+                    changes: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_overloads_by_signature_before_name() {
+        let mut old = class("order", "Order");
+        old.methods = vec![
+            method("find", &[("id", "int")], "Order"),
+            method("find", &[("name", "String")], "Order"),
+            method("total", &[], "int"),
+        ];
+        let mut new = class("order", "Order");
+        new.methods = vec![
+            method("find", &[("key", "String")], "Order"),
+            method("total", &[("tax", "bool")], "long"),
+        ];
+
+        let diff = diff(&file(vec![old]), &file(vec![new]));
+        let methods: Vec<(i32, &str, &[ValueChange])> = diff.classes[0]
+            .methods
+            .iter()
+            .map(|method| {
+                (
+                    method.kind,
+                    method.signature.as_str(),
+                    method.changes.as_slice(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            methods,
+            vec![
+                (
+                    ChangeKind::Changed as i32,
+                    "find(String)",
+                    &[value("parameters", "name: String", "key: String")][..]
+                ),
+                (
+                    ChangeKind::Changed as i32,
+                    "total(bool)",
+                    &[
+                        value("return_type", "int", "long"),
+                        value("parameters", "", "tax: bool"),
+                    ][..]
+                ),
+                (ChangeKind::Removed as i32, "find(int)", &[][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_relations_by_target() {
+        let mut old = class("order", "Order");
+        old.relations = Some(RelationInfoList {
+            relation_infos: vec![
+                relation("line", RelationKind::Aggregation),
+                relation("customer", RelationKind::Association),
+            ],
+        });
+        let mut new = class("order", "Order");
+        new.relations = Some(RelationInfoList {
+            relation_infos: vec![
+                RelationInfo {
+                    multiplicity_c: Some(Multiplicity {
+                        lower: 1,
+                        upper: None,
+                    }),
+                    role_name_c: Some("lines".to_string()),
+                    ..relation("line", RelationKind::Composition)
+                },
+                relation("customer", RelationKind::Association),
+                relation("address", RelationKind::Dependency),
+            ],
+        });
+
+        let diff = diff(&file(vec![old]), &file(vec![new]));
+        assert_eq!(
+            diff.classes[0].relations,
+            vec![
+                RelationChange {
+                    kind: ChangeKind::Changed as i32,
+                    target_class_id: "line".to_string(),
+                    relation: "composition".to_string(),
+                    changes: vec![
+                        value("relation", "aggregation", "composition"),
+                        value("multiplicity_c", "", "1..*"),
+                        value("role_name_c", "", "lines"),
+                    ],
+                },
+                RelationChange {
+                    kind: ChangeKind::Added as i32,
+                    target_class_id: "address".to_string(),
+                    relation: "dependency".to_string(),
+                    changes: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pair_matches_duplicates_in_order() {
+        let old = ["a", "b", "a"];
+        let new = ["a", "c", "a", "a"];
+        let key = |item: &&str| item.to_string();
+        let pairs = pair(&old, &new, key, key);
+        let pairs: Vec<(Option<&str>, Option<&str>)> = pairs
+            .into_iter()
+            .map(|(old, new)| (old.copied(), new.copied()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (Some("a"), Some("a")),
+                (None, Some("c")),
+                (Some("a"), Some("a")),
+                (None, Some("a")),
+                (Some("b"), None),
+            ]
+        );
+    }
+}
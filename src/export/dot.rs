@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+use super::{class_aliases, end_label};
+use crate::model::{display_name, is_abstract_class, RelationKind, Visibility};
+use crate::server::class::{File, Method, Variable};
+
+// ファイルをGraphvizのDOTに変換
+// クラスはrecord形式のノード（名前|属性|メソッド）、関係は関係元から関係先への辺で表す
+pub fn to_dot(file: &File) -> String {
+    let aliases = class_aliases(file);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "digraph \"{}\" {{", escape_string(&file.name));
+    // 親クラス・全体側が上になるように下から上へ配置
+    out.push_str("  rankdir=BT;\n");
+    out.push_str("  node [shape=record, fontname=\"Helvetica\", fontsize=10];\n");
+    out.push_str("  edge [fontname=\"Helvetica\", fontsize=9];\n");
+
+    for (index, class) in file.classes.iter().enumerate() {
+        let mut header = String::new();
+        if is_abstract_class(class) {
+            header.push_str("«abstract»\\n");
+        }
+        header.push_str(&escape_record(display_name(class)));
+
+        let attributes: String = class
+            .attributes
+            .iter()
+            .map(|attribute| format!("{}\\l", escape_record(&attribute_line(attribute))))
+            .collect();
+        let methods: String = class
+            .methods
+            .iter()
+            .map(|method| format!("{}\\l", escape_record(&method_line(method))))
+            .collect();
+
+        let _ = writeln!(
+            out,
+            "  C{} [label=\"{{{}|{}|{}}}\"];",
+            index, header, attributes, methods
+        );
+    }
+
+    for (index, class) in file.classes.iter().enumerate() {
+        let Some(relations) = &class.relations else {
+            continue;
+        };
+
+        for relation in &relations.relation_infos {
+            let Some(target) = aliases.get(relation.target_class_id.as_str()) else {
+                let _ = writeln!(
+                    out,
+                    "  // unresolved relation target: {}",
+                    escape_string(&relation.target_class_id)
+                );
+                continue;
+            };
+
+            let kind = RelationKind::from_i32(relation.relation);
+            // rankdir=BTでは継承・実現の矢印が親（上）を向く
+            let mut attributes = vec![match kind {
+                RelationKind::Inheritance => "arrowhead=empty".to_string(),
+                RelationKind::Realization => "arrowhead=empty, style=dashed".to_string(),
+                RelationKind::Aggregation => "arrowhead=odiamond".to_string(),
+                RelationKind::Composition => "arrowhead=diamond".to_string(),
+                RelationKind::Dependency => "arrowhead=vee, style=dashed".to_string(),
+                RelationKind::Association => "arrowhead=vee".to_string(),
+            }];
+
+            let source = format!("C{}", index);
+            let source_label = end_label(
+                relation.multiplicity_p.as_ref(),
+                relation.role_name_p.as_deref(),
+            );
+            let target_label = end_label(
+                relation.multiplicity_c.as_ref(),
+                relation.role_name_c.as_deref(),
+            );
+
+            // 集約・コンポジションは全体側（関係元）を上にするため、部分から全体への辺にする
+            let (from, from_label, to, to_label) = match kind {
+                RelationKind::Aggregation | RelationKind::Composition => {
+                    (target.as_str(), target_label, source.as_str(), source_label)
+                }
+                _ => (source.as_str(), source_label, target.as_str(), target_label),
+            };
+            if let Some(label) = from_label {
+                attributes.push(format!("taillabel=\"{}\"", escape_string(&label)));
+            }
+            if let Some(label) = to_label {
+                attributes.push(format!("headlabel=\"{}\"", escape_string(&label)));
+            }
+
+            let _ = writeln!(out, "  {} -> {} [{}];", from, to, attributes.join(", "));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn attribute_line(attribute: &Variable) -> String {
+    let mut line = String::new();
+    if let Some(visibility) = attribute.visibility {
+        line.push(Visibility::from_i32(visibility).symbol());
+    }
+    line.push_str(&attribute.name);
+    if !attribute.r#type.is_empty() {
+        let _ = write!(line, " : {}", attribute.r#type);
+    }
+    if attribute.is_static.unwrap_or(false) {
+        line.push_str(" {static}");
+    }
+    line
+}
+
+fn method_line(method: &Method) -> String {
+    let parameters: Vec<String> = method
+        .parameters
+        .iter()
+        .map(|parameter| {
+            if parameter.r#type.is_empty() {
+                parameter.name.clone()
+            } else {
+                format!("{} : {}", parameter.name, parameter.r#type)
+            }
+        })
+        .collect();
+
+    let mut line = format!(
+        "{}{}({})",
+        Visibility::from_i32(method.visibility).symbol(),
+        method.name,
+        parameters.join(", ")
+    );
+    if !method.return_type.is_empty() {
+        let _ = write!(line, " : {}", method.return_type);
+    }
+    if method.is_abstract.unwrap_or(false) {
+        line.push_str(" {abstract}");
+    }
+    if method.is_static.unwrap_or(false) {
+        line.push_str(" {static}");
+    }
+    line
+}
+
+// record形式のラベルで特別な意味を持つ文字をエスケープ
+fn escape_record(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_string(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,117 @@
+use std::fmt::Write;
+
+use super::{class_aliases, relation_edges};
+use crate::model::{display_name, is_abstract_class, Visibility};
+use crate::server::class::{File, Method, Variable};
+
+// ファイルをMermaidのclassDiagramに変換
+pub fn to_mermaid(file: &File) -> String {
+    let aliases = class_aliases(file);
+    let mut out = String::new();
+
+    if !file.name.is_empty() {
+        let _ = writeln!(out, "---\ntitle: {}\n---", file.name);
+    }
+    out.push_str("classDiagram\n");
+
+    for (index, class) in file.classes.iter().enumerate() {
+        // クラス名は空白などを含むことがあるため、別名にラベルとして付ける
+        let alias = format!("C{}", index);
+        let _ = writeln!(
+            out,
+            "    class {}[\"{}\"]",
+            alias,
+            escape(display_name(class))
+        );
+        if is_abstract_class(class) {
+            let _ = writeln!(out, "    <<abstract>> {}", alias);
+        }
+        for attribute in &class.attributes {
+            let _ = writeln!(out, "    {} : {}", alias, attribute_line(attribute));
+        }
+        for method in &class.methods {
+            let _ = writeln!(out, "    {} : {}", alias, method_line(method));
+        }
+    }
+
+    for edge in relation_edges(file, &aliases) {
+        match edge {
+            Ok(edge) => {
+                let _ = writeln!(
+                    out,
+                    "    {}{} {}{} {}",
+                    edge.left,
+                    quoted(edge.left_label),
+                    edge.arrow,
+                    quoted(edge.right_label),
+                    edge.right
+                );
+            }
+            Err(target) => {
+                let _ = writeln!(out, "    %% unresolved relation target: {}", target);
+            }
+        }
+    }
+
+    out
+}
+
+// 例: -List~Order~ orders, +int count$
+fn attribute_line(attribute: &Variable) -> String {
+    let mut line = String::new();
+    if let Some(visibility) = attribute.visibility {
+        line.push(Visibility::from_i32(visibility).symbol());
+    }
+    if !attribute.r#type.is_empty() {
+        let _ = write!(line, "{} ", generic(&attribute.r#type));
+    }
+    line.push_str(&attribute.name);
+    if attribute.is_static.unwrap_or(false) {
+        line.push('$');
+    }
+    line
+}
+
+// 例: +find(UUID id) Order, #validate() bool*
+fn method_line(method: &Method) -> String {
+    let mut line = String::new();
+    line.push(Visibility::from_i32(method.visibility).symbol());
+
+    let parameters: Vec<String> = method
+        .parameters
+        .iter()
+        .map(|parameter| {
+            if parameter.r#type.is_empty() {
+                parameter.name.clone()
+            } else {
+                format!("{} {}", generic(&parameter.r#type), parameter.name)
+            }
+        })
+        .collect();
+    let _ = write!(line, "{}({})", method.name, parameters.join(", "));
+
+    if !method.return_type.is_empty() {
+        let _ = write!(line, " {}", generic(&method.return_type));
+    }
+    if method.is_abstract.unwrap_or(false) {
+        line.push('*');
+    } else if method.is_static.unwrap_or(false) {
+        line.push('$');
+    }
+    line
+}
+
+// Mermaidでは型引数を ~ で囲む（List<Order> → List~Order~）
+fn generic(type_name: &str) -> String {
+    type_name.replace(['<', '>'], "~")
+}
+
+fn quoted(label: Option<String>) -> String {
+    label
+        .map(|label| format!(" \"{}\"", escape(&label)))
+        .unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('"', "'")
+}
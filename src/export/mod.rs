@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use crate::model::RelationKind;
+use crate::server::class::{File, Multiplicity};
+use crate::server::diagram_ext::{ExportFormat, ExportedDiagram};
+
+pub mod dot;
+pub mod mermaid;
+pub mod plantuml;
+pub mod xmi;
+
+// 保存されたファイルを指定した形式に変換
+pub fn export(file: &File, format: ExportFormat) -> ExportedDiagram {
+    let (content, media_type, extension) = match format {
+        ExportFormat::Plantuml => (
+            plantuml::to_plantuml(file),
+            "text/plain; charset=utf-8",
+            "puml",
+        ),
+        ExportFormat::Mermaid => (
+            mermaid::to_mermaid(file),
+            "text/vnd.mermaid; charset=utf-8",
+            "mmd",
+        ),
+        ExportFormat::Dot => (dot::to_dot(file), "text/vnd.graphviz; charset=utf-8", "dot"),
+        ExportFormat::Svg => (crate::render::svg::to_svg(file), "image/svg+xml", "svg"),
+        ExportFormat::Xmi => (xmi::to_xmi(file), "application/vnd.xmi+xml", "xmi"),
+    };
+
+    ExportedDiagram {
+        content,
+        media_type: media_type.to_string(),
+        file_name: format!("{}.{}", file_stem(file), extension),
+    }
+}
+
+// ダウンロード時のファイル名（ファイル名に使えない文字は _ に置き換える）
+pub(crate) fn file_stem(file: &File) -> String {
+    let stem: String = file
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "diagram".to_string()
+    } else {
+        stem
+    }
+}
+
+// クラスIDは識別子として使えない文字を含むことがあるため、出力では位置に基づく別名を使う
+// IDが重複している場合は最初のクラスを指す
+pub(crate) fn class_aliases(file: &File) -> HashMap<&str, String> {
+    let mut aliases = HashMap::new();
+    for (index, class) in file.classes.iter().enumerate() {
+        aliases
+            .entry(class.id.as_str())
+            .or_insert_with(|| format!("C{}", index));
+    }
+    aliases
+}
+
+// PlantUML・Mermaidで共通の関係の表記（左 "ラベル" 矢印 "ラベル" 右）
+pub(crate) struct RelationEdge<'a> {
+    pub left: &'a str,
+    pub left_label: Option<String>,
+    pub arrow: &'static str,
+    pub right: &'a str,
+    pub right_label: Option<String>,
+}
+
+// 全クラスの関係を別名を使った表記に変換
+// 関係先が見つからない場合はErrに関係先のIDを入れる
+pub(crate) fn relation_edges<'a>(
+    file: &'a File,
+    aliases: &'a HashMap<&str, String>,
+) -> Vec<Result<RelationEdge<'a>, &'a str>> {
+    let mut edges = Vec::new();
+    for class in &file.classes {
+        let Some(relations) = &class.relations else {
+            continue;
+        };
+        let source = aliases[class.id.as_str()].as_str();
+
+        for relation in &relations.relation_infos {
+            let Some(target) = aliases.get(relation.target_class_id.as_str()) else {
+                edges.push(Err(relation.target_class_id.as_str()));
+                continue;
+            };
+            let target = target.as_str();
+
+            let source_label = end_label(
+                relation.multiplicity_p.as_ref(),
+                relation.role_name_p.as_deref(),
+            );
+            let target_label = end_label(
+                relation.multiplicity_c.as_ref(),
+                relation.role_name_c.as_deref(),
+            );
+
+            // 継承・実現は親を左に置いて矢印を親側に向ける
+            let (left, left_label, arrow, right, right_label) =
+                match RelationKind::from_i32(relation.relation) {
+                    RelationKind::Inheritance => {
+                        (target, target_label, "<|--", source, source_label)
+                    }
+                    RelationKind::Realization => {
+                        (target, target_label, "<|..", source, source_label)
+                    }
+                    RelationKind::Aggregation => {
+                        (source, source_label, "o--", target, target_label)
+                    }
+                    RelationKind::Composition => {
+                        (source, source_label, "*--", target, target_label)
+                    }
+                    RelationKind::Dependency => (source, source_label, "..>", target, target_label),
+                    RelationKind::Association => {
+                        (source, source_label, "-->", target, target_label)
+                    }
+                };
+
+            edges.push(Ok(RelationEdge {
+                left,
+                left_label,
+                arrow,
+                right,
+                right_label,
+            }));
+        }
+    }
+    edges
+}
+
+// 関係の端に表示するラベル（ロール名と多重度）
+pub(crate) fn end_label(multiplicity: Option<&Multiplicity>, role: Option<&str>) -> Option<String> {
+    let multiplicity = multiplicity.map(crate::model::format_multiplicity);
+    let role = role.filter(|role| !role.is_empty());
+
+    match (role, multiplicity) {
+        (Some(role), Some(multiplicity)) => Some(format!("{} {}", role, multiplicity)),
+        (Some(role), None) => Some(role.to_string()),
+        (None, Some(multiplicity)) => Some(multiplicity),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Write;
+
+use super::{class_aliases, relation_edges};
+use crate::model::{display_name, is_abstract_class, Visibility};
+use crate::server::class::{File, Method, Variable};
+
+// ファイルをPlantUMLのクラス図に変換
+pub fn to_plantuml(file: &File) -> String {
+    let aliases = class_aliases(file);
+    let mut out = String::new();
+
+    out.push_str("@startuml\n");
+    if !file.name.is_empty() {
+        let _ = writeln!(out, "title {}", file.name);
+    }
+
+    for (index, class) in file.classes.iter().enumerate() {
+        let keyword = if is_abstract_class(class) {
+            "abstract class"
+        } else {
+            "class"
+        };
+        let _ = writeln!(
+            out,
+            "{} \"{}\" as C{} {{",
+            keyword,
+            escape(display_name(class)),
+            index
+        );
+        for attribute in &class.attributes {
+            let _ = writeln!(out, "  {}", attribute_line(attribute));
+        }
+        for method in &class.methods {
+            let _ = writeln!(out, "  {}", method_line(method));
+        }
+        out.push_str("}\n");
+    }
+
+    for edge in relation_edges(file, &aliases) {
+        match edge {
+            Ok(edge) => {
+                let _ = writeln!(
+                    out,
+                    "{}{} {}{} {}",
+                    edge.left,
+                    quoted(edge.left_label),
+                    edge.arrow,
+                    quoted(edge.right_label),
+                    edge.right
+                );
+            }
+            Err(target) => {
+                let _ = writeln!(out, "' unresolved relation target: {}", target);
+            }
+        }
+    }
+
+    out.push_str("@enduml\n");
+    out
+}
+
+fn attribute_line(attribute: &Variable) -> String {
+    let mut line = String::new();
+    if attribute.is_static.unwrap_or(false) {
+        line.push_str("{static} ");
+    }
+    if let Some(visibility) = attribute.visibility {
+        line.push(Visibility::from_i32(visibility).symbol());
+    }
+    line.push_str(&attribute.name);
+    if !attribute.r#type.is_empty() {
+        let _ = write!(line, " : {}", attribute.r#type);
+    }
+    line
+}
+
+fn method_line(method: &Method) -> String {
+    let mut line = String::new();
+    if method.is_static.unwrap_or(false) {
+        line.push_str("{static} ");
+    }
+    if method.is_abstract.unwrap_or(false) {
+        line.push_str("{abstract} ");
+    }
+    line.push(Visibility::from_i32(method.visibility).symbol());
+
+    let parameters: Vec<String> = method
+        .parameters
+        .iter()
+        .map(|parameter| {
+            if parameter.r#type.is_empty() {
+                parameter.name.clone()
+            } else {
+                format!("{} : {}", parameter.name, parameter.r#type)
+            }
+        })
+        .collect();
+    let _ = write!(line, "{}({})", method.name, parameters.join(", "));
+
+    if !method.return_type.is_empty() {
+        let _ = write!(line, " : {}", method.return_type);
+    }
+    line
+}
+
+// 関係の端のラベルは " " で囲み、矢印との間に空白を入れる
+fn quoted(label: Option<String>) -> String {
+    label
+        .map(|label| format!(" \"{}\"", escape(&label)))
+        .unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('"', "'")
+}
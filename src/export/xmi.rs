@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::model::{is_abstract_class, RelationKind, Visibility};
+use crate::server::class::{File, Multiplicity, RelationInfo, Variable};
+
+// 出力するXMI・UMLの名前空間（XMI 2.5.1 / UML 2.5.1）
+const XMI_NAMESPACE: &str = "http://www.omg.org/spec/XMI/20131001";
+const UML_NAMESPACE: &str = "http://www.omg.org/spec/UML/20161101";
+
+// ファイルをUMLモデルのXMIに変換
+// xmi:idは位置に基づく別名（C0, C0_a0 など）とし、クラスIDはxmi:uuidに入れる
+// 属性・引数の型はクラス名と一致すればそのクラス、それ以外はuml:DataTypeを参照する
+pub fn to_xmi(file: &File) -> String {
+    let mut out = String::new();
+    let mut types = TypeTable::new(file);
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<xmi:XMI xmi:version=\"2.5.1\" xmlns:xmi=\"{}\" xmlns:uml=\"{}\">",
+        XMI_NAMESPACE, UML_NAMESPACE
+    );
+    let _ = writeln!(
+        out,
+        "  <uml:Model xmi:type=\"uml:Model\" xmi:id=\"model\" name=\"{}\">",
+        escape(&file.name)
+    );
+
+    // クラスIDから別名（IDが重複している場合は最初のクラス）
+    let mut alias_of: HashMap<&str, String> = HashMap::new();
+    for (index, class) in file.classes.iter().enumerate() {
+        alias_of
+            .entry(class.id.as_str())
+            .or_insert_with(|| format!("C{}", index));
+    }
+
+    // 関係ごとの要素は、継承以外はクラスの外に並べる
+    let mut relations = String::new();
+    let mut relation_count = 0;
+
+    for (index, class) in file.classes.iter().enumerate() {
+        let alias = format!("C{}", index);
+        let _ = write!(
+            out,
+            "    <packagedElement xmi:type=\"uml:Class\" xmi:id=\"{}\" xmi:uuid=\"{}\" name=\"{}\"",
+            alias,
+            escape(&class.id),
+            escape(&class.name)
+        );
+        if is_abstract_class(class) {
+            out.push_str(" isAbstract=\"true\"");
+        }
+        out.push_str(">\n");
+
+        for relation in class
+            .relations
+            .iter()
+            .flat_map(|relations| &relations.relation_infos)
+        {
+            let id = format!("R{}", relation_count);
+            relation_count += 1;
+            let Some(target) = alias_of.get(relation.target_class_id.as_str()) else {
+                let _ = writeln!(
+                    relations,
+                    "    <!-- unresolved relation target: {} -->",
+                    escape(&relation.target_class_id.replace("--", "- -"))
+                );
+                continue;
+            };
+
+            match RelationKind::from_i32(relation.relation) {
+                RelationKind::Inheritance => {
+                    let _ = writeln!(
+                        out,
+                        "      <generalization xmi:type=\"uml:Generalization\" xmi:id=\"{}\" general=\"{}\"/>",
+                        id, target
+                    );
+                }
+                RelationKind::Realization => {
+                    write_dependency(&mut relations, "uml:Realization", &id, &alias, target);
+                }
+                RelationKind::Dependency => {
+                    write_dependency(&mut relations, "uml:Dependency", &id, &alias, target);
+                }
+                kind => write_association(&mut relations, kind, &id, &alias, target, relation),
+            }
+        }
+
+        for (attribute_index, attribute) in class.attributes.iter().enumerate() {
+            let _ = write!(
+                out,
+                "      <ownedAttribute xmi:type=\"uml:Property\" xmi:id=\"{}_a{}\" name=\"{}\"",
+                alias,
+                attribute_index,
+                escape(&attribute.name)
+            );
+            write_visibility(&mut out, attribute.visibility);
+            write_static(&mut out, attribute.is_static);
+            write_type(&mut out, &mut types, &attribute.r#type);
+            out.push_str("/>\n");
+        }
+
+        for (method_index, method) in class.methods.iter().enumerate() {
+            let method_id = format!("{}_o{}", alias, method_index);
+            let _ = write!(
+                out,
+                "      <ownedOperation xmi:type=\"uml:Operation\" xmi:id=\"{}\" name=\"{}\"",
+                method_id,
+                escape(&method.name)
+            );
+            write_visibility(&mut out, Some(method.visibility));
+            write_static(&mut out, method.is_static);
+            if method.is_abstract.unwrap_or(false) {
+                out.push_str(" isAbstract=\"true\"");
+            }
+            out.push_str(">\n");
+
+            for (parameter_index, parameter) in method.parameters.iter().enumerate() {
+                write_parameter(
+                    &mut out,
+                    &mut types,
+                    &format!("{}_p{}", method_id, parameter_index),
+                    Some(parameter),
+                    &parameter.r#type,
+                );
+            }
+            if !method.return_type.is_empty() {
+                write_parameter(
+                    &mut out,
+                    &mut types,
+                    &format!("{}_r", method_id),
+                    None,
+                    &method.return_type,
+                );
+            }
+            out.push_str("      </ownedOperation>\n");
+        }
+
+        out.push_str("    </packagedElement>\n");
+    }
+
+    out.push_str(&relations);
+    for (name, id) in types.data_types {
+        let _ = writeln!(
+            out,
+            "    <packagedElement xmi:type=\"uml:DataType\" xmi:id=\"{}\" name=\"{}\"/>",
+            id,
+            escape(&name)
+        );
+    }
+
+    out.push_str("  </uml:Model>\n");
+    out.push_str("</xmi:XMI>\n");
+    out
+}
+
+// 型名から参照先のxmi:idへの対応（クラス名以外の型はuml:DataTypeとして出力する）
+struct TypeTable {
+    classes: HashMap<String, String>,
+    // 出力順を固定するため登場順に並べる
+    data_types: Vec<(String, String)>,
+}
+
+impl TypeTable {
+    fn new(file: &File) -> Self {
+        let mut classes = HashMap::new();
+        for (index, class) in file.classes.iter().enumerate() {
+            classes
+                .entry(class.name.clone())
+                .or_insert_with(|| format!("C{}", index));
+        }
+        Self {
+            classes,
+            data_types: Vec::new(),
+        }
+    }
+
+    fn id(&mut self, type_name: &str) -> String {
+        if let Some(id) = self.classes.get(type_name) {
+            return id.clone();
+        }
+        if let Some((_, id)) = self.data_types.iter().find(|(name, _)| name == type_name) {
+            return id.clone();
+        }
+        let id = format!("T{}", self.data_types.len());
+        self.data_types.push((type_name.to_string(), id.clone()));
+        id
+    }
+}
+
+fn write_visibility(out: &mut String, visibility: Option<i32>) {
+    if let Some(visibility) = visibility {
+        let _ = write!(
+            out,
+            " visibility=\"{}\"",
+            Visibility::from_i32(visibility).uml_name()
+        );
+    }
+}
+
+fn write_static(out: &mut String, is_static: Option<bool>) {
+    if is_static.unwrap_or(false) {
+        out.push_str(" isStatic=\"true\"");
+    }
+}
+
+fn write_type(out: &mut String, types: &mut TypeTable, type_name: &str) {
+    if !type_name.is_empty() {
+        let _ = write!(out, " type=\"{}\"", types.id(type_name));
+    }
+}
+
+// 引数（parameterがNoneの場合は戻り値）
+fn write_parameter(
+    out: &mut String,
+    types: &mut TypeTable,
+    id: &str,
+    parameter: Option<&Variable>,
+    type_name: &str,
+) {
+    let _ = write!(
+        out,
+        "        <ownedParameter xmi:type=\"uml:Parameter\" xmi:id=\"{}\"",
+        id
+    );
+    match parameter {
+        Some(parameter) => {
+            let _ = write!(
+                out,
+                " name=\"{}\" direction=\"in\"",
+                escape(&parameter.name)
+            );
+        }
+        None => out.push_str(" direction=\"return\""),
+    }
+    write_type(out, types, type_name);
+    out.push_str("/>\n");
+}
+
+// 実現・依存（clientが関係元、supplierが関係先）
+fn write_dependency(out: &mut String, xmi_type: &str, id: &str, client: &str, supplier: &str) {
+    let _ = writeln!(
+        out,
+        "    <packagedElement xmi:type=\"{}\" xmi:id=\"{}\" client=\"{}\" supplier=\"{}\"/>",
+        xmi_type, id, client, supplier
+    );
+}
+
+// 関連・集約・コンポジション
+// 両端をassociationのownedEndとし、関係先の端を誘導可能にする
+// 集約・コンポジションは部分（関係先）の端にaggregationを付ける
+fn write_association(
+    out: &mut String,
+    kind: RelationKind,
+    id: &str,
+    source: &str,
+    target: &str,
+    relation: &RelationInfo,
+) {
+    let _ = writeln!(
+        out,
+        "    <packagedElement xmi:type=\"uml:Association\" xmi:id=\"{id}\" memberEnd=\"{id}_source {id}_target\" navigableOwnedEnd=\"{id}_target\">",
+    );
+    write_association_end(
+        out,
+        &format!("{}_source", id),
+        id,
+        source,
+        relation.role_name_p.as_deref(),
+        relation.multiplicity_p.as_ref(),
+        None,
+    );
+    let aggregation = match kind {
+        RelationKind::Aggregation => Some("shared"),
+        RelationKind::Composition => Some("composite"),
+        _ => None,
+    };
+    write_association_end(
+        out,
+        &format!("{}_target", id),
+        id,
+        target,
+        relation.role_name_c.as_deref(),
+        relation.multiplicity_c.as_ref(),
+        aggregation,
+    );
+    out.push_str("    </packagedElement>\n");
+}
+
+fn write_association_end(
+    out: &mut String,
+    id: &str,
+    association: &str,
+    type_id: &str,
+    role: Option<&str>,
+    multiplicity: Option<&Multiplicity>,
+    aggregation: Option<&str>,
+) {
+    let _ = write!(
+        out,
+        "      <ownedEnd xmi:type=\"uml:Property\" xmi:id=\"{}\" type=\"{}\" association=\"{}\"",
+        id, type_id, association
+    );
+    if let Some(role) = role.filter(|role| !role.is_empty()) {
+        let _ = write!(out, " name=\"{}\"", escape(role));
+    }
+    if let Some(aggregation) = aggregation {
+        let _ = write!(out, " aggregation=\"{}\"", aggregation);
+    }
+
+    let Some(multiplicity) = multiplicity else {
+        out.push_str("/>\n");
+        return;
+    };
+    out.push_str(">\n");
+    let _ = writeln!(
+        out,
+        "        <lowerValue xmi:type=\"uml:LiteralInteger\" xmi:id=\"{}_lower\" value=\"{}\"/>",
+        id, multiplicity.lower
+    );
+    let upper = multiplicity
+        .upper
+        .map_or_else(|| "*".to_string(), |upper| upper.to_string());
+    let _ = writeln!(
+        out,
+        "        <upperValue xmi:type=\"uml:LiteralUnlimitedNatural\" xmi:id=\"{}_upper\" value=\"{}\"/>",
+        id, upper
+    );
+    out.push_str("      </ownedEnd>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
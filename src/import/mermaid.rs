@@ -0,0 +1,565 @@
+use std::collections::HashMap;
+
+use super::{
+    char_count, parse_quoted, parse_relation_name, parse_signature, split_keyword, Arrow,
+    DiagramBuilder, ImportError, Signature,
+};
+use crate::model::Visibility;
+use crate::server::class::{File, Method, Variable};
+
+// <<interface>> などの注釈
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Annotation {
+    Interface,
+    Enumeration,
+}
+
+// 読み込み中の { } のブロック
+enum Block {
+    // クラスの本体（メンバの並び）
+    Class { key: String, line: usize },
+    // namespace
+    Group,
+}
+
+// MermaidのclassDiagramをファイルに変換
+// 対応する構文: front matterのtitle、class の宣言（~T~・["ラベル"]）と本体、
+// 「クラス : メンバ」、<<interface>> などの注釈、関係（カーディナリティ付き）、namespace
+// Markdownのコードブロック（```mermaid）で囲まれていてもよい
+pub fn from_mermaid(source: &str) -> Result<File, ImportError> {
+    let mut parser = Parser::default();
+    let mut started = false;
+    let mut front_matter = false;
+    let mut last_line = 0;
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        last_line = line;
+        let text = raw.trim();
+        let column = char_count(&raw[..raw.len() - raw.trim_start().len()]) + 1;
+
+        // --- で囲まれたfront matter（title: のみ使う）
+        if front_matter {
+            if text == "---" {
+                front_matter = false;
+            } else if let Some(title) = text.strip_prefix("title:") {
+                parser.builder.set_name(title.trim().trim_matches('"'));
+            }
+            continue;
+        }
+        if text.is_empty() || text.starts_with("%%") || text.starts_with("```") {
+            continue;
+        }
+        if !started {
+            if text == "---" {
+                front_matter = true;
+                continue;
+            }
+            let (keyword, rest) = split_keyword(text);
+            if keyword != "classDiagram" && keyword != "classDiagram-v2" {
+                return Err(ImportError::new(line, column, "expected classDiagram"));
+            }
+            started = true;
+            if rest.trim().is_empty() {
+                continue;
+            }
+            return Err(ImportError::new(
+                line,
+                column + char_count(keyword) + 1,
+                format!("unexpected text after {}", keyword),
+            ));
+        }
+
+        if let Some(Block::Class { key, .. }) = parser.blocks.last() {
+            let key = key.clone();
+            if text == "}" {
+                parser.blocks.pop();
+            } else if let Some(annotation) = parse_annotation(text) {
+                let annotation = annotation.map_err(|(offset, message)| {
+                    ImportError::new(line, column + offset, message)
+                })?;
+                parser.annotate(&key, annotation);
+            } else {
+                parser
+                    .add_member(&key, text)
+                    .map_err(|message| ImportError::new(line, column, message))?;
+            }
+            continue;
+        }
+
+        if text == "}" {
+            if parser.blocks.pop().is_none() {
+                return Err(ImportError::new(line, column, "unexpected '}'"));
+            }
+            continue;
+        }
+
+        parser
+            .statement(text, line)
+            .map_err(|(offset, message)| ImportError::new(line, column + offset, message))?;
+    }
+
+    if front_matter {
+        return Err(ImportError::new(last_line, 1, "front matter is not closed"));
+    }
+    if !started {
+        return Err(ImportError::new(
+            last_line.max(1),
+            1,
+            "expected classDiagram",
+        ));
+    }
+    if let Some(Block::Class { key, line }) = parser
+        .blocks
+        .iter()
+        .find(|block| matches!(block, Block::Class { .. }))
+    {
+        return Err(ImportError::new(
+            *line,
+            1,
+            format!("body of class {} is not closed", key),
+        ));
+    }
+
+    Ok(parser.finish())
+}
+
+#[derive(Default)]
+struct Parser {
+    builder: DiagramBuilder,
+    // 注釈の付いたクラス（注釈はメンバより後に書かれることがあるため最後に反映する）
+    annotations: HashMap<String, Annotation>,
+    blocks: Vec<Block>,
+}
+
+impl Parser {
+    // クラス本体の外の1行を処理する
+    // エラーは行頭からの文字数とメッセージ
+    fn statement(&mut self, text: &str, line: usize) -> Result<(), (usize, String)> {
+        let offset = |rest: &str| char_count(&text[..text.len() - rest.len()]);
+        let (keyword, rest) = split_keyword(text);
+
+        match keyword {
+            "class" => return self.declaration(rest, text, line),
+            "namespace" => {
+                if !text.ends_with('{') {
+                    return Err((offset(""), "expected '{' after namespace".to_string()));
+                }
+                self.blocks.push(Block::Group);
+                return Ok(());
+            }
+            "direction" | "note" | "click" | "link" | "callback" | "style" | "classDef"
+            | "cssClass" | "title" | "accTitle" | "accDescr" => return Ok(()),
+            _ => {}
+        }
+
+        // <<interface>> クラス名
+        if let Some(annotation) = parse_annotation(text) {
+            let annotation = annotation?;
+            let rest = &text[text.find(">>").map_or(0, |end| end + 2)..];
+            let (key, after) = parse_name(rest.trim_start())
+                .ok_or_else(|| (offset(rest), "class name is missing".to_string()))?;
+            if !after.trim().is_empty() {
+                return Err((offset(after), format!("unexpected text: {}", after.trim())));
+            }
+            self.builder.class(&key);
+            self.annotate(&key, annotation);
+            return Ok(());
+        }
+
+        // 関係（A "1" --> "*" B : label）
+        let (left, after_left) =
+            parse_name(text).ok_or_else(|| (0, format!("unrecognized statement: {}", text)))?;
+        let after_left = after_left.trim_start();
+        let (left_label, after_label) = parse_quoted(after_left);
+        let arrow_text = after_label.trim_start();
+        if let Some((arrow, length)) = parse_arrow(arrow_text) {
+            let rest = arrow_text[length..].trim_start();
+            let (right_label, rest) = parse_quoted(rest);
+            let rest = rest.trim_start();
+            let (right, rest) = parse_name(rest)
+                .ok_or_else(|| (offset(rest), "relation target is missing".to_string()))?;
+            let rest = rest.trim_start();
+            if !rest.is_empty() && !rest.starts_with(':') {
+                return Err((
+                    offset(rest),
+                    format!("unexpected text after relation: {}", rest),
+                ));
+            }
+
+            self.builder.add_arrow_relation(
+                &left,
+                left_label,
+                &arrow,
+                &right,
+                right_label,
+                parse_relation_name(rest),
+            );
+            return Ok(());
+        }
+        if left_label.is_none() && !arrow_text.starts_with(':') {
+            if let Some(c) = arrow_text.chars().next() {
+                if matches!(c, '<' | '-' | '.' | '*' | '|') {
+                    return Err((offset(arrow_text), "invalid relation arrow".to_string()));
+                }
+            }
+        }
+
+        // クラス : メンバ
+        if left_label.is_none() {
+            if let Some(member) = after_left.strip_prefix(':') {
+                let member_offset = offset(member);
+                self.builder.class(&left);
+                return self
+                    .add_member(&left, member.trim())
+                    .map_err(|message| (member_offset, message));
+            }
+        }
+
+        Err((0, format!("unrecognized statement: {}", text)))
+    }
+
+    // class 名前~T~["ラベル"]:::style {
+    fn declaration(&mut self, rest: &str, text: &str, line: usize) -> Result<(), (usize, String)> {
+        let offset = |rest: &str| char_count(&text[..text.len() - rest.len()]);
+        let rest = rest.trim_start();
+        let (key, mut rest) =
+            parse_name(rest).ok_or_else(|| (offset(rest), "class name is missing".to_string()))?;
+
+        let mut name = key.clone();
+        // 型引数（~T~）
+        if rest.starts_with('~') {
+            let limit = rest.find(['[', '{']).unwrap_or(rest.len());
+            let end = rest[..limit]
+                .rfind('~')
+                .filter(|&end| end > 0)
+                .ok_or_else(|| (offset(rest), "generic type is not closed".to_string()))?;
+            name = format!("{}{}", key, generic(&rest[..=end]));
+            rest = &rest[end + 1..];
+        }
+        // 表示名（["ラベル"]）
+        if let Some(after) = rest.strip_prefix('[') {
+            let (label, after) = parse_quoted(after);
+            let label =
+                label.ok_or_else(|| (offset(after), "class label must be quoted".to_string()))?;
+            let after = after
+                .strip_prefix(']')
+                .ok_or_else(|| (offset(after), "expected ']'".to_string()))?;
+            name = label.to_string();
+            rest = after;
+        }
+        // スタイル（:::name）
+        if let Some(after) = rest.strip_prefix(":::") {
+            rest = after.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '-');
+        }
+
+        if name == key {
+            self.builder.class(&key);
+        } else {
+            self.builder.declare(&key, &name);
+        }
+
+        match rest.trim() {
+            "" | "{}" => Ok(()),
+            "{" => {
+                self.blocks.push(Block::Class { key, line });
+                Ok(())
+            }
+            other => Err((
+                offset(rest.trim_start()),
+                format!("unexpected text in class declaration: {}", other),
+            )),
+        }
+    }
+
+    fn annotate(&mut self, key: &str, annotation: Option<Annotation>) {
+        if let Some(annotation) = annotation {
+            self.annotations.insert(key.to_string(), annotation);
+        }
+    }
+
+    // メンバを1つ追加（括弧を含む場合はメソッド）
+    // 末尾の $ は静的、* は抽象
+    fn add_member(&mut self, key: &str, text: &str) -> Result<(), String> {
+        let mut text = text.trim();
+        let mut is_static = false;
+        let mut is_abstract = false;
+        if let Some(rest) = text.strip_suffix('$') {
+            is_static = true;
+            text = rest.trim_end();
+        } else if let Some(rest) = text.strip_suffix('*') {
+            is_abstract = true;
+            text = rest.trim_end();
+        }
+        // 戻り値の型の前に書かれた場合（create()$ Animal）
+        let text = match text.find(")$").or_else(|| text.find(")*")) {
+            Some(index) => {
+                if &text[index + 1..index + 2] == "$" {
+                    is_static = true;
+                } else {
+                    is_abstract = true;
+                }
+                format!("{}{}", &text[..=index], &text[index + 2..])
+            }
+            None => text.to_string(),
+        };
+        let mut text = text.as_str();
+
+        let visibility = text.chars().next().and_then(Visibility::from_symbol);
+        if visibility.is_some() {
+            text = text[1..].trim_start();
+        }
+
+        let class = self.builder.class(key);
+        match parse_signature(&generic(text))? {
+            Signature::Attribute(variable) => class.attributes.push(Variable {
+                visibility: visibility.map(Visibility::to_i32),
+                is_static: Some(is_static),
+                ..variable
+            }),
+            Signature::Method(method) => class.methods.push(Method {
+                visibility: visibility.unwrap_or(Visibility::Public).to_i32(),
+                is_abstract: Some(is_abstract),
+                is_static: Some(is_static),
+                ..method
+            }),
+        }
+        Ok(())
+    }
+
+    // 注釈を反映してファイルを返す
+    // インターフェースのメソッドは静的でなければ抽象メソッド、列挙型の値は静的な属性
+    fn finish(mut self) -> File {
+        for (key, annotation) in std::mem::take(&mut self.annotations) {
+            let class = self.builder.class(&key);
+            match annotation {
+                Annotation::Interface => {
+                    for method in &mut class.methods {
+                        if !method.is_static.unwrap_or(false) {
+                            method.is_abstract = Some(true);
+                        }
+                    }
+                }
+                Annotation::Enumeration => {
+                    for attribute in &mut class.attributes {
+                        attribute.is_static = Some(true);
+                    }
+                }
+            }
+        }
+        self.builder.finish()
+    }
+}
+
+// <<...>> で始まる行の注釈（クラス図に反映しない注釈はNone）
+fn parse_annotation(text: &str) -> Option<Result<Option<Annotation>, (usize, String)>> {
+    let after = text.strip_prefix("<<")?;
+    let Some(end) = after.find(">>") else {
+        return Some(Err((0, "annotation is not closed".to_string())));
+    };
+    let annotation = match after[..end].trim().to_lowercase().as_str() {
+        "interface" => Some(Annotation::Interface),
+        "enumeration" | "enum" => Some(Annotation::Enumeration),
+        _ => None,
+    };
+    Some(Ok(annotation))
+}
+
+// クラス名（識別子、または `...` で囲んだ名前）
+fn parse_name(text: &str) -> Option<(String, &str)> {
+    if let Some(after) = text.strip_prefix('`') {
+        let end = after.find('`')?;
+        return Some((after[..end].to_string(), &after[end + 1..]));
+    }
+
+    let end = text
+        .char_indices()
+        .find(|&(_, c)| !(c.is_alphanumeric() || c == '_'))
+        .map_or(text.len(), |(index, _)| index);
+    if end == 0 {
+        return None;
+    }
+    Some((text[..end].to_string(), &text[end..]))
+}
+
+// 矢印（<|--, *--, o--, -->, ..>, ..|> など）を解釈し、矢印の長さ（バイト数）と共に返す
+fn parse_arrow(text: &str) -> Option<(Arrow, usize)> {
+    const LEFT_HEADS: [&str; 4] = ["<|", "*", "o", "<"];
+    const RIGHT_HEADS: [&str; 4] = ["|>", "*", "o", ">"];
+
+    let left = LEFT_HEADS
+        .into_iter()
+        .find(|head| {
+            text.strip_prefix(head)
+                .is_some_and(|rest| rest.starts_with(['-', '.']))
+        })
+        .unwrap_or("");
+    let rest = &text[left.len()..];
+    let (dashed, rest) = if let Some(rest) = rest.strip_prefix("--") {
+        (false, rest)
+    } else if let Some(rest) = rest.strip_prefix("..") {
+        (true, rest)
+    } else {
+        return None;
+    };
+
+    // o は名前の先頭と区別するため、後ろに名前が続かない場合のみ記号とする
+    let right = RIGHT_HEADS
+        .into_iter()
+        .find(|head| {
+            rest.strip_prefix(head).is_some_and(|after| {
+                !(*head == "o" && after.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+            })
+        })
+        .unwrap_or("");
+    let length = text.len() - rest.len() + right.len();
+
+    Some((
+        Arrow {
+            left,
+            right,
+            dashed,
+        },
+        length,
+    ))
+}
+
+// Mermaidの型引数の表記を<>に戻す（List~Order~ → List<Order>、List~List~int~~ → List<List<int>>）
+// 名前が続く ~ は開き、それ以外は閉じとみなす
+fn generic(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            let opens = chars
+                .peek()
+                .is_some_and(|next| next.is_alphanumeric() || *next == '_');
+            result.push(if opens { '<' } else { '>' });
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::RelationKind;
+    use crate::server::class::{Class, RelationInfo};
+
+    fn class<'a>(file: &'a File, name: &str) -> &'a Class {
+        file.classes
+            .iter()
+            .find(|class| class.name == name)
+            .unwrap_or_else(|| panic!("class {} is missing", name))
+    }
+
+    fn relations<'a>(file: &'a File, name: &str) -> &'a [RelationInfo] {
+        class(file, name)
+            .relations
+            .as_ref()
+            .map_or(&[], |relations| &relations.relation_infos)
+    }
+
+    #[test]
+    fn keeps_relation_name_as_target_role() {
+        let file = from_mermaid("classDiagram\nA \"1\" *-- \"0..*\" B : has\n").unwrap();
+
+        let relation = &relations(&file, "A")[0];
+        assert_eq!(relation.target_class_id, class(&file, "B").id);
+        assert_eq!(relation.relation, RelationKind::Composition.to_i32());
+        assert_eq!(relation.role_name_c.as_deref(), Some("has"));
+        assert_eq!(relation.role_name_p, None);
+    }
+
+    #[test]
+    fn reads_fenced_diagram_with_front_matter() {
+        let source = "\
+```mermaid
+---
+title: Zoo
+---
+classDiagram
+  %% comment
+  namespace Animals {
+    class Animal~T~ {
+      <<interface>>
+      +List~List~T~~ children
+      +create()$ Animal
+      +speak() String
+    }
+  }
+  class Dog[\"Good Dog\"]:::pet
+  Dog : -int age$
+  Animal <|.. Dog
+  <<enumeration>> Color
+  Color : RED
+```
+";
+        let file = from_mermaid(source).unwrap();
+        assert_eq!(file.name, "Zoo");
+
+        let animal = class(&file, "Animal<T>");
+        assert_eq!(animal.attributes[0].r#type, "List<List<T>>");
+        let methods: Vec<(&str, &str, Option<bool>, Option<bool>)> = animal
+            .methods
+            .iter()
+            .map(|method| {
+                (
+                    method.name.as_str(),
+                    method.return_type.as_str(),
+                    method.is_static,
+                    method.is_abstract,
+                )
+            })
+            .collect();
+        assert_eq!(
+            methods,
+            vec![
+                ("create", "Animal", Some(true), Some(false)),
+                ("speak", "String", Some(false), Some(true)),
+            ]
+        );
+
+        let dog = class(&file, "Good Dog");
+        assert_eq!(dog.attributes[0].name, "age");
+        assert_eq!(dog.attributes[0].is_static, Some(true));
+        assert_eq!(
+            relations(&file, "Good Dog")[0].relation,
+            RelationKind::Realization.to_i32()
+        );
+
+        // 列挙型の値は静的な属性
+        assert_eq!(class(&file, "Color").attributes[0].is_static, Some(true));
+    }
+
+    #[test]
+    fn lowercase_o_target_is_not_an_arrow_head() {
+        let file = from_mermaid("classDiagram\nA --o B\nC --order\n").unwrap();
+        assert_eq!(
+            relations(&file, "B")[0].relation,
+            RelationKind::Aggregation.to_i32()
+        );
+        assert_eq!(
+            relations(&file, "C")[0].target_class_id,
+            class(&file, "order").id
+        );
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let error = from_mermaid("graph TD\n").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 1));
+
+        let error = from_mermaid("classDiagram\n  class A {\n  +name\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.message.contains("not closed"));
+
+        let error = from_mermaid("classDiagram\n  A --> B extra\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+
+        let error = from_mermaid("classDiagram\n}\n").unwrap_err();
+        assert_eq!(error.message, "unexpected '}'");
+    }
+}
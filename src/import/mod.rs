@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::model::{parse_multiplicity, RelationKind};
+use crate::server::class::{
+    Class, File, Method, Multiplicity, RelationInfo, RelationInfoList, Variable,
+};
+use crate::server::diagram_ext::ImportFormat;
+
+pub mod mermaid;
+pub mod plantuml;
+pub mod xmi;
+
+// インポートできなかった箇所（行・列は1から数える）
+#[derive(Debug, Clone)]
+pub struct ImportError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ImportError {
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+// 他のツールの形式のテキストをファイルに変換
+// ファイルID・日時は設定しない（保存時に呼び出し側で設定する）
+pub fn import(source: &str, format: ImportFormat) -> Result<File, ImportError> {
+    match format {
+        ImportFormat::Plantuml => plantuml::from_plantuml(source),
+        ImportFormat::Mermaid => mermaid::from_mermaid(source),
+        ImportFormat::Xmi => xmi::from_xmi(source),
+    }
+}
+
+// 新しいクラス・ファイルのID
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// 関係の矢印の両端の記号（<|, *, o, < など、無い場合は空）と線の種類
+pub(crate) struct Arrow {
+    pub left: &'static str,
+    pub right: &'static str,
+    pub dashed: bool,
+}
+
+// メンバの可視性・修飾子を除いた部分
+pub(crate) enum Signature {
+    Attribute(Variable),
+    Method(Method),
+}
+
+// 関係の端のロール名と多重度
+#[derive(Debug, Default)]
+pub(crate) struct RelationEnd {
+    pub role: Option<String>,
+    pub multiplicity: Option<Multiplicity>,
+}
+
+// 関係の端のラベル（例: "items 1..*"）をロール名と多重度に分ける
+// エクスポートのend_labelと同じく多重度は最後に書かれている前提
+pub(crate) fn parse_end_label(label: &str) -> RelationEnd {
+    let label = label.trim();
+    let (role, multiplicity) = match label.rsplit_once(char::is_whitespace) {
+        Some((role, last)) => match parse_multiplicity(last) {
+            Some(multiplicity) => (role.trim(), Some(multiplicity)),
+            None => (label, None),
+        },
+        None => match parse_multiplicity(label) {
+            Some(multiplicity) => ("", Some(multiplicity)),
+            None => (label, None),
+        },
+    };
+
+    RelationEnd {
+        role: (!role.is_empty()).then(|| role.to_string()),
+        multiplicity,
+    }
+}
+
+// テキスト上の名前（別名）でクラスを参照しながらファイルを組み立てる
+// 宣言されずに関係で参照されたクラスも作成する
+#[derive(Debug, Default)]
+pub(crate) struct DiagramBuilder {
+    name: String,
+    classes: Vec<Class>,
+    index_of: HashMap<String, usize>,
+}
+
+impl DiagramBuilder {
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    // 名前（別名）のクラス、まだ無い場合は同じ表示名で作成
+    pub fn class(&mut self, key: &str) -> &mut Class {
+        let index = self.position(key);
+        &mut self.classes[index]
+    }
+
+    // 別名と表示名が異なるクラスを宣言（表示名でも参照できるようにする）
+    pub fn declare(&mut self, key: &str, name: &str) -> &mut Class {
+        let index = self.position(key);
+        self.index_of.entry(name.to_string()).or_insert(index);
+        let class = &mut self.classes[index];
+        class.name = name.to_string();
+        class
+    }
+
+    fn position(&mut self, key: &str) -> usize {
+        if let Some(&index) = self.index_of.get(key) {
+            return index;
+        }
+        self.classes.push(Class {
+            id: new_id(),
+            name: key.to_string(),
+            ..Default::default()
+        });
+        self.index_of
+            .insert(key.to_string(), self.classes.len() - 1);
+        self.classes.len() - 1
+    }
+
+    // holderのクラスにtargetへの関係を追加
+    pub fn add_relation(
+        &mut self,
+        holder: &str,
+        target: &str,
+        kind: RelationKind,
+        holder_end: RelationEnd,
+        target_end: RelationEnd,
+    ) {
+        let target_class_id = self.class(target).id.clone();
+        let relation = RelationInfo {
+            target_class_id,
+            relation: kind.to_i32(),
+            multiplicity_p: holder_end.multiplicity,
+            multiplicity_c: target_end.multiplicity,
+            role_name_p: holder_end.role,
+            role_name_c: target_end.role,
+        };
+        self.class(holder)
+            .relations
+            .get_or_insert_with(RelationInfoList::default)
+            .relation_infos
+            .push(relation);
+    }
+
+    // 矢印で書かれた関係を追加
+    // 継承・実現は子、集約・コンポジションは全体側、関連・依存は矢印の根元を関係元とする
+    // 関係名（A --> B : has）は保持する項目が無いため、関係先の端にロール名が無い場合はそのロール名とする
+    pub fn add_arrow_relation(
+        &mut self,
+        left: &str,
+        left_label: Option<&str>,
+        arrow: &Arrow,
+        right: &str,
+        right_label: Option<&str>,
+        name: Option<&str>,
+    ) {
+        let left_end = parse_end_label(left_label.unwrap_or_default());
+        let right_end = parse_end_label(right_label.unwrap_or_default());
+        let generalization = if arrow.dashed {
+            RelationKind::Realization
+        } else {
+            RelationKind::Inheritance
+        };
+        let link = if arrow.dashed {
+            RelationKind::Dependency
+        } else {
+            RelationKind::Association
+        };
+
+        let (holder_is_left, kind) = match (arrow.left, arrow.right) {
+            (_, "|>") => (true, generalization),
+            ("<|", _) => (false, generalization),
+            ("*", _) => (true, RelationKind::Composition),
+            (_, "*") => (false, RelationKind::Composition),
+            ("o", _) => (true, RelationKind::Aggregation),
+            (_, "o") => (false, RelationKind::Aggregation),
+            ("<", "") => (false, link),
+            _ => (true, link),
+        };
+
+        let (holder, holder_end, target, mut target_end) = if holder_is_left {
+            (left, left_end, right, right_end)
+        } else {
+            (right, right_end, left, left_end)
+        };
+        if target_end.role.is_none() {
+            target_end.role = name.map(str::to_string);
+        }
+        self.add_relation(holder, target, kind, holder_end, target_end);
+    }
+
+    pub fn finish(self) -> File {
+        File {
+            name: self.name,
+            classes: self.classes,
+            ..Default::default()
+        }
+    }
+}
+
+// メンバを解釈（括弧を含む場合はメソッド）
+// メソッドは「名前(引数) : 戻り値の型」「名前(引数) 戻り値の型」「戻り値の型 名前(引数)」を受け付ける
+pub(crate) fn parse_signature(text: &str) -> Result<Signature, String> {
+    let Some(open) = text.find('(') else {
+        return parse_variable(text).map(Signature::Attribute);
+    };
+
+    let close = text
+        .rfind(')')
+        .filter(|&close| close > open)
+        .ok_or_else(|| format!("missing ')' in method: {}", text))?;
+    let prefix = text[..open].trim();
+    let suffix = text[close + 1..].trim();
+    let mut return_type = suffix
+        .strip_prefix(':')
+        .unwrap_or(suffix)
+        .trim()
+        .to_string();
+    let name = match prefix.rsplit_once(char::is_whitespace) {
+        Some((type_name, name)) => {
+            if return_type.is_empty() {
+                return_type = type_name.trim().to_string();
+            }
+            name
+        }
+        None => prefix,
+    };
+    if name.is_empty() {
+        return Err(format!("method name is missing: {}", text));
+    }
+
+    let parameters = split_top_level(&text[open + 1..close])
+        .into_iter()
+        .filter(|parameter| !parameter.trim().is_empty())
+        .map(|parameter| parse_variable(parameter.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Signature::Method(Method {
+        name: name.to_string(),
+        return_type,
+        parameters,
+        ..Default::default()
+    }))
+}
+
+// 「名前 : 型」「型 名前」「名前」のいずれか（= 以降の初期値は無視）
+fn parse_variable(text: &str) -> Result<Variable, String> {
+    let text = text
+        .split_once('=')
+        .map_or(text, |(before, _)| before)
+        .trim();
+    let (name, type_name) = match text.split_once(':') {
+        Some((name, type_name)) => (name.trim(), type_name.trim()),
+        None => match text.rsplit_once(char::is_whitespace) {
+            Some((type_name, name)) => (name.trim(), type_name.trim()),
+            None => (text, ""),
+        },
+    };
+    if name.is_empty() {
+        return Err(format!("member name is missing: {}", text));
+    }
+
+    Ok(Variable {
+        name: name.to_string(),
+        r#type: type_name.to_string(),
+        ..Default::default()
+    })
+}
+
+// 型引数の中のカンマを除いてカンマで区切る（Map<K, V> key, int count）
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// 関係の後ろの「: 関係名」（読む向きを示す < > は除く、無い場合は無し）
+pub(crate) fn parse_relation_name(text: &str) -> Option<&str> {
+    let name = text
+        .trim()
+        .strip_prefix(':')?
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+    (!name.is_empty()).then_some(name)
+}
+
+// 先頭の "..." を取り出す（閉じていない場合は無し）
+pub(crate) fn parse_quoted(text: &str) -> (Option<&str>, &str) {
+    let Some(after) = text.strip_prefix('"') else {
+        return (None, text);
+    };
+    match after.find('"') {
+        Some(end) => (Some(&after[..end]), &after[end + 1..]),
+        None => (None, text),
+    }
+}
+
+// 最初の単語と残り
+pub(crate) fn split_keyword(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], &text[index..]),
+        None => (text, ""),
+    }
+}
+
+pub(crate) fn char_count(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{format_multiplicity, Visibility};
+
+    fn attribute(visibility: Visibility, name: &str, type_name: &str, is_static: bool) -> Variable {
+        Variable {
+            name: name.to_string(),
+            r#type: type_name.to_string(),
+            visibility: Some(visibility.to_i32()),
+            is_static: Some(is_static),
+        }
+    }
+
+    fn method(
+        visibility: Visibility,
+        name: &str,
+        parameters: &[(&str, &str)],
+        return_type: &str,
+        is_abstract: bool,
+    ) -> Method {
+        Method {
+            name: name.to_string(),
+            return_type: return_type.to_string(),
+            visibility: visibility.to_i32(),
+            is_abstract: Some(is_abstract),
+            is_static: Some(false),
+            parameters: parameters
+                .iter()
+                .map(|(name, type_name)| Variable {
+                    name: name.to_string(),
+                    r#type: type_name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn relation(
+        target: &str,
+        kind: RelationKind,
+        holder_end: (&str, Option<&str>),
+        target_end: (&str, Option<&str>),
+    ) -> RelationInfo {
+        RelationInfo {
+            target_class_id: target.to_string(),
+            relation: kind.to_i32(),
+            multiplicity_p: parse_multiplicity(holder_end.0),
+            multiplicity_c: parse_multiplicity(target_end.0),
+            role_name_p: holder_end.1.map(str::to_string),
+            role_name_c: target_end.1.map(str::to_string),
+        }
+    }
+
+    fn class(id: &str, attributes: Vec<Variable>, methods: Vec<Method>) -> Class {
+        Class {
+            id: id.to_string(),
+            name: id.to_string(),
+            attributes,
+            methods,
+            ..Default::default()
+        }
+    }
+
+    fn with_relations(mut class: Class, relations: Vec<RelationInfo>) -> Class {
+        class.relations = Some(RelationInfoList {
+            relation_infos: relations,
+        });
+        class
+    }
+
+    // 全ての種類の関係と、可視性・型・静的・抽象のメンバを含むファイル
+    fn sample_file() -> File {
+        File {
+            name: "Shop".to_string(),
+            classes: vec![
+                with_relations(
+                    class(
+                        "Order",
+                        vec![
+                            attribute(Visibility::Private, "id", "UUID", false),
+                            attribute(Visibility::Public, "count", "int", true),
+                        ],
+                        vec![
+                            method(Visibility::Public, "total", &[], "Money", false),
+                            method(
+                                Visibility::Protected,
+                                "validate",
+                                &[("strict", "bool"), ("limit", "int")],
+                                "bool",
+                                false,
+                            ),
+                        ],
+                    ),
+                    vec![
+                        relation(
+                            "OrderLine",
+                            RelationKind::Composition,
+                            ("1", None),
+                            ("1..*", Some("lines")),
+                        ),
+                        relation("Customer", RelationKind::Dependency, ("", None), ("", None)),
+                    ],
+                ),
+                class(
+                    "OrderLine",
+                    vec![attribute(Visibility::Private, "quantity", "int", false)],
+                    vec![],
+                ),
+                with_relations(
+                    class(
+                        "Customer",
+                        vec![attribute(Visibility::Public, "name", "String", false)],
+                        vec![],
+                    ),
+                    vec![
+                        relation(
+                            "Order",
+                            RelationKind::Association,
+                            ("1", Some("buyer")),
+                            ("0..*", Some("orders")),
+                        ),
+                        relation(
+                            "Address",
+                            RelationKind::Aggregation,
+                            ("", None),
+                            ("0..1", None),
+                        ),
+                    ],
+                ),
+                class("Address", vec![], vec![]),
+                class(
+                    "Shape",
+                    vec![],
+                    vec![method(Visibility::Public, "area", &[], "double", true)],
+                ),
+                with_relations(
+                    class(
+                        "Circle",
+                        vec![attribute(Visibility::Private, "radius", "double", false)],
+                        vec![method(Visibility::Public, "area", &[], "double", false)],
+                    ),
+                    vec![
+                        relation("Shape", RelationKind::Inheritance, ("", None), ("", None)),
+                        relation(
+                            "Drawable",
+                            RelationKind::Realization,
+                            ("", None),
+                            ("", None),
+                        ),
+                    ],
+                ),
+                class(
+                    "Drawable",
+                    vec![],
+                    vec![method(
+                        Visibility::Public,
+                        "draw",
+                        &[("scale", "double")],
+                        "",
+                        true,
+                    )],
+                ),
+            ],
+            ..Default::default()
+        }
+    }
+
+    // クラスIDを名前に置き換えた、形式によらない比較用の表現（順不同）
+    fn describe(file: &File) -> Vec<String> {
+        let name_of: HashMap<&str, &str> = file
+            .classes
+            .iter()
+            .map(|class| (class.id.as_str(), class.name.as_str()))
+            .collect();
+        let mut lines = vec![format!("file {}", file.name)];
+        for class in &file.classes {
+            lines.push(format!("class {}", class.name));
+            for attribute in &class.attributes {
+                lines.push(format!(
+                    "attribute {}.{} : {} visibility={:?} static={}",
+                    class.name,
+                    attribute.name,
+                    attribute.r#type,
+                    attribute.visibility,
+                    attribute.is_static.unwrap_or(false)
+                ));
+            }
+            for method in &class.methods {
+                let parameters: Vec<String> = method
+                    .parameters
+                    .iter()
+                    .map(|parameter| format!("{} : {}", parameter.name, parameter.r#type))
+                    .collect();
+                lines.push(format!(
+                    "method {}.{}({}) : {} visibility={} static={} abstract={}",
+                    class.name,
+                    method.name,
+                    parameters.join(", "),
+                    method.return_type,
+                    method.visibility,
+                    method.is_static.unwrap_or(false),
+                    method.is_abstract.unwrap_or(false)
+                ));
+            }
+            for relation in class
+                .relations
+                .iter()
+                .flat_map(|relations| &relations.relation_infos)
+            {
+                let end = |multiplicity: Option<&Multiplicity>, role: Option<&String>| {
+                    format!(
+                        "{} {}",
+                        role.map_or("", String::as_str),
+                        multiplicity.map(format_multiplicity).unwrap_or_default()
+                    )
+                };
+                lines.push(format!(
+                    "relation {} -{}-> {} [{}] [{}]",
+                    class.name,
+                    RelationKind::from_i32(relation.relation).name(),
+                    name_of
+                        .get(relation.target_class_id.as_str())
+                        .copied()
+                        .unwrap_or("?"),
+                    end(
+                        relation.multiplicity_p.as_ref(),
+                        relation.role_name_p.as_ref()
+                    ),
+                    end(
+                        relation.multiplicity_c.as_ref(),
+                        relation.role_name_c.as_ref()
+                    )
+                ));
+            }
+        }
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn plantuml_export_imports_back_unchanged() {
+        let file = sample_file();
+        let exported = crate::export::plantuml::to_plantuml(&file);
+        let imported = import(&exported, ImportFormat::Plantuml).unwrap();
+        assert_eq!(describe(&imported), describe(&file));
+    }
+
+    #[test]
+    fn mermaid_export_imports_back_unchanged() {
+        let file = sample_file();
+        let exported = crate::export::mermaid::to_mermaid(&file);
+        let imported = import(&exported, ImportFormat::Mermaid).unwrap();
+        assert_eq!(describe(&imported), describe(&file));
+    }
+
+    #[test]
+    fn xmi_export_imports_back_unchanged() {
+        let file = sample_file();
+        let exported = crate::export::xmi::to_xmi(&file);
+        let imported = import(&exported, ImportFormat::Xmi).unwrap();
+        assert_eq!(describe(&imported), describe(&file));
+
+        // XMIはクラスIDも保つ
+        let ids = |file: &File| {
+            let mut ids: Vec<String> = file.classes.iter().map(|class| class.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&imported), ids(&file));
+    }
+
+    #[test]
+    fn parses_end_labels() {
+        let end = parse_end_label("items 1..*");
+        assert_eq!(end.role.as_deref(), Some("items"));
+        assert_eq!(
+            end.multiplicity.map(|m| (m.lower, m.upper)),
+            Some((1, None))
+        );
+
+        let end = parse_end_label("0..1");
+        assert_eq!(end.role, None);
+        assert_eq!(
+            end.multiplicity.map(|m| (m.lower, m.upper)),
+            Some((0, Some(1)))
+        );
+
+        let end = parse_end_label("owner");
+        assert_eq!(end.role.as_deref(), Some("owner"));
+        assert!(end.multiplicity.is_none());
+    }
+
+    #[test]
+    fn parses_relation_names() {
+        assert_eq!(parse_relation_name(" : has"), Some("has"));
+        assert_eq!(parse_relation_name(": owns >"), Some("owns"));
+        assert_eq!(parse_relation_name(": < uses"), Some("uses"));
+        assert_eq!(parse_relation_name(":"), None);
+        assert_eq!(parse_relation_name(""), None);
+    }
+
+    #[test]
+    fn parses_member_signatures() {
+        match parse_signature("find(id : UUID, Map<K, V> filters) : Order").unwrap() {
+            Signature::Method(method) => {
+                assert_eq!(method.name, "find");
+                assert_eq!(method.return_type, "Order");
+                let parameters: Vec<(&str, &str)> = method
+                    .parameters
+                    .iter()
+                    .map(|parameter| (parameter.name.as_str(), parameter.r#type.as_str()))
+                    .collect();
+                assert_eq!(parameters, vec![("id", "UUID"), ("filters", "Map<K, V>")]);
+            }
+            Signature::Attribute(_) => panic!("expected a method"),
+        }
+        match parse_signature("int count = 0").unwrap() {
+            Signature::Attribute(variable) => {
+                assert_eq!(variable.name, "count");
+                assert_eq!(variable.r#type, "int");
+            }
+            Signature::Method(_) => panic!("expected an attribute"),
+        }
+        assert!(parse_signature("broken(").is_err());
+    }
+}
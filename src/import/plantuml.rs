@@ -0,0 +1,656 @@
+use std::collections::HashMap;
+
+use super::{
+    char_count, parse_quoted, parse_relation_name, parse_signature, split_keyword, Arrow,
+    DiagramBuilder, ImportError, Signature,
+};
+use crate::model::{RelationKind, Visibility};
+use crate::server::class::{File, Method, Variable};
+
+// クラスの宣言の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassKind {
+    Class,
+    Interface,
+    Enum,
+}
+
+// 読み込み中の { } のブロック
+enum Block {
+    // クラスの本体（メンバの並び）
+    Class { key: String, line: usize },
+    // package・namespace など、中身もクラス図として読むブロック
+    Group,
+    // skinparam など、中身を読み飛ばすブロック
+    Skip,
+}
+
+// PlantUMLのクラス図（@startuml〜@enduml）をファイルに変換
+// 対応する構文: class / abstract class / interface / enum の宣言と本体、
+// 「クラス : メンバ」、関係（ラベル・多重度付き）、extends / implements、title
+pub fn from_plantuml(source: &str) -> Result<File, ImportError> {
+    let mut parser = Parser::default();
+    let mut block_comment = false;
+    let mut note = false;
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = raw.trim();
+        let column = raw[..raw.len() - raw.trim_start().len()].chars().count() + 1;
+
+        // /' 〜 '/ の複数行コメント
+        if block_comment {
+            block_comment = !text.contains("'/");
+            continue;
+        }
+        // note 〜 end note
+        if note {
+            note = !text.to_lowercase().starts_with("end note");
+            continue;
+        }
+        if let Some(comment) = text.strip_prefix("/'") {
+            block_comment = !comment.contains("'/");
+            continue;
+        }
+        if text.is_empty() || text.starts_with('\'') || text.starts_with('!') {
+            continue;
+        }
+        if text.starts_with("@startuml") {
+            continue;
+        }
+        if text.starts_with("@enduml") {
+            break;
+        }
+
+        if let Some(Block::Skip) = parser.blocks.last() {
+            if text == "}" {
+                parser.blocks.pop();
+            } else if text.ends_with('{') {
+                parser.blocks.push(Block::Skip);
+            }
+            continue;
+        }
+
+        if let Some(Block::Class { key, .. }) = parser.blocks.last() {
+            if text == "}" {
+                parser.blocks.pop();
+            } else if !is_separator(text) {
+                let key = key.clone();
+                parser
+                    .add_member(&key, text)
+                    .map_err(|message| ImportError::new(line, column, message))?;
+            }
+            continue;
+        }
+
+        if text == "}" {
+            if parser.blocks.pop().is_none() {
+                return Err(ImportError::new(line, column, "unexpected '}'"));
+            }
+            continue;
+        }
+
+        if is_multiline_note(text) {
+            note = true;
+            continue;
+        }
+
+        parser
+            .statement(text, line)
+            .map_err(|(offset, message)| ImportError::new(line, column + offset, message))?;
+    }
+
+    if block_comment {
+        return Err(ImportError::new(
+            source.lines().count(),
+            1,
+            "block comment is not closed",
+        ));
+    }
+    if let Some(Block::Class { key, line }) = parser
+        .blocks
+        .iter()
+        .find(|block| matches!(block, Block::Class { .. }))
+    {
+        return Err(ImportError::new(
+            *line,
+            1,
+            format!("body of class {} is not closed", key),
+        ));
+    }
+
+    Ok(parser.builder.finish())
+}
+
+#[derive(Default)]
+struct Parser {
+    builder: DiagramBuilder,
+    // 宣言されたクラスの種類（未宣言はClass）
+    kinds: HashMap<String, ClassKind>,
+    blocks: Vec<Block>,
+}
+
+impl Parser {
+    // クラス本体の外の1行を処理する
+    // エラーは行頭からの文字数とメッセージ
+    fn statement(&mut self, text: &str, line: usize) -> Result<(), (usize, String)> {
+        let (keyword, rest) = split_keyword(text);
+
+        match keyword.to_lowercase().as_str() {
+            "title" => {
+                self.builder.set_name(rest.trim());
+                return Ok(());
+            }
+            "class" | "entity" => return self.declaration(ClassKind::Class, rest, text, line),
+            "interface" => return self.declaration(ClassKind::Interface, rest, text, line),
+            "enum" => return self.declaration(ClassKind::Enum, rest, text, line),
+            "abstract" => {
+                // abstract class Name と abstract Name の両方を受け付ける
+                let (next, after) = split_keyword(rest);
+                let rest = if next == "class" { after } else { rest };
+                return self.declaration(ClassKind::Class, rest, text, line);
+            }
+            "package" | "namespace" | "together" => {
+                if text.ends_with('{') {
+                    self.blocks.push(Block::Group);
+                }
+                return Ok(());
+            }
+            "skinparam" | "hide" | "show" | "left" | "top" | "scale" | "set" | "note"
+            | "caption" | "header" | "footer" | "legend" => {
+                if text.ends_with('{') {
+                    self.blocks.push(Block::Skip);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        // 関係（A "1" *-- "many" B : label）
+        let (left, after_left) =
+            parse_name(text).ok_or_else(|| (0, format!("unrecognized statement: {}", text)))?;
+        let after_left_trimmed = after_left.trim_start();
+        let (left_label, after_label) = parse_quoted(after_left_trimmed);
+        let arrow_text = after_label.trim_start();
+        if let Some((arrow, length)) = parse_arrow(arrow_text) {
+            let rest = arrow_text[length..].trim_start();
+            let (right_label, rest) = parse_quoted(rest);
+            let rest = rest.trim_start();
+            let (right, rest) = parse_name(rest).ok_or_else(|| {
+                (
+                    char_count(&text[..text.len() - rest.len()]),
+                    "relation target is missing".to_string(),
+                )
+            })?;
+            let rest = rest.trim_start();
+            if !rest.is_empty() && !rest.starts_with(':') {
+                return Err((
+                    char_count(&text[..text.len() - rest.len()]),
+                    format!("unexpected text after relation: {}", rest),
+                ));
+            }
+
+            self.builder.add_arrow_relation(
+                &left,
+                left_label,
+                &arrow,
+                &right,
+                right_label,
+                parse_relation_name(rest),
+            );
+            return Ok(());
+        }
+
+        // クラス : メンバ
+        if left_label.is_none() {
+            if let Some(member) = after_left_trimmed.strip_prefix(':') {
+                let offset = char_count(&text[..text.len() - member.len()]);
+                return self
+                    .add_member(&left, member.trim())
+                    .map_err(|message| (offset, message));
+            }
+        }
+
+        Err((0, format!("unrecognized statement: {}", text)))
+    }
+
+    // クラスの宣言（class "表示名" as 別名 <<stereotype>> extends A implements B {）
+    fn declaration(
+        &mut self,
+        kind: ClassKind,
+        rest: &str,
+        text: &str,
+        line: usize,
+    ) -> Result<(), (usize, String)> {
+        let offset = |rest: &str| char_count(&text[..text.len() - rest.len()]);
+        let rest = rest.trim_start();
+
+        let (first, mut rest) = parse_declared_name(rest)
+            .ok_or_else(|| (offset(rest), "class name is missing".to_string()))?;
+
+        // 「"表示名" as 別名」または「別名 as "表示名"」
+        let (mut key, mut name) = (first.key.clone(), first.name.clone());
+        let (next, after) = split_keyword(rest.trim_start());
+        if next == "as" {
+            let (second, after) = parse_declared_name(after.trim_start())
+                .ok_or_else(|| (offset(after), "alias is missing after 'as'".to_string()))?;
+            if second.quoted {
+                key = first.key;
+                name = second.name;
+            } else {
+                key = second.key;
+            }
+            rest = after;
+        }
+
+        if key == name {
+            self.builder.class(&key);
+        } else {
+            self.builder.declare(&key, &name);
+        }
+        self.kinds.insert(key.clone(), kind);
+
+        // ステレオタイプ・色・継承の指定
+        let mut rest = rest.trim_start();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("<<") {
+                let end = after
+                    .find(">>")
+                    .ok_or_else(|| (offset(rest), "stereotype is not closed".to_string()))?;
+                rest = after[end + 2..].trim_start();
+                continue;
+            }
+            if rest.starts_with('#') {
+                rest = split_keyword(rest).1;
+                continue;
+            }
+            if rest == "{" {
+                self.blocks.push(Block::Class { key, line });
+                return Ok(());
+            }
+            if rest.starts_with('{') && rest[1..].trim() == "}" {
+                return Ok(());
+            }
+
+            let (keyword, after) = split_keyword(rest);
+            let relation = match keyword {
+                "extends" => RelationKind::Inheritance,
+                "implements" => RelationKind::Realization,
+                _ => {
+                    return Err((
+                        offset(rest),
+                        format!("unexpected text in class declaration: {}", rest),
+                    ))
+                }
+            };
+            // extends A, B のように複数指定できる
+            let mut after = after.trim_start();
+            loop {
+                let (parent, next) = parse_declared_name(after).ok_or_else(|| {
+                    (
+                        offset(after),
+                        format!("class name is missing after '{}'", keyword),
+                    )
+                })?;
+                self.builder.add_relation(
+                    &key,
+                    &parent.key,
+                    relation,
+                    Default::default(),
+                    Default::default(),
+                );
+                after = next.trim_start();
+                match after.strip_prefix(',') {
+                    Some(next) => after = next.trim_start(),
+                    None => break,
+                }
+            }
+            rest = after;
+        }
+        Ok(())
+    }
+
+    // メンバを1つ追加（括弧を含む場合はメソッド）
+    fn add_member(&mut self, key: &str, text: &str) -> Result<(), String> {
+        let kind = self.kinds.get(key).copied().unwrap_or(ClassKind::Class);
+        let mut text = text.to_string();
+        // 修飾子を取り除き、含まれていたかを返す
+        let mut take = |modifiers: &[&str]| {
+            let mut found = false;
+            for modifier in modifiers {
+                if text.contains(modifier) {
+                    found = true;
+                    text = text.replace(modifier, " ");
+                }
+            }
+            found
+        };
+        let mut is_static = take(&["{static}", "{classifier}"]);
+        let mut is_abstract = take(&["{abstract}"]);
+
+        let mut text = text.trim();
+        let visibility = text.chars().next().and_then(Visibility::from_symbol);
+        if visibility.is_some() {
+            text = text[1..].trim_start();
+        }
+        // Java風の修飾子
+        loop {
+            let (keyword, rest) = split_keyword(text);
+            match keyword {
+                "static" => is_static = true,
+                "abstract" => is_abstract = true,
+                "final" => {}
+                _ => break,
+            }
+            text = rest.trim_start();
+        }
+
+        let class = self.builder.class(key);
+        match parse_signature(text)? {
+            Signature::Attribute(variable) => class.attributes.push(Variable {
+                visibility: visibility.map(Visibility::to_i32),
+                // 列挙型の値は静的な属性として扱う
+                is_static: Some(is_static || kind == ClassKind::Enum),
+                ..variable
+            }),
+            Signature::Method(method) => class.methods.push(Method {
+                visibility: visibility.unwrap_or(Visibility::Public).to_i32(),
+                // インターフェースのメソッドは静的でなければ抽象メソッド
+                is_abstract: Some(is_abstract || (kind == ClassKind::Interface && !is_static)),
+                is_static: Some(is_static),
+                ..method
+            }),
+        }
+        Ok(())
+    }
+}
+
+// 宣言された名前（ジェネリクス付きの場合は型引数を除いたものを別名にする）
+struct DeclaredName {
+    key: String,
+    name: String,
+    quoted: bool,
+}
+
+fn parse_declared_name(text: &str) -> Option<(DeclaredName, &str)> {
+    if let (Some(name), rest) = parse_quoted(text) {
+        let declared = DeclaredName {
+            key: name.to_string(),
+            name: name.to_string(),
+            quoted: true,
+        };
+        return Some((declared, rest));
+    }
+
+    let (key, rest) = parse_name(text)?;
+    // Name<T> の型引数
+    if rest.starts_with('<') && !rest.starts_with("<<") {
+        let mut depth = 0;
+        for (index, c) in rest.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                let declared = DeclaredName {
+                    name: format!("{}{}", key, &rest[..=index]),
+                    key,
+                    quoted: false,
+                };
+                return Some((declared, &rest[index + 1..]));
+            }
+        }
+        return None;
+    }
+
+    let declared = DeclaredName {
+        name: key.clone(),
+        key,
+        quoted: false,
+    };
+    Some((declared, rest))
+}
+
+// クラス名（"..." で囲んだ名前、または識別子）
+fn parse_name(text: &str) -> Option<(String, &str)> {
+    if let (Some(name), rest) = parse_quoted(text) {
+        return Some((name.to_string(), rest));
+    }
+
+    let end = text
+        .char_indices()
+        .find(|&(_, c)| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '$')))
+        .map_or(text.len(), |(index, _)| index);
+    if end == 0 {
+        return None;
+    }
+    Some((text[..end].to_string(), &text[end..]))
+}
+
+// 矢印（<|--, *--, ..>, -up->, -[#red]-> など）を解釈し、矢印の長さ（バイト数）と共に返す
+fn parse_arrow(text: &str) -> Option<(Arrow, usize)> {
+    const LEFT_HEADS: [&str; 9] = ["<|", "<", "*", "o", "+", "#", "x", "}", "^"];
+    const RIGHT_HEADS: [&str; 9] = ["|>", ">", "*", "o", "+", "#", "x", "{", "^"];
+
+    let left = LEFT_HEADS
+        .into_iter()
+        .find(|head| {
+            text.strip_prefix(head)
+                .is_some_and(|rest| rest.starts_with(['-', '.']))
+        })
+        .unwrap_or("");
+    let mut position = left.len();
+
+    // 線の部分（- または . の並び、途中の方向指定・色指定を含む）
+    let mut dashed = false;
+    let mut line_length = 0;
+    let bytes = text.as_bytes();
+    while position < text.len() {
+        match bytes[position] {
+            b'-' => {
+                line_length += 1;
+                position += 1;
+            }
+            b'.' => {
+                dashed = true;
+                line_length += 1;
+                position += 1;
+            }
+            b'[' if line_length > 0 => {
+                position += text[position..].find(']')? + 1;
+            }
+            c if line_length > 0 && c.is_ascii_alphabetic() => {
+                let word_length = text[position..]
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(text.len() - position);
+                let word = &text[position..position + word_length];
+                let followed_by_line = text[position + word_length..].starts_with(['-', '.']);
+                if followed_by_line
+                    && matches!(
+                        word,
+                        "up" | "down" | "left" | "right" | "u" | "d" | "l" | "r"
+                    )
+                {
+                    position += word_length;
+                } else {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    if line_length == 0 {
+        return None;
+    }
+
+    // o・x は名前の先頭と区別するため、後ろに名前が続かない場合のみ記号とする
+    let rest = &text[position..];
+    let right = RIGHT_HEADS
+        .into_iter()
+        .find(|head| {
+            rest.strip_prefix(head).is_some_and(|after| {
+                !(matches!(*head, "o" | "x")
+                    && after.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+            })
+        })
+        .unwrap_or("");
+    position += right.len();
+
+    Some((
+        Arrow {
+            left,
+            right,
+            dashed,
+        },
+        position,
+    ))
+}
+
+// クラス本体の区切り線（--, .., ==, __ や -- 見出し --）
+fn is_separator(text: &str) -> bool {
+    ["--", "..", "==", "__"]
+        .iter()
+        .any(|mark| text.starts_with(mark) && text.ends_with(mark))
+}
+
+// 「note left of A」のように本文が次の行から始まり end note で終わる注記
+fn is_multiline_note(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    lower.starts_with("note ") && !text.contains(':') && !text.contains('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::class::{Class, RelationInfo};
+
+    fn class<'a>(file: &'a File, name: &str) -> &'a Class {
+        file.classes
+            .iter()
+            .find(|class| class.name == name)
+            .unwrap_or_else(|| panic!("class {} is missing", name))
+    }
+
+    fn relations<'a>(file: &'a File, name: &str) -> &'a [RelationInfo] {
+        class(file, name)
+            .relations
+            .as_ref()
+            .map_or(&[], |relations| &relations.relation_infos)
+    }
+
+    #[test]
+    fn keeps_relation_name_as_target_role() {
+        let file = from_plantuml("@startuml\nA \"1\" *-- \"0..*\" B : has\n@enduml\n").unwrap();
+
+        let relation = &relations(&file, "A")[0];
+        assert_eq!(relation.target_class_id, class(&file, "B").id);
+        assert_eq!(relation.relation, RelationKind::Composition.to_i32());
+        assert_eq!(relation.multiplicity_p.as_ref().map(|m| m.lower), Some(1));
+        assert_eq!(
+            relation.multiplicity_c.as_ref().map(|m| (m.lower, m.upper)),
+            Some((0, None))
+        );
+        assert_eq!(relation.role_name_c.as_deref(), Some("has"));
+        assert_eq!(relation.role_name_p, None);
+    }
+
+    #[test]
+    fn relation_name_does_not_replace_end_role() {
+        let file = from_plantuml("A --> \"items *\" B : contains >\n").unwrap();
+        assert_eq!(
+            relations(&file, "A")[0].role_name_c.as_deref(),
+            Some("items")
+        );
+    }
+
+    #[test]
+    fn reads_declarations_members_and_inheritance() {
+        let source = "\
+@startuml
+title Shapes
+' comment
+/' block
+   comment '/
+abstract class \"Base Shape\" as Shape <<entity>> {
+  {abstract} +area() : double
+  --
+  -{static} count : int
+}
+interface Drawable {
+  draw(scale : double)
+}
+class Circle extends Shape implements Drawable
+Circle : -radius : double
+note left of Circle
+  ignored
+end note
+@enduml
+";
+        let file = from_plantuml(source).unwrap();
+        assert_eq!(file.name, "Shapes");
+        assert_eq!(file.classes.len(), 3);
+
+        let shape = class(&file, "Base Shape");
+        assert_eq!(shape.methods[0].is_abstract, Some(true));
+        assert_eq!(shape.attributes[0].name, "count");
+        assert_eq!(shape.attributes[0].is_static, Some(true));
+        assert_eq!(
+            shape.attributes[0].visibility,
+            Some(Visibility::Private.to_i32())
+        );
+
+        // インターフェースのメソッドは抽象メソッド
+        assert_eq!(class(&file, "Drawable").methods[0].is_abstract, Some(true));
+
+        let circle = class(&file, "Circle");
+        assert_eq!(circle.attributes[0].name, "radius");
+        let kinds: Vec<(i32, &str)> = relations(&file, "Circle")
+            .iter()
+            .map(|relation| (relation.relation, relation.target_class_id.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (RelationKind::Inheritance.to_i32(), shape.id.as_str()),
+                (
+                    RelationKind::Realization.to_i32(),
+                    class(&file, "Drawable").id.as_str()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrow_direction_decides_the_holder() {
+        let file = from_plantuml("Parent <|-- Child\nWhole o-- Part\nA <.. B\n").unwrap();
+
+        assert_eq!(
+            relations(&file, "Child")[0].relation,
+            RelationKind::Inheritance.to_i32()
+        );
+        assert_eq!(
+            relations(&file, "Whole")[0].relation,
+            RelationKind::Aggregation.to_i32()
+        );
+        assert_eq!(
+            relations(&file, "B")[0].relation,
+            RelationKind::Dependency.to_i32()
+        );
+        assert!(relations(&file, "A").is_empty());
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let error = from_plantuml("@startuml\nclass A {\n  +name : String\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.message.contains("not closed"));
+
+        let error = from_plantuml("@startuml\n  A --> \n@enduml\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("relation target is missing"));
+
+        let error = from_plantuml("A -->  B extra\n").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 10));
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+
+use roxmltree::{Document, Node};
+
+use super::{new_id, ImportError};
+use crate::model::{RelationKind, Visibility};
+use crate::server::class::{
+    Class, File, Method, Multiplicity, RelationInfo, RelationInfoList, Variable,
+};
+
+// UMLモデルのXMI（2.x）をファイルに変換
+// uml:Class・uml:Interface・uml:Enumerationをクラスとし、
+// 汎化・実現・依存・関連（集約・コンポジションを含む）を関係に変換する
+// XMI・UMLの名前空間のバージョンは問わない（Enterprise Architect・Papyrusの出力を想定）
+pub fn from_xmi(source: &str) -> Result<File, ImportError> {
+    let document = Document::parse(source).map_err(|e| {
+        let position = e.pos();
+        ImportError::new(position.row as usize, position.col as usize, e.to_string())
+    })?;
+
+    // xmi:idから要素
+    let elements: HashMap<&str, Node> = document
+        .descendants()
+        .filter_map(|node| xmi_attribute(node, "id").map(|id| (id, node)))
+        .collect();
+
+    let model = document
+        .descendants()
+        .find(|node| node.is_element() && node.tag_name().name() == "Model")
+        .unwrap_or_else(|| document.root_element());
+
+    let classifiers: Vec<Node> = model
+        .descendants()
+        .filter(|node| {
+            matches!(uml_type(*node), Some("Class" | "Interface" | "Enumeration"))
+                && matches!(
+                    node.tag_name().name(),
+                    "packagedElement" | "ownedMember" | "nestedClassifier"
+                )
+        })
+        .collect();
+
+    // xmi:idからクラスの位置
+    let mut index_of: HashMap<&str, usize> = HashMap::new();
+    let mut classes = Vec::with_capacity(classifiers.len());
+    for (index, node) in classifiers.iter().enumerate() {
+        if let Some(id) = xmi_attribute(*node, "id") {
+            index_of.insert(id, index);
+        }
+        classes.push(Class {
+            // 元のクラスIDがxmi:uuidにあればそれを使う
+            id: xmi_attribute(*node, "uuid")
+                .or_else(|| xmi_attribute(*node, "id"))
+                .map_or_else(new_id, str::to_string),
+            name: attribute(*node, "name").unwrap_or_default().to_string(),
+            ..Default::default()
+        });
+    }
+
+    let reader = Reader {
+        document: &document,
+        elements: &elements,
+    };
+
+    for (index, node) in classifiers.iter().enumerate() {
+        let kind = uml_type(*node);
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                // 関連の端は属性ではなく関係として扱う
+                "ownedAttribute" if attribute(child, "association").is_none() => {
+                    classes[index].attributes.push(reader.variable(child));
+                }
+                "ownedLiteral" => classes[index].attributes.push(Variable {
+                    name: attribute(child, "name").unwrap_or_default().to_string(),
+                    is_static: Some(true),
+                    ..Default::default()
+                }),
+                "ownedOperation" => {
+                    let mut method = reader.method(child)?;
+                    // インターフェースのメソッドは静的でなければ抽象メソッド
+                    if kind == Some("Interface") && !method.is_static.unwrap_or(false) {
+                        method.is_abstract = Some(true);
+                    }
+                    classes[index].methods.push(method);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // 関係（関係を持つクラスの位置, 関係）
+    let mut relations: Vec<(usize, RelationInfo)> = Vec::new();
+    let mut add = |holder: Option<&usize>,
+                   target: Option<&usize>,
+                   kind: RelationKind,
+                   holder_end: Option<Node>,
+                   target_end: Option<Node>| {
+        let (Some(&holder), Some(&target)) = (holder, target) else {
+            return;
+        };
+        let (multiplicity_p, role_name_p) = reader.end(holder_end);
+        let (multiplicity_c, role_name_c) = reader.end(target_end);
+        relations.push((
+            holder,
+            RelationInfo {
+                target_class_id: classes[target].id.clone(),
+                relation: kind.to_i32(),
+                multiplicity_p,
+                multiplicity_c,
+                role_name_p,
+                role_name_c,
+            },
+        ));
+    };
+
+    for node in model.descendants().filter(Node::is_element) {
+        match (node.tag_name().name(), uml_type(node)) {
+            ("generalization", _) => {
+                let child = node
+                    .parent_element()
+                    .and_then(|parent| xmi_attribute(parent, "id"));
+                for general in reader.references(node, "general") {
+                    add(
+                        child.and_then(|id| index_of.get(id)),
+                        index_of.get(general),
+                        RelationKind::Inheritance,
+                        None,
+                        None,
+                    );
+                }
+            }
+            ("interfaceRealization", _)
+            | (_, Some("Realization" | "InterfaceRealization" | "Dependency" | "Usage")) => {
+                let kind = match (node.tag_name().name(), uml_type(node)) {
+                    (_, Some("Dependency" | "Usage")) => RelationKind::Dependency,
+                    _ => RelationKind::Realization,
+                };
+                let mut suppliers = reader.references(node, "supplier");
+                suppliers.extend(reader.references(node, "contract"));
+                suppliers.dedup();
+                let mut clients = reader.references(node, "client");
+                // クラスの中のinterfaceRealizationはclientを省略できる
+                if clients.is_empty() && node.tag_name().name() == "interfaceRealization" {
+                    clients.extend(
+                        node.parent_element()
+                            .and_then(|parent| xmi_attribute(parent, "id")),
+                    );
+                }
+                for client in &clients {
+                    for supplier in &suppliers {
+                        add(
+                            index_of.get(client),
+                            index_of.get(supplier),
+                            kind,
+                            None,
+                            None,
+                        );
+                    }
+                }
+            }
+            (_, Some("Association")) => {
+                let ends: Vec<Node> = reader
+                    .references(node, "memberEnd")
+                    .into_iter()
+                    .filter_map(|id| elements.get(id).copied())
+                    .collect();
+                // 3項以上の関連は表せないため読み飛ばす
+                let [first, second] = ends[..] else {
+                    continue;
+                };
+                let navigable = reader.references(node, "navigableOwnedEnd");
+                let is_navigable = |end: Node| {
+                    xmi_attribute(end, "id").is_some_and(|id| navigable.contains(&id))
+                        || end.tag_name().name() == "ownedAttribute"
+                };
+
+                // 集約・コンポジションは部分の端にaggregationがある
+                // それ以外は誘導可能な端を関係先とする（決まらない場合は最初の端が関係元）
+                let aggregation = |end: Node| match attribute(end, "aggregation") {
+                    Some("shared") => Some(RelationKind::Aggregation),
+                    Some("composite") => Some(RelationKind::Composition),
+                    _ => None,
+                };
+                let (source, target, kind) = if let Some(kind) = aggregation(second) {
+                    (first, second, kind)
+                } else if let Some(kind) = aggregation(first) {
+                    (second, first, kind)
+                } else if is_navigable(first) && !is_navigable(second) {
+                    (second, first, RelationKind::Association)
+                } else {
+                    (first, second, RelationKind::Association)
+                };
+
+                add(
+                    reader
+                        .type_reference(source)
+                        .and_then(|id| index_of.get(id)),
+                    reader
+                        .type_reference(target)
+                        .and_then(|id| index_of.get(id)),
+                    kind,
+                    Some(source),
+                    Some(target),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    for (holder, relation) in relations {
+        classes[holder]
+            .relations
+            .get_or_insert_with(RelationInfoList::default)
+            .relation_infos
+            .push(relation);
+    }
+
+    Ok(File {
+        name: attribute(model, "name").unwrap_or_default().to_string(),
+        classes,
+        ..Default::default()
+    })
+}
+
+struct Reader<'a, 'input> {
+    document: &'a Document<'input>,
+    elements: &'a HashMap<&'a str, Node<'a, 'input>>,
+}
+
+impl<'a, 'input> Reader<'a, 'input> {
+    // 属性・引数
+    fn variable(&self, node: Node) -> Variable {
+        Variable {
+            name: attribute(node, "name").unwrap_or_default().to_string(),
+            r#type: self.type_name(node),
+            visibility: attribute(node, "visibility")
+                .and_then(Visibility::from_uml_name)
+                .map(Visibility::to_i32),
+            is_static: flag(node, "isStatic"),
+        }
+    }
+
+    // 操作（direction="return"の引数は戻り値の型）
+    fn method(&self, node: Node) -> Result<Method, ImportError> {
+        let name = attribute(node, "name").unwrap_or_default();
+        if name.is_empty() {
+            return Err(self.error(node, "operation name is missing"));
+        }
+
+        let mut return_type = String::new();
+        let mut parameters = Vec::new();
+        for parameter in node
+            .children()
+            .filter(|child| child.tag_name().name() == "ownedParameter")
+        {
+            match attribute(parameter, "direction") {
+                Some("return") => return_type = self.type_name(parameter),
+                _ => parameters.push(Variable {
+                    visibility: None,
+                    is_static: None,
+                    ..self.variable(parameter)
+                }),
+            }
+        }
+
+        Ok(Method {
+            name: name.to_string(),
+            return_type,
+            visibility: attribute(node, "visibility")
+                .and_then(Visibility::from_uml_name)
+                .unwrap_or(Visibility::Public)
+                .to_i32(),
+            is_abstract: flag(node, "isAbstract"),
+            is_static: flag(node, "isStatic"),
+            parameters,
+        })
+    }
+
+    // 関連の端の多重度とロール名
+    fn end(&self, end: Option<Node>) -> (Option<Multiplicity>, Option<String>) {
+        let Some(end) = end else {
+            return (None, None);
+        };
+        let role = attribute(end, "name")
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+        (multiplicity(end), role)
+    }
+
+    // 型の参照先のxmi:id（type属性、または<type xmi:idref>）
+    fn type_reference<'n>(&self, node: Node<'n, 'input>) -> Option<&'n str> {
+        attribute(node, "type").or_else(|| {
+            node.children()
+                .find(|child| child.tag_name().name() == "type")
+                .and_then(|child| xmi_attribute(child, "idref"))
+        })
+    }
+
+    // 型名（参照先の要素の名前、標準ライブラリの型はhrefの#以降）
+    fn type_name(&self, node: Node) -> String {
+        if let Some(id) = self.type_reference(node) {
+            return match self
+                .elements
+                .get(id)
+                .and_then(|element| attribute(*element, "name"))
+            {
+                Some(name) => name.to_string(),
+                // Enterprise Architectの組み込み型（EAJava_int など）
+                None => id
+                    .split_once('_')
+                    .filter(|(prefix, _)| prefix.starts_with("EA"))
+                    .map_or(id, |(_, name)| name)
+                    .to_string(),
+            };
+        }
+
+        node.children()
+            .find(|child| child.tag_name().name() == "type")
+            .and_then(|child| attribute(child, "href"))
+            .and_then(|href| href.rsplit_once('#'))
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_default()
+    }
+
+    // 参照（空白区切りの属性、または子要素のxmi:idref）
+    fn references<'n>(&self, node: Node<'n, 'input>, name: &str) -> Vec<&'n str> {
+        let mut ids: Vec<&str> = attribute(node, name)
+            .map(|value| value.split_whitespace().collect())
+            .unwrap_or_default();
+        ids.extend(
+            node.children()
+                .filter(|child| child.tag_name().name() == name)
+                .filter_map(|child| xmi_attribute(child, "idref")),
+        );
+        ids
+    }
+
+    fn error(&self, node: Node, message: &str) -> ImportError {
+        let position = self.document.text_pos_at(node.range().start);
+        ImportError::new(position.row as usize, position.col as usize, message)
+    }
+}
+
+// lowerValue・upperValue（どちらも無い場合は多重度無し、片方だけの場合はUMLの既定値1）
+fn multiplicity(node: Node) -> Option<Multiplicity> {
+    let value = |name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .map(|child| attribute(child, "value").unwrap_or("0"))
+    };
+    let lower = value("lowerValue");
+    let upper = value("upperValue");
+    if lower.is_none() && upper.is_none() {
+        return None;
+    }
+
+    Some(Multiplicity {
+        lower: lower.and_then(|lower| lower.parse().ok()).unwrap_or(1),
+        // * と -1 は上限無し
+        upper: match upper {
+            None => Some(1),
+            Some("*" | "-1") => None,
+            Some(upper) => upper.parse().ok(),
+        },
+    })
+}
+
+// 名前空間の無い属性（name, type など）
+fn attribute<'n>(node: Node<'n, '_>, name: &str) -> Option<&'n str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name && attribute.namespace().is_none())
+        .map(|attribute| attribute.value())
+}
+
+// 真偽値の属性（省略された場合はNone）
+fn flag(node: Node, name: &str) -> Option<bool> {
+    attribute(node, name).map(|value| value == "true")
+}
+
+// XMIの名前空間の属性（xmi:id, xmi:type など）
+fn xmi_attribute<'n>(node: Node<'n, '_>, name: &str) -> Option<&'n str> {
+    node.attributes()
+        .find(|attribute| {
+            attribute.name() == name
+                && attribute
+                    .namespace()
+                    .is_some_and(|namespace| namespace.to_lowercase().contains("xmi"))
+        })
+        .map(|attribute| attribute.value())
+}
+
+// xmi:typeのUMLの型名（uml:Class → Class）
+fn uml_type<'n>(node: Node<'n, '_>) -> Option<&'n str> {
+    xmi_attribute(node, "type").map(|value| value.rsplit(':').next().unwrap_or(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class<'a>(file: &'a File, name: &str) -> &'a Class {
+        file.classes
+            .iter()
+            .find(|class| class.name == name)
+            .unwrap_or_else(|| panic!("class {} is missing", name))
+    }
+
+    fn relations<'a>(file: &'a File, name: &str) -> &'a [RelationInfo] {
+        class(file, name)
+            .relations
+            .as_ref()
+            .map_or(&[], |relations| &relations.relation_infos)
+    }
+
+    const SOURCE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xmi:XMI xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.omg.org/spec/UML/20131001">
+  <uml:Model xmi:id="model" name="Library">
+    <packagedElement xmi:type="uml:Class" xmi:id="book" name="Book">
+      <ownedAttribute xmi:id="book_title" name="title" visibility="private" type="EAJava_String"/>
+      <ownedAttribute xmi:id="book_pages" name="pages" association="book_pages_assoc" type="page">
+        <lowerValue xmi:type="uml:LiteralInteger" value="1"/>
+        <upperValue xmi:type="uml:LiteralUnlimitedNatural" value="*"/>
+      </ownedAttribute>
+      <ownedOperation xmi:id="book_read" name="read" isStatic="true">
+        <ownedParameter xmi:id="book_read_from" name="from" type="page"/>
+        <ownedParameter xmi:id="book_read_return" direction="return">
+          <type href="http://www.omg.org/spec/UML/20131001/PrimitiveTypes.xmi#Boolean"/>
+        </ownedParameter>
+      </ownedOperation>
+      <generalization xmi:id="book_general" general="item"/>
+      <interfaceRealization xmi:id="book_realization" contract="readable"/>
+    </packagedElement>
+    <packagedElement xmi:type="uml:Class" xmi:id="page" name="Page"/>
+    <packagedElement xmi:type="uml:Class" xmi:id="item" name="Item"/>
+    <packagedElement xmi:type="uml:Interface" xmi:id="readable" name="Readable">
+      <ownedOperation xmi:id="readable_read" name="read"/>
+    </packagedElement>
+    <packagedElement xmi:type="uml:Enumeration" xmi:id="genre" name="Genre">
+      <ownedLiteral xmi:id="genre_novel" name="NOVEL"/>
+    </packagedElement>
+    <packagedElement xmi:type="uml:Association" xmi:id="book_pages_assoc" memberEnd="book_pages book_pages_owner">
+      <ownedEnd xmi:id="book_pages_owner" name="book" type="book" aggregation="composite"/>
+    </packagedElement>
+    <packagedElement xmi:type="uml:Dependency" xmi:id="uses" client="page" supplier="genre"/>
+  </uml:Model>
+</xmi:XMI>
+"#;
+
+    #[test]
+    fn reads_classifiers_and_members() {
+        let file = from_xmi(SOURCE).unwrap();
+        assert_eq!(file.name, "Library");
+        assert_eq!(file.classes.len(), 5);
+
+        // 関連の端は属性にしない
+        let book = class(&file, "Book");
+        assert_eq!(book.attributes.len(), 1);
+        assert_eq!(book.attributes[0].r#type, "String");
+        assert_eq!(
+            book.attributes[0].visibility,
+            Some(Visibility::Private.to_i32())
+        );
+
+        let read = &book.methods[0];
+        assert_eq!(read.return_type, "Boolean");
+        assert_eq!(read.is_static, Some(true));
+        assert_eq!(read.parameters[0].r#type, "Page");
+
+        assert_eq!(class(&file, "Readable").methods[0].is_abstract, Some(true));
+        assert_eq!(class(&file, "Genre").attributes[0].is_static, Some(true));
+    }
+
+    #[test]
+    fn reads_relations() {
+        let file = from_xmi(SOURCE).unwrap();
+        let id = |name: &str| class(&file, name).id.clone();
+
+        let book: Vec<(i32, String)> = relations(&file, "Book")
+            .iter()
+            .map(|relation| (relation.relation, relation.target_class_id.clone()))
+            .collect();
+        assert_eq!(
+            book,
+            vec![
+                (RelationKind::Inheritance.to_i32(), id("Item")),
+                (RelationKind::Realization.to_i32(), id("Readable")),
+            ]
+        );
+
+        // コンポジションは全体（aggregationのある端の型）が関係を持つ
+        let whole = &relations(&file, "Page")[0];
+        assert_eq!(whole.relation, RelationKind::Composition.to_i32());
+        assert_eq!(whole.target_class_id, id("Book"));
+        assert_eq!(whole.role_name_c.as_deref(), Some("book"));
+        assert_eq!(whole.role_name_p.as_deref(), Some("pages"));
+        assert_eq!(
+            whole.multiplicity_p.as_ref().map(|m| (m.lower, m.upper)),
+            Some((1, None))
+        );
+
+        let dependency = &relations(&file, "Page")[1];
+        assert_eq!(dependency.relation, RelationKind::Dependency.to_i32());
+        assert_eq!(dependency.target_class_id, id("Genre"));
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let error = from_xmi("<xmi:XMI>\n  <broken\n").unwrap_err();
+        assert_eq!(error.line, 1);
+
+        let source = r#"<xmi:XMI xmlns:xmi="http://www.omg.org/spec/XMI/20131001">
+  <packagedElement xmi:type="uml:Class" name="A">
+    <ownedOperation/>
+  </packagedElement>
+</xmi:XMI>
+"#;
+        let error = from_xmi(source).unwrap_err();
+        assert_eq!((error.line, error.column), (3, 5));
+        assert_eq!(error.message, "operation name is missing");
+    }
+}
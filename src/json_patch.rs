@@ -0,0 +1,359 @@
+use std::fmt;
+
+use serde_json::Value;
+
+// JSON Patch（RFC 6902）を適用できなかった理由
+#[derive(Debug)]
+pub enum PatchError {
+    // パッチの形式が正しくない
+    Invalid(String),
+    // パスが存在しない・testが一致しないなど、対象の文書に適用できない
+    Failed(String),
+}
+
+impl PatchError {
+    // 何番目（0から数える）の操作で失敗したかをメッセージに加える
+    fn at(self, index: usize) -> Self {
+        match self {
+            PatchError::Invalid(message) => {
+                PatchError::Invalid(format!("operation {}: {}", index, message))
+            }
+            PatchError::Failed(message) => {
+                PatchError::Failed(format!("operation {}: {}", index, message))
+            }
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Invalid(message) => write!(f, "Invalid patch: {}", message),
+            PatchError::Failed(message) => write!(f, "Failed to apply patch: {}", message),
+        }
+    }
+}
+
+// パッチの操作を順に適用する（1つでも失敗した場合は文書を変更しない）
+pub fn apply(document: &mut Value, patch: &Value) -> Result<(), PatchError> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| PatchError::Invalid("patch must be an array of operations".to_string()))?;
+
+    let mut patched = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation).map_err(|e| e.at(index))?;
+    }
+    *document = patched;
+    Ok(())
+}
+
+fn apply_operation(document: &mut Value, operation: &Value) -> Result<(), PatchError> {
+    let op = string_member(operation, "op")?;
+    let path = string_member(operation, "path")?;
+    let tokens = parse_pointer(path)?;
+
+    match op {
+        "add" => add(document, path, &tokens, value_member(operation)?.clone()),
+        "remove" => remove(document, path, &tokens).map(|_| ()),
+        "replace" => {
+            let value = value_member(operation)?.clone();
+            *pointer_mut(document, path, &tokens)? = value;
+            Ok(())
+        }
+        "move" => {
+            let from = string_member(operation, "from")?;
+            // 自身の子孫への移動はできない
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(PatchError::Invalid(format!(
+                    "cannot move {} into its own child {}",
+                    from, path
+                )));
+            }
+            let value = remove(document, from, &parse_pointer(from)?)?;
+            add(document, path, &tokens, value)
+        }
+        "copy" => {
+            let from = string_member(operation, "from")?;
+            let value = pointer_mut(document, from, &parse_pointer(from)?)?.clone();
+            add(document, path, &tokens, value)
+        }
+        "test" => {
+            let expected = value_member(operation)?;
+            if pointer_mut(document, path, &tokens)? != expected {
+                return Err(PatchError::Failed(format!("test failed at {}", path)));
+            }
+            Ok(())
+        }
+        other => Err(PatchError::Invalid(format!("unknown op: {}", other))),
+    }
+}
+
+fn string_member<'a>(operation: &'a Value, name: &str) -> Result<&'a str, PatchError> {
+    operation
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| PatchError::Invalid(format!("{} is required", name)))
+}
+
+fn value_member(operation: &Value) -> Result<&Value, PatchError> {
+    operation
+        .get("value")
+        .ok_or_else(|| PatchError::Invalid("value is required".to_string()))
+}
+
+// JSON Pointer（RFC 6901）をトークンに分ける（空文字列は文書全体）
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, PatchError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(PatchError::Invalid(format!(
+            "path must start with '/': {}",
+            pointer
+        )));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn pointer_mut<'a>(
+    document: &'a mut Value,
+    pointer: &str,
+    tokens: &[String],
+) -> Result<&'a mut Value, PatchError> {
+    let mut current = document;
+    for token in tokens {
+        current = match current {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => match array_index(token) {
+                Some(index) => items.get_mut(index),
+                None => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| PatchError::Failed(format!("path not found: {}", pointer)))?;
+    }
+    Ok(current)
+}
+
+fn add(
+    document: &mut Value,
+    pointer: &str,
+    tokens: &[String],
+    value: Value,
+) -> Result<(), PatchError> {
+    let Some((last, parent)) = tokens.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+    match pointer_mut(document, pointer, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        // 配列の"-"は末尾への追加
+        Value::Array(items) if last == "-" => items.push(value),
+        Value::Array(items) => match array_index(last) {
+            Some(index) if index <= items.len() => items.insert(index, value),
+            _ => {
+                return Err(PatchError::Failed(format!(
+                    "index out of range: {}",
+                    pointer
+                )))
+            }
+        },
+        _ => {
+            return Err(PatchError::Failed(format!(
+                "parent is not an object or array: {}",
+                pointer
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, pointer: &str, tokens: &[String]) -> Result<Value, PatchError> {
+    let Some((last, parent)) = tokens.split_last() else {
+        return Err(PatchError::Invalid(
+            "cannot remove the whole document".to_string(),
+        ));
+    };
+    match pointer_mut(document, pointer, parent)? {
+        Value::Object(map) => map.remove(last),
+        Value::Array(items) => match array_index(last) {
+            Some(index) if index < items.len() => Some(items.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    }
+    .ok_or_else(|| PatchError::Failed(format!("path not found: {}", pointer)))
+}
+
+// 配列の添字（先頭の0は不可）
+fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patched(document: Value, patch: Value) -> Result<Value, PatchError> {
+        let mut document = document;
+        apply(&mut document, &patch)?;
+        Ok(document)
+    }
+
+    #[test]
+    fn applies_operations_in_order() {
+        let document = json!({"name": "Shop", "classes": [{"id": "a"}, {"id": "b"}]});
+        let result = patched(
+            document,
+            json!([
+                {"op": "test", "path": "/name", "value": "Shop"},
+                {"op": "replace", "path": "/name", "value": "Store"},
+                {"op": "add", "path": "/classes/1", "value": {"id": "c"}},
+                {"op": "remove", "path": "/classes/0"},
+                {"op": "copy", "from": "/classes/0", "path": "/first"},
+                {"op": "move", "from": "/first", "path": "/classes/0/copy"},
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            json!({"name": "Store", "classes": [{"id": "c", "copy": {"id": "c"}}, {"id": "b"}]})
+        );
+    }
+
+    #[test]
+    fn dash_appends_only_when_adding() {
+        let result = patched(
+            json!({"items": [1, 2]}),
+            json!([{"op": "add", "path": "/items/-", "value": 3}]),
+        )
+        .unwrap();
+        assert_eq!(result, json!({"items": [1, 2, 3]}));
+
+        for op in ["remove", "replace"] {
+            let error = patched(
+                json!({"items": [1, 2]}),
+                json!([{"op": op, "path": "/items/-", "value": 3}]),
+            )
+            .unwrap_err();
+            assert!(matches!(error, PatchError::Failed(_)), "{}", op);
+        }
+    }
+
+    #[test]
+    fn rejects_leading_zero_indices() {
+        let document = json!({"items": ["a", "b"]});
+        for path in ["/items/01", "/items/00", "/items/+1", "/items/"] {
+            let error = patched(
+                document.clone(),
+                json!([{"op": "replace", "path": path, "value": "c"}]),
+            )
+            .unwrap_err();
+            assert!(matches!(error, PatchError::Failed(_)), "{}", path);
+        }
+
+        let result = patched(
+            document,
+            json!([{"op": "replace", "path": "/items/0", "value": "c"}]),
+        )
+        .unwrap();
+        assert_eq!(result, json!({"items": ["c", "b"]}));
+
+        // 末尾の次の位置への追加はできるが、それより後はできない
+        let error =
+            patched(json!([]), json!([{"op": "add", "path": "/1", "value": 1}])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to apply patch: operation 0: index out of range: /1"
+        );
+    }
+
+    #[test]
+    fn cannot_move_into_own_child() {
+        let document = json!({"a": {"b": 1}, "ab": 2});
+        for (from, path) in [("/a", "/a/b/c"), ("", "/a")] {
+            let error = patched(
+                document.clone(),
+                json!([{"op": "move", "from": from, "path": path}]),
+            )
+            .unwrap_err();
+            assert!(
+                matches!(error, PatchError::Invalid(_)),
+                "{} -> {}",
+                from,
+                path
+            );
+        }
+
+        // 名前の先頭が同じだけの兄弟には移動できる
+        let result = patched(
+            document,
+            json!([{"op": "move", "from": "/a", "path": "/abc"}]),
+        )
+        .unwrap();
+        assert_eq!(result, json!({"ab": 2, "abc": {"b": 1}}));
+    }
+
+    #[test]
+    fn failed_patch_leaves_document_unchanged() {
+        let mut document = json!({"name": "Shop", "classes": []});
+        let original = document.clone();
+        let error = apply(
+            &mut document,
+            &json!([
+                {"op": "replace", "path": "/name", "value": "Store"},
+                {"op": "add", "path": "/classes/-", "value": {"id": "a"}},
+                {"op": "test", "path": "/name", "value": "Shop"},
+            ]),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Failed to apply patch: operation 2: test failed at /name"
+        );
+        assert_eq!(document, original);
+    }
+
+    #[test]
+    fn unescapes_pointer_tokens() {
+        let result = patched(
+            json!({"a/b": 1, "m~n": 2}),
+            json!([
+                {"op": "remove", "path": "/a~1b"},
+                {"op": "replace", "path": "/m~0n", "value": 3},
+            ]),
+        )
+        .unwrap();
+        assert_eq!(result, json!({"m~n": 3}));
+    }
+
+    #[test]
+    fn rejects_malformed_patches() {
+        for patch in [
+            json!({"op": "add", "path": "/a", "value": 1}),
+            json!([{"path": "/a", "value": 1}]),
+            json!([{"op": "add", "path": "a", "value": 1}]),
+            json!([{"op": "add", "path": "/a"}]),
+            json!([{"op": "move", "path": "/a"}]),
+            json!([{"op": "remove", "path": ""}]),
+            json!([{"op": "merge", "path": "/a"}]),
+        ] {
+            let error = patched(json!({}), patch.clone()).unwrap_err();
+            assert!(matches!(error, PatchError::Invalid(_)), "{}", patch);
+        }
+    }
+}
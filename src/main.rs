@@ -1,7 +1,25 @@
 use std::net::SocketAddr;
 use tokio::signal;
+mod auth;
+mod codegen;
+mod config;
+mod crdt;
+mod diff;
+mod export;
+mod import;
+mod json_patch;
+mod merge;
+mod model;
+mod operations;
+mod presence;
 mod proxy;
+mod render;
+mod reverse;
+mod search;
 mod server;
+mod store;
+mod validation;
+mod watch;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -14,13 +32,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .map_err(|e| format!("Failed to parse proxy address: {}", e))?;
 
+    // 環境変数から設定を読み込み
+    let config = config::ServerConfig::from_env()?;
+    println!("Storage backend: {:?}", config.storage_backend);
+    println!("Validation mode: {:?}", config.validation_mode);
+    println!("Lock mode: {:?}", config.lock_mode);
+    println!("Auth mode: {:?}", config.auth_mode);
+    let auth_mode = config.auth_mode;
+
     // gRPCサーバの起動
     println!("gRPC server address: {}", server_addr);
     let (server_ready_tx, server_ready_rx) = tokio::sync::oneshot::channel::<Result<(), String>>();
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
     let mut server_handle = tokio::spawn(async move {
         println!("Starting gRPC server on {}", server_addr);
-        match server::start_server(server_addr).await {
+        match server::start_server(server_addr, config).await {
             Ok(service) => {
                 println!("gRPC server started successfully");
                 // サーバーが起動したことを通知
@@ -65,7 +91,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("REST proxy address: {}", proxy_addr);
     let mut proxy_handle = tokio::spawn(async move {
         println!("Starting REST proxy on {}", proxy_addr);
-        if let Err(e) = proxy::start_proxy(proxy_addr, server_addr).await {
+        if let Err(e) = proxy::start_proxy(proxy_addr, server_addr, auth_mode).await {
             eprintln!("REST proxy error: {}", e);
         }
     });
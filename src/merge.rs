@@ -0,0 +1,839 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diff::{
+    flag, method_signature, multiplicity, optional_visibility, pair, parameters, relations,
+};
+use crate::model::{display_name, RelationKind, Visibility};
+use crate::server::class::{Class, File, Method, RelationInfo, RelationInfoList, Variable};
+use crate::server::diagram_ext::{ConflictKind, MergeConflict, MergeResult};
+
+// 共通の祖先（base）と2つの子孫（ours・theirs）を三方向マージ
+// クラスはClass.id、属性は名前、メソッドは名前と引数の型、関係は関係先と種類で対応付け、
+// 一方だけが変更した項目はその変更を採用する
+// 両方が異なる値に変更した項目は衝突としてoursの値を採用し、
+// 変更と削除が衝突した要素は変更した側を残す
+// ファイルID・作成日時はoursのものを使い、版番号は呼び出し側で設定する
+pub fn merge(base: &File, ours: &File, theirs: &File) -> MergeResult {
+    let mut merger = Merger::default();
+    let name = merger.value("", "name", &base.name, &ours.name, &theirs.name, |name| {
+        name.clone()
+    });
+
+    let base_classes: HashMap<&str, &Class> = by_id(base);
+    let ours_classes: HashMap<&str, &Class> = by_id(ours);
+    let theirs_classes: HashMap<&str, &Class> = by_id(theirs);
+
+    // oursの順に並べ、theirsだけで追加・変更されたクラスは最後に置く
+    let mut classes = Vec::new();
+    for class in &ours.classes {
+        let id = class.id.as_str();
+        match (base_classes.get(id), theirs_classes.get(id)) {
+            (Some(old), Some(other)) => classes.push(merger.class(old, class, other)),
+            (Some(old), None) => {
+                // theirsで削除された
+                if class != *old {
+                    merger.conflict(
+                        ConflictKind::ModifiedAndDeleted,
+                        id,
+                        display_name(class),
+                        display_name(old),
+                        display_name(class),
+                        "",
+                    );
+                    classes.push(class.clone());
+                }
+            }
+            (None, Some(other)) => {
+                // 同じIDのクラスを両方が追加した
+                if class != *other {
+                    merger.conflict(
+                        ConflictKind::BothAdded,
+                        id,
+                        display_name(class),
+                        "",
+                        display_name(class),
+                        display_name(other),
+                    );
+                }
+                classes.push(class.clone());
+            }
+            (None, None) => classes.push(class.clone()),
+        }
+    }
+    for class in &theirs.classes {
+        let id = class.id.as_str();
+        if ours_classes.contains_key(id) {
+            continue;
+        }
+        match base_classes.get(id) {
+            // oursで削除された
+            Some(old) => {
+                if class != *old {
+                    merger.conflict(
+                        ConflictKind::ModifiedAndDeleted,
+                        id,
+                        display_name(class),
+                        display_name(old),
+                        "",
+                        display_name(class),
+                    );
+                    classes.push(class.clone());
+                }
+            }
+            None => classes.push(class.clone()),
+        }
+    }
+
+    // 削除されたクラスへの関係を取り除く
+    let ids: HashSet<String> = classes.iter().map(|class| class.id.clone()).collect();
+    for class in &mut classes {
+        let path = display_name(class).to_string();
+        let Some(list) = class.relations.as_mut() else {
+            continue;
+        };
+        let mut kept = Vec::new();
+        for relation in list.relation_infos.drain(..) {
+            if ids.contains(&relation.target_class_id) {
+                kept.push(relation);
+            } else {
+                merger.conflict(
+                    ConflictKind::TargetDeleted,
+                    &class.id,
+                    &format!("{}.relations[{}]", path, relation_text(&relation)),
+                    "",
+                    &relation_text(&relation),
+                    "",
+                );
+            }
+        }
+        list.relation_infos = kept;
+    }
+
+    MergeResult {
+        file: Some(File {
+            last_modified: ours.last_modified.max(theirs.last_modified),
+            created_at: ours.created_at,
+            file_id: ours.file_id.clone(),
+            name,
+            classes,
+        }),
+        conflicts: merger.conflicts,
+        revision: 0,
+    }
+}
+
+#[derive(Default)]
+struct Merger {
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merger {
+    fn conflict(
+        &mut self,
+        kind: ConflictKind,
+        class_id: &str,
+        path: &str,
+        base: &str,
+        ours: &str,
+        theirs: &str,
+    ) {
+        self.conflicts.push(MergeConflict {
+            kind: kind as i32,
+            class_id: class_id.to_string(),
+            path: path.to_string(),
+            base_value: base.to_string(),
+            ours_value: ours.to_string(),
+            theirs_value: theirs.to_string(),
+        });
+    }
+
+    // 1つの項目の三方向マージ（両方が異なる値に変更した場合はoursの値）
+    fn value<T: PartialEq + Clone>(
+        &mut self,
+        class_id: &str,
+        path: &str,
+        base: &T,
+        ours: &T,
+        theirs: &T,
+        text: impl Fn(&T) -> String,
+    ) -> T {
+        if ours == theirs || theirs == base {
+            ours.clone()
+        } else if ours == base {
+            theirs.clone()
+        } else {
+            self.conflict(
+                ConflictKind::BothModified,
+                class_id,
+                path,
+                &text(base),
+                &text(ours),
+                &text(theirs),
+            );
+            ours.clone()
+        }
+    }
+
+    // 3つとも存在するクラスのマージ
+    fn class(&mut self, base: &Class, ours: &Class, theirs: &Class) -> Class {
+        let id = ours.id.as_str();
+        let path = display_name(ours).to_string();
+        let name = self.value(
+            id,
+            &format!("{}.name", path),
+            &base.name,
+            &ours.name,
+            &theirs.name,
+            |name| name.clone(),
+        );
+
+        let attributes = self.list(
+            id,
+            &format!("{}.attributes", path),
+            [&base.attributes, &ours.attributes, &theirs.attributes],
+        );
+        let methods = self.list(
+            id,
+            &format!("{}.methods", path),
+            [&base.methods, &ours.methods, &theirs.methods],
+        );
+        let relation_infos = self.list(
+            id,
+            &format!("{}.relations", path),
+            [relations(base), relations(ours), relations(theirs)],
+        );
+
+        Class {
+            id: ours.id.clone(),
+            name,
+            relations: (ours.relations.is_some()
+                || theirs.relations.is_some()
+                || !relation_infos.is_empty())
+            .then_some(RelationInfoList { relation_infos }),
+            attributes,
+            methods,
+        }
+    }
+
+    // 属性・メソッド・関係の並びのマージ（パスにはexactのキーを使う）
+    // 並びはoursの順で、theirsだけで追加された要素は最後に置く
+    fn list<T: Member>(
+        &mut self,
+        class_id: &str,
+        path: &str,
+        [base, ours, theirs]: [&[T]; 3],
+    ) -> Vec<T> {
+        // baseの要素の位置 → theirsで対応する要素
+        let mut theirs_of: HashMap<usize, &T> = HashMap::new();
+        let mut theirs_added: Vec<Option<&T>> = Vec::new();
+        for (old, new) in pair(base, theirs, T::key, T::loose_key) {
+            match (old, new) {
+                (Some(old), Some(new)) => {
+                    theirs_of.insert(position(base, old), new);
+                }
+                (None, Some(new)) => theirs_added.push(Some(new)),
+                _ => {}
+            }
+        }
+
+        let mut merged = Vec::new();
+        let mut deleted_by_ours = Vec::new();
+        for (old, new) in pair(base, ours, T::key, T::loose_key) {
+            match (old, new) {
+                (Some(old), Some(new)) => {
+                    let item_path = format!("{}[{}]", path, T::key(new));
+                    match theirs_of.get(&position(base, old)) {
+                        Some(other) => {
+                            merged.push(T::merge(self, class_id, &item_path, old, new, other))
+                        }
+                        None if new != old => {
+                            // oursで変更、theirsで削除
+                            self.conflict(
+                                ConflictKind::ModifiedAndDeleted,
+                                class_id,
+                                &item_path,
+                                &T::text(old),
+                                &T::text(new),
+                                "",
+                            );
+                            merged.push(new.clone());
+                        }
+                        None => {}
+                    }
+                }
+                (None, Some(new)) => {
+                    // 両方が同じ要素を追加した場合は1つにまとめる
+                    let key = T::key(new);
+                    let same = theirs_added
+                        .iter_mut()
+                        .find(|other| other.is_some_and(|other| T::key(other) == key))
+                        .and_then(Option::take);
+                    if let Some(other) = same {
+                        if other != new {
+                            self.conflict(
+                                ConflictKind::BothAdded,
+                                class_id,
+                                &format!("{}[{}]", path, key),
+                                "",
+                                &T::text(new),
+                                &T::text(other),
+                            );
+                        }
+                    }
+                    merged.push(new.clone());
+                }
+                (Some(old), None) => deleted_by_ours.push(old),
+                (None, None) => {}
+            }
+        }
+
+        for old in deleted_by_ours {
+            // oursで削除、theirsで変更
+            if let Some(&other) = theirs_of.get(&position(base, old)) {
+                if other != old {
+                    self.conflict(
+                        ConflictKind::ModifiedAndDeleted,
+                        class_id,
+                        &format!("{}[{}]", path, T::key(other)),
+                        &T::text(old),
+                        "",
+                        &T::text(other),
+                    );
+                    merged.push(other.clone());
+                }
+            }
+        }
+        merged.extend(theirs_added.into_iter().flatten().cloned());
+        merged
+    }
+}
+
+// 並びのマージで対応付ける要素（属性・メソッド・関係）
+// keyが一致するもの、残りをloose_keyが一致するもので対応付ける
+trait Member: PartialEq + Clone {
+    fn key(&self) -> String;
+    fn loose_key(&self) -> String;
+    // 衝突の記録に使う表記
+    fn text(&self) -> String;
+    // 3つとも存在する要素の項目ごとのマージ
+    fn merge(
+        merger: &mut Merger,
+        class_id: &str,
+        path: &str,
+        base: &Self,
+        ours: &Self,
+        theirs: &Self,
+    ) -> Self;
+}
+
+impl Member for Variable {
+    fn key(&self) -> String {
+        self.name.clone()
+    }
+
+    fn loose_key(&self) -> String {
+        self.name.clone()
+    }
+
+    // orders: List<Order>
+    fn text(&self) -> String {
+        if self.r#type.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}: {}", self.name, self.r#type)
+        }
+    }
+
+    fn merge(
+        merger: &mut Merger,
+        class_id: &str,
+        path: &str,
+        base: &Variable,
+        ours: &Variable,
+        theirs: &Variable,
+    ) -> Self {
+        Variable {
+            name: ours.name.clone(),
+            r#type: merger.value(
+                class_id,
+                &format!("{}.type", path),
+                &base.r#type,
+                &ours.r#type,
+                &theirs.r#type,
+                Clone::clone,
+            ),
+            visibility: merger.value(
+                class_id,
+                &format!("{}.visibility", path),
+                &base.visibility,
+                &ours.visibility,
+                &theirs.visibility,
+                |visibility| optional_visibility(*visibility),
+            ),
+            is_static: merger.value(
+                class_id,
+                &format!("{}.is_static", path),
+                &base.is_static,
+                &ours.is_static,
+                &theirs.is_static,
+                |value| flag(*value),
+            ),
+        }
+    }
+}
+
+impl Member for Method {
+    fn key(&self) -> String {
+        method_signature(self)
+    }
+
+    fn loose_key(&self) -> String {
+        self.name.clone()
+    }
+
+    // find(id: UUID): Order
+    fn text(&self) -> String {
+        let mut text = format!("{}({})", self.name, parameters(&self.parameters));
+        if !self.return_type.is_empty() {
+            text.push_str(&format!(": {}", self.return_type));
+        }
+        text
+    }
+
+    fn merge(
+        merger: &mut Merger,
+        class_id: &str,
+        path: &str,
+        base: &Method,
+        ours: &Method,
+        theirs: &Method,
+    ) -> Self {
+        Method {
+            name: ours.name.clone(),
+            return_type: merger.value(
+                class_id,
+                &format!("{}.return_type", path),
+                &base.return_type,
+                &ours.return_type,
+                &theirs.return_type,
+                Clone::clone,
+            ),
+            visibility: merger.value(
+                class_id,
+                &format!("{}.visibility", path),
+                &base.visibility,
+                &ours.visibility,
+                &theirs.visibility,
+                |visibility| Visibility::from_i32(*visibility).uml_name().to_string(),
+            ),
+            is_abstract: merger.value(
+                class_id,
+                &format!("{}.is_abstract", path),
+                &base.is_abstract,
+                &ours.is_abstract,
+                &theirs.is_abstract,
+                |value| flag(*value),
+            ),
+            is_static: merger.value(
+                class_id,
+                &format!("{}.is_static", path),
+                &base.is_static,
+                &ours.is_static,
+                &theirs.is_static,
+                |value| flag(*value),
+            ),
+            parameters: merger.value(
+                class_id,
+                &format!("{}.parameters", path),
+                &base.parameters,
+                &ours.parameters,
+                &theirs.parameters,
+                |parameters| self::parameters(parameters),
+            ),
+        }
+    }
+}
+
+impl Member for RelationInfo {
+    fn key(&self) -> String {
+        relation_text(self)
+    }
+
+    fn loose_key(&self) -> String {
+        self.target_class_id.clone()
+    }
+
+    fn text(&self) -> String {
+        relation_text(self)
+    }
+
+    fn merge(
+        merger: &mut Merger,
+        class_id: &str,
+        path: &str,
+        base: &RelationInfo,
+        ours: &RelationInfo,
+        theirs: &RelationInfo,
+    ) -> Self {
+        RelationInfo {
+            target_class_id: ours.target_class_id.clone(),
+            relation: merger.value(
+                class_id,
+                &format!("{}.relation", path),
+                &base.relation,
+                &ours.relation,
+                &theirs.relation,
+                |relation| RelationKind::from_i32(*relation).name().to_string(),
+            ),
+            multiplicity_p: merger.value(
+                class_id,
+                &format!("{}.multiplicity_p", path),
+                &base.multiplicity_p,
+                &ours.multiplicity_p,
+                &theirs.multiplicity_p,
+                multiplicity,
+            ),
+            multiplicity_c: merger.value(
+                class_id,
+                &format!("{}.multiplicity_c", path),
+                &base.multiplicity_c,
+                &ours.multiplicity_c,
+                &theirs.multiplicity_c,
+                multiplicity,
+            ),
+            role_name_p: merger.value(
+                class_id,
+                &format!("{}.role_name_p", path),
+                &base.role_name_p,
+                &ours.role_name_p,
+                &theirs.role_name_p,
+                |role| role.clone().unwrap_or_default(),
+            ),
+            role_name_c: merger.value(
+                class_id,
+                &format!("{}.role_name_c", path),
+                &base.role_name_c,
+                &ours.role_name_c,
+                &theirs.role_name_c,
+                |role| role.clone().unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+fn by_id(file: &File) -> HashMap<&str, &Class> {
+    file.classes
+        .iter()
+        .map(|class| (class.id.as_str(), class))
+        .collect()
+}
+
+// 並びの中での要素の位置（pairが返した参照から求める）
+fn position<T>(items: &[T], item: &T) -> usize {
+    items
+        .iter()
+        .position(|candidate| std::ptr::eq(candidate, item))
+        .unwrap_or(usize::MAX)
+}
+
+// composition -> c2
+fn relation_text(relation: &RelationInfo) -> String {
+    format!(
+        "{} -> {}",
+        RelationKind::from_i32(relation.relation).name(),
+        relation.target_class_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, type_name: &str) -> Variable {
+        Variable {
+            name: name.to_string(),
+            r#type: type_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn relation(target: &str, kind: RelationKind) -> RelationInfo {
+        RelationInfo {
+            target_class_id: target.to_string(),
+            relation: kind.to_i32(),
+            ..Default::default()
+        }
+    }
+
+    fn class(id: &str, name: &str, attributes: Vec<Variable>) -> Class {
+        Class {
+            id: id.to_string(),
+            name: name.to_string(),
+            attributes,
+            ..Default::default()
+        }
+    }
+
+    fn file(classes: Vec<Class>) -> File {
+        File {
+            name: "Shop".to_string(),
+            classes,
+            ..Default::default()
+        }
+    }
+
+    fn base() -> File {
+        file(vec![
+            class("order", "Order", vec![variable("id", "int")]),
+            class("line", "OrderLine", vec![variable("count", "int")]),
+        ])
+    }
+
+    // (種類, パス, base, ours, theirs)
+    fn conflicts(result: &MergeResult) -> Vec<(ConflictKind, &str, &str, &str, &str)> {
+        result
+            .conflicts
+            .iter()
+            .map(|conflict| {
+                (
+                    ConflictKind::try_from(conflict.kind).unwrap(),
+                    conflict.path.as_str(),
+                    conflict.base_value.as_str(),
+                    conflict.ours_value.as_str(),
+                    conflict.theirs_value.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn merged(result: &MergeResult) -> &File {
+        result.file.as_ref().unwrap()
+    }
+
+    #[test]
+    fn combines_changes_on_different_items() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[0].name = "PurchaseOrder".to_string();
+        ours.classes[0].attributes[0].r#type = "UUID".to_string();
+        let mut theirs = base.clone();
+        theirs.name = "Store".to_string();
+        theirs.classes[0].attributes.push(variable("total", "int"));
+        theirs
+            .classes
+            .push(class("customer", "Customer", Vec::new()));
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        let file = merged(&result);
+        assert_eq!(file.name, "Store");
+        assert_eq!(
+            file.classes[0],
+            class(
+                "order",
+                "PurchaseOrder",
+                vec![variable("id", "UUID"), variable("total", "int")]
+            )
+        );
+        // theirsだけで追加されたクラスは最後
+        let ids: Vec<&str> = file.classes.iter().map(|class| class.id.as_str()).collect();
+        assert_eq!(ids, vec!["order", "line", "customer"]);
+    }
+
+    #[test]
+    fn same_change_on_both_sides_is_not_a_conflict() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[1].attributes[0].r#type = "long".to_string();
+        let theirs = ours.clone();
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(merged(&result).classes, ours.classes);
+    }
+
+    #[test]
+    fn different_changes_to_one_value_keep_ours() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[0].attributes[0].r#type = "UUID".to_string();
+        ours.classes[1].name = "Line".to_string();
+        let mut theirs = base.clone();
+        theirs.classes[0].attributes[0].r#type = "long".to_string();
+        theirs.classes[1].name = "Item".to_string();
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            conflicts(&result),
+            vec![
+                (
+                    ConflictKind::BothModified,
+                    "Order.attributes[id].type",
+                    "int",
+                    "UUID",
+                    "long"
+                ),
+                (
+                    ConflictKind::BothModified,
+                    "Line.name",
+                    "OrderLine",
+                    "Line",
+                    "Item"
+                ),
+            ]
+        );
+        let file = merged(&result);
+        assert_eq!(file.classes[0].attributes[0].r#type, "UUID");
+        assert_eq!(file.classes[1].name, "Line");
+    }
+
+    #[test]
+    fn modified_and_deleted_items_keep_the_modification() {
+        let base = base();
+        // oursはOrderLineを変更してidを削除、theirsはOrderLineを削除してidを変更
+        let mut ours = base.clone();
+        ours.classes[1].attributes[0].r#type = "long".to_string();
+        ours.classes[0].attributes.clear();
+        let mut theirs = base.clone();
+        theirs.classes.remove(1);
+        theirs.classes[0].attributes[0].r#type = "UUID".to_string();
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            conflicts(&result),
+            vec![
+                (
+                    ConflictKind::ModifiedAndDeleted,
+                    "Order.attributes[id]",
+                    "id: int",
+                    "",
+                    "id: UUID"
+                ),
+                (
+                    ConflictKind::ModifiedAndDeleted,
+                    "OrderLine",
+                    "OrderLine",
+                    "OrderLine",
+                    ""
+                ),
+            ]
+        );
+        let file = merged(&result);
+        assert_eq!(file.classes[0].attributes, vec![variable("id", "UUID")]);
+        assert_eq!(file.classes[1], ours.classes[1]);
+    }
+
+    #[test]
+    fn unchanged_items_follow_deletion() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[0].attributes.clear();
+        let mut theirs = base.clone();
+        theirs.classes.remove(1);
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(
+            merged(&result).classes,
+            vec![class("order", "Order", Vec::new())]
+        );
+    }
+
+    #[test]
+    fn items_added_on_both_sides() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[0].attributes.push(variable("total", "int"));
+        ours.classes[0].attributes.push(variable("note", "String"));
+        ours.classes.push(class("customer", "Customer", Vec::new()));
+        let mut theirs = base.clone();
+        theirs.classes[0]
+            .attributes
+            .push(variable("note", "String"));
+        theirs.classes[0].attributes.push(variable("total", "long"));
+        theirs.classes.push(class("customer", "Client", Vec::new()));
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            conflicts(&result),
+            vec![
+                (
+                    ConflictKind::BothAdded,
+                    "Order.attributes[total]",
+                    "",
+                    "total: int",
+                    "total: long"
+                ),
+                (
+                    ConflictKind::BothAdded,
+                    "Customer",
+                    "",
+                    "Customer",
+                    "Client"
+                ),
+            ]
+        );
+        // 同じものは1つにまとめる
+        let file = merged(&result);
+        assert_eq!(
+            file.classes[0].attributes,
+            vec![
+                variable("id", "int"),
+                variable("total", "int"),
+                variable("note", "String"),
+            ]
+        );
+        assert_eq!(file.classes[2].name, "Customer");
+    }
+
+    #[test]
+    fn relations_to_deleted_classes_are_dropped() {
+        let base = base();
+        let mut ours = base.clone();
+        ours.classes[0].relations = Some(RelationInfoList {
+            relation_infos: vec![relation("line", RelationKind::Composition)],
+        });
+        let mut theirs = base.clone();
+        theirs.classes.remove(1);
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            conflicts(&result),
+            vec![(
+                ConflictKind::TargetDeleted,
+                "Order.relations[composition -> line]",
+                "",
+                "composition -> line",
+                ""
+            )]
+        );
+        let file = merged(&result);
+        assert_eq!(file.classes.len(), 1);
+        assert_eq!(relations(&file.classes[0]), &[]);
+    }
+
+    #[test]
+    fn relation_kind_change_is_paired_by_target() {
+        let mut base = base();
+        base.classes[0].relations = Some(RelationInfoList {
+            relation_infos: vec![relation("line", RelationKind::Aggregation)],
+        });
+        let mut ours = base.clone();
+        ours.classes[0].relations = Some(RelationInfoList {
+            relation_infos: vec![relation("line", RelationKind::Composition)],
+        });
+        let mut theirs = base.clone();
+        theirs.classes[0].relations.as_mut().unwrap().relation_infos[0].role_name_c =
+            Some("lines".to_string());
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(
+            relations(&merged(&result).classes[0]),
+            &[RelationInfo {
+                role_name_c: Some("lines".to_string()),
+                ..relation("line", RelationKind::Composition)
+            }]
+        );
+    }
+}
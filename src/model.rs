@@ -0,0 +1,173 @@
+// class.protoの列挙値（i32）とUML上の意味の対応
+// エクスポート・インポート・コード生成で共通して使う
+
+use crate::server::class::{Class, Multiplicity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+    Package,
+}
+
+impl Visibility {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => Visibility::Private,
+            2 => Visibility::Protected,
+            3 => Visibility::Package,
+            _ => Visibility::Public,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Visibility::Public => 0,
+            Visibility::Private => 1,
+            Visibility::Protected => 2,
+            Visibility::Package => 3,
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '+' => Some(Visibility::Public),
+            '-' => Some(Visibility::Private),
+            '#' => Some(Visibility::Protected),
+            '~' => Some(Visibility::Package),
+            _ => None,
+        }
+    }
+
+    // UMLのVisibilityKind（XMIのvisibility属性の値）
+    pub fn uml_name(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+            Visibility::Protected => "protected",
+            Visibility::Package => "package",
+        }
+    }
+
+    pub fn from_uml_name(name: &str) -> Option<Self> {
+        match name {
+            "public" => Some(Visibility::Public),
+            "private" => Some(Visibility::Private),
+            "protected" => Some(Visibility::Protected),
+            "package" => Some(Visibility::Package),
+            _ => None,
+        }
+    }
+
+    // UMLの可視性記号（+ - # ~）
+    pub fn symbol(self) -> char {
+        match self {
+            Visibility::Public => '+',
+            Visibility::Private => '-',
+            Visibility::Protected => '#',
+            Visibility::Package => '~',
+        }
+    }
+}
+
+// 関係の種類
+// RelationInfoは関係元のクラスが持ち、target_class_idが関係先を指す
+// multiplicity_p/role_name_pは関係元の端、multiplicity_c/role_name_cは関係先の端
+// 継承・実現は関係元が子、集約・コンポジションは関係元が全体側
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+    Association,
+    Inheritance,
+    Realization,
+    Aggregation,
+    Composition,
+    Dependency,
+}
+
+impl RelationKind {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => RelationKind::Inheritance,
+            2 => RelationKind::Realization,
+            3 => RelationKind::Aggregation,
+            4 => RelationKind::Composition,
+            5 => RelationKind::Dependency,
+            _ => RelationKind::Association,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            RelationKind::Association => 0,
+            RelationKind::Inheritance => 1,
+            RelationKind::Realization => 2,
+            RelationKind::Aggregation => 3,
+            RelationKind::Composition => 4,
+            RelationKind::Dependency => 5,
+        }
+    }
+
+    // 差分・ログなどで使う名前
+    pub fn name(self) -> &'static str {
+        match self {
+            RelationKind::Association => "association",
+            RelationKind::Inheritance => "inheritance",
+            RelationKind::Realization => "realization",
+            RelationKind::Aggregation => "aggregation",
+            RelationKind::Composition => "composition",
+            RelationKind::Dependency => "dependency",
+        }
+    }
+}
+
+// 多重度をUMLの表記に変換（例: 1, 0..1, 1..*）
+pub fn format_multiplicity(multiplicity: &Multiplicity) -> String {
+    match multiplicity.upper {
+        Some(upper) if upper == multiplicity.lower => upper.to_string(),
+        Some(upper) => format!("{}..{}", multiplicity.lower, upper),
+        None if multiplicity.lower == 0 => "*".to_string(),
+        None => format!("{}..*", multiplicity.lower),
+    }
+}
+
+// UMLの多重度の表記を解釈（format_multiplicityの逆、n も受け付ける）
+pub fn parse_multiplicity(text: &str) -> Option<Multiplicity> {
+    let bound = |value: &str| -> Option<Option<u32>> {
+        match value.trim() {
+            "*" | "n" => Some(None),
+            value => value.parse().ok().map(Some),
+        }
+    };
+
+    let (lower, upper) = match text.trim().split_once("..") {
+        Some((lower, upper)) => (bound(lower)?, bound(upper)?),
+        None => match bound(text)? {
+            // 「*」だけの場合は 0..*
+            None => (Some(0), None),
+            Some(value) => (Some(value), Some(value)),
+        },
+    };
+
+    Some(Multiplicity {
+        lower: lower?,
+        upper,
+    })
+}
+
+// クラスの表示名（名前が無い場合はID）
+pub fn display_name(class: &Class) -> &str {
+    if class.name.is_empty() {
+        &class.id
+    } else {
+        &class.name
+    }
+}
+
+// 抽象メソッドを持つクラスは抽象クラスとして扱う
+pub fn is_abstract_class(class: &Class) -> bool {
+    class
+        .methods
+        .iter()
+        .any(|method| method.is_abstract.unwrap_or(false))
+}
@@ -0,0 +1,241 @@
+use crate::diff::method_signature;
+use crate::import::new_id;
+use crate::model::RelationKind;
+use crate::server::class::{Class, File, RelationInfo, RelationInfoList};
+use crate::server::diagram_ext::{diagram_operation::Operation, DiagramOperation};
+
+// 操作の並びを順に適用する
+// 失敗した場合は何番目（0から数える）のどの操作かをエラーに含め、fileは途中までの状態になる
+// （呼び出し側で複製に適用し、成功した時だけ保存する）
+pub fn apply(file: &mut File, operations: &[DiagramOperation]) -> Result<(), String> {
+    for (index, operation) in operations.iter().enumerate() {
+        let Some(operation) = &operation.operation else {
+            return Err(format!("Operation {}: operation is empty", index));
+        };
+        apply_one(file, operation)
+            .map_err(|e| format!("Operation {} ({}): {}", index, name(operation), e))?;
+    }
+    Ok(())
+}
+
+fn apply_one(file: &mut File, operation: &Operation) -> Result<(), String> {
+    match operation {
+        Operation::AddClass(add) => {
+            let mut class = add.class.clone().ok_or("class is required")?;
+            if class.id.is_empty() {
+                class.id = new_id();
+            } else if file.classes.iter().any(|other| other.id == class.id) {
+                return Err(format!("class already exists: {}", class.id));
+            }
+            file.classes.push(class);
+        }
+        Operation::RemoveClass(remove) => {
+            let index = file
+                .classes
+                .iter()
+                .position(|class| class.id == remove.class_id)
+                .ok_or_else(|| format!("class not found: {}", remove.class_id))?;
+            file.classes.remove(index);
+            // 削除したクラスへの関係も取り除く
+            for class in &mut file.classes {
+                if let Some(list) = class.relations.as_mut() {
+                    list.relation_infos
+                        .retain(|relation| relation.target_class_id != remove.class_id);
+                }
+            }
+        }
+        Operation::RenameClass(rename) => {
+            if rename.name.is_empty() {
+                return Err("name is required".to_string());
+            }
+            class_mut(file, &rename.class_id)?.name = rename.name.clone();
+        }
+        Operation::AddAttribute(add) => {
+            let attribute = add.attribute.clone().ok_or("attribute is required")?;
+            let class = class_mut(file, &add.class_id)?;
+            if class
+                .attributes
+                .iter()
+                .any(|other| other.name == attribute.name)
+            {
+                return Err(format!("attribute already exists: {}", attribute.name));
+            }
+            class.attributes.push(attribute);
+        }
+        Operation::UpdateAttribute(update) => {
+            let attribute = update.attribute.clone().ok_or("attribute is required")?;
+            let class = class_mut(file, &update.class_id)?;
+            let index = class
+                .attributes
+                .iter()
+                .position(|other| other.name == update.name)
+                .ok_or_else(|| format!("attribute not found: {}", update.name))?;
+            if attribute.name != update.name
+                && class
+                    .attributes
+                    .iter()
+                    .any(|other| other.name == attribute.name)
+            {
+                return Err(format!("attribute already exists: {}", attribute.name));
+            }
+            class.attributes[index] = attribute;
+        }
+        Operation::RemoveAttribute(remove) => {
+            let class = class_mut(file, &remove.class_id)?;
+            let index = class
+                .attributes
+                .iter()
+                .position(|other| other.name == remove.name)
+                .ok_or_else(|| format!("attribute not found: {}", remove.name))?;
+            class.attributes.remove(index);
+        }
+        Operation::AddMethod(add) => {
+            let method = add.method.clone().ok_or("method is required")?;
+            let class = class_mut(file, &add.class_id)?;
+            let signature = method_signature(&method);
+            if class
+                .methods
+                .iter()
+                .any(|other| method_signature(other) == signature)
+            {
+                return Err(format!("method already exists: {}", signature));
+            }
+            class.methods.push(method);
+        }
+        Operation::UpdateMethod(update) => {
+            let method = update.method.clone().ok_or("method is required")?;
+            let class = class_mut(file, &update.class_id)?;
+            let index = method_index(class, &update.signature)?;
+            let signature = method_signature(&method);
+            if signature != update.signature
+                && class
+                    .methods
+                    .iter()
+                    .any(|other| method_signature(other) == signature)
+            {
+                return Err(format!("method already exists: {}", signature));
+            }
+            class.methods[index] = method;
+        }
+        Operation::RemoveMethod(remove) => {
+            let class = class_mut(file, &remove.class_id)?;
+            let index = method_index(class, &remove.signature)?;
+            class.methods.remove(index);
+        }
+        Operation::AddRelation(add) => {
+            let relation = add.relation.clone().ok_or("relation is required")?;
+            if !file
+                .classes
+                .iter()
+                .any(|class| class.id == relation.target_class_id)
+            {
+                return Err(format!(
+                    "target class not found: {}",
+                    relation.target_class_id
+                ));
+            }
+            let class = class_mut(file, &add.class_id)?;
+            let list = class
+                .relations
+                .get_or_insert_with(RelationInfoList::default);
+            if list.relation_infos.iter().any(|other| {
+                other.target_class_id == relation.target_class_id
+                    && other.relation == relation.relation
+            }) {
+                return Err(format!(
+                    "relation already exists: {}",
+                    relation_name(&relation.target_class_id, relation.relation)
+                ));
+            }
+            list.relation_infos.push(relation);
+        }
+        Operation::RemoveRelation(remove) => {
+            let class = class_mut(file, &remove.class_id)?;
+            let list = class.relations.as_mut().ok_or_else(|| {
+                format!(
+                    "relation not found: {}",
+                    relation_name(&remove.target_class_id, remove.relation)
+                )
+            })?;
+            let index = relation_index(
+                &list.relation_infos,
+                &remove.target_class_id,
+                remove.relation,
+            )?;
+            list.relation_infos.remove(index);
+        }
+        Operation::SetMultiplicity(set) => {
+            let class = class_mut(file, &set.class_id)?;
+            let relations = class
+                .relations
+                .as_mut()
+                .map_or(&mut [][..], |list| list.relation_infos.as_mut_slice());
+            let index = relation_index(relations, &set.target_class_id, set.relation)?;
+            let relation = &mut relations[index];
+            if set.multiplicity_p.is_some() {
+                relation.multiplicity_p = set.multiplicity_p;
+            }
+            if set.multiplicity_c.is_some() {
+                relation.multiplicity_c = set.multiplicity_c;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn class_mut<'a>(file: &'a mut File, class_id: &str) -> Result<&'a mut Class, String> {
+    file.classes
+        .iter_mut()
+        .find(|class| class.id == class_id)
+        .ok_or_else(|| format!("class not found: {}", class_id))
+}
+
+fn method_index(class: &Class, signature: &str) -> Result<usize, String> {
+    class
+        .methods
+        .iter()
+        .position(|method| method_signature(method) == signature)
+        .ok_or_else(|| format!("method not found: {}", signature))
+}
+
+fn relation_index(
+    relations: &[RelationInfo],
+    target_class_id: &str,
+    relation: i32,
+) -> Result<usize, String> {
+    relations
+        .iter()
+        .position(|other| other.target_class_id == target_class_id && other.relation == relation)
+        .ok_or_else(|| {
+            format!(
+                "relation not found: {}",
+                relation_name(target_class_id, relation)
+            )
+        })
+}
+
+// composition -> c2
+fn relation_name(target_class_id: &str, relation: i32) -> String {
+    format!(
+        "{} -> {}",
+        RelationKind::from_i32(relation).name(),
+        target_class_id
+    )
+}
+
+fn name(operation: &Operation) -> &'static str {
+    match operation {
+        Operation::AddClass(_) => "add_class",
+        Operation::RemoveClass(_) => "remove_class",
+        Operation::RenameClass(_) => "rename_class",
+        Operation::AddAttribute(_) => "add_attribute",
+        Operation::UpdateAttribute(_) => "update_attribute",
+        Operation::RemoveAttribute(_) => "remove_attribute",
+        Operation::AddMethod(_) => "add_method",
+        Operation::UpdateMethod(_) => "update_method",
+        Operation::RemoveMethod(_) => "remove_method",
+        Operation::AddRelation(_) => "add_relation",
+        Operation::RemoveRelation(_) => "remove_relation",
+        Operation::SetMultiplicity(_) => "set_multiplicity",
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::server::diagram_ext::{ClassLock, LockResponse, Participant, SessionState};
+
+// この間に更新しないセッションは終わったものとみなす（秒）
+const SESSION_TIMEOUT: i64 = 60;
+
+// ロックの有効期間（秒）
+const DEFAULT_LOCK_SECONDS: u32 = 60;
+const MAX_LOCK_SECONDS: u32 = 600;
+
+// ファイルごとの共同編集のセッションとクラスのソフトロック
+// 期限切れのセッション・ロックはそのファイルへの次の操作の時に取り除き、
+// セッションが無くなったファイルも取り除く
+#[derive(Debug, Default)]
+pub struct Presence {
+    files: Mutex<HashMap<String, FileSessions>>,
+}
+
+#[derive(Debug, Default)]
+struct FileSessions {
+    sessions: HashMap<String, Session>,
+    // クラスID → ロック
+    locks: HashMap<String, Lock>,
+}
+
+#[derive(Debug)]
+struct Session {
+    // セッションを作成した利用者（認証されていない場合はNone）
+    owner: Option<String>,
+    user_name: String,
+    last_seen: i64,
+}
+
+#[derive(Debug)]
+struct Lock {
+    session_id: String,
+    expires_at: i64,
+}
+
+impl FileSessions {
+    fn prune(&mut self, now: i64) {
+        self.sessions
+            .retain(|_, session| session.last_seen + SESSION_TIMEOUT > now);
+        let sessions = &self.sessions;
+        self.locks
+            .retain(|_, lock| lock.expires_at > now && sessions.contains_key(&lock.session_id));
+    }
+
+    // セッションが存在し、利用者が作成したものか
+    fn owned(&self, session_id: &str, owner: Option<&str>) -> bool {
+        self.sessions
+            .get(session_id)
+            .is_some_and(|session| session.owner.as_deref() == owner)
+    }
+
+    // 他のセッションのIDは返さない（viewerのセッションのロックだけIDを入れる）
+    fn class_lock(&self, class_id: &str, lock: &Lock, viewer: &str) -> ClassLock {
+        ClassLock {
+            class_id: class_id.to_string(),
+            session_id: if lock.session_id == viewer {
+                lock.session_id.clone()
+            } else {
+                String::new()
+            },
+            user_name: self
+                .sessions
+                .get(&lock.session_id)
+                .map(|session| session.user_name.clone())
+                .unwrap_or_default(),
+            expires_at: lock.expires_at,
+        }
+    }
+
+    // 他のセッションのIDは返さない（session_idのセッションだけIDを入れる）
+    fn state(&self, session_id: &str) -> SessionState {
+        let mut participants: Vec<Participant> = self
+            .sessions
+            .iter()
+            .map(|(id, session)| Participant {
+                session_id: if id == session_id {
+                    id.clone()
+                } else {
+                    String::new()
+                },
+                user_name: session.user_name.clone(),
+                last_seen: session.last_seen,
+            })
+            .collect();
+        participants.sort_by(|a, b| {
+            a.user_name
+                .cmp(&b.user_name)
+                .then(a.last_seen.cmp(&b.last_seen))
+        });
+        let mut locks: Vec<ClassLock> = self
+            .locks
+            .iter()
+            .map(|(class_id, lock)| self.class_lock(class_id, lock, session_id))
+            .collect();
+        locks.sort_by(|a, b| a.class_id.cmp(&b.class_id));
+
+        SessionState {
+            session_id: session_id.to_string(),
+            participants,
+            locks,
+            expires_at: self
+                .sessions
+                .get(session_id)
+                .map_or(0, |session| session.last_seen + SESSION_TIMEOUT),
+        }
+    }
+}
+
+impl Presence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // セッションを作成・更新し、ファイルを開いている利用者とロックを返す
+    // 他の利用者のセッションは更新しない（None）
+    pub fn join(
+        &self,
+        file_id: &str,
+        session_id: &str,
+        owner: Option<&str>,
+        user_name: &str,
+    ) -> Option<SessionState> {
+        let now = now();
+        let mut files = self.lock_files();
+        let sessions = files.entry(file_id.to_string()).or_default();
+        sessions.prune(now);
+
+        let session_id = if session_id.is_empty() {
+            crate::import::new_id()
+        } else {
+            session_id.to_string()
+        };
+        if sessions.sessions.contains_key(&session_id) && !sessions.owned(&session_id, owner) {
+            return None;
+        }
+        sessions.sessions.insert(
+            session_id.clone(),
+            Session {
+                owner: owner.map(str::to_string),
+                user_name: user_name.to_string(),
+                last_seen: now,
+            },
+        );
+        Some(sessions.state(&session_id))
+    }
+
+    // 利用者のセッションのうち、session_idのものが存在するか（ファイルは問わない）
+    pub fn owns(&self, session_id: &str, owner: Option<&str>) -> bool {
+        let now = now();
+        let mut files = self.lock_files();
+        let mut owned = false;
+        files.retain(|_, sessions| {
+            sessions.prune(now);
+            owned |= sessions.owned(session_id, owner);
+            !sessions.sessions.is_empty()
+        });
+        owned
+    }
+
+    // セッションを終了し、そのロックを解除する（利用者のセッションが無い場合はfalse）
+    pub fn leave(&self, file_id: &str, session_id: &str, owner: Option<&str>) -> bool {
+        let now = now();
+        let mut files = self.lock_files();
+        let Some(sessions) = files.get_mut(file_id) else {
+            return false;
+        };
+        let removed =
+            sessions.owned(session_id, owner) && sessions.sessions.remove(session_id).is_some();
+        sessions.prune(now);
+        remove_if_empty(&mut files, file_id);
+        removed
+    }
+
+    pub fn state(&self, file_id: &str) -> SessionState {
+        let now = now();
+        let mut files = self.lock_files();
+        let Some(sessions) = files.get_mut(file_id) else {
+            return SessionState::default();
+        };
+        sessions.prune(now);
+        let state = sessions.state("");
+        remove_if_empty(&mut files, file_id);
+        state
+    }
+
+    // クラスのロックを取得・延長（他のセッションのロックがある場合はどれも取得しない）
+    // 利用者のセッションが無い（期限切れを含む）場合はErr
+    pub fn lock(
+        &self,
+        file_id: &str,
+        session_id: &str,
+        owner: Option<&str>,
+        class_ids: &[String],
+        ttl_seconds: u32,
+    ) -> Result<LockResponse, String> {
+        let now = now();
+        let mut files = self.lock_files();
+        let sessions = files
+            .get_mut(file_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        sessions.prune(now);
+        if !sessions.owned(session_id, owner) {
+            remove_if_empty(&mut files, file_id);
+            return Err(format!("Session not found: {}", session_id));
+        }
+        let session = sessions
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        session.last_seen = now;
+
+        let conflicts: Vec<ClassLock> = class_ids
+            .iter()
+            .filter_map(|class_id| {
+                let lock = sessions.locks.get(class_id)?;
+                (lock.session_id != session_id)
+                    .then(|| sessions.class_lock(class_id, lock, session_id))
+            })
+            .collect();
+        if !conflicts.is_empty() {
+            return Ok(LockResponse {
+                locks: Vec::new(),
+                conflicts,
+            });
+        }
+
+        let ttl = match ttl_seconds {
+            0 => DEFAULT_LOCK_SECONDS,
+            ttl => ttl.min(MAX_LOCK_SECONDS),
+        };
+        let mut locks = Vec::new();
+        for class_id in class_ids {
+            let lock = Lock {
+                session_id: session_id.to_string(),
+                expires_at: now + i64::from(ttl),
+            };
+            locks.push(sessions.class_lock(class_id, &lock, session_id));
+            sessions.locks.insert(class_id.clone(), lock);
+        }
+        Ok(LockResponse {
+            locks,
+            conflicts: Vec::new(),
+        })
+    }
+
+    // セッションのロックを解除し、解除した数を返す（class_idsが空の場合は全て）
+    // 利用者のセッションでない場合は解除しない
+    pub fn unlock(
+        &self,
+        file_id: &str,
+        session_id: &str,
+        owner: Option<&str>,
+        class_ids: &[String],
+    ) -> usize {
+        let now = now();
+        let mut files = self.lock_files();
+        let Some(sessions) = files.get_mut(file_id) else {
+            return 0;
+        };
+        sessions.prune(now);
+        if !sessions.owned(session_id, owner) {
+            remove_if_empty(&mut files, file_id);
+            return 0;
+        }
+        let before = sessions.locks.len();
+        sessions.locks.retain(|class_id, lock| {
+            lock.session_id != session_id || !(class_ids.is_empty() || class_ids.contains(class_id))
+        });
+        before - sessions.locks.len()
+    }
+
+    // 指定したクラスのうち、他のセッション（session_idがNoneの場合は全てのセッション）が
+    // ロックしているもの
+    pub fn locked_by_others(
+        &self,
+        file_id: &str,
+        session_id: Option<&str>,
+        class_ids: &[String],
+    ) -> Vec<ClassLock> {
+        let now = now();
+        let mut files = self.lock_files();
+        let Some(sessions) = files.get_mut(file_id) else {
+            return Vec::new();
+        };
+        sessions.prune(now);
+        let locked = class_ids
+            .iter()
+            .filter_map(|class_id| {
+                let lock = sessions.locks.get(class_id)?;
+                (Some(lock.session_id.as_str()) != session_id)
+                    .then(|| sessions.class_lock(class_id, lock, ""))
+            })
+            .collect();
+        remove_if_empty(&mut files, file_id);
+        locked
+    }
+
+    fn lock_files(&self) -> MutexGuard<'_, HashMap<String, FileSessions>> {
+        self.files
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// セッションが無くなったファイルを取り除く
+fn remove_if_empty(files: &mut HashMap<String, FileSessions>, file_id: &str) {
+    if files
+        .get(file_id)
+        .is_some_and(|sessions| sessions.sessions.is_empty())
+    {
+        files.remove(file_id);
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(class_ids: &[&str]) -> Vec<String> {
+        class_ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn join(presence: &Presence, owner: &str, user_name: &str) -> String {
+        presence
+            .join("file", "", Some(owner), user_name)
+            .unwrap()
+            .session_id
+    }
+
+    // 時刻を進める代わりに、セッションの最終更新とロックの期限を過去にずらす
+    fn age(presence: &Presence, seconds: i64) {
+        for sessions in presence.lock_files().values_mut() {
+            for session in sessions.sessions.values_mut() {
+                session.last_seen -= seconds;
+            }
+            for lock in sessions.locks.values_mut() {
+                lock.expires_at -= seconds;
+            }
+        }
+    }
+
+    #[test]
+    fn sessions_belong_to_their_owner() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+
+        assert!(presence.owns(&alice, Some("alice")));
+        assert!(!presence.owns(&alice, Some("bob")));
+        assert!(!presence.owns(&alice, None));
+        assert!(presence.join("file", &alice, Some("bob"), "Bob").is_none());
+        assert!(presence
+            .lock("file", &alice, Some("bob"), &ids(&["a"]), 0)
+            .is_err());
+        assert!(!presence.leave("file", &alice, Some("bob")));
+
+        assert!(presence.leave("file", &alice, Some("alice")));
+        assert!(!presence.owns(&alice, Some("alice")));
+        assert!(presence.lock_files().is_empty());
+    }
+
+    #[test]
+    fn state_hides_other_session_ids() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+        let state = presence.join("file", "", Some("bob"), "Bob").unwrap();
+        presence
+            .lock("file", &alice, Some("alice"), &ids(&["a"]), 0)
+            .unwrap();
+
+        let names: Vec<(&str, bool)> = state
+            .participants
+            .iter()
+            .map(|participant| {
+                (
+                    participant.user_name.as_str(),
+                    participant.session_id.is_empty(),
+                )
+            })
+            .collect();
+        assert_eq!(names, vec![("Alice", true), ("Bob", false)]);
+
+        let state = presence.state("file");
+        assert!(state
+            .participants
+            .iter()
+            .all(|participant| participant.session_id.is_empty()));
+        assert_eq!(state.locks[0].user_name, "Alice");
+        assert_eq!(state.locks[0].session_id, "");
+    }
+
+    #[test]
+    fn locks_conflict_until_released() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+        let bob = join(&presence, "bob", "Bob");
+
+        let response = presence
+            .lock("file", &alice, Some("alice"), &ids(&["a", "b"]), 30)
+            .unwrap();
+        assert_eq!(response.locks.len(), 2);
+        assert_eq!(response.locks[0].session_id, alice);
+
+        // 1つでも他のセッションのロックがあればどれも取得しない
+        let response = presence
+            .lock("file", &bob, Some("bob"), &ids(&["c", "b"]), 0)
+            .unwrap();
+        assert!(response.locks.is_empty());
+        assert_eq!(response.conflicts.len(), 1);
+        assert_eq!(response.conflicts[0].class_id, "b");
+        assert_eq!(response.conflicts[0].session_id, "");
+        assert_eq!(
+            presence.locked_by_others("file", Some(&bob), &ids(&["a", "c"]))[0].class_id,
+            "a"
+        );
+        assert!(presence
+            .locked_by_others("file", Some(&alice), &ids(&["a", "b"]))
+            .is_empty());
+        assert_eq!(
+            presence
+                .locked_by_others("file", None, &ids(&["a", "b"]))
+                .len(),
+            2
+        );
+
+        // 他の利用者は解除できない
+        assert_eq!(presence.unlock("file", &alice, Some("bob"), &[]), 0);
+        assert_eq!(
+            presence.unlock("file", &alice, Some("alice"), &ids(&["b"])),
+            1
+        );
+        assert_eq!(
+            presence
+                .lock("file", &bob, Some("bob"), &ids(&["b"]), 0)
+                .unwrap()
+                .locks
+                .len(),
+            1
+        );
+        assert_eq!(presence.unlock("file", &alice, Some("alice"), &[]), 1);
+    }
+
+    #[test]
+    fn lock_duration_is_bounded() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+
+        let lock = |ttl| {
+            let response = presence
+                .lock("file", &alice, Some("alice"), &ids(&["a"]), ttl)
+                .unwrap();
+            response.locks[0].expires_at - now()
+        };
+        assert!((DEFAULT_LOCK_SECONDS as i64 - 1..=DEFAULT_LOCK_SECONDS as i64).contains(&lock(0)));
+        assert!((MAX_LOCK_SECONDS as i64 - 1..=MAX_LOCK_SECONDS as i64).contains(&lock(3600)));
+    }
+
+    #[test]
+    fn expired_locks_and_sessions_are_removed() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+        let bob = join(&presence, "bob", "Bob");
+        presence
+            .lock("file", &alice, Some("alice"), &ids(&["a"]), 10)
+            .unwrap();
+
+        age(&presence, 10);
+        assert!(presence.state("file").locks.is_empty());
+        assert_eq!(
+            presence
+                .lock("file", &bob, Some("bob"), &ids(&["a"]), 0)
+                .unwrap()
+                .locks
+                .len(),
+            1
+        );
+
+        // 期限切れのセッションのロックも解除される
+        presence
+            .join("file", &alice, Some("alice"), "Alice")
+            .unwrap();
+        age(&presence, SESSION_TIMEOUT);
+        presence
+            .join("file", &alice, Some("alice"), "Alice")
+            .unwrap();
+        let state = presence.state("file");
+        assert_eq!(state.participants.len(), 1);
+        assert!(state.locks.is_empty());
+        assert!(presence
+            .locked_by_others("file", None, &ids(&["a"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn files_without_sessions_are_removed() {
+        let presence = Presence::new();
+        let alice = join(&presence, "alice", "Alice");
+        presence.join("other", "", Some("bob"), "Bob").unwrap();
+        age(&presence, SESSION_TIMEOUT);
+
+        assert!(!presence.owns(&alice, Some("alice")));
+        assert!(presence.lock_files().is_empty());
+
+        join(&presence, "alice", "Alice");
+        age(&presence, SESSION_TIMEOUT);
+        assert_eq!(presence.state("file"), SessionState::default());
+        assert!(presence.lock_files().is_empty());
+    }
+}
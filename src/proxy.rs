@@ -1,35 +1,111 @@
 use axum::{
-    extract::{Path, State},
-    response::Json,
-    routing::{delete, get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{delete, get, patch, post},
     Router,
 };
+use prost::Message;
+use serde::Deserialize;
+use std::convert::Infallible;
 use std::net::SocketAddr;
+use tokio_stream::{Stream, StreamExt};
+use tonic::service::{interceptor::InterceptedService, Interceptor};
+use tonic::transport::{Channel, Endpoint};
+
+use crate::codegen::{to_zip, GeneratedSource};
+use crate::config::AuthMode;
+use crate::json_patch::{self, PatchError};
+use crate::server::MAX_MESSAGE_SIZE;
 
 pub mod class {
     tonic::include_proto!("class");
 }
 
+pub mod diagram_ext {
+    tonic::include_proto!("diagram_ext");
+}
+
 use class::{
     diagram_service_client::DiagramServiceClient, Class, File, FileId, Method, Multiplicity,
     RelationInfo, RelationInfoList, Variable,
 };
+use diagram_ext::{
+    auth_service_client::AuthServiceClient, diagram_ext_service_client::DiagramExtServiceClient,
+    ApiKey, AuthenticateRequest, ChangeKind, ClassLock, CodeLanguage, ConflictKind,
+    CreateApiKeyRequest, CreateUserRequest, DiagramDiff, DiagramEvent, DiagramEventKind,
+    DiagramSummary, DiffRequest, ExportFormat, ExportRequest, GenerateCodeRequest, ImportFormat,
+    ImportRequest, JoinSessionRequest, ListApiKeysRequest, ListClassDiagramsRequest, LockConflicts,
+    LockRequest, LoginRequest, MemberChange, MergeConflict, MergeRequest, MergeResult,
+    MergeSaveRequest, ParseError, ReverseEngineerRequest, RevisionInfo, RevisionRequest,
+    RevokeApiKeyRequest, SearchField, SearchHit, SearchRequest, SessionRequest, SessionState,
+    SkippedSource, SortField, SortOrder, SourceLanguage, UnlockRequest, User, ValidationReport,
+    ValueChange,
+};
 
 pub async fn start_proxy(
     proxy_addr: SocketAddr,
     dest_addr: SocketAddr,
+    auth_mode: AuthMode,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let listener = tokio::net::TcpListener::bind(proxy_addr).await?;
     let cors = tower_http::cors::CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
         .allow_methods(tower_http::cors::Any)
-        .allow_headers(tower_http::cors::Any);
+        .allow_headers(tower_http::cors::Any)
+        .expose_headers([
+            header::ETAG,
+            HeaderName::from_static("x-validation-warnings"),
+            HeaderName::from_static("x-lock-conflicts"),
+        ]);
 
     let app = Router::new()
+        .route("/api_p1", get(list_diagrams))
+        .route("/api_p1/auth/login", post(login))
+        .route("/api_p1/auth/me", get(current_user))
+        .route("/api_p1/auth/users", post(create_user))
+        .route("/api_p1/auth/keys", get(list_api_keys))
+        .route("/api_p1/auth/keys", post(create_api_key))
+        .route("/api_p1/auth/keys/{key_id}", delete(revoke_api_key))
         .route("/api_p1", post(save_diagram))
+        .route("/api_p1/search", get(search_diagrams))
+        .route("/api_p1/validate", post(validate_diagram))
+        .route("/api_p1/import/{format}", post(import_diagram))
+        .route("/api_p1/merge", post(merge_diagrams))
+        .route(
+            "/api_p1/reverse/{language}",
+            post(reverse_engineer).layer(DefaultBodyLimit::max(MAX_MESSAGE_SIZE)),
+        )
         .route("/api_p1/{file_id}", get(get_diagram))
         .route("/api_p1/{file_id}", delete(delete_diagram))
+        .route("/api_p1/{file_id}", patch(patch_diagram))
         .route("/api_p1/{file_id}/exists", get(check_exists))
+        .route("/api_p1/{file_id}/export/{format}", get(export_diagram))
+        .route("/api_p1/{file_id}/render.svg", get(render_svg))
+        .route("/api_p1/{file_id}/codegen/{language}", get(generate_code))
+        .route("/api_p1/{file_id}/diff", get(diff_diagrams))
+        .route("/api_p1/{file_id}/merge", post(save_merged_diagram))
+        .route("/api_p1/{file_id}/watch", get(watch_diagram))
+        .route("/api_p1/{file_id}/sessions", get(list_sessions))
+        .route("/api_p1/{file_id}/sessions", post(join_session))
+        .route(
+            "/api_p1/{file_id}/sessions/{session_id}",
+            delete(leave_session),
+        )
+        .route("/api_p1/{file_id}/locks", post(lock_classes))
+        .route("/api_p1/{file_id}/locks/{class_id}", delete(unlock_class))
+        .route("/api_p1/{file_id}/revisions", get(list_revisions))
+        .route("/api_p1/{file_id}/revisions/{revision}", get(get_revision))
+        .route(
+            "/api_p1/{file_id}/revisions/{revision}/restore",
+            post(restore_revision),
+        )
+        .layer(middleware::from_fn_with_state(auth_mode, authenticate))
         .layer(cors)
         .with_state(dest_addr);
 
@@ -37,133 +113,1662 @@ pub async fn start_proxy(
     Ok(())
 }
 
-async fn save_diagram(
+async fn save_diagram(
+    State(dest_addr): State<SocketAddr>,
+    headers: HeaderMap,
+    Json(json): Json<serde_json::Value>,
+) -> Result<(HeaderMap, String), Response> {
+    // Logic to save the diagram
+    println!("Saving diagram: {:?}", json);
+
+    // gRPCクライアントを作成し、データを転送
+    let mut client = diagram_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // JSONをprotoのFile構造体に変換
+    let file = json_to_proto_file(json).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+
+    // gRPCリクエストを作成
+    let mut request = tonic::Request::new(file);
+
+    // If-Matchヘッダを期待する版、X-Session-Idヘッダを保存するセッションとしてgRPCサーバに渡す
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    forward_session(&headers, &mut request).map_err(IntoResponse::into_response)?;
+
+    // gRPCサーバに送信（版が一致しない場合は409）
+    let response = client
+        .save_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to save diagram", &status))?;
+
+    let headers = saved_headers(response.metadata());
+
+    let result = response.into_inner();
+    if result.value {
+        Ok((headers, "Diagram saved successfully".to_string()))
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            result
+                .message
+                .unwrap_or_else(|| "Unknown error".to_string()),
+        )
+            .into_response())
+    }
+}
+
+async fn validate_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Json(json): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, Response> {
+    println!("Validating diagram: {:?}", json);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // JSONをprotoのFile構造体に変換
+    let file = json_to_proto_file(json).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+
+    // gRPCサーバで検証（保存はしない）
+    let response = client
+        .validate_class_diagram(tonic::Request::new(file))
+        .await
+        .map_err(|status| grpc_error_response("Failed to validate diagram", &status))?;
+
+    Ok(Json(proto_validation_report_to_json(&response.into_inner())))
+}
+
+// POST /api_p1/import/{format} と POST /api_p1/reverse/{language} のクエリパラメータ
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    // 保存先のファイルID（省略時は新しいIDを割り当てる）
+    file_id: Option<String>,
+    name: Option<String>,
+}
+
+// テキスト（PlantUMLなど）を変換して保存し、保存したファイルを返す
+async fn import_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(format): Path<String>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    source: String,
+) -> Result<(StatusCode, HeaderMap, Json<serde_json::Value>), Response> {
+    println!("Importing diagram as {}", format);
+
+    // パスの形式名（plantuml など）をImportFormatに変換
+    let import_format =
+        ImportFormat::from_str_name(&format!("IMPORT_FORMAT_{}", format.to_uppercase()))
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown import format: {}", format),
+                )
+                    .into_response()
+            })?;
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let mut request = tonic::Request::new(ImportRequest {
+        format: import_format as i32,
+        source,
+        file_id: query.file_id.map(|id| FileId { id }),
+        name: query.name.unwrap_or_default(),
+    });
+
+    // 既存のファイルに上書きする場合はIf-Matchを期待する版として渡す
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+
+    // gRPCサーバで変換して保存（解釈できない場合は位置付きの400、検証で拒否された場合は422）
+    let response = client
+        .import_class_diagram(request)
+        .await
+        .map_err(|status| import_error_response(&status))?;
+
+    let headers = saved_headers(response.metadata());
+    let file = response.into_inner();
+    Ok((StatusCode::CREATED, headers, Json(proto_file_to_json(&file))))
+}
+
+// ソースコードのアーカイブ（zip・tar・tar.gz）からクラス図を作成して保存し、
+// 保存したファイルと解析できなかったファイルの一覧を返す
+async fn reverse_engineer(
+    State(dest_addr): State<SocketAddr>,
+    Path(language): Path<String>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    archive: Bytes,
+) -> Result<(StatusCode, HeaderMap, Json<serde_json::Value>), Response> {
+    println!(
+        "Reverse engineering {} sources ({} bytes)",
+        language,
+        archive.len()
+    );
+
+    // パスの言語名（java など）をSourceLanguageに変換
+    let source_language =
+        SourceLanguage::from_str_name(&format!("SOURCE_LANGUAGE_{}", language.to_uppercase()))
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown source language: {}", language),
+                )
+                    .into_response()
+            })?;
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let mut request = tonic::Request::new(ReverseEngineerRequest {
+        language: source_language as i32,
+        archive: archive.to_vec(),
+        file_id: query.file_id.map(|id| FileId { id }),
+        name: query.name.unwrap_or_default(),
+    });
+
+    // 既存のファイルに上書きする場合はIf-Matchを期待する版として渡す
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+
+    // gRPCサーバで解析して保存
+    let response = client
+        .reverse_engineer(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to reverse engineer sources", &status))?;
+
+    let headers = saved_headers(response.metadata());
+    let reversed = response.into_inner();
+    let file = reversed.file.unwrap_or_default();
+    let skipped: Vec<serde_json::Value> = reversed
+        .skipped
+        .iter()
+        .map(proto_skipped_source_to_json)
+        .collect();
+    Ok((
+        StatusCode::CREATED,
+        headers,
+        Json(serde_json::json!({
+            "file": proto_file_to_json(&file),
+            "skipped": skipped
+        })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    page_size: Option<u32>,
+    page_token: Option<String>,
+    // name または last_modified
+    order_by: Option<String>,
+    // asc または desc
+    order: Option<String>,
+}
+
+async fn list_diagrams(
+    State(dest_addr): State<SocketAddr>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    println!("Listing diagrams: {:?}", query);
+
+    let order_by = match query.order_by.as_deref() {
+        None | Some("last_modified") => SortField::LastModified,
+        Some("name") => SortField::Name,
+        Some(other) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown order_by: {} (expected name or last_modified)", other),
+            )
+                .into_response())
+        }
+    };
+    let order = match query.order.as_deref() {
+        None => SortOrder::Unspecified,
+        Some("asc") => SortOrder::Ascending,
+        Some("desc") => SortOrder::Descending,
+        Some(other) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown order: {} (expected asc or desc)", other),
+            )
+                .into_response())
+        }
+    };
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(ListClassDiagramsRequest {
+        page_size: query.page_size.unwrap_or_default(),
+        page_token: query.page_token.unwrap_or_default(),
+        order_by: order_by as i32,
+        order: order as i32,
+    });
+
+    // gRPCサーバから取得
+    let response = client
+        .list_class_diagrams(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to list diagrams", &status))?;
+
+    let response = response.into_inner();
+    let diagrams: Vec<serde_json::Value> = response
+        .diagrams
+        .iter()
+        .map(proto_diagram_summary_to_json)
+        .collect();
+
+    // 次のページが無い場合はnull
+    let next_page_token = if response.next_page_token.is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::json!(response.next_page_token)
+    };
+
+    Ok(Json(serde_json::json!({
+        "diagrams": diagrams,
+        "next_page_token": next_page_token,
+        "total_count": response.total_count
+    })))
+}
+
+// GET /api_p1/search のクエリパラメータ
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<u32>,
+}
+
+async fn search_diagrams(
+    State(dest_addr): State<SocketAddr>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    println!("Searching diagrams: {:?}", query);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(SearchRequest {
+        query: query.q,
+        limit: query.limit.unwrap_or_default(),
+    });
+
+    // gRPCサーバで検索
+    let response = client
+        .search_diagrams(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to search diagrams", &status))?;
+
+    let hits: Vec<serde_json::Value> = response
+        .into_inner()
+        .hits
+        .iter()
+        .map(proto_search_hit_to_json)
+        .collect();
+
+    Ok(Json(serde_json::json!({ "hits": hits })))
+}
+
+async fn get_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<(HeaderMap, Json<serde_json::Value>), Response> {
+    // Logic to retrieve the diagram
+    println!("Retrieving diagram for file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = diagram_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(FileId {
+        id: file_id.clone(),
+    });
+
+    // gRPCサーバから取得（存在しない場合は404）
+    let response = client
+        .get_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to get diagram", &status))?;
+
+    // 現在の版をETagヘッダで返す
+    let headers = etag_headers(response.metadata());
+    let file = response.into_inner();
+
+    // protoのFileをJSONに変換
+    let json = proto_file_to_json(&file);
+
+    Ok((headers, Json(json)))
+}
+
+// JSON Patch（RFC 6902）を現在の版に適用して保存する
+// If-Matchが無い場合も、パッチを適用した版の後に他の保存があった場合は409を返す
+async fn patch_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    headers: HeaderMap,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<(HeaderMap, Json<serde_json::Value>), Response> {
+    println!("Patching diagram for file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = diagram_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // 現在の版を取得
+    let response = client
+        .get_class_diagram(tonic::Request::new(FileId {
+            id: file_id.clone(),
+        }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to get diagram", &status))?;
+    let etag = response.metadata().get("etag").cloned();
+
+    // JSONの表現にパッチを適用し、protoのFile構造体に戻す（保存先はパスのファイルID）
+    let mut json = proto_file_to_json(&response.into_inner());
+    json_patch::apply(&mut json, &patch).map_err(|e| {
+        let status = match e {
+            PatchError::Invalid(_) => StatusCode::BAD_REQUEST,
+            PatchError::Failed(_) => StatusCode::CONFLICT,
+        };
+        (status, e.to_string()).into_response()
+    })?;
+    let mut file = json_to_proto_file(json)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())?;
+    file.file_id = Some(FileId { id: file_id });
+
+    // If-Matchが無い場合は取得した版を期待する版とする
+    let mut request = tonic::Request::new(file.clone());
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    forward_session(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    if !headers.contains_key(header::IF_MATCH) {
+        if let Some(etag) = etag {
+            request.metadata_mut().insert("if-match", etag);
+        }
+    }
+
+    // gRPCサーバに送信（版が一致しない場合は409）
+    let response = client
+        .save_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to save diagram", &status))?;
+
+    let headers = saved_headers(response.metadata());
+    let result = response.into_inner();
+    if !result.value {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            result
+                .message
+                .unwrap_or_else(|| "Unknown error".to_string()),
+        )
+            .into_response());
+    }
+    Ok((headers, Json(proto_file_to_json(&file))))
+}
+
+async fn delete_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<String, Response> {
+    // Logic to delete the diagram
+    println!("Deleting diagram for file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = diagram_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let mut request = tonic::Request::new(FileId {
+        id: file_id.clone(),
+    });
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    forward_session(&headers, &mut request).map_err(IntoResponse::into_response)?;
+
+    // サーバから削除（If-Matchの版が一致しない場合は412、他のセッションがロックしているクラスがある場合は423）
+    let response = client
+        .delete_class_diagram(request)
+        .await
+        .map_err(|status| {
+            if headers.contains_key(header::IF_MATCH) {
+                precondition_error_response("Failed to delete diagram", &status)
+            } else {
+                grpc_error_response("Failed to delete diagram", &status)
+            }
+        })?;
+
+    let result = response.into_inner();
+    if result.value {
+        Ok("Diagram deleted successfully".to_string())
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            result
+                .message
+                .unwrap_or_else(|| "File not found".to_string()),
+        )
+            .into_response())
+    }
+}
+
+async fn check_exists(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // Logic to check if diagram exists
+    println!("Checking existence of diagram for file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = diagram_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(FileId {
+        id: file_id.clone(),
+    });
+
+    // gRPCサーバから確認
+    let response = client
+        .is_existing_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to check diagram existence", &status))?;
+
+    let result = response.into_inner();
+
+    Ok(Json(serde_json::json!({
+        "exists": result.value,
+        "message": result.message
+    })))
+}
+
+async fn export_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path((file_id, format)): Path<(String, String)>,
+) -> Result<(HeaderMap, String), Response> {
+    println!("Exporting diagram for file_id: {} as {}", file_id, format);
+
+    // パスの形式名（plantuml など）をExportFormatに変換
+    let export_format =
+        ExportFormat::from_str_name(&format!("EXPORT_FORMAT_{}", format.to_uppercase()))
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown export format: {}", format),
+                )
+                    .into_response()
+            })?;
+
+    fetch_export(dest_addr, file_id, export_format).await
+}
+
+async fn render_svg(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<(HeaderMap, String), Response> {
+    println!("Rendering diagram for file_id: {}", file_id);
+
+    fetch_export(dest_addr, file_id, ExportFormat::Svg).await
+}
+
+// gRPCサーバで変換し、メディアタイプとファイル名をヘッダに設定
+async fn fetch_export(
+    dest_addr: SocketAddr,
+    file_id: String,
+    export_format: ExportFormat,
+) -> Result<(HeaderMap, String), Response> {
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(ExportRequest {
+        file_id: Some(FileId { id: file_id }),
+        format: export_format as i32,
+    });
+
+    // gRPCサーバで変換
+    let response = client
+        .export_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to export diagram", &status))?;
+
+    let mut headers = etag_headers(response.metadata());
+    let exported = response.into_inner();
+    if let Ok(value) = HeaderValue::from_str(&exported.media_type) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("inline; filename=\"{}\"", exported.file_name))
+    {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+
+    Ok((headers, exported.content))
+}
+
+// ソースを生成し、zipにまとめて返す
+async fn generate_code(
+    State(dest_addr): State<SocketAddr>,
+    Path((file_id, language)): Path<(String, String)>,
+) -> Result<(HeaderMap, Vec<u8>), Response> {
+    println!("Generating {} code for file_id: {}", language, file_id);
+
+    // パスの言語名（java など）をCodeLanguageに変換
+    let code_language =
+        CodeLanguage::from_str_name(&format!("CODE_LANGUAGE_{}", language.to_uppercase()))
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown code language: {}", language),
+                )
+                    .into_response()
+            })?;
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(GenerateCodeRequest {
+        file_id: Some(FileId { id: file_id }),
+        language: code_language as i32,
+    });
+
+    // gRPCサーバで生成
+    let response = client
+        .generate_code(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to generate code", &status))?;
+
+    let mut headers = etag_headers(response.metadata());
+    let code = response.into_inner();
+    let sources: Vec<GeneratedSource> = code
+        .files
+        .into_iter()
+        .map(|file| GeneratedSource {
+            path: file.path,
+            content: file.content,
+        })
+        .collect();
+    let archive =
+        to_zip(&sources).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e).into_response())?;
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", code.archive_name))
+    {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+
+    Ok((headers, archive))
+}
+
+#[derive(Debug, Deserialize)]
+struct DiffQuery {
+    // 比較の基準（変更前）のファイルID（省略時は同じファイル）
+    against: Option<String>,
+    // 比較の基準の版（省略時は現在の版）
+    against_revision: Option<u64>,
+    // このファイルの版（省略時は現在の版）
+    revision: Option<u64>,
+}
+
+// 別のファイル・版からこのファイルへの変更を返す
+async fn diff_diagrams(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // 同じファイルの同じ版どうしの比較は意味が無いため、どちらかの指定を必須とする
+    if query.against.is_none() && query.against_revision.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "against or against_revision is required",
+        )
+            .into_response());
+    }
+    let against = query.against.unwrap_or_else(|| file_id.clone());
+    println!("Comparing file_id: {} against {}", file_id, against);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成（版の0は現在の版）
+    let request = tonic::Request::new(DiffRequest {
+        base: Some(RevisionRequest {
+            file_id: Some(FileId { id: against }),
+            revision: query.against_revision.unwrap_or(0),
+        }),
+        target: Some(RevisionRequest {
+            file_id: Some(FileId { id: file_id }),
+            revision: query.revision.unwrap_or(0),
+        }),
+    });
+
+    // gRPCサーバで比較
+    let response = client
+        .diff_class_diagrams(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to diff diagrams", &status))?;
+
+    let diff = response.into_inner();
+    Ok(Json(proto_diagram_diff_to_json(&diff)))
+}
+
+// 共通の祖先と2つの子孫のファイルをマージした結果を返す（保存しない）
+async fn merge_diagrams(
+    State(dest_addr): State<SocketAddr>,
+    Json(json): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // JSONをprotoのFile構造体に変換
+    let file = |side: &str| -> Result<Option<File>, (StatusCode, String)> {
+        let json = json
+            .get(side)
+            .cloned()
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("{} is required", side)))?;
+        json_to_proto_file(json)
+            .map(Some)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))
+    };
+    let request = MergeRequest {
+        base: file("base").map_err(IntoResponse::into_response)?,
+        ours: file("ours").map_err(IntoResponse::into_response)?,
+        theirs: file("theirs").map_err(IntoResponse::into_response)?,
+    };
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCサーバでマージ
+    let response = client
+        .merge_class_diagrams(tonic::Request::new(request))
+        .await
+        .map_err(|status| grpc_error_response("Failed to merge diagrams", &status))?;
+
+    let result = response.into_inner();
+    Ok(Json(proto_merge_result_to_json(&result)))
+}
+
+#[derive(Deserialize)]
+struct MergeQuery {
+    // 編集を始めたときの版
+    base_revision: u64,
+}
+
+// 編集を始めた版からの変更を現在の版とマージして保存する
+// 衝突がある場合は保存せず、マージ結果と衝突の一覧を409で返す
+async fn save_merged_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    Query(query): Query<MergeQuery>,
+    headers: HeaderMap,
+    Json(json): Json<serde_json::Value>,
+) -> Result<(StatusCode, HeaderMap, Json<serde_json::Value>), Response> {
+    println!(
+        "Merging file_id: {} from revision {}",
+        file_id, query.base_revision
+    );
+
+    // JSONをprotoのFile構造体に変換（保存先はパスのファイルID）
+    let mut file =
+        json_to_proto_file(json).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    file.file_id = Some(FileId { id: file_id });
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCサーバでマージして保存
+    let mut request = tonic::Request::new(MergeSaveRequest {
+        file: Some(file),
+        base_revision: query.base_revision,
+    });
+    forward_session(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    let response = client
+        .save_merged_class_diagram(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to merge diagram", &status))?;
+
+    let headers = saved_headers(response.metadata());
+    let result = response.into_inner();
+    let status = if result.conflicts.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::CONFLICT
+    };
+    Ok((status, headers, Json(proto_merge_result_to_json(&result))))
+}
+
+// ファイルの変更をServer-Sent Eventsで送る
+// 最初に現在の内容（snapshot）、以降は保存（saved）・削除（deleted）のたびにイベントを送る
+async fn watch_diagram(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Response> {
+    println!("Watching file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCサーバの変更の購読を開始（ファイルが無い場合は404）
+    let response = client
+        .watch_class_diagram(tonic::Request::new(FileId { id: file_id }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to watch diagram", &status))?;
+
+    // gRPCのストリームが終わる（エラーを含む）とイベントの送信も終わる
+    let events = response.into_inner().map(|event| {
+        Ok(match event {
+            Ok(event) => diagram_event_to_sse(&event),
+            Err(status) => Event::default().event("error").data(status.message()),
+        })
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct JoinSessionBody {
+    // 認証されていない場合の表示名（認証された場合は利用者名を使う）
+    #[serde(default)]
+    user_name: String,
+    // 指定した場合はそのセッションを更新する
+    #[serde(default)]
+    session_id: String,
+}
+
+// ファイルを開いたことを知らせ、開いている利用者とロックを返す
+// セッションを続けるには期限（expires_at）の前に同じsession_idで呼び直す
+async fn join_session(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    Json(body): Json<JoinSessionBody>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCサーバでセッションを作成・更新（ファイルが無い場合は404）
+    let response = client
+        .join_session(tonic::Request::new(JoinSessionRequest {
+            file_id: Some(FileId { id: file_id }),
+            session_id: body.session_id,
+            user_name: body.user_name,
+        }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to join session", &status))?;
+
+    Ok(Json(proto_session_state_to_json(&response.into_inner())))
+}
+
+async fn list_sessions(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    let response = client
+        .get_sessions(tonic::Request::new(FileId { id: file_id }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to get sessions", &status))?;
+
+    Ok(Json(proto_session_state_to_json(&response.into_inner())))
+}
+
+async fn leave_session(
+    State(dest_addr): State<SocketAddr>,
+    Path((file_id, session_id)): Path<(String, String)>,
+) -> Result<String, Response> {
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    let response = client
+        .leave_session(tonic::Request::new(SessionRequest {
+            file_id: Some(FileId { id: file_id }),
+            session_id,
+        }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to leave session", &status))?;
+
+    let result = response.into_inner();
+    let message = result
+        .message
+        .unwrap_or_else(|| "Unknown error".to_string());
+    if result.value {
+        Ok(message)
+    } else {
+        Err((StatusCode::NOT_FOUND, message).into_response())
+    }
+}
+
+#[derive(Deserialize)]
+struct LockClassesBody {
+    class_ids: Vec<String>,
+    // 0または省略した場合は60秒
+    #[serde(default)]
+    ttl_seconds: u32,
+}
+
+// X-Session-Idのセッションでクラスのソフトロックを取得・延長する
+// 他のセッションのロックがある場合はどれも取得せず、ロックしているセッションを423で返す
+async fn lock_classes(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<LockClassesBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Response> {
+    let session_id = session_header(&headers).map_err(IntoResponse::into_response)?;
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // セッションが無い（期限切れを含む）場合は404
+    let response = client
+        .lock_classes(tonic::Request::new(LockRequest {
+            file_id: Some(FileId { id: file_id }),
+            session_id,
+            class_ids: body.class_ids,
+            ttl_seconds: body.ttl_seconds,
+        }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to lock classes", &status))?;
+
+    let result = response.into_inner();
+    let status = if result.conflicts.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::LOCKED
+    };
+    let locks: Vec<serde_json::Value> = result.locks.iter().map(proto_class_lock_to_json).collect();
+    let conflicts: Vec<serde_json::Value> = result
+        .conflicts
+        .iter()
+        .map(proto_class_lock_to_json)
+        .collect();
+    Ok((
+        status,
+        Json(serde_json::json!({
+            "locks": locks,
+            "conflicts": conflicts
+        })),
+    ))
+}
+
+async fn unlock_class(
+    State(dest_addr): State<SocketAddr>,
+    Path((file_id, class_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<String, Response> {
+    let session_id = session_header(&headers).map_err(IntoResponse::into_response)?;
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    let response = client
+        .unlock_classes(tonic::Request::new(UnlockRequest {
+            file_id: Some(FileId { id: file_id }),
+            session_id,
+            class_ids: vec![class_id],
+        }))
+        .await
+        .map_err(|status| grpc_error_response("Failed to unlock class", &status))?;
+
+    let result = response.into_inner();
+    if result.value {
+        Ok("Lock released successfully".to_string())
+    } else {
+        Err((StatusCode::NOT_FOUND, "Lock not found".to_string()).into_response())
+    }
+}
+
+async fn list_revisions(
+    State(dest_addr): State<SocketAddr>,
+    Path(file_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    println!("Listing revisions for file_id: {}", file_id);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(FileId {
+        id: file_id.clone(),
+    });
+
+    // gRPCサーバから取得
+    let response = client
+        .list_revisions(request)
+        .await
+        .map_err(|status| grpc_error_response("Failed to list revisions", &status))?;
+
+    let revisions: Vec<serde_json::Value> = response
+        .into_inner()
+        .revisions
+        .iter()
+        .map(proto_revision_info_to_json)
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "file_id": {"id": file_id},
+        "revisions": revisions
+    })))
+}
+
+async fn get_revision(
     State(dest_addr): State<SocketAddr>,
-    Json(json): Json<serde_json::Value>,
-) -> Result<String, String> {
-    // Logic to save the diagram
-    println!("Saving diagram: {:?}", json);
+    Path((file_id, revision)): Path<(String, u64)>,
+) -> Result<Json<serde_json::Value>, Response> {
+    println!("Retrieving revision {} for file_id: {}", revision, file_id);
 
-    // gRPCクライアントを作成し、データを転送
-    let mut client = DiagramServiceClient::connect(format!("http://{}", dest_addr))
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // gRPCリクエストを作成
+    let request = tonic::Request::new(RevisionRequest {
+        file_id: Some(FileId { id: file_id }),
+        revision,
+    });
+
+    // gRPCサーバから取得
+    let response = client
+        .get_revision(request)
         .await
-        .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to get revision", &status))?;
 
-    // JSONをprotoのFile構造体に変換
-    let file = json_to_proto_file(json)?;
+    let revision = response.into_inner();
+    let info = revision
+        .info
+        .as_ref()
+        .map(proto_revision_info_to_json)
+        .unwrap_or_else(|| serde_json::json!(null));
+    let file = revision
+        .file
+        .as_ref()
+        .map(proto_file_to_json)
+        .unwrap_or_else(|| serde_json::json!(null));
+
+    Ok(Json(serde_json::json!({
+        "revision": info,
+        "file": file
+    })))
+}
+
+async fn restore_revision(
+    State(dest_addr): State<SocketAddr>,
+    Path((file_id, revision)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, String), Response> {
+    println!("Restoring revision {} for file_id: {}", revision, file_id);
+
+    // gRPCクライアントを作成
+    let mut client = ext_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
 
     // gRPCリクエストを作成
-    let request = tonic::Request::new(file);
+    let mut request = tonic::Request::new(RevisionRequest {
+        file_id: Some(FileId { id: file_id }),
+        revision,
+    });
+    // If-Matchヘッダを期待する版、X-Session-Idヘッダを復元するセッションとしてgRPCサーバに渡す
+    forward_if_match(&headers, &mut request).map_err(IntoResponse::into_response)?;
+    forward_session(&headers, &mut request).map_err(IntoResponse::into_response)?;
 
-    // gRPCサーバに送信
+    // gRPCサーバで復元（版が存在しない場合は404、現在の版が一致しない場合は409）
     let response = client
-        .save_class_diagram(request)
+        .restore_revision(request)
         .await
-        .map_err(|e| format!("Failed to save diagram: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to restore revision", &status))?;
+
+    let headers = saved_headers(response.metadata());
 
     let result = response.into_inner();
     if result.value {
-        Ok("Diagram saved successfully".to_string())
+        Ok((
+            headers,
+            result
+                .message
+                .unwrap_or_else(|| "Revision restored successfully".to_string()),
+        ))
     } else {
-        Err(result
-            .message
-            .unwrap_or_else(|| "Unknown error".to_string()))
+        Err((
+            StatusCode::BAD_REQUEST,
+            result
+                .message
+                .unwrap_or_else(|| "Unknown error".to_string()),
+        )
+            .into_response())
     }
 }
 
-async fn get_diagram(
-    State(dest_addr): State<SocketAddr>,
-    Path(file_id): Path<String>,
-) -> Result<Json<serde_json::Value>, String> {
-    // Logic to retrieve the diagram
-    println!("Retrieving diagram for file_id: {}", file_id);
+#[derive(Deserialize)]
+struct LoginBody {
+    user_name: String,
+    password: String,
+}
 
+// パスワードでログインし、Authorization: Bearer <token>で送るトークンを返す
+async fn login(
+    State(dest_addr): State<SocketAddr>,
+    Json(body): Json<LoginBody>,
+) -> Result<Json<serde_json::Value>, Response> {
     // gRPCクライアントを作成
-    let mut client = DiagramServiceClient::connect(format!("http://{}", dest_addr))
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // パスワードが一致しない場合は401
+    let response = client
+        .login(tonic::Request::new(LoginRequest {
+            user_name: body.user_name,
+            password: body.password,
+        }))
         .await
-        .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to log in", &status))?;
 
-    // gRPCリクエストを作成
-    let request = tonic::Request::new(FileId {
-        id: file_id.clone(),
-    });
+    let token = response.into_inner();
+    Ok(Json(serde_json::json!({
+        "token": token.token,
+        "token_type": "Bearer",
+        "expires_at": token.expires_at,
+        "user": token.user.as_ref().map(proto_user_to_json)
+    })))
+}
+
+// 資格情報の利用者
+async fn current_user(
+    State(dest_addr): State<SocketAddr>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // gRPCクライアントを作成
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
 
-    // gRPCサーバから取得
     let response = client
-        .get_class_diagram(request)
+        .authenticate(tonic::Request::new(AuthenticateRequest {}))
         .await
-        .map_err(|e| format!("Failed to get diagram: {}", e))?;
-
-    let file = response.into_inner();
+        .map_err(|status| grpc_error_response("Failed to authenticate", &status))?;
 
-    // protoのFileをJSONに変換
-    let json = proto_file_to_json(&file);
+    Ok(Json(proto_user_to_json(&response.into_inner())))
+}
 
-    Ok(Json(json))
+#[derive(Deserialize)]
+struct CreateUserBody {
+    user_name: String,
+    password: String,
+    #[serde(default)]
+    admin: bool,
 }
 
-async fn delete_diagram(
+// 管理者が利用者を作成する
+async fn create_user(
     State(dest_addr): State<SocketAddr>,
-    Path(file_id): Path<String>,
-) -> Result<String, String> {
-    // Logic to delete the diagram
-    println!("Deleting diagram for file_id: {}", file_id);
-
+    Json(body): Json<CreateUserBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Response> {
     // gRPCクライアントを作成
-    let mut client = DiagramServiceClient::connect(format!("http://{}", dest_addr))
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // 管理者でない場合は403、既に存在する場合は409
+    let response = client
+        .create_user(tonic::Request::new(CreateUserRequest {
+            user_name: body.user_name,
+            password: body.password,
+            admin: body.admin,
+        }))
         .await
-        .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to create user", &status))?;
 
-    // gRPCリクエストを作成
-    let request = tonic::Request::new(FileId {
-        id: file_id.clone(),
-    });
+    Ok((
+        StatusCode::CREATED,
+        Json(proto_user_to_json(&response.into_inner())),
+    ))
+}
+
+// 資格情報の利用者のAPIキーの一覧
+async fn list_api_keys(
+    State(dest_addr): State<SocketAddr>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // gRPCクライアントを作成
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
 
-    // サーバから削除
     let response = client
-        .delete_class_diagram(request)
+        .list_api_keys(tonic::Request::new(ListApiKeysRequest {}))
         .await
-        .map_err(|e| format!("Failed to delete diagram: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to list API keys", &status))?;
 
-    let result = response.into_inner();
-    if result.value {
-        Ok("Diagram deleted successfully".to_string())
-    } else {
-        Err(result
-            .message
-            .unwrap_or_else(|| "Unknown error".to_string()))
-    }
+    let keys: Vec<serde_json::Value> = response
+        .into_inner()
+        .keys
+        .iter()
+        .map(proto_api_key_to_json)
+        .collect();
+    Ok(Json(serde_json::json!({ "keys": keys })))
 }
 
-async fn check_exists(
-    State(dest_addr): State<SocketAddr>,
-    Path(file_id): Path<String>,
-) -> Result<Json<serde_json::Value>, String> {
-    // Logic to check if diagram exists
-    println!("Checking existence of diagram for file_id: {}", file_id);
+#[derive(Deserialize)]
+struct CreateApiKeyBody {
+    #[serde(default)]
+    name: String,
+}
 
+// APIキーを作成する（キーの値はこの応答でしか返さない）
+async fn create_api_key(
+    State(dest_addr): State<SocketAddr>,
+    Json(body): Json<CreateApiKeyBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Response> {
     // gRPCクライアントを作成
-    let mut client = DiagramServiceClient::connect(format!("http://{}", dest_addr))
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    let response = client
+        .create_api_key(tonic::Request::new(CreateApiKeyRequest { name: body.name }))
         .await
-        .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to create API key", &status))?;
 
-    // gRPCリクエストを作成
-    let request = tonic::Request::new(FileId {
-        id: file_id.clone(),
-    });
+    let created = response.into_inner();
+    let mut json = created
+        .info
+        .as_ref()
+        .map(proto_api_key_to_json)
+        .unwrap_or_else(|| serde_json::json!({}));
+    json["key"] = serde_json::json!(created.key);
+    Ok((StatusCode::CREATED, Json(json)))
+}
 
-    // gRPCサーバから確認
+async fn revoke_api_key(
+    State(dest_addr): State<SocketAddr>,
+    Path(key_id): Path<String>,
+) -> Result<String, Response> {
+    // gRPCクライアントを作成
+    let mut client = auth_client(dest_addr).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to connect to gRPC server: {}", e),
+        )
+            .into_response()
+    })?;
+
+    // 資格情報の利用者のキーでない場合は404
     let response = client
-        .is_existing_class_diagram(request)
+        .revoke_api_key(tonic::Request::new(RevokeApiKeyRequest { key_id }))
         .await
-        .map_err(|e| format!("Failed to check diagram existence: {}", e))?;
+        .map_err(|status| grpc_error_response("Failed to revoke API key", &status))?;
 
     let result = response.into_inner();
+    Ok(result
+        .message
+        .unwrap_or_else(|| "API key revoked successfully".to_string()))
+}
 
-    Ok(Json(serde_json::json!({
-        "exists": result.value,
-        "message": result.message
-    })))
+// リクエストの資格情報（Authorizationヘッダの値）
+// ミドルウェアが設定し、gRPCクライアントがauthorizationメタデータとして送る
+tokio::task_local! {
+    static CREDENTIALS: Option<String>;
+}
+
+// 資格情報無しで受け付けるパス
+const PUBLIC_PATHS: &[&str] = &["/api_p1/auth/login"];
+
+#[derive(Deserialize)]
+struct AccessTokenQuery {
+    access_token: Option<String>,
+}
+
+// REST APIの各リクエストの資格情報をgRPCの呼び出しに引き継ぐ
+// 資格情報の検証はgRPCサーバが行い、拒否された場合はgrpc_error_responseが401を返す
+// EventSourceのようにヘッダを設定できないクライアントはaccess_tokenクエリでも送れる
+async fn authenticate(
+    State(auth_mode): State<AuthMode>,
+    request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    let credentials = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| value.to_str().map(str::to_string))
+        .or_else(|| {
+            Query::<AccessTokenQuery>::try_from_uri(request.uri())
+                .ok()
+                .and_then(|Query(query)| query.access_token)
+                .map(|token| Ok(format!("Bearer {}", token)))
+        });
+    let credentials = match credentials {
+        Some(Ok(credentials)) => Some(credentials),
+        Some(Err(_)) => return unauthorized("Invalid Authorization header"),
+        None => None,
+    };
+
+    if credentials.is_none()
+        && auth_mode != AuthMode::Off
+        && !PUBLIC_PATHS.contains(&request.uri().path())
+    {
+        return unauthorized("Credentials are required");
+    }
+
+    CREDENTIALS.scope(credentials, next.run(request)).await
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        format!("Authentication failed: {}", message),
+    )
+        .into_response()
+}
+
+// gRPCの呼び出しにリクエストの資格情報を付ける
+#[derive(Debug, Clone)]
+struct ForwardCredentials(Option<String>);
+
+impl Interceptor for ForwardCredentials {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(credentials) = &self.0 {
+            let value = credentials
+                .parse()
+                .map_err(|_| tonic::Status::unauthenticated("Invalid Authorization header"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+type AuthenticatedChannel = InterceptedService<Channel, ForwardCredentials>;
+
+// リクエストの資格情報を付けて呼び出すgRPCチャネル
+async fn authenticated_channel(
+    dest_addr: SocketAddr,
+) -> Result<(Channel, ForwardCredentials), tonic::transport::Error> {
+    let channel = Endpoint::from_shared(format!("http://{}", dest_addr))?
+        .connect()
+        .await?;
+    let credentials = CREDENTIALS
+        .try_with(|credentials| credentials.clone())
+        .ok()
+        .flatten();
+    Ok((channel, ForwardCredentials(credentials)))
+}
+
+async fn diagram_client(
+    dest_addr: SocketAddr,
+) -> Result<DiagramServiceClient<AuthenticatedChannel>, tonic::transport::Error> {
+    let (channel, credentials) = authenticated_channel(dest_addr).await?;
+    Ok(DiagramServiceClient::with_interceptor(channel, credentials))
+}
+
+async fn ext_client(
+    dest_addr: SocketAddr,
+) -> Result<DiagramExtServiceClient<AuthenticatedChannel>, tonic::transport::Error> {
+    let (channel, credentials) = authenticated_channel(dest_addr).await?;
+    Ok(DiagramExtServiceClient::with_interceptor(
+        channel,
+        credentials,
+    ))
+}
+
+async fn auth_client(
+    dest_addr: SocketAddr,
+) -> Result<AuthServiceClient<AuthenticatedChannel>, tonic::transport::Error> {
+    let (channel, credentials) = authenticated_channel(dest_addr).await?;
+    Ok(AuthServiceClient::with_interceptor(channel, credentials))
+}
+
+// gRPCのステータスコードをHTTPステータスに変換
+fn http_status(status: &tonic::Status) -> StatusCode {
+    match status.code() {
+        tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        tonic::Code::NotFound => StatusCode::NOT_FOUND,
+        tonic::Code::AlreadyExists
+        | tonic::Code::FailedPrecondition
+        | tonic::Code::Aborted => StatusCode::CONFLICT,
+        tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+        tonic::Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        tonic::Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// gRPCのエラーをHTTPレスポンスに変換（現在の版が分かる場合はETagヘッダを付ける）
+// 資格情報が拒否された場合はWWW-Authenticateヘッダを付けて401を返す
+// 検証で拒否された場合は問題の一覧を422のJSONで返す
+// ロックで拒否された場合はロックしているセッションを423のJSONで返す
+fn grpc_error_response(context: &str, status: &tonic::Status) -> Response {
+    if status.code() == tonic::Code::Unauthenticated {
+        return unauthorized(status.message());
+    }
+
+    if status.code() == tonic::Code::FailedPrecondition {
+        if let Ok(conflicts) = LockConflicts::decode(status.details()) {
+            if !conflicts.locks.is_empty() {
+                let locks: Vec<serde_json::Value> = conflicts
+                    .locks
+                    .iter()
+                    .map(proto_class_lock_to_json)
+                    .collect();
+                return (
+                    StatusCode::LOCKED,
+                    Json(serde_json::json!({
+                        "message": format!("{}: {}", context, status.message()),
+                        "locks": locks
+                    })),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    if status.code() == tonic::Code::InvalidArgument {
+        if let Ok(report) = ValidationReport::decode(status.details()) {
+            if !report.violations.is_empty() {
+                let mut body = proto_validation_report_to_json(&report);
+                body["message"] = serde_json::json!(format!("{}: {}", context, status.message()));
+                return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+            }
+        }
+    }
+
+    (
+        http_status(status),
+        etag_headers(status.metadata()),
+        format!("{}: {}", context, status.message()),
+    )
+        .into_response()
+}
+
+// If-Matchを指定したリクエストで版が一致しなかった場合は412を返す
+// （ロックによる拒否など、それ以外のエラーはgrpc_error_responseと同じ）
+fn precondition_error_response(context: &str, status: &tonic::Status) -> Response {
+    let locked = LockConflicts::decode(status.details())
+        .map(|conflicts| !conflicts.locks.is_empty())
+        .unwrap_or(false);
+    if status.code() == tonic::Code::FailedPrecondition && !locked {
+        return (
+            StatusCode::PRECONDITION_FAILED,
+            etag_headers(status.metadata()),
+            format!("{}: {}", context, status.message()),
+        )
+            .into_response();
+    }
+
+    grpc_error_response(context, status)
+}
+
+// インポートで解釈できなかった場合は位置をJSONで返す（400）
+fn import_error_response(status: &tonic::Status) -> Response {
+    if status.code() == tonic::Code::InvalidArgument {
+        if let Ok(error) = ParseError::decode(status.details()) {
+            if error.line > 0 {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "message": format!("Failed to import diagram: {}", status.message()),
+                        "line": error.line,
+                        "column": error.column,
+                        "error": error.message
+                    })),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    grpc_error_response("Failed to import diagram", status)
+}
+
+// gRPCメタデータのetagをHTTPのETagヘッダに変換
+fn etag_headers(metadata: &tonic::metadata::MetadataMap) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = metadata
+        .get("etag")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| HeaderValue::from_str(value).ok())
+    {
+        headers.insert(header::ETAG, value);
+    }
+    headers
+}
+
+// 保存の応答のETagと、警告モードで検証の問題があった場合はその件数、
+// 他のセッションがロックしているクラスを変更した場合はクラスとロックしている利用者（c2=alice, c3=bob）をヘッダで返す
+fn saved_headers(metadata: &tonic::metadata::MetadataMap) -> HeaderMap {
+    let mut headers = etag_headers(metadata);
+    if let Some(report) = metadata
+        .get_bin("validation-report-bin")
+        .and_then(|value| value.to_bytes().ok())
+        .and_then(|bytes| ValidationReport::decode(bytes).ok())
+    {
+        headers.insert(
+            "x-validation-warnings",
+            HeaderValue::from(report.violations.len()),
+        );
+    }
+    if let Some(conflicts) = metadata
+        .get_bin("lock-conflicts-bin")
+        .and_then(|value| value.to_bytes().ok())
+        .and_then(|bytes| LockConflicts::decode(bytes).ok())
+    {
+        let holders: Vec<String> = conflicts
+            .locks
+            .iter()
+            .map(|lock| format!("{}={}", lock.class_id, lock.user_name))
+            .collect();
+        if let Ok(value) = HeaderValue::from_bytes(holders.join(", ").as_bytes()) {
+            headers.insert("x-lock-conflicts", value);
+        }
+    }
+    headers
+}
+
+// HTTPのIf-MatchヘッダをgRPCメタデータに設定
+fn forward_if_match<T>(
+    headers: &HeaderMap,
+    request: &mut tonic::Request<T>,
+) -> Result<(), (StatusCode, &'static str)> {
+    if let Some(value) = headers.get(header::IF_MATCH) {
+        let value = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid If-Match header"))?;
+        request.metadata_mut().insert("if-match", value);
+    }
+    Ok(())
+}
+
+// HTTPのX-Session-IdヘッダのセッションIDを取得
+fn session_header(headers: &HeaderMap) -> Result<String, (StatusCode, &'static str)> {
+    headers
+        .get("x-session-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .ok_or((StatusCode::BAD_REQUEST, "X-Session-Id header is required"))
+}
+
+// HTTPのX-Session-IdヘッダをgRPCメタデータのsession-idに設定
+// （そのセッションのロックは保存時の衝突とみなさない）
+fn forward_session<T>(
+    headers: &HeaderMap,
+    request: &mut tonic::Request<T>,
+) -> Result<(), (StatusCode, &'static str)> {
+    if let Some(value) = headers.get("x-session-id") {
+        let value = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid X-Session-Id header"))?;
+        request.metadata_mut().insert("session-id", value);
+    }
+    Ok(())
 }
 
 // JSONをprotoのFile構造体に変換する関数
@@ -486,3 +2091,267 @@ fn proto_multiplicity_to_json(multiplicity: &Multiplicity) -> serde_json::Value
         "upper": multiplicity.upper
     })
 }
+
+fn proto_diagram_summary_to_json(summary: &DiagramSummary) -> serde_json::Value {
+    serde_json::json!({
+        "file_id": summary.file_id.as_ref().map(|id| serde_json::json!({"id": id.id})),
+        "name": summary.name,
+        "created_at": summary.created_at,
+        "last_modified": summary.last_modified,
+        "class_count": summary.class_count,
+        "revision": summary.revision
+    })
+}
+
+fn proto_validation_report_to_json(report: &ValidationReport) -> serde_json::Value {
+    let violations: Vec<serde_json::Value> = report
+        .violations
+        .iter()
+        .map(|violation| {
+            serde_json::json!({
+                "code": violation.code,
+                "path": violation.path,
+                "class_id": violation.class_id,
+                "message": violation.message
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "valid": violations.is_empty(),
+        "violations": violations
+    })
+}
+
+fn proto_search_hit_to_json(hit: &SearchHit) -> serde_json::Value {
+    let matches: Vec<serde_json::Value> = hit
+        .matches
+        .iter()
+        .map(|m| {
+            // SEARCH_FIELD_CLASS_NAME → class_name
+            let field = SearchField::try_from(m.field)
+                .map(|field| {
+                    field
+                        .as_str_name()
+                        .trim_start_matches("SEARCH_FIELD_")
+                        .to_lowercase()
+                })
+                .unwrap_or_default();
+            serde_json::json!({
+                "field": field,
+                "path": m.path,
+                "class_id": m.class_id,
+                "value": m.value
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "file_id": hit.file_id.as_ref().map(|id| serde_json::json!({"id": id.id})),
+        "name": hit.name,
+        "matches": matches
+    })
+}
+
+fn proto_revision_info_to_json(info: &RevisionInfo) -> serde_json::Value {
+    serde_json::json!({
+        "revision": info.revision,
+        "saved_at": info.saved_at,
+        "name": info.name,
+        "class_count": info.class_count,
+        "is_current": info.is_current
+    })
+}
+
+fn proto_skipped_source_to_json(skipped: &SkippedSource) -> serde_json::Value {
+    serde_json::json!({
+        "path": skipped.path,
+        "line": skipped.line,
+        "message": skipped.message
+    })
+}
+
+fn proto_diagram_diff_to_json(diff: &DiagramDiff) -> serde_json::Value {
+    let file_changes: Vec<serde_json::Value> = diff
+        .file_changes
+        .iter()
+        .map(proto_value_change_to_json)
+        .collect();
+    let classes: Vec<serde_json::Value> = diff
+        .classes
+        .iter()
+        .map(|class| {
+            let attributes: Vec<serde_json::Value> = class
+                .attributes
+                .iter()
+                .map(proto_member_change_to_json)
+                .collect();
+            let methods: Vec<serde_json::Value> = class
+                .methods
+                .iter()
+                .map(proto_member_change_to_json)
+                .collect();
+            let relations: Vec<serde_json::Value> = class
+                .relations
+                .iter()
+                .map(|relation| {
+                    let changes: Vec<serde_json::Value> = relation
+                        .changes
+                        .iter()
+                        .map(proto_value_change_to_json)
+                        .collect();
+                    serde_json::json!({
+                        "kind": change_kind_name(relation.kind),
+                        "target_class_id": relation.target_class_id,
+                        "relation": relation.relation,
+                        "changes": changes
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "kind": change_kind_name(class.kind),
+                "class_id": class.class_id,
+                "name": class.name,
+                "previous_name": (!class.previous_name.is_empty()).then_some(&class.previous_name),
+                "attributes": attributes,
+                "methods": methods,
+                "relations": relations
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "base_revision": diff.base_revision,
+        "target_revision": diff.target_revision,
+        "file_changes": file_changes,
+        "classes": classes
+    })
+}
+
+fn proto_member_change_to_json(member: &MemberChange) -> serde_json::Value {
+    let changes: Vec<serde_json::Value> = member
+        .changes
+        .iter()
+        .map(proto_value_change_to_json)
+        .collect();
+    serde_json::json!({
+        "kind": change_kind_name(member.kind),
+        "signature": member.signature,
+        "changes": changes
+    })
+}
+
+fn proto_value_change_to_json(change: &ValueChange) -> serde_json::Value {
+    serde_json::json!({
+        "field": change.field,
+        "old": change.old_value,
+        "new": change.new_value
+    })
+}
+
+// CHANGE_KIND_ADDED → added
+fn change_kind_name(kind: i32) -> String {
+    ChangeKind::try_from(kind)
+        .map(|kind| {
+            kind.as_str_name()
+                .trim_start_matches("CHANGE_KIND_")
+                .to_lowercase()
+        })
+        .unwrap_or_default()
+}
+
+fn proto_merge_result_to_json(result: &MergeResult) -> serde_json::Value {
+    let conflicts: Vec<serde_json::Value> = result
+        .conflicts
+        .iter()
+        .map(proto_merge_conflict_to_json)
+        .collect();
+    serde_json::json!({
+        "file": result.file.as_ref().map(proto_file_to_json),
+        "conflicts": conflicts,
+        "revision": (result.revision > 0).then_some(result.revision)
+    })
+}
+
+fn proto_merge_conflict_to_json(conflict: &MergeConflict) -> serde_json::Value {
+    let kind = ConflictKind::try_from(conflict.kind)
+        .map(|kind| {
+            kind.as_str_name()
+                .trim_start_matches("CONFLICT_KIND_")
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+    serde_json::json!({
+        "kind": kind,
+        "class_id": conflict.class_id,
+        "path": conflict.path,
+        "base": conflict.base_value,
+        "ours": conflict.ours_value,
+        "theirs": conflict.theirs_value
+    })
+}
+
+fn proto_session_state_to_json(state: &SessionState) -> serde_json::Value {
+    let participants: Vec<serde_json::Value> = state
+        .participants
+        .iter()
+        .map(|participant| {
+            serde_json::json!({
+                "session_id": (!participant.session_id.is_empty()).then_some(&participant.session_id),
+                "user_name": participant.user_name,
+                "last_seen": participant.last_seen
+            })
+        })
+        .collect();
+    let locks: Vec<serde_json::Value> = state.locks.iter().map(proto_class_lock_to_json).collect();
+    serde_json::json!({
+        "session_id": (!state.session_id.is_empty()).then_some(&state.session_id),
+        "expires_at": (state.expires_at > 0).then_some(state.expires_at),
+        "participants": participants,
+        "locks": locks
+    })
+}
+
+fn proto_class_lock_to_json(lock: &ClassLock) -> serde_json::Value {
+    serde_json::json!({
+        "class_id": lock.class_id,
+        "session_id": (!lock.session_id.is_empty()).then_some(&lock.session_id),
+        "user_name": lock.user_name,
+        "expires_at": lock.expires_at
+    })
+}
+
+fn proto_user_to_json(user: &User) -> serde_json::Value {
+    serde_json::json!({
+        "user_name": user.user_name,
+        "admin": user.admin,
+        "created_at": user.created_at
+    })
+}
+
+fn proto_api_key_to_json(key: &ApiKey) -> serde_json::Value {
+    serde_json::json!({
+        "key_id": key.key_id,
+        "name": key.name,
+        "created_at": key.created_at
+    })
+}
+
+// イベント名は種類（snapshot・saved・deleted）、IDは版番号
+fn diagram_event_to_sse(event: &DiagramEvent) -> Event {
+    let kind = DiagramEventKind::try_from(event.kind)
+        .map(|kind| {
+            kind.as_str_name()
+                .trim_start_matches("DIAGRAM_EVENT_KIND_")
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+    let data = serde_json::json!({
+        "revision": event.revision,
+        "file": event.file.as_ref().map(proto_file_to_json)
+    });
+    Event::default()
+        .event(kind)
+        .id(event.revision.to_string())
+        .data(data.to_string())
+}
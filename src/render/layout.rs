@@ -0,0 +1,405 @@
+// 階層型（Sugiyama方式）のグラフレイアウト
+// 1. 閉路を取り除く（DFSの帰りがけ順に逆らう辺を反転）
+// 2. 最長経路で各ノードの層を決め、複数の層をまたぐ辺にはダミーノードを挟む
+// 3. 重心法で層内の並び順を決め、辺の交差を減らす
+// 4. 隣接ノードの中心に寄せつつ、重ならないようにx座標を決める
+
+// 層の間隔とノードの間隔
+const LAYER_GAP: f64 = 70.0;
+const NODE_GAP: f64 = 40.0;
+const MARGIN: f64 = 20.0;
+// 層をまたぐ辺が通る幅
+const DUMMY_WIDTH: f64 = 16.0;
+// 並び順・位置を改善する回数
+const ORDER_ITERATIONS: usize = 8;
+const POSITION_ITERATIONS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    // 中心からtowardに向かう線と矩形の境界の交点
+    pub fn boundary_point(&self, toward: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.center();
+        let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+
+        let scale_x = if dx != 0.0 {
+            (self.width / 2.0) / dx.abs()
+        } else {
+            f64::INFINITY
+        };
+        let scale_y = if dy != 0.0 {
+            (self.height / 2.0) / dy.abs()
+        } else {
+            f64::INFINITY
+        };
+        let scale = scale_x.min(scale_y);
+        (cx + dx * scale, cy + dy * scale)
+    }
+}
+
+#[derive(Debug)]
+pub struct Layout {
+    // ノードごとの配置（入力と同じ順）
+    pub nodes: Vec<Rect>,
+    // 辺ごとの経由点（入力と同じ順・同じ向き、隣接する層の間の辺と自己ループは空）
+    pub edges: Vec<Vec<(f64, f64)>>,
+    pub width: f64,
+    pub height: f64,
+}
+
+// edgesの(u, v)はuをvより上の層に置く制約として扱う
+pub fn layered_layout(sizes: &[Size], edges: &[(usize, usize)]) -> Layout {
+    let count = sizes.len();
+    if count == 0 {
+        return Layout {
+            nodes: Vec::new(),
+            edges: vec![Vec::new(); edges.len()],
+            width: MARGIN * 2.0,
+            height: MARGIN * 2.0,
+        };
+    }
+
+    // 閉路を作らない向きに揃える（反転した辺はtrue）
+    let oriented = orient_edges(count, edges);
+    let ranks = assign_ranks(count, &oriented);
+
+    // 層をまたぐ辺をダミーノードの列に分割
+    let mut graph = Graph {
+        sizes: sizes.to_vec(),
+        ranks,
+        segments: Vec::new(),
+    };
+    let paths: Vec<Option<(Vec<usize>, bool)>> = oriented
+        .iter()
+        .map(|edge| edge.map(|(from, to, reversed)| (graph.add_path(from, to), reversed)))
+        .collect();
+
+    let layers = order_layers(&graph);
+    let (rects, width, height) = place_nodes(&graph, &layers);
+
+    // ダミーノードの中心を辺の経由点とする
+    let edges = paths
+        .iter()
+        .map(|path| match path {
+            Some((path, reversed)) => {
+                let mut points: Vec<(f64, f64)> = path[1..path.len() - 1]
+                    .iter()
+                    .map(|&node| rects[node].center())
+                    .collect();
+                if *reversed {
+                    points.reverse();
+                }
+                points
+            }
+            None => Vec::new(),
+        })
+        .collect();
+
+    Layout {
+        nodes: rects[..count].to_vec(),
+        edges,
+        width,
+        height,
+    }
+}
+
+// ダミーノードを含むグラフ
+struct Graph {
+    sizes: Vec<Size>,
+    ranks: Vec<usize>,
+    // 隣接する層の間の辺
+    segments: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    // fromからtoまでの経路を作り、通るノードの列を返す
+    fn add_path(&mut self, from: usize, to: usize) -> Vec<usize> {
+        let mut path = vec![from];
+        let mut previous = from;
+        for rank in self.ranks[from] + 1..self.ranks[to] {
+            let dummy = self.sizes.len();
+            self.sizes.push(Size {
+                width: DUMMY_WIDTH,
+                height: 0.0,
+            });
+            self.ranks.push(rank);
+            self.segments.push((previous, dummy));
+            path.push(dummy);
+            previous = dummy;
+        }
+        self.segments.push((previous, to));
+        path.push(to);
+        path
+    }
+}
+
+// DFSの帰りがけ順の逆（閉路が無ければトポロジカル順）に沿う向きに辺を揃える
+// 自己ループと範囲外の辺はNone
+fn orient_edges(count: usize, edges: &[(usize, usize)]) -> Vec<Option<(usize, usize, bool)>> {
+    let mut adjacency = vec![Vec::new(); count];
+    for &(from, to) in edges {
+        if from != to && from < count && to < count {
+            adjacency[from].push(to);
+        }
+    }
+
+    let mut visited = vec![false; count];
+    let mut postorder = Vec::with_capacity(count);
+    for start in 0..count {
+        if visited[start] {
+            continue;
+        }
+        // 再帰の代わりに (ノード, 次に見る辺の位置) のスタックを使う
+        let mut stack = vec![(start, 0usize)];
+        visited[start] = true;
+        while let Some((node, next)) = stack.pop() {
+            if let Some(&to) = adjacency[node].get(next) {
+                stack.push((node, next + 1));
+                if !visited[to] {
+                    visited[to] = true;
+                    stack.push((to, 0));
+                }
+            } else {
+                postorder.push(node);
+            }
+        }
+    }
+
+    let mut position = vec![0usize; count];
+    for (index, &node) in postorder.iter().rev().enumerate() {
+        position[node] = index;
+    }
+
+    edges
+        .iter()
+        .map(|&(from, to)| {
+            if from == to || from >= count || to >= count {
+                None
+            } else if position[from] < position[to] {
+                Some((from, to, false))
+            } else {
+                Some((to, from, true))
+            }
+        })
+        .collect()
+}
+
+// 最長経路法で層を割り当てる（上位ノードの無いノードが層0）
+fn assign_ranks(count: usize, edges: &[Option<(usize, usize, bool)>]) -> Vec<usize> {
+    let mut in_degree = vec![0usize; count];
+    let mut successors = vec![Vec::new(); count];
+    for &(from, to, _) in edges.iter().flatten() {
+        in_degree[to] += 1;
+        successors[from].push(to);
+    }
+
+    let mut ranks = vec![0usize; count];
+    let mut queue: Vec<usize> = (0..count).filter(|&node| in_degree[node] == 0).collect();
+    while let Some(node) = queue.pop() {
+        for &to in &successors[node] {
+            ranks[to] = ranks[to].max(ranks[node] + 1);
+            in_degree[to] -= 1;
+            if in_degree[to] == 0 {
+                queue.push(to);
+            }
+        }
+    }
+    ranks
+}
+
+// 重心法で各層の並び順を決める
+fn order_layers(graph: &Graph) -> Vec<Vec<usize>> {
+    let count = graph.sizes.len();
+    let layer_count = graph.ranks.iter().max().map_or(0, |max| max + 1);
+    let mut layers = vec![Vec::new(); layer_count];
+    for node in 0..count {
+        layers[graph.ranks[node]].push(node);
+    }
+
+    let mut predecessors = vec![Vec::new(); count];
+    let mut successors = vec![Vec::new(); count];
+    for &(from, to) in &graph.segments {
+        successors[from].push(to);
+        predecessors[to].push(from);
+    }
+
+    for iteration in 0..ORDER_ITERATIONS {
+        // 偶数回目は上の層、奇数回目は下の層の並びに合わせる
+        let (range, neighbors): (Vec<usize>, _) = if iteration % 2 == 0 {
+            ((1..layer_count).collect(), &predecessors)
+        } else {
+            (
+                (0..layer_count.saturating_sub(1)).rev().collect(),
+                &successors,
+            )
+        };
+
+        for layer in range {
+            let position = normalized_positions(count, &layers);
+            let mut keyed: Vec<(f64, usize)> = layers[layer]
+                .iter()
+                .map(|&node| {
+                    let related = &neighbors[node];
+                    let key = if related.is_empty() {
+                        position[node]
+                    } else {
+                        related.iter().map(|&other| position[other]).sum::<f64>()
+                            / related.len() as f64
+                    };
+                    (key, node)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[layer] = keyed.into_iter().map(|(_, node)| node).collect();
+        }
+    }
+
+    layers
+}
+
+// 層内の位置を0〜1に正規化（層ごとのノード数の違いを吸収する）
+fn normalized_positions(count: usize, layers: &[Vec<usize>]) -> Vec<f64> {
+    let mut position = vec![0.0; count];
+    for layer in layers {
+        for (index, &node) in layer.iter().enumerate() {
+            position[node] = (index as f64 + 0.5) / layer.len() as f64;
+        }
+    }
+    position
+}
+
+// 各ノードの座標を決め、全体の幅と高さと共に返す
+fn place_nodes(graph: &Graph, layers: &[Vec<usize>]) -> (Vec<Rect>, f64, f64) {
+    let sizes = &graph.sizes;
+    let count = sizes.len();
+    let mut neighbors = vec![Vec::new(); count];
+    for &(from, to) in &graph.segments {
+        neighbors[from].push(to);
+        neighbors[to].push(from);
+    }
+
+    // 層ごとのy座標（ノードの上端を揃え、ダミーノードは層の中央に置く）
+    let mut y = vec![0.0; count];
+    let mut top = MARGIN;
+    for layer in layers {
+        let height = layer
+            .iter()
+            .map(|&node| sizes[node].height)
+            .fold(0.0, f64::max);
+        for &node in layer {
+            y[node] = if sizes[node].height == 0.0 {
+                top + height / 2.0
+            } else {
+                top
+            };
+        }
+        top += height + LAYER_GAP;
+    }
+    let height = top - LAYER_GAP + MARGIN;
+
+    // 初期配置は各層を中央揃えで詰めて並べる
+    let layer_width = |layer: &Vec<usize>| -> f64 {
+        layer.iter().map(|&node| sizes[node].width).sum::<f64>()
+            + NODE_GAP * layer.len().saturating_sub(1) as f64
+    };
+    let widest = layers.iter().map(layer_width).fold(0.0, f64::max);
+    let mut center = vec![0.0; count];
+    for layer in layers {
+        let mut left = (widest - layer_width(layer)) / 2.0;
+        for &node in layer {
+            center[node] = left + sizes[node].width / 2.0;
+            left += sizes[node].width + NODE_GAP;
+        }
+    }
+
+    // 隣接ノードの中心の平均に寄せ、重なりを解消する
+    for _ in 0..POSITION_ITERATIONS {
+        for layer in layers {
+            let desired: Vec<f64> = layer
+                .iter()
+                .map(|&node| {
+                    if neighbors[node].is_empty() {
+                        center[node]
+                    } else {
+                        neighbors[node]
+                            .iter()
+                            .map(|&other| center[other])
+                            .sum::<f64>()
+                            / neighbors[node].len() as f64
+                    }
+                })
+                .collect();
+            resolve_overlaps(layer, sizes, &desired, &mut center);
+        }
+    }
+
+    // 左端が余白の位置になるように平行移動
+    let min_left = (0..count)
+        .map(|node| center[node] - sizes[node].width / 2.0)
+        .fold(f64::INFINITY, f64::min);
+    let shift = MARGIN - min_left;
+    let rects: Vec<Rect> = (0..count)
+        .map(|node| Rect {
+            x: center[node] - sizes[node].width / 2.0 + shift,
+            y: y[node],
+            width: sizes[node].width,
+            height: sizes[node].height,
+        })
+        .collect();
+    let width = rects
+        .iter()
+        .map(|rect| rect.x + rect.width)
+        .fold(0.0, f64::max)
+        + MARGIN;
+
+    (rects, width, height)
+}
+
+// 並び順を保ったまま、希望の位置にできるだけ近く、間隔を空けて配置する
+fn resolve_overlaps(layer: &[usize], sizes: &[Size], desired: &[f64], center: &mut [f64]) {
+    if layer.is_empty() {
+        return;
+    }
+
+    // 左から詰めて最小間隔を確保
+    let mut placed: Vec<f64> = Vec::with_capacity(layer.len());
+    for (index, &node) in layer.iter().enumerate() {
+        let mut x = desired[index];
+        if let Some(&previous) = placed.last() {
+            let previous_node = layer[index - 1];
+            let min = previous + (sizes[previous_node].width + sizes[node].width) / 2.0 + NODE_GAP;
+            x = x.max(min);
+        }
+        placed.push(x);
+    }
+
+    // 右に寄りすぎた分を、層全体のずれの平均だけ戻す
+    let drift = placed
+        .iter()
+        .zip(desired)
+        .map(|(placed, desired)| placed - desired)
+        .sum::<f64>()
+        / layer.len() as f64;
+    for (index, &node) in layer.iter().enumerate() {
+        center[node] = placed[index] - drift;
+    }
+}
@@ -0,0 +1,3 @@
+// ダイアグラムの画像への描画
+pub mod layout;
+pub mod svg;
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::layout::{layered_layout, Rect, Size};
+use crate::export::end_label;
+use crate::model::{display_name, is_abstract_class, RelationKind, Visibility};
+use crate::server::class::{Class, File, Method, Variable};
+
+// 文字幅は等幅フォントを前提に見積もる
+const FONT_SIZE: f64 = 12.0;
+const CHAR_WIDTH: f64 = 7.2;
+const LINE_HEIGHT: f64 = 16.0;
+const PADDING: f64 = 8.0;
+const MIN_BOX_WIDTH: f64 = 80.0;
+// 空の区画の高さ
+const EMPTY_COMPARTMENT: f64 = 8.0;
+// 矢印・ひし形の大きさ
+const MARKER_LENGTH: f64 = 14.0;
+const MARKER_WIDTH: f64 = 8.0;
+// 同じクラス間の関係同士の間隔
+const PARALLEL_GAP: f64 = 12.0;
+
+// クラスの箱に描く内容
+struct ClassBox<'a> {
+    class: &'a Class,
+    is_abstract: bool,
+    attributes: Vec<Member>,
+    methods: Vec<Member>,
+}
+
+struct Member {
+    text: String,
+    is_static: bool,
+    is_abstract: bool,
+}
+
+impl ClassBox<'_> {
+    fn header_lines(&self) -> usize {
+        if self.is_abstract {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn compartment_height(lines: usize) -> f64 {
+        if lines == 0 {
+            EMPTY_COMPARTMENT
+        } else {
+            lines as f64 * LINE_HEIGHT + PADDING
+        }
+    }
+
+    fn header_height(&self) -> f64 {
+        self.header_lines() as f64 * LINE_HEIGHT + PADDING
+    }
+
+    fn size(&self) -> Size {
+        let longest = std::iter::once(text_width(display_name(self.class)))
+            .chain(
+                self.attributes
+                    .iter()
+                    .map(|member| text_width(&member.text)),
+            )
+            .chain(self.methods.iter().map(|member| text_width(&member.text)))
+            .fold(0.0, f64::max);
+
+        Size {
+            width: (longest + PADDING * 2.0).max(MIN_BOX_WIDTH),
+            height: self.header_height()
+                + Self::compartment_height(self.attributes.len())
+                + Self::compartment_height(self.methods.len()),
+        }
+    }
+}
+
+// ファイルをUMLクラス図のSVGとして描画
+pub fn to_svg(file: &File) -> String {
+    let boxes: Vec<ClassBox> = file.classes.iter().map(class_box).collect();
+    let sizes: Vec<Size> = boxes.iter().map(ClassBox::size).collect();
+
+    // 関係を (関係元, 関係先, 関係) の位置で集める
+    // IDが重複している場合は最初のクラスを指す
+    let mut index_of: HashMap<&str, usize> = HashMap::new();
+    for (index, class) in file.classes.iter().enumerate() {
+        index_of.entry(class.id.as_str()).or_insert(index);
+    }
+    let mut relations = Vec::new();
+    for (source, class) in file.classes.iter().enumerate() {
+        let Some(list) = &class.relations else {
+            continue;
+        };
+        for relation in &list.relation_infos {
+            if let Some(&target) = index_of.get(relation.target_class_id.as_str()) {
+                relations.push((source, target, relation));
+            }
+        }
+    }
+
+    // 継承・実現は親を、集約・コンポジションなどは関係元を上の層に置く
+    let layout_edges: Vec<(usize, usize)> = relations
+        .iter()
+        .map(
+            |&(source, target, relation)| match RelationKind::from_i32(relation.relation) {
+                RelationKind::Inheritance | RelationKind::Realization => (target, source),
+                _ => (source, target),
+            },
+        )
+        .collect();
+    let layout = layered_layout(&sizes, &layout_edges);
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.0} {h:.0}\" font-family=\"monospace\" font-size=\"{}\">",
+        FONT_SIZE,
+        w = layout.width,
+        h = layout.height,
+    );
+    if !file.name.is_empty() {
+        let _ = writeln!(out, "<title>{}</title>", escape(&file.name));
+    }
+    out.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+
+    // 同じ2クラス間の関係が重ならないように、何本目かを数えておく
+    let mut parallel: HashMap<(usize, usize), usize> = HashMap::new();
+    let lanes: Vec<usize> = relations
+        .iter()
+        .map(|&(source, target, _)| {
+            let count = parallel
+                .entry((source.min(target), source.max(target)))
+                .or_insert(0);
+            *count += 1;
+            *count - 1
+        })
+        .collect();
+
+    // 辺を先に描き、クラスの箱で端を隠す
+    out.push_str("<g class=\"relations\" stroke=\"black\" fill=\"none\">\n");
+    for (index, &(source, target, relation)) in relations.iter().enumerate() {
+        let kind = RelationKind::from_i32(relation.relation);
+        if source == target {
+            draw_self_relation(&mut out, &layout.nodes[source], kind, lanes[index]);
+            continue;
+        }
+
+        let source_label = end_label(
+            relation.multiplicity_p.as_ref(),
+            relation.role_name_p.as_deref(),
+        );
+        let target_label = end_label(
+            relation.multiplicity_c.as_ref(),
+            relation.role_name_c.as_deref(),
+        );
+
+        // レイアウトの経由点は上下を入れ替えた辺では逆順になっている
+        let mut waypoints = layout.edges[index].clone();
+        if matches!(kind, RelationKind::Inheritance | RelationKind::Realization) {
+            waypoints.reverse();
+        }
+        let source_rect = &layout.nodes[source];
+        let target_rect = &layout.nodes[target];
+        let mut points =
+            vec![source_rect
+                .boundary_point(waypoints.first().copied().unwrap_or(target_rect.center()))];
+        points.extend(waypoints.iter().copied());
+        points.push(
+            target_rect.boundary_point(waypoints.last().copied().unwrap_or(source_rect.center())),
+        );
+
+        // 平行する辺は向きに垂直な方向へずらす（向きは番号の小さいクラスから見て揃える）
+        let count = parallel[&(source.min(target), source.max(target))];
+        if count > 1 {
+            let offset = (lanes[index] as f64 - (count - 1) as f64 / 2.0) * PARALLEL_GAP;
+            let (from, to) = if source < target {
+                (source_rect.center(), target_rect.center())
+            } else {
+                (target_rect.center(), source_rect.center())
+            };
+            let (ux, uy) = unit(from, to);
+            for point in &mut points {
+                point.0 -= uy * offset;
+                point.1 += ux * offset;
+            }
+        }
+
+        draw_relation(
+            &mut out,
+            &points,
+            kind,
+            source_label.as_deref(),
+            target_label.as_deref(),
+        );
+    }
+    out.push_str("</g>\n");
+
+    for (index, class_box) in boxes.iter().enumerate() {
+        draw_class(&mut out, index, class_box, &layout.nodes[index]);
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+fn class_box(class: &Class) -> ClassBox<'_> {
+    ClassBox {
+        class,
+        is_abstract: is_abstract_class(class),
+        attributes: class.attributes.iter().map(attribute_member).collect(),
+        methods: class.methods.iter().map(method_member).collect(),
+    }
+}
+
+fn attribute_member(attribute: &Variable) -> Member {
+    let mut text = String::new();
+    if let Some(visibility) = attribute.visibility {
+        text.push(Visibility::from_i32(visibility).symbol());
+    }
+    text.push_str(&attribute.name);
+    if !attribute.r#type.is_empty() {
+        let _ = write!(text, " : {}", attribute.r#type);
+    }
+    Member {
+        text,
+        is_static: attribute.is_static.unwrap_or(false),
+        is_abstract: false,
+    }
+}
+
+fn method_member(method: &Method) -> Member {
+    let parameters: Vec<String> = method
+        .parameters
+        .iter()
+        .map(|parameter| {
+            if parameter.r#type.is_empty() {
+                parameter.name.clone()
+            } else {
+                format!("{} : {}", parameter.name, parameter.r#type)
+            }
+        })
+        .collect();
+
+    let mut text = format!(
+        "{}{}({})",
+        Visibility::from_i32(method.visibility).symbol(),
+        method.name,
+        parameters.join(", ")
+    );
+    if !method.return_type.is_empty() {
+        let _ = write!(text, " : {}", method.return_type);
+    }
+    Member {
+        text,
+        is_static: method.is_static.unwrap_or(false),
+        is_abstract: method.is_abstract.unwrap_or(false),
+    }
+}
+
+// 名前・属性・メソッドの3区画を持つ箱を描く
+// 抽象クラス・抽象メソッドは斜体、静的メンバは下線
+fn draw_class(out: &mut String, index: usize, class_box: &ClassBox, rect: &Rect) {
+    let _ = writeln!(
+        out,
+        "<g class=\"class\" id=\"C{}\" data-class-id=\"{}\">",
+        index,
+        escape(&class_box.class.id)
+    );
+    let _ = writeln!(
+        out,
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#fefece\" stroke=\"black\"/>",
+        rect.x, rect.y, rect.width, rect.height
+    );
+
+    let center_x = rect.x + rect.width / 2.0;
+    let mut baseline = rect.y + PADDING / 2.0 + LINE_HEIGHT - 4.0;
+    if class_box.is_abstract {
+        let _ = writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">«abstract»</text>",
+            center_x, baseline
+        );
+        baseline += LINE_HEIGHT;
+    }
+    let _ = writeln!(
+        out,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-weight=\"bold\"{}>{}</text>",
+        center_x,
+        baseline,
+        if class_box.is_abstract {
+            " font-style=\"italic\""
+        } else {
+            ""
+        },
+        escape(display_name(class_box.class))
+    );
+
+    let mut top = rect.y + class_box.header_height();
+    for members in [&class_box.attributes, &class_box.methods] {
+        let _ = writeln!(
+            out,
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"black\"/>",
+            rect.x,
+            top,
+            rect.x + rect.width,
+            top
+        );
+        let mut baseline = top + PADDING / 2.0 + LINE_HEIGHT - 4.0;
+        for member in members {
+            let mut style = String::new();
+            if member.is_static {
+                style.push_str(" text-decoration=\"underline\"");
+            }
+            if member.is_abstract {
+                style.push_str(" font-style=\"italic\"");
+            }
+            let _ = writeln!(
+                out,
+                "<text x=\"{:.1}\" y=\"{:.1}\"{}>{}</text>",
+                rect.x + PADDING,
+                baseline,
+                style,
+                escape(&member.text)
+            );
+            baseline += LINE_HEIGHT;
+        }
+        top += ClassBox::compartment_height(members.len());
+    }
+
+    out.push_str("</g>\n");
+}
+
+// 関係の種類に応じて線の種類と端の記号を描き分ける
+// 継承: 実線・白抜き三角, 実現: 破線・白抜き三角, 依存: 破線・矢印, 関連: 実線・矢印
+// 集約: 関係元に白抜きひし形, コンポジション: 関係元に黒塗りひし形
+// pointsは関係元の境界から経由点を通って関係先の境界までの折れ線
+fn draw_relation(
+    out: &mut String,
+    points: &[(f64, f64)],
+    kind: RelationKind,
+    source_label: Option<&str>,
+    target_label: Option<&str>,
+) {
+    let last = points.len() - 1;
+    let start = points[0];
+    let end = points[last];
+    // 端の記号とラベルの向きは最初と最後の線分で決める
+    let (sx, sy) = unit(start, points[1]);
+    let (ex, ey) = unit(points[last - 1], end);
+
+    // 記号と重ならないように線の端を縮める
+    let mut line = points.to_vec();
+    match kind {
+        RelationKind::Inheritance | RelationKind::Realization => {
+            line[last] = (end.0 - ex * MARKER_LENGTH, end.1 - ey * MARKER_LENGTH);
+        }
+        RelationKind::Aggregation | RelationKind::Composition => {
+            line[0] = (
+                start.0 + sx * MARKER_LENGTH * 2.0,
+                start.1 + sy * MARKER_LENGTH * 2.0,
+            );
+        }
+        RelationKind::Association | RelationKind::Dependency => {}
+    }
+    let dashed = matches!(kind, RelationKind::Realization | RelationKind::Dependency);
+    let _ = writeln!(
+        out,
+        "<polyline points=\"{}\"{}/>",
+        line.iter()
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect::<Vec<_>>()
+            .join(" "),
+        if dashed {
+            " stroke-dasharray=\"6,4\""
+        } else {
+            ""
+        }
+    );
+
+    match kind {
+        RelationKind::Inheritance | RelationKind::Realization => {
+            let base = (end.0 - ex * MARKER_LENGTH, end.1 - ey * MARKER_LENGTH);
+            let (left, right) = spread(base, (ex, ey), MARKER_WIDTH);
+            let _ = writeln!(
+                out,
+                "<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"white\" stroke-dasharray=\"none\"/>",
+                end.0, end.1, left.0, left.1, right.0, right.1
+            );
+        }
+        RelationKind::Aggregation | RelationKind::Composition => {
+            let middle = (start.0 + sx * MARKER_LENGTH, start.1 + sy * MARKER_LENGTH);
+            let tip = (
+                start.0 + sx * MARKER_LENGTH * 2.0,
+                start.1 + sy * MARKER_LENGTH * 2.0,
+            );
+            let (left, right) = spread(middle, (sx, sy), MARKER_WIDTH / 1.5);
+            let fill = if kind == RelationKind::Composition {
+                "black"
+            } else {
+                "white"
+            };
+            let _ = writeln!(
+                out,
+                "<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{}\"/>",
+                start.0, start.1, left.0, left.1, tip.0, tip.1, right.0, right.1, fill
+            );
+        }
+        RelationKind::Association | RelationKind::Dependency => {
+            let base = (end.0 - ex * MARKER_LENGTH, end.1 - ey * MARKER_LENGTH);
+            let (left, right) = spread(base, (ex, ey), MARKER_WIDTH / 1.5);
+            let _ = writeln!(
+                out,
+                "<polyline points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" stroke-dasharray=\"none\"/>",
+                left.0, left.1, end.0, end.1, right.0, right.1
+            );
+        }
+    }
+
+    // 端のラベルは線から少し離して置く
+    for (label, point, direction) in [
+        (source_label, start, (sx, sy)),
+        (target_label, end, (-ex, -ey)),
+    ] {
+        let Some(label) = label else {
+            continue;
+        };
+        let x = point.0 + direction.0 * 24.0 - direction.1 * 10.0;
+        let y = point.1 + direction.1 * 24.0 + direction.0 * 10.0 + 4.0;
+        let _ = writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" stroke=\"none\" fill=\"black\" font-size=\"{}\">{}</text>",
+            x,
+            y,
+            FONT_SIZE - 1.0,
+            escape(label)
+        );
+    }
+}
+
+// 自己関連は箱の右側にループを描く（複数ある場合は外側に重ねる）
+fn draw_self_relation(out: &mut String, rect: &Rect, kind: RelationKind, lane: usize) {
+    let x = rect.x + rect.width;
+    let top = rect.y + rect.height * 0.25;
+    let bottom = rect.y + rect.height * 0.5;
+    let reach = 40.0 + lane as f64 * PARALLEL_GAP * 2.0;
+    let dashed = matches!(kind, RelationKind::Realization | RelationKind::Dependency);
+    let _ = writeln!(
+        out,
+        "<path d=\"M {:.1} {:.1} C {:.1} {:.1}, {:.1} {:.1}, {:.1} {:.1}\"{}/>",
+        x,
+        top,
+        x + reach,
+        top - 10.0,
+        x + reach,
+        bottom + 10.0,
+        x,
+        bottom,
+        if dashed {
+            " stroke-dasharray=\"6,4\""
+        } else {
+            ""
+        }
+    );
+}
+
+fn unit(from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        (0.0, 1.0)
+    } else {
+        (dx / length, dy / length)
+    }
+}
+
+// 方向に垂直な両側の点
+fn spread(point: (f64, f64), direction: (f64, f64), half_width: f64) -> ((f64, f64), (f64, f64)) {
+    let (px, py) = (-direction.1 * half_width, direction.0 * half_width);
+    ((point.0 + px, point.1 + py), (point.0 - px, point.1 - py))
+}
+
+// 全角文字は2文字分の幅として見積もる
+fn text_width(text: &str) -> f64 {
+    text.chars()
+        .map(|c| if (c as u32) >= 0x1100 { 2.0 } else { 1.0 })
+        .sum::<f64>()
+        * CHAR_WIDTH
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
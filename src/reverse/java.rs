@@ -0,0 +1,755 @@
+use super::{simple_name, Declaration, Field, ParseResult};
+use crate::model::{RelationKind, Visibility};
+use crate::server::class::{Method, Multiplicity, Variable};
+
+// 要素の型を最初の型引数に持つコレクション
+const COLLECTIONS: [&str; 18] = [
+    "List",
+    "ArrayList",
+    "LinkedList",
+    "CopyOnWriteArrayList",
+    "Set",
+    "HashSet",
+    "LinkedHashSet",
+    "TreeSet",
+    "SortedSet",
+    "NavigableSet",
+    "EnumSet",
+    "Collection",
+    "Iterable",
+    "Queue",
+    "Deque",
+    "ArrayDeque",
+    "Stream",
+    "Vector",
+];
+
+// 値の型を2番目の型引数に持つマップ
+const MAPS: [&str; 7] = [
+    "Map",
+    "HashMap",
+    "LinkedHashMap",
+    "TreeMap",
+    "SortedMap",
+    "NavigableMap",
+    "ConcurrentHashMap",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // 識別子・キーワード・数値
+    Word(String),
+    Symbol(char),
+    // 文字列・文字リテラル
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Class,
+    Interface,
+    Enum,
+    Record,
+}
+
+// 宣言の修飾子
+#[derive(Debug, Default)]
+struct Modifiers {
+    visibility: Option<Visibility>,
+    is_static: bool,
+    is_abstract: bool,
+}
+
+// Javaのソースファイル1つからクラス・インターフェース・列挙型・レコードの宣言を読み取る
+// メソッドの本体・初期化式は読み飛ばし、入れ子の型も別のクラスとして取り出す
+pub(crate) fn parse(source: &str) -> ParseResult<Vec<Declaration>> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        declarations: Vec::new(),
+    };
+    parser.compilation_unit()?;
+    Ok(parser.declarations)
+}
+
+// コメント・空白を除いてトークンに分ける（行番号付き）
+fn tokenize(source: &str) -> ParseResult<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let start_line = line;
+        if c == '\n' {
+            line += 1;
+            index += 1;
+        } else if c.is_whitespace() {
+            index += 1;
+        } else if c == '/' && chars.get(index + 1) == Some(&'/') {
+            while index < chars.len() && chars[index] != '\n' {
+                index += 1;
+            }
+        } else if c == '/' && chars.get(index + 1) == Some(&'*') {
+            index += 2;
+            loop {
+                match chars.get(index) {
+                    None => return Err((start_line, "comment is not closed".to_string())),
+                    Some('*') if chars.get(index + 1) == Some(&'/') => {
+                        index += 2;
+                        break;
+                    }
+                    Some('\n') => line += 1,
+                    _ => {}
+                }
+                index += 1;
+            }
+        } else if c == '"'
+            && chars.get(index + 1) == Some(&'"')
+            && chars.get(index + 2) == Some(&'"')
+        {
+            // テキストブロック
+            index += 3;
+            loop {
+                match chars.get(index) {
+                    None => return Err((start_line, "text block is not closed".to_string())),
+                    Some('\\') => index += 1,
+                    Some('"')
+                        if chars.get(index + 1) == Some(&'"')
+                            && chars.get(index + 2) == Some(&'"') =>
+                    {
+                        index += 3;
+                        break;
+                    }
+                    Some('\n') => line += 1,
+                    _ => {}
+                }
+                index += 1;
+            }
+            tokens.push((Token::Literal, start_line));
+        } else if c == '"' || c == '\'' {
+            index += 1;
+            loop {
+                match chars.get(index) {
+                    None | Some('\n') => {
+                        return Err((start_line, "literal is not closed".to_string()))
+                    }
+                    Some('\\') => index += 1,
+                    Some(&quote) if quote == c => {
+                        index += 1;
+                        break;
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+            tokens.push((Token::Literal, start_line));
+        } else if c.is_alphanumeric() || c == '_' || c == '$' {
+            let start = index;
+            while index < chars.len()
+                && (chars[index].is_alphanumeric() || chars[index] == '_' || chars[index] == '$')
+            {
+                index += 1;
+            }
+            let word: String = chars[start..index].iter().collect();
+            tokens.push((Token::Word(word), start_line));
+        } else {
+            tokens.push((Token::Symbol(c), start_line));
+            index += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    declarations: Vec<Declaration>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .get(self.position + offset)
+            .map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.is_word(word);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        Err((self.line(), message.into()))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> ParseResult<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", symbol))
+        }
+    }
+
+    fn identifier(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.position += 1;
+                Ok(word)
+            }
+            _ => self.error("expected an identifier"),
+        }
+    }
+
+    // 開き括弧から対応する閉じ括弧までを読み飛ばす
+    fn skip_balanced(&mut self, open: char, close: char) -> ParseResult<()> {
+        let line = self.line();
+        self.expect_symbol(open)?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                None => return Err((line, format!("'{}' is not closed", open))),
+                Some(Token::Symbol(c)) if *c == open => depth += 1,
+                Some(Token::Symbol(c)) if *c == close => depth -= 1,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    // 括弧の外にある区切り記号の手前まで読み飛ばす（初期化式・注釈の既定値）
+    fn skip_until(&mut self, stops: &[char]) -> ParseResult<()> {
+        loop {
+            match self.peek() {
+                None => return self.error("unexpected end of file"),
+                Some(Token::Symbol(c)) if stops.contains(c) => return Ok(()),
+                Some(Token::Symbol('(')) => self.skip_balanced('(', ')')?,
+                Some(Token::Symbol('{')) => self.skip_balanced('{', '}')?,
+                Some(Token::Symbol('[')) => self.skip_balanced('[', ']')?,
+                _ => self.position += 1,
+            }
+        }
+    }
+
+    // package・import と型の宣言の並び
+    fn compilation_unit(&mut self) -> ParseResult<()> {
+        while let Some(token) = self.peek() {
+            if token == &Token::Symbol(';') {
+                self.position += 1;
+            } else if self.is_word("package") || self.is_word("import") {
+                self.skip_until(&[';'])?;
+                self.position += 1;
+            } else {
+                self.modifiers()?;
+                match self.declaration_kind() {
+                    Some(kind) => self.type_declaration(kind)?,
+                    None => return self.error("expected a class, interface or enum declaration"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 注釈（@Name(...)）
+    fn annotation(&mut self) -> ParseResult<()> {
+        self.expect_symbol('@')?;
+        self.identifier()?;
+        while self.is_symbol('.') {
+            self.position += 1;
+            self.identifier()?;
+        }
+        if self.is_symbol('(') {
+            self.skip_balanced('(', ')')?;
+        }
+        Ok(())
+    }
+
+    fn modifiers(&mut self) -> ParseResult<Modifiers> {
+        let mut modifiers = Modifiers::default();
+        loop {
+            // @interface は注釈ではなく宣言
+            if self.is_symbol('@') && self.peek_at(1) != Some(&Token::Word("interface".into())) {
+                self.annotation()?;
+                continue;
+            }
+            let Some(Token::Word(word)) = self.peek() else {
+                break;
+            };
+            match word.as_str() {
+                "public" => modifiers.visibility = Some(Visibility::Public),
+                "private" => modifiers.visibility = Some(Visibility::Private),
+                "protected" => modifiers.visibility = Some(Visibility::Protected),
+                "static" => modifiers.is_static = true,
+                "abstract" => modifiers.is_abstract = true,
+                "default" | "final" | "transient" | "volatile" | "synchronized" | "native"
+                | "strictfp" | "sealed" => {}
+                "non" if self.peek_at(1) == Some(&Token::Symbol('-')) => self.position += 2,
+                _ => break,
+            }
+            self.position += 1;
+        }
+        Ok(modifiers)
+    }
+
+    // 次が型の宣言であればその種類
+    fn declaration_kind(&self) -> Option<Kind> {
+        match self.peek()? {
+            Token::Word(word) => match word.as_str() {
+                "class" => Some(Kind::Class),
+                "interface" => Some(Kind::Interface),
+                "enum" => Some(Kind::Enum),
+                // record は文脈によってのみキーワード（record Name( または record Name<）
+                "record" => match (self.peek_at(1), self.peek_at(2)) {
+                    (Some(Token::Word(_)), Some(Token::Symbol('(' | '<'))) => Some(Kind::Record),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Token::Symbol('@') => (self.peek_at(1) == Some(&Token::Word("interface".into())))
+                .then_some(Kind::Interface),
+            _ => None,
+        }
+    }
+
+    // class Name<T> extends A implements B { ... }
+    fn type_declaration(&mut self, kind: Kind) -> ParseResult<()> {
+        self.eat_symbol('@');
+        self.position += 1;
+        let key = self.identifier()?;
+        let mut name = key.clone();
+        if self.is_symbol('<') {
+            name.push_str(&self.type_arguments()?);
+        }
+
+        let mut declaration = Declaration {
+            key,
+            name,
+            ..Default::default()
+        };
+
+        // レコードの構成要素は属性にする
+        if kind == Kind::Record {
+            self.expect_symbol('(')?;
+            while !self.eat_symbol(')') {
+                let variable = self.parameter()?;
+                declaration.fields.push(field(Variable {
+                    visibility: Some(Visibility::Private.to_i32()),
+                    is_static: Some(false),
+                    ..variable
+                }));
+                if !self.is_symbol(')') {
+                    self.expect_symbol(',')?;
+                }
+            }
+        }
+
+        loop {
+            let relation = if self.eat_word("extends") {
+                // インターフェースの extends もクラス図では汎化
+                RelationKind::Inheritance
+            } else if self.eat_word("implements") {
+                RelationKind::Realization
+            } else if self.eat_word("permits") {
+                self.type_list()?;
+                continue;
+            } else {
+                break;
+            };
+            for parent in self.type_list()? {
+                declaration
+                    .parents
+                    .push((simple_name(&parent).to_string(), relation));
+            }
+        }
+
+        // 宣言の本体
+        self.expect_symbol('{')?;
+        if kind == Kind::Enum {
+            self.enum_constants(&mut declaration)?;
+        }
+        let index = self.declarations.len();
+        self.declarations.push(Declaration::default());
+        while !self.eat_symbol('}') {
+            if self.peek().is_none() {
+                return self.error(format!("'{}' is not closed", declaration.key));
+            }
+            self.member(kind, &mut declaration)?;
+        }
+        // 入れ子の型より前に置く
+        self.declarations[index] = declaration;
+        Ok(())
+    }
+
+    // 列挙定数（引数・本体は読み飛ばす）
+    fn enum_constants(&mut self, declaration: &mut Declaration) -> ParseResult<()> {
+        loop {
+            while self.is_symbol('@') {
+                self.annotation()?;
+            }
+            if self.eat_symbol(';') || self.is_symbol('}') {
+                return Ok(());
+            }
+            let name = self.identifier()?;
+            if self.is_symbol('(') {
+                self.skip_balanced('(', ')')?;
+            }
+            if self.is_symbol('{') {
+                self.skip_balanced('{', '}')?;
+            }
+            declaration.attributes.push(Variable {
+                name,
+                r#type: String::new(),
+                visibility: Some(Visibility::Public.to_i32()),
+                is_static: Some(true),
+            });
+            if !self.eat_symbol(',') && !self.is_symbol(';') && !self.is_symbol('}') {
+                return self.error("expected ',' or ';' after enum constant");
+            }
+        }
+    }
+
+    // 本体の中の宣言1つ（フィールド・メソッド・コンストラクタ・入れ子の型・初期化ブロック）
+    fn member(&mut self, kind: Kind, declaration: &mut Declaration) -> ParseResult<()> {
+        if self.eat_symbol(';') {
+            return Ok(());
+        }
+        if self.is_symbol('{')
+            || (self.is_word("static") && self.peek_at(1) == Some(&Token::Symbol('{')))
+        {
+            self.eat_word("static");
+            return self.skip_balanced('{', '}');
+        }
+
+        let modifiers = self.modifiers()?;
+        if let Some(nested) = self.declaration_kind() {
+            return self.type_declaration(nested);
+        }
+
+        // インターフェースのメンバは既定で公開
+        let visibility = modifiers.visibility.unwrap_or(if kind == Kind::Interface {
+            Visibility::Public
+        } else {
+            Visibility::Package
+        });
+
+        // ジェネリックメソッドの型引数
+        if self.is_symbol('<') {
+            self.skip_balanced('<', '>')?;
+        }
+
+        // コンストラクタ（レコードのコンパクトコンストラクタは読み飛ばす）
+        if self.is_word(&declaration.key) {
+            match self.peek_at(1) {
+                Some(Token::Symbol('(')) => {
+                    let name = self.identifier()?;
+                    let parameters = self.parameters()?;
+                    self.method_tail()?;
+                    declaration.methods.push(Method {
+                        name,
+                        return_type: String::new(),
+                        visibility: visibility.to_i32(),
+                        is_abstract: Some(false),
+                        is_static: Some(false),
+                        parameters,
+                    });
+                    return Ok(());
+                }
+                Some(Token::Symbol('{')) if kind == Kind::Record => {
+                    self.position += 1;
+                    return self.skip_balanced('{', '}');
+                }
+                _ => {}
+            }
+        }
+
+        let type_name = self.type_name()?;
+        let name = self.identifier()?;
+
+        if self.is_symbol('(') {
+            let parameters = self.parameters()?;
+            let mut return_type = type_name;
+            // 古い書き方の配列の戻り値（int values()[]）
+            while self.eat_symbol('[') {
+                self.expect_symbol(']')?;
+                return_type.push_str("[]");
+            }
+            let has_body = self.method_tail()?;
+            let is_abstract = modifiers.is_abstract
+                || (kind == Kind::Interface && !has_body && !modifiers.is_static);
+            declaration.methods.push(Method {
+                name,
+                return_type,
+                visibility: visibility.to_i32(),
+                is_abstract: Some(is_abstract),
+                is_static: Some(modifiers.is_static),
+                parameters,
+            });
+            return Ok(());
+        }
+
+        // フィールド（int a, b[] = {..}; のようにまとめて宣言できる）
+        // インターフェースのフィールドは定数
+        let is_static = modifiers.is_static || kind == Kind::Interface;
+        let mut name = name;
+        loop {
+            let mut field_type = type_name.clone();
+            while self.eat_symbol('[') {
+                self.expect_symbol(']')?;
+                field_type.push_str("[]");
+            }
+            declaration.fields.push(field(Variable {
+                name,
+                r#type: field_type,
+                visibility: Some(visibility.to_i32()),
+                is_static: Some(is_static),
+            }));
+            if self.eat_symbol('=') {
+                self.skip_until(&[',', ';'])?;
+            }
+            if self.eat_symbol(';') {
+                return Ok(());
+            }
+            self.expect_symbol(',')?;
+            name = self.identifier()?;
+        }
+    }
+
+    // 引数の並びの後（throws、本体または ; 、注釈の既定値）、本体があったかを返す
+    fn method_tail(&mut self) -> ParseResult<bool> {
+        if self.eat_word("throws") {
+            self.type_list()?;
+        }
+        if self.eat_word("default") {
+            self.skip_until(&[';'])?;
+        }
+        if self.eat_symbol(';') {
+            return Ok(false);
+        }
+        if self.is_symbol('{') {
+            self.skip_balanced('{', '}')?;
+            return Ok(true);
+        }
+        self.error("expected method body or ';'")
+    }
+
+    // (int count, final String... names)
+    fn parameters(&mut self) -> ParseResult<Vec<Variable>> {
+        self.expect_symbol('(')?;
+        let mut parameters = Vec::new();
+        while !self.eat_symbol(')') {
+            parameters.push(self.parameter()?);
+            if !self.is_symbol(')') {
+                self.expect_symbol(',')?;
+            }
+        }
+        Ok(parameters)
+    }
+
+    fn parameter(&mut self) -> ParseResult<Variable> {
+        self.modifiers()?;
+        let mut type_name = self.type_name()?;
+        // 可変長引数は配列として扱う
+        if self.is_symbol('.') {
+            for _ in 0..3 {
+                self.expect_symbol('.')?;
+            }
+            type_name.push_str("[]");
+        }
+        // 受け取り側のパラメータ（Foo this）
+        let name = self.identifier()?;
+        while self.eat_symbol('[') {
+            self.expect_symbol(']')?;
+            type_name.push_str("[]");
+        }
+        Ok(Variable {
+            name,
+            r#type: type_name,
+            ..Default::default()
+        })
+    }
+
+    // A, B<T>, C
+    fn type_list(&mut self) -> ParseResult<Vec<String>> {
+        let mut types = vec![self.type_name()?];
+        while self.eat_symbol(',') {
+            types.push(self.type_name()?);
+        }
+        Ok(types)
+    }
+
+    // 型（パッケージ名・型引数・配列を含む）を書かれた通りの文字列にする
+    fn type_name(&mut self) -> ParseResult<String> {
+        while self.is_symbol('@') {
+            self.annotation()?;
+        }
+        let mut text = self.identifier()?;
+        loop {
+            if self.is_symbol('<') {
+                text.push_str(&self.type_arguments()?);
+            }
+            if self.is_symbol('.') && matches!(self.peek_at(1), Some(Token::Word(_))) {
+                self.position += 1;
+                text.push('.');
+                text.push_str(&self.identifier()?);
+            } else {
+                break;
+            }
+        }
+        while self.is_symbol('[') && self.peek_at(1) == Some(&Token::Symbol(']')) {
+            self.position += 2;
+            text.push_str("[]");
+        }
+        Ok(text)
+    }
+
+    // <K, List<? extends V>>（型引数・型パラメータの宣言）
+    fn type_arguments(&mut self) -> ParseResult<String> {
+        self.expect_symbol('<')?;
+        let mut arguments = Vec::new();
+        while !self.eat_symbol('>') {
+            let mut argument = if self.eat_symbol('?') {
+                "?".to_string()
+            } else {
+                self.type_name()?
+            };
+            for bound in ["extends", "super"] {
+                if self.eat_word(bound) {
+                    argument.push_str(&format!(" {} {}", bound, self.type_name()?));
+                    while self.eat_symbol('&') {
+                        argument.push_str(&format!(" & {}", self.type_name()?));
+                    }
+                }
+            }
+            arguments.push(argument);
+            if !self.is_symbol('>') {
+                self.expect_symbol(',')?;
+            }
+        }
+        Ok(format!("<{}>", arguments.join(", ")))
+    }
+}
+
+// フィールドの型から関係先の候補と多重度を決める
+// コレクション・配列は 0..*、Optional は 0..1、それ以外は 1
+fn field(variable: Variable) -> Field {
+    let type_name = variable.r#type.trim();
+    let many = Multiplicity {
+        lower: 0,
+        upper: None,
+    };
+    let (target, multiplicity) = if let Some(element) = type_name.strip_suffix("[]") {
+        (simple_name(element).to_string(), many)
+    } else {
+        let base = simple_name(type_name);
+        let arguments = type_arguments(type_name);
+        let argument = |index: usize| {
+            arguments
+                .get(index)
+                .map(|argument| simple_name(bound(argument)).to_string())
+                .unwrap_or_default()
+        };
+        if COLLECTIONS.contains(&base) {
+            (argument(0), many)
+        } else if MAPS.contains(&base) {
+            (argument(1), many)
+        } else if base == "Optional" {
+            (
+                argument(0),
+                Multiplicity {
+                    lower: 0,
+                    upper: Some(1),
+                },
+            )
+        } else {
+            (
+                base.to_string(),
+                Multiplicity {
+                    lower: 1,
+                    upper: Some(1),
+                },
+            )
+        }
+    };
+
+    Field {
+        variable,
+        target,
+        kind: RelationKind::Association,
+        multiplicity,
+    }
+}
+
+// 一番外側の型引数（Map<K, List<V>> → ["K", "List<V>"]）
+fn type_arguments(type_name: &str) -> Vec<&str> {
+    let Some(open) = type_name.find('<') else {
+        return Vec::new();
+    };
+    let Some(close) = type_name.rfind('>') else {
+        return Vec::new();
+    };
+    let inner = &type_name[open + 1..close];
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(inner[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(inner[start..].trim());
+    arguments
+}
+
+// ワイルドカードの境界（? extends Item → Item）
+fn bound(argument: &str) -> &str {
+    argument
+        .strip_prefix('?')
+        .map(|rest| {
+            let rest = rest.trim_start();
+            rest.strip_prefix("extends")
+                .or_else(|| rest.strip_prefix("super"))
+                .unwrap_or(rest)
+                .trim()
+        })
+        .unwrap_or(argument)
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::import::new_id;
+use crate::model::RelationKind;
+use crate::server::class::{
+    Class, File, Method, Multiplicity, RelationInfo, RelationInfoList, Variable,
+};
+use crate::server::diagram_ext::SourceLanguage;
+
+pub mod java;
+pub mod rust;
+
+// 展開後のソースファイルの合計サイズの上限
+const MAX_EXTRACTED_SIZE: u64 = 64 * 1024 * 1024;
+
+// アーカイブの中のソースファイル
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+// 解析できなかったファイル（行は1から数え、分からない場合は0）
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+// 解析の結果（エラーは1から数える行とメッセージ）
+pub(crate) type ParseResult<T> = Result<T, (usize, String)>;
+
+pub struct ReverseEngineered {
+    pub file: File,
+    pub skipped: Vec<SkippedFile>,
+}
+
+// ソースコードのアーカイブ（zip・tar・tar.gz）からクラス図を作成
+// ファイルID・日時は設定しない（保存時に呼び出し側で設定する）
+pub fn reverse_engineer(
+    archive: &[u8],
+    language: SourceLanguage,
+) -> Result<ReverseEngineered, String> {
+    let extension = match language {
+        SourceLanguage::Java => "java",
+        SourceLanguage::Rust => "rs",
+    };
+    let (sources, mut skipped) = extract_sources(archive, extension)?;
+    if sources.is_empty() && skipped.is_empty() {
+        return Err(format!("archive contains no .{} files", extension));
+    }
+
+    let (declarations, unparsed) = match language {
+        SourceLanguage::Java => parse_each(&sources, java::parse),
+        // implブロックは型の宣言と別のファイルにある場合があるため全ファイルをまとめて読む
+        SourceLanguage::Rust => rust::parse(&sources),
+    };
+    skipped.extend(unparsed);
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(ReverseEngineered {
+        file: build(declarations),
+        skipped,
+    })
+}
+
+// ソースファイルを1つずつ読み、解析できなかったファイルは読み飛ばす
+fn parse_each(
+    sources: &[SourceFile],
+    parse: fn(&str) -> ParseResult<Vec<Declaration>>,
+) -> (Vec<Declaration>, Vec<SkippedFile>) {
+    let mut declarations = Vec::new();
+    let mut skipped = Vec::new();
+    for source in sources {
+        match parse(&source.content) {
+            Ok(found) => declarations.extend(found),
+            Err((line, message)) => skipped.push(SkippedFile {
+                path: source.path.clone(),
+                line,
+                message,
+            }),
+        }
+    }
+    (declarations, skipped)
+}
+
+// アーカイブの形式は先頭のバイト列で判定する
+fn extract_sources(
+    archive: &[u8],
+    extension: &str,
+) -> Result<(Vec<SourceFile>, Vec<SkippedFile>), String> {
+    let suffix = format!(".{}", extension);
+    let mut sources = Vec::new();
+    let mut skipped = Vec::new();
+    let mut total = 0;
+    let mut add = |path: String, reader: &mut dyn Read| -> Result<(), String> {
+        // ディレクトリや対象外のファイルは読み飛ばす
+        if !path.ends_with(&suffix) || path.ends_with('/') {
+            return Ok(());
+        }
+        let mut bytes = Vec::new();
+        reader
+            .take(MAX_EXTRACTED_SIZE - total + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        total += bytes.len() as u64;
+        if total > MAX_EXTRACTED_SIZE {
+            return Err(format!(
+                "archive is too large (over {} bytes of source)",
+                MAX_EXTRACTED_SIZE
+            ));
+        }
+        match String::from_utf8(bytes) {
+            Ok(content) => sources.push(SourceFile { path, content }),
+            Err(_) => skipped.push(SkippedFile {
+                path,
+                line: 0,
+                message: "file is not valid UTF-8".to_string(),
+            }),
+        }
+        Ok(())
+    };
+
+    if archive.starts_with(b"PK") {
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive))
+            .map_err(|e| format!("Failed to open zip archive: {}", e))?;
+        for index in 0..zip.len() {
+            let mut entry = zip
+                .by_index(index)
+                .map_err(|e| format!("Failed to read zip archive: {}", e))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let path = entry.name().to_string();
+            add(path, &mut entry)?;
+        }
+    } else if archive.starts_with(&[0x1f, 0x8b]) {
+        let decoder = flate2::read::GzDecoder::new(archive);
+        read_tar(tar::Archive::new(decoder), &mut add)?;
+    } else if archive.get(257..262) == Some(b"ustar".as_slice()) {
+        read_tar(tar::Archive::new(archive), &mut add)?;
+    } else {
+        return Err("archive must be a zip, tar or tar.gz file".to_string());
+    }
+
+    // パスの順に並べて結果を安定させる
+    sources.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((sources, skipped))
+}
+
+fn read_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    add: &mut dyn FnMut(String, &mut dyn Read) -> Result<(), String>,
+) -> Result<(), String> {
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read tar archive: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| format!("Failed to read tar archive: {}", e))?
+            .to_string_lossy()
+            .into_owned();
+        add(path, &mut entry)?;
+    }
+    Ok(())
+}
+
+// ソースコードから読み取った型の宣言
+#[derive(Debug, Default)]
+pub(crate) struct Declaration {
+    // 参照に使う名前（型引数を除いたもの）
+    pub key: String,
+    // クラス図上の名前（型引数を含む）
+    pub name: String,
+    // 親の型の名前と関係の種類（継承・実現）
+    pub parents: Vec<(String, RelationKind)>,
+    pub attributes: Vec<Variable>,
+    pub methods: Vec<Method>,
+    pub fields: Vec<Field>,
+}
+
+// フィールド（型が宣言されたクラスを指す場合は関係、それ以外は属性にする）
+#[derive(Debug)]
+pub(crate) struct Field {
+    pub variable: Variable,
+    // 要素の型の名前（List<Item> の Item など）
+    pub target: String,
+    pub kind: RelationKind,
+    pub multiplicity: Multiplicity,
+}
+
+// 宣言の並びからファイルを組み立てる
+// 同じ名前の宣言が複数ある場合、参照は最初のものに解決する
+// 宣言されていない型（ライブラリのクラスなど）への継承・実現は省略する
+fn build(declarations: Vec<Declaration>) -> File {
+    let ids: Vec<String> = declarations.iter().map(|_| new_id()).collect();
+    let mut id_of: HashMap<&str, &str> = HashMap::new();
+    for (declaration, id) in declarations.iter().zip(&ids) {
+        id_of.entry(declaration.key.as_str()).or_insert(id.as_str());
+    }
+
+    let mut classes = Vec::new();
+    for (declaration, id) in declarations.iter().zip(&ids) {
+        let mut relations = Vec::new();
+        for (parent, kind) in &declaration.parents {
+            if let Some(&target) = id_of.get(parent.as_str()) {
+                relations.push(RelationInfo {
+                    target_class_id: target.to_string(),
+                    relation: kind.to_i32(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        let mut attributes = declaration.attributes.clone();
+        for field in &declaration.fields {
+            match id_of.get(field.target.as_str()) {
+                Some(&target) => relations.push(RelationInfo {
+                    target_class_id: target.to_string(),
+                    relation: field.kind.to_i32(),
+                    multiplicity_c: Some(field.multiplicity),
+                    role_name_c: Some(field.variable.name.clone()),
+                    ..Default::default()
+                }),
+                None => attributes.push(field.variable.clone()),
+            }
+        }
+
+        classes.push(Class {
+            id: id.clone(),
+            name: declaration.name.clone(),
+            relations: (!relations.is_empty()).then_some(RelationInfoList {
+                relation_infos: relations,
+            }),
+            attributes,
+            methods: declaration.methods.clone(),
+        });
+    }
+
+    File {
+        name: "Reverse engineered diagram".to_string(),
+        classes,
+        ..Default::default()
+    }
+}
+
+// 型の文字列から、パッケージ・モジュールのパスと型引数を除いた名前
+pub(crate) fn simple_name(type_name: &str) -> &str {
+    let base = type_name
+        .split(['<', '['])
+        .next()
+        .unwrap_or(type_name)
+        .trim();
+    base.rsplit(['.', ':']).next().unwrap_or(base).trim()
+}
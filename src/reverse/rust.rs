@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+
+use syn::{
+    Fields, FnArg, GenericArgument, GenericParam, Generics, ImplItem, Item, Pat, PathArguments,
+    ReturnType, Signature, TraitItem, Type, TypeParamBound,
+};
+
+use super::{Declaration, Field, SkippedFile, SourceFile};
+use crate::model::{RelationKind, Visibility};
+use crate::server::class::{Method, Multiplicity, Variable};
+
+// 要素の型を最初の型引数に持つコレクション
+const COLLECTIONS: [&str; 7] = [
+    "Vec",
+    "VecDeque",
+    "LinkedList",
+    "HashSet",
+    "BTreeSet",
+    "BinaryHeap",
+    "IndexSet",
+];
+
+// 値の型を2番目の型引数に持つマップ
+const MAPS: [&str; 3] = ["HashMap", "BTreeMap", "IndexMap"];
+
+// 所有権に影響しない包み（中の型をそのまま関係先にする）
+const WRAPPERS: [&str; 8] = [
+    "Box", "Cell", "RefCell", "Mutex", "RwLock", "Pin", "Cow", "OnceCell",
+];
+
+// implブロックから読み取った内容（型の宣言と別のファイルにある場合があるため後でまとめる）
+struct ImplBlock {
+    key: String,
+    parent: Option<String>,
+    attributes: Vec<Variable>,
+    methods: Vec<Method>,
+}
+
+// Rustのソースファイル（.rs）から構造体・列挙型・トレイトの宣言を読み取る
+// implブロックのメソッドは型に、トレイトの実装は実現の関係にする
+// 同じ名前の型が複数ある場合、implブロックは最初の宣言に付ける
+pub(crate) fn parse(sources: &[SourceFile]) -> (Vec<Declaration>, Vec<SkippedFile>) {
+    let mut declarations = Vec::new();
+    let mut impls = Vec::new();
+    let mut skipped = Vec::new();
+
+    for source in sources {
+        match syn::parse_file(&source.content) {
+            Ok(file) => collect_items(&file.items, &mut declarations, &mut impls),
+            Err(e) => skipped.push(SkippedFile {
+                path: source.path.clone(),
+                line: e.span().start().line,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    let mut index_of = HashMap::new();
+    for (index, declaration) in declarations.iter().enumerate() {
+        index_of.entry(declaration.key.clone()).or_insert(index);
+    }
+    // 宣言されていない型（外部のクレートの型など）へのimplは省略する
+    for block in impls {
+        let Some(&index) = index_of.get(&block.key) else {
+            continue;
+        };
+        let declaration: &mut Declaration = &mut declarations[index];
+        if let Some(parent) = block.parent {
+            declaration
+                .parents
+                .push((parent, RelationKind::Realization));
+        }
+        declaration.attributes.extend(block.attributes);
+        // implブロックの中の Self は型の名前に置き換える
+        for mut method in block.methods {
+            method.return_type = replace_self(&method.return_type, &block.key);
+            for parameter in &mut method.parameters {
+                parameter.r#type = replace_self(&parameter.r#type, &block.key);
+            }
+            declaration.methods.push(method);
+        }
+    }
+
+    (declarations, skipped)
+}
+
+// モジュールの中も含めて項目を読み取る
+fn collect_items(items: &[Item], declarations: &mut Vec<Declaration>, impls: &mut Vec<ImplBlock>) {
+    for item in items {
+        match item {
+            Item::Struct(item) => {
+                let mut declaration = declaration(&item.ident, &item.generics);
+                for (index, field) in item.fields.iter().enumerate() {
+                    let name = field
+                        .ident
+                        .as_ref()
+                        .map_or_else(|| index.to_string(), ToString::to_string);
+                    declaration.fields.push(self::field(
+                        Variable {
+                            name,
+                            r#type: type_text(&field.ty),
+                            visibility: Some(visibility(&field.vis).to_i32()),
+                            is_static: Some(false),
+                        },
+                        &field.ty,
+                    ));
+                }
+                declarations.push(declaration);
+            }
+            Item::Enum(item) => {
+                let mut declaration = declaration(&item.ident, &item.generics);
+                // バリアントは静的な属性、型にはデータの形を書く
+                for variant in &item.variants {
+                    let r#type = match &variant.fields {
+                        Fields::Named(fields) => {
+                            let fields: Vec<String> = fields
+                                .named
+                                .iter()
+                                .map(|field| {
+                                    format!(
+                                        "{}: {}",
+                                        field
+                                            .ident
+                                            .as_ref()
+                                            .map(ToString::to_string)
+                                            .unwrap_or_default(),
+                                        type_text(&field.ty)
+                                    )
+                                })
+                                .collect();
+                            format!("{{ {} }}", fields.join(", "))
+                        }
+                        Fields::Unnamed(fields) => {
+                            let fields: Vec<String> = fields
+                                .unnamed
+                                .iter()
+                                .map(|field| type_text(&field.ty))
+                                .collect();
+                            format!("({})", fields.join(", "))
+                        }
+                        Fields::Unit => String::new(),
+                    };
+                    declaration.attributes.push(Variable {
+                        name: variant.ident.to_string(),
+                        r#type,
+                        visibility: Some(Visibility::Public.to_i32()),
+                        is_static: Some(true),
+                    });
+                }
+                declarations.push(declaration);
+            }
+            Item::Trait(item) => {
+                let mut declaration = declaration(&item.ident, &item.generics);
+                // スーパートレイトは汎化
+                for bound in &item.supertraits {
+                    if let TypeParamBound::Trait(bound) = bound {
+                        if let Some(segment) = bound.path.segments.last() {
+                            declaration
+                                .parents
+                                .push((segment.ident.to_string(), RelationKind::Inheritance));
+                        }
+                    }
+                }
+                for trait_item in &item.items {
+                    match trait_item {
+                        TraitItem::Fn(function) => declaration.methods.push(method(
+                            &function.sig,
+                            Visibility::Public,
+                            function.default.is_none(),
+                        )),
+                        TraitItem::Const(constant) => declaration.attributes.push(Variable {
+                            name: constant.ident.to_string(),
+                            r#type: type_text(&constant.ty),
+                            visibility: Some(Visibility::Public.to_i32()),
+                            is_static: Some(true),
+                        }),
+                        _ => {}
+                    }
+                }
+                declarations.push(declaration);
+            }
+            Item::Impl(item) => {
+                let Some(key) = type_key(&item.self_ty) else {
+                    continue;
+                };
+                // トレイトの実装は実現（メソッドはトレイト側に書かれている）
+                if let Some((_, path, _)) = &item.trait_ {
+                    impls.push(ImplBlock {
+                        key,
+                        parent: path
+                            .segments
+                            .last()
+                            .map(|segment| segment.ident.to_string()),
+                        attributes: Vec::new(),
+                        methods: Vec::new(),
+                    });
+                    continue;
+                }
+
+                let mut block = ImplBlock {
+                    key,
+                    parent: None,
+                    attributes: Vec::new(),
+                    methods: Vec::new(),
+                };
+                for impl_item in &item.items {
+                    match impl_item {
+                        ImplItem::Fn(function) => block.methods.push(method(
+                            &function.sig,
+                            visibility(&function.vis),
+                            false,
+                        )),
+                        ImplItem::Const(constant) => block.attributes.push(Variable {
+                            name: constant.ident.to_string(),
+                            r#type: type_text(&constant.ty),
+                            visibility: Some(visibility(&constant.vis).to_i32()),
+                            is_static: Some(true),
+                        }),
+                        _ => {}
+                    }
+                }
+                impls.push(block);
+            }
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    collect_items(items, declarations, impls);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 型の宣言（名前には型パラメータを含める）
+fn declaration(ident: &syn::Ident, generics: &Generics) -> Declaration {
+    let key = ident.to_string();
+    let parameters: Vec<String> = generics
+        .params
+        .iter()
+        .filter_map(|parameter| match parameter {
+            GenericParam::Type(parameter) => Some(parameter.ident.to_string()),
+            GenericParam::Const(parameter) => Some(parameter.ident.to_string()),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    let name = if parameters.is_empty() {
+        key.clone()
+    } else {
+        format!("{}<{}>", key, parameters.join(", "))
+    };
+    Declaration {
+        key,
+        name,
+        ..Default::default()
+    }
+}
+
+// pub は公開、pub(crate)・pub(super) などはパッケージ、無指定は非公開
+fn visibility(visibility: &syn::Visibility) -> Visibility {
+    match visibility {
+        syn::Visibility::Public(_) => Visibility::Public,
+        syn::Visibility::Restricted(_) => Visibility::Package,
+        syn::Visibility::Inherited => Visibility::Private,
+    }
+}
+
+// self を受け取らない関数は静的なメソッド
+fn method(signature: &Signature, visibility: Visibility, is_abstract: bool) -> Method {
+    let mut is_static = true;
+    let mut parameters = Vec::new();
+    for (index, input) in signature.inputs.iter().enumerate() {
+        match input {
+            FnArg::Receiver(_) => is_static = false,
+            FnArg::Typed(argument) => {
+                let name = match &*argument.pat {
+                    Pat::Ident(pattern) => pattern.ident.to_string(),
+                    _ => format!("arg{}", index),
+                };
+                parameters.push(Variable {
+                    name,
+                    r#type: type_text(&argument.ty),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    let return_type = match &signature.output {
+        ReturnType::Default => String::new(),
+        ReturnType::Type(_, ty) => type_text(ty),
+    };
+
+    Method {
+        name: signature.ident.to_string(),
+        return_type,
+        visibility: visibility.to_i32(),
+        is_abstract: Some(is_abstract),
+        is_static: Some(is_static),
+        parameters,
+    }
+}
+
+// implの対象の型の名前（Foo<T> → Foo）
+fn type_key(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        Type::Paren(paren) => type_key(&paren.elem),
+        Type::Group(group) => type_key(&group.elem),
+        _ => None,
+    }
+}
+
+// フィールドの型から関係先の候補・関係の種類・多重度を決める
+// 所有する型（T、Box<T>、Option<T>、Vec<T> など）はコンポジション、
+// Rc・Arc による共有は集約、参照・Weak は関連
+fn field(variable: Variable, ty: &Type) -> Field {
+    let mut kind = RelationKind::Composition;
+    let mut multiplicity = Multiplicity {
+        lower: 1,
+        upper: Some(1),
+    };
+    let target = element_type(ty, &mut kind, &mut multiplicity).unwrap_or_default();
+    Field {
+        variable,
+        target,
+        kind,
+        multiplicity,
+    }
+}
+
+fn element_type(
+    ty: &Type,
+    kind: &mut RelationKind,
+    multiplicity: &mut Multiplicity,
+) -> Option<String> {
+    let many = |multiplicity: &mut Multiplicity| {
+        *multiplicity = Multiplicity {
+            lower: 0,
+            upper: None,
+        }
+    };
+    match ty {
+        Type::Reference(reference) => {
+            *kind = RelationKind::Association;
+            element_type(&reference.elem, kind, multiplicity)
+        }
+        Type::Slice(slice) => {
+            many(multiplicity);
+            element_type(&slice.elem, kind, multiplicity)
+        }
+        Type::Array(array) => {
+            many(multiplicity);
+            element_type(&array.elem, kind, multiplicity)
+        }
+        Type::Paren(paren) => element_type(&paren.elem, kind, multiplicity),
+        Type::Group(group) => element_type(&group.elem, kind, multiplicity),
+        // Box<dyn Trait> はトレイトへの関係
+        Type::TraitObject(object) => object.bounds.iter().find_map(|bound| match bound {
+            TypeParamBound::Trait(bound) => bound
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string()),
+            _ => None,
+        }),
+        Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            let name = segment.ident.to_string();
+            let arguments: Vec<&Type> = match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => arguments
+                    .args
+                    .iter()
+                    .filter_map(|argument| match argument {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let name = name.as_str();
+            if name == "Option" {
+                if multiplicity.upper == Some(1) {
+                    multiplicity.lower = 0;
+                }
+                element_type(arguments.first()?, kind, multiplicity)
+            } else if COLLECTIONS.contains(&name) {
+                many(multiplicity);
+                element_type(arguments.first()?, kind, multiplicity)
+            } else if MAPS.contains(&name) {
+                many(multiplicity);
+                element_type(arguments.get(1)?, kind, multiplicity)
+            } else if WRAPPERS.contains(&name) {
+                element_type(arguments.first()?, kind, multiplicity)
+            } else if name == "Rc" || name == "Arc" {
+                if *kind == RelationKind::Composition {
+                    *kind = RelationKind::Aggregation;
+                }
+                element_type(arguments.first()?, kind, multiplicity)
+            } else if name == "Weak" {
+                // 参照先が既に破棄されている場合がある
+                *kind = RelationKind::Association;
+                if multiplicity.upper == Some(1) {
+                    multiplicity.lower = 0;
+                }
+                element_type(arguments.first()?, kind, multiplicity)
+            } else {
+                Some(name.to_string())
+            }
+        }
+        _ => None,
+    }
+}
+
+// 識別子としての Self だけを置き換える（SelfRef などは残す）
+fn replace_self(text: &str, name: &str) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        out.push_str(if word == "Self" { name } else { &word });
+        word.clear();
+        out.push(c);
+    }
+    out.pop();
+    out
+}
+
+// 型をソースに近い形の文字列にする（パスは書かれた通り、ライフタイムは省略）
+fn type_text(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path_text(&path.path),
+        Type::Reference(reference) => format!(
+            "&{}{}",
+            if reference.mutability.is_some() {
+                "mut "
+            } else {
+                ""
+            },
+            type_text(&reference.elem)
+        ),
+        Type::Slice(slice) => format!("[{}]", type_text(&slice.elem)),
+        Type::Array(array) => {
+            let length = match &array.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(length),
+                    ..
+                }) => length.base10_digits().to_string(),
+                syn::Expr::Path(path) => path_text(&path.path),
+                _ => "_".to_string(),
+            };
+            format!("[{}; {}]", type_text(&array.elem), length)
+        }
+        Type::Tuple(tuple) => {
+            let elements: Vec<String> = tuple.elems.iter().map(type_text).collect();
+            format!("({})", elements.join(", "))
+        }
+        Type::Ptr(pointer) => format!(
+            "*{} {}",
+            if pointer.mutability.is_some() {
+                "mut"
+            } else {
+                "const"
+            },
+            type_text(&pointer.elem)
+        ),
+        Type::TraitObject(object) => format!("dyn {}", bounds_text(&object.bounds)),
+        Type::ImplTrait(object) => format!("impl {}", bounds_text(&object.bounds)),
+        Type::BareFn(function) => {
+            let inputs: Vec<String> = function
+                .inputs
+                .iter()
+                .map(|input| type_text(&input.ty))
+                .collect();
+            let output = match &function.output {
+                ReturnType::Default => String::new(),
+                ReturnType::Type(_, ty) => format!(" -> {}", type_text(ty)),
+            };
+            format!("fn({}){}", inputs.join(", "), output)
+        }
+        Type::Paren(paren) => format!("({})", type_text(&paren.elem)),
+        Type::Group(group) => type_text(&group.elem),
+        Type::Never(_) => "!".to_string(),
+        _ => "_".to_string(),
+    }
+}
+
+fn path_text(path: &syn::Path) -> String {
+    let segments: Vec<String> = path
+        .segments
+        .iter()
+        .map(|segment| {
+            let mut text = segment.ident.to_string();
+            match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => {
+                    let arguments: Vec<String> = arguments
+                        .args
+                        .iter()
+                        .filter_map(|argument| match argument {
+                            GenericArgument::Type(ty) => Some(type_text(ty)),
+                            GenericArgument::AssocType(assoc) => {
+                                Some(format!("{} = {}", assoc.ident, type_text(&assoc.ty)))
+                            }
+                            GenericArgument::Lifetime(_) => None,
+                            _ => Some("_".to_string()),
+                        })
+                        .collect();
+                    if !arguments.is_empty() {
+                        text.push_str(&format!("<{}>", arguments.join(", ")));
+                    }
+                }
+                // Fn(A, B) -> C
+                PathArguments::Parenthesized(arguments) => {
+                    let inputs: Vec<String> = arguments.inputs.iter().map(type_text).collect();
+                    text.push_str(&format!("({})", inputs.join(", ")));
+                    if let ReturnType::Type(_, ty) = &arguments.output {
+                        text.push_str(&format!(" -> {}", type_text(ty)));
+                    }
+                }
+                PathArguments::None => {}
+            }
+            text
+        })
+        .collect();
+    segments.join("::")
+}
+
+fn bounds_text<'a>(bounds: impl IntoIterator<Item = &'a TypeParamBound>) -> String {
+    let bounds: Vec<String> = bounds
+        .into_iter()
+        .filter_map(|bound| match bound {
+            TypeParamBound::Trait(bound) => Some(path_text(&bound.path)),
+            _ => None,
+        })
+        .collect();
+    bounds.join(" + ")
+}
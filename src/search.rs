@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::RwLock;
+
+use crate::server::class::{File, FileId};
+use crate::server::diagram_ext::{SearchField, SearchHit, SearchMatch};
+
+// 保存されたダイアグラムの名前・型を検索するための転置インデックス
+// ファイル名・クラス名・属性/引数の名前と型・メソッド名と戻り値の型を対象とする
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    state: RwLock<IndexState>,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    // ファイルIDごとのインデックス対象
+    documents: HashMap<String, IndexedDiagram>,
+    // 小文字化したトークン → そのトークンを含むファイルID
+    terms: BTreeMap<String, HashSet<String>>,
+}
+
+#[derive(Debug)]
+struct IndexedDiagram {
+    revision: u64,
+    name: String,
+    fields: Vec<IndexedField>,
+}
+
+#[derive(Debug)]
+struct IndexedField {
+    field: SearchField,
+    // クラス・メンバのパス（例: OrderService.find(id)）
+    path: String,
+    class_id: String,
+    value: String,
+    tokens: Vec<String>,
+}
+
+// 検索語（field:語 の形式で対象を絞り込める）
+#[derive(Debug)]
+struct QueryTerm {
+    fields: Option<&'static [SearchField]>,
+    token: String,
+}
+
+const TYPE_FIELDS: &[SearchField] = &[
+    SearchField::AttributeType,
+    SearchField::ReturnType,
+    SearchField::ParameterType,
+];
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // ファイルをインデックスに登録（既に新しい版が登録されている場合は何もしない）
+    pub fn index(&self, file_id: &str, revision: u64, file: &File) {
+        let Ok(mut state) = self.state.write() else {
+            return;
+        };
+        if state
+            .documents
+            .get(file_id)
+            .is_some_and(|indexed| indexed.revision > revision)
+        {
+            return;
+        }
+
+        state.remove_document(file_id);
+
+        let document = IndexedDiagram {
+            revision,
+            name: file.name.clone(),
+            fields: collect_fields(file),
+        };
+        for field in &document.fields {
+            for token in &field.tokens {
+                state
+                    .terms
+                    .entry(token.clone())
+                    .or_default()
+                    .insert(file_id.to_string());
+            }
+        }
+        state.documents.insert(file_id.to_string(), document);
+    }
+
+    // ファイルをインデックスから削除
+    pub fn remove(&self, file_id: &str) {
+        if let Ok(mut state) = self.state.write() {
+            state.remove_document(file_id);
+        }
+    }
+
+    // すべての検索語を含むファイルを、一致した箇所と共に返す
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let terms = parse_query(query);
+        if terms.is_empty() {
+            return Err("Search query is empty".to_string());
+        }
+
+        let state = self
+            .state
+            .read()
+            .map_err(|_| "Failed to acquire search index lock".to_string())?;
+
+        // 各検索語を前方一致で含むファイルの積集合
+        let mut candidates: Option<HashSet<&String>> = None;
+        for term in &terms {
+            let matched: HashSet<&String> = state
+                .terms
+                .range(term.token.clone()..)
+                .take_while(|(token, _)| token.starts_with(&term.token))
+                .flat_map(|(_, file_ids)| file_ids.iter())
+                .collect();
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.intersection(&matched).copied().collect(),
+                None => matched,
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|file_id| {
+                let document = state.documents.get(file_id)?;
+                search_document(file_id, document, &terms)
+            })
+            .collect();
+
+        // 一致した箇所が多い順、同数の場合は名前とファイルIDの順
+        hits.sort_by(|a, b| {
+            b.matches
+                .len()
+                .cmp(&a.matches.len())
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| file_id_of(a).cmp(file_id_of(b)))
+        });
+        hits.truncate(limit);
+        Ok(hits)
+    }
+}
+
+impl IndexState {
+    fn remove_document(&mut self, file_id: &str) {
+        let Some(document) = self.documents.remove(file_id) else {
+            return;
+        };
+        for field in &document.fields {
+            for token in &field.tokens {
+                if let Some(file_ids) = self.terms.get_mut(token) {
+                    file_ids.remove(file_id);
+                    if file_ids.is_empty() {
+                        self.terms.remove(token);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn file_id_of(hit: &SearchHit) -> &str {
+    hit.file_id.as_ref().map_or("", |file_id| file_id.id.as_str())
+}
+
+// 1つのファイルについて、各検索語が少なくとも1箇所に一致するか確認
+fn search_document(
+    file_id: &str,
+    document: &IndexedDiagram,
+    terms: &[QueryTerm],
+) -> Option<SearchHit> {
+    let mut matched_terms = vec![false; terms.len()];
+    let mut matches = Vec::new();
+
+    for field in &document.fields {
+        let mut field_matched = false;
+        for (index, term) in terms.iter().enumerate() {
+            if term_matches(term, field) {
+                matched_terms[index] = true;
+                field_matched = true;
+            }
+        }
+        if field_matched {
+            matches.push(SearchMatch {
+                field: field.field as i32,
+                path: field.path.clone(),
+                class_id: field.class_id.clone(),
+                value: field.value.clone(),
+            });
+        }
+    }
+
+    if !matched_terms.iter().all(|matched| *matched) {
+        return None;
+    }
+
+    Some(SearchHit {
+        file_id: Some(FileId {
+            id: file_id.to_string(),
+        }),
+        name: document.name.clone(),
+        matches,
+    })
+}
+
+fn term_matches(term: &QueryTerm, field: &IndexedField) -> bool {
+    if let Some(fields) = term.fields {
+        if !fields.contains(&field.field) {
+            return false;
+        }
+    }
+    field
+        .tokens
+        .iter()
+        .any(|token| token.starts_with(&term.token))
+}
+
+// クエリを空白で区切り、file: class: attribute: method: param: type: の指定を解釈
+fn parse_query(query: &str) -> Vec<QueryTerm> {
+    let mut terms = Vec::new();
+    for word in query.split_whitespace() {
+        let (fields, text) = match word.split_once(':') {
+            Some((prefix, text)) => match field_filter(prefix) {
+                Some(fields) => (Some(fields), text),
+                None => (None, word),
+            },
+            None => (None, word),
+        };
+
+        for token in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+        {
+            terms.push(QueryTerm {
+                fields,
+                token: token.to_lowercase(),
+            });
+        }
+    }
+    terms
+}
+
+fn field_filter(prefix: &str) -> Option<&'static [SearchField]> {
+    match prefix.to_lowercase().as_str() {
+        "file" => Some(&[SearchField::FileName]),
+        "class" => Some(&[SearchField::ClassName]),
+        "attribute" | "attr" => Some(&[SearchField::AttributeName]),
+        "method" => Some(&[SearchField::MethodName]),
+        "param" | "parameter" => Some(&[SearchField::ParameterName]),
+        "type" => Some(TYPE_FIELDS),
+        _ => None,
+    }
+}
+
+// ファイルからインデックス対象の値をパス付きで取り出す
+fn collect_fields(file: &File) -> Vec<IndexedField> {
+    let mut fields = Vec::new();
+    let mut push = |field: SearchField, path: String, class_id: &str, value: &str| {
+        let tokens = tokenize(value);
+        if !tokens.is_empty() {
+            fields.push(IndexedField {
+                field,
+                path,
+                class_id: class_id.to_string(),
+                value: value.to_string(),
+                tokens,
+            });
+        }
+    };
+
+    push(SearchField::FileName, String::new(), "", &file.name);
+
+    for class in &file.classes {
+        // 名前が無いクラスはIDでパスを表す
+        let class_path = if class.name.is_empty() {
+            class.id.clone()
+        } else {
+            class.name.clone()
+        };
+        push(SearchField::ClassName, class_path.clone(), &class.id, &class.name);
+
+        for attribute in &class.attributes {
+            let path = format!("{}.{}", class_path, attribute.name);
+            push(
+                SearchField::AttributeName,
+                path.clone(),
+                &class.id,
+                &attribute.name,
+            );
+            push(SearchField::AttributeType, path, &class.id, &attribute.r#type);
+        }
+
+        for method in &class.methods {
+            let method_path = format!("{}.{}()", class_path, method.name);
+            push(
+                SearchField::MethodName,
+                method_path.clone(),
+                &class.id,
+                &method.name,
+            );
+            push(
+                SearchField::ReturnType,
+                method_path,
+                &class.id,
+                &method.return_type,
+            );
+
+            for parameter in &method.parameters {
+                let path = format!("{}.{}({})", class_path, method.name, parameter.name);
+                push(
+                    SearchField::ParameterName,
+                    path.clone(),
+                    &class.id,
+                    &parameter.name,
+                );
+                push(SearchField::ParameterType, path, &class.id, &parameter.r#type);
+            }
+        }
+    }
+
+    fields
+}
+
+// 英数字以外で区切り、さらにキャメルケースの単語にも分割する
+// 例: "Map<String, OrderId>" → map, string, orderid, order, id
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        tokens.push(word.to_lowercase());
+        let parts = split_camel_case(word);
+        if parts.len() > 1 {
+            tokens.extend(parts.into_iter().map(str::to_lowercase));
+        }
+    }
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+// "HTTPServerError" → HTTP, Server, Error
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..chars.len() {
+        let (offset, c) = chars[i];
+        let prev = chars[i - 1].1;
+        let next_is_lower = chars.get(i + 1).is_some_and(|(_, next)| next.is_lowercase());
+
+        let boundary = (prev.is_lowercase() && c.is_uppercase())
+            || (prev.is_uppercase() && c.is_uppercase() && next_is_lower)
+            || (prev.is_alphabetic() != c.is_alphabetic());
+        if boundary {
+            parts.push(&word[start..offset]);
+            start = offset;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
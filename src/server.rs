@@ -1,163 +1,276 @@
 use prost::Message;
-use std::collections::HashMap;
-use std::io::Read;
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::fs;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
 use tokio::time::interval;
-use tonic::{transport::Server, Request, Response, Status};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    service::interceptor::InterceptedService,
+    transport::Server,
+    Request, Response, Status,
+};
 use tonic_web::GrpcWebLayer;
 use tower_http::cors::CorsLayer;
 
+use crate::auth::{AuthInterceptor, Authenticator, Principal};
+use crate::config::{LockMode, ServerConfig, ValidationMode};
+use crate::crdt::{CrdtDiagram, CrdtDiagrams};
+use crate::import::ImportError;
+use crate::presence::Presence;
+use crate::search::SearchIndex;
+use crate::store::{
+    self, insert_etag, parse_etag, ApiKeyRecord, DiagramStore, DiagramSummary as StoredSummary,
+    StoredRevision,
+};
+use crate::watch::{self, WatchHub};
+
 pub mod class {
     tonic::include_proto!("class");
 }
 
+pub mod diagram_ext {
+    tonic::include_proto!("diagram_ext");
+}
+
 use class::{
     diagram_service_server::{DiagramService, DiagramServiceServer},
     File, FileId, Result as ProtoResult,
 };
+use diagram_ext::{
+    auth_service_server::{AuthService, AuthServiceServer},
+    diagram_ext_service_server::{DiagramExtService, DiagramExtServiceServer},
+    ApiKey, ApiKeyList, ApplyOperationsRequest, ApplyOperationsResponse, AuthToken,
+    AuthenticateRequest, CodeLanguage, CrdtStateVector, CrdtSyncRequest, CrdtSyncResponse,
+    CreateApiKeyRequest, CreateUserRequest, CreatedApiKey, DiagramDiff, DiagramEvent,
+    DiagramEventKind, DiagramSummary, DiffRequest, ExportFormat, ExportRequest, ExportedDiagram,
+    GenerateCodeRequest, GeneratedCode, GeneratedFile, ImportFormat, ImportRequest,
+    JoinSessionRequest, ListApiKeysRequest, ListClassDiagramsRequest, ListClassDiagramsResponse,
+    LockConflicts, LockRequest, LockResponse, LoginRequest, MergeRequest, MergeResult,
+    MergeSaveRequest, ParseError, ReverseEngineerRequest, ReverseEngineerResponse, Revision,
+    RevisionInfo, RevisionList, RevisionRequest, RevokeApiKeyRequest, SearchRequest,
+    SearchResponse, SessionRequest, SessionState, SkippedSource, SortField, SortOrder,
+    SourceLanguage, UnlockRequest, User, ValidationReport,
+};
+
+// ファイル一覧の1ページの件数
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+// 検索結果のファイル数
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
 
-#[derive(Debug, Default, Clone)]
+// 受け付けるメッセージの大きさ（リバースエンジニアリングのアーカイブを含む）
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
 pub struct DiagramServiceImpl {
-    // ファイルをメモリ内に保存するためのストレージ
-    files: Arc<Mutex<HashMap<String, File>>>,
-    // 永続化ディレクトリのパス
-    persistence_dir: String,
+    // ダイアグラムの保存先
+    store: Arc<dyn DiagramStore>,
+    // 保存されたファイルの検索インデックス
+    search: Arc<SearchIndex>,
+    // 保存時の検証
+    validation_mode: ValidationMode,
+    // ファイルの変更の購読者（WatchClassDiagram）
+    watchers: Arc<WatchHub>,
+    // オフラインのクライアントと同期するCRDT
+    crdt: Arc<CrdtDiagrams>,
+    // 共同編集のセッションとクラスのロック
+    presence: Arc<Presence>,
+    // 保存時のクラスのロックの確認
+    lock_mode: LockMode,
+    // 利用者の認証とAPIキーの管理
+    auth: Arc<Authenticator>,
 }
 
 impl DiagramServiceImpl {
-    pub fn new() -> Self {
+    pub fn new(
+        store: Arc<dyn DiagramStore>,
+        auth: Arc<Authenticator>,
+        config: &ServerConfig,
+    ) -> Self {
         Self {
-            files: Arc::new(Mutex::new(HashMap::new())),
-            persistence_dir: "data".to_string(),
+            store,
+            search: Arc::new(SearchIndex::new()),
+            validation_mode: config.validation_mode,
+            watchers: Arc::new(WatchHub::new()),
+            crdt: Arc::new(CrdtDiagrams::new()),
+            presence: Arc::new(Presence::new()),
+            lock_mode: config.lock_mode,
+            auth,
         }
     }
 
-    // インメモリ情報をディスクにダンプ
-    pub async fn save_to_disk(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let files = {
-            let files_guard = self.files.lock().map_err(|_| "Failed to acquire lock")?;
-            files_guard.clone() // Mutexからデータをクローンしてロックを解放
-        };
+    // ストアの全ファイルから検索インデックスを作り直し、登録したファイル数を返す
+    pub fn rebuild_search_index(&self) -> store::StoreResult<usize> {
+        let mut count = 0;
+        for summary in self.store.list()? {
+            if let Some(stored) = self.store.get_current(&summary.file_id)? {
+                self.search
+                    .index(&summary.file_id, stored.revision, &stored.file);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
 
-        // ディレクトリが存在しない場合は作成
-        tokio::fs::create_dir_all(&self.persistence_dir).await?;
-        let file_path = format!("{}/snapshot.bin", self.persistence_dir);
-        
-        // HashMapをバイナリとしてシリアライズ
-        let mut buffer = Vec::new();
-        
-        // ファイル数を最初に書き込み
-        let file_count = files.len() as u32;
-        buffer.extend_from_slice(&file_count.to_be_bytes());
-        
-        // 各ファイルをエンコード
-        for (file_id, file) in files.iter() {
-            // ファイルIDの長さとファイルIDを書き込み
-            let file_id_bytes = file_id.as_bytes();
-            let file_id_len = file_id_bytes.len() as u32;
-            buffer.extend_from_slice(&file_id_len.to_be_bytes());
-            buffer.extend_from_slice(file_id_bytes);
-            
-            // ファイルデータをエンコード
-            let mut file_buffer = Vec::new();
-            file.encode(&mut file_buffer)?;
-            
-            // ファイルデータの長さとファイルデータを書き込み
-            let file_data_len = file_buffer.len() as u32;
-            buffer.extend_from_slice(&file_data_len.to_be_bytes());
-            buffer.extend_from_slice(&file_buffer);
-        }
-        
-        tokio::fs::write(file_path, buffer).await?;
-
-        println!("Saved {} files snapshot to disk", files.len());
-        Ok(())
+    // 設定に応じて保存前にファイルを検証する
+    // 拒否する場合はErr、警告として返す問題がある場合はSomeを返す
+    fn validate_for_save(&self, file: &File) -> Result<Option<ValidationReport>, ValidationReport> {
+        if self.validation_mode == ValidationMode::Off {
+            return Ok(None);
+        }
+
+        let report = crate::validation::validate(file);
+        if report.violations.is_empty() {
+            return Ok(None);
+        }
+
+        match self.validation_mode {
+            ValidationMode::Reject => Err(report),
+            _ => {
+                eprintln!(
+                    "Saving file with {} validation warnings",
+                    report.violations.len()
+                );
+                Ok(Some(report))
+            }
+        }
     }
 
-    // ファイルをディスクにエクスポート
-    pub async fn export_files(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let files = self.files.lock().map_err(|_| "Failed to acquire lock")?;
-        let date = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
+    // ファイルを新しい版として保存し、検索インデックスの更新と購読者への通知を行う
+    fn put_file(
+        &self,
+        file_id: &str,
+        file: File,
+        expected_revision: Option<u64>,
+    ) -> store::StoreResult<u64> {
+        let indexed = file.clone();
+        let revision = self.store.put(file_id, file, expected_revision)?;
+        self.search.index(file_id, revision, &indexed);
+        self.watchers.saved(file_id, revision, &indexed);
+        Ok(revision)
+    }
 
-        // エクスポートディレクトリを作成
-        tokio::fs::create_dir_all(format!("{}/exported/{}", self.persistence_dir, date)).await?;
-        for (file_id, file) in files.iter() {
-            let file_path = format!("{}/exported/{}/{}.bin", self.persistence_dir, date, file_id);
+    // ストアへの書き込み（SQLite・WALの同期）はランタイムのスレッドをブロックしないように別スレッドで行う
+    async fn save_file(
+        &self,
+        file_id: &str,
+        file: File,
+        expected_revision: Option<u64>,
+    ) -> Result<u64, Status> {
+        let service = self.clone();
+        let id = file_id.to_string();
+        let revision =
+            tokio::task::spawn_blocking(move || service.put_file(&id, file, expected_revision))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to save file: {}", e)))??;
+        Ok(revision)
+    }
+
+    // ファイルを削除する（保存と同様に別スレッドで行う）
+    async fn delete_file(
+        &self,
+        file_id: &str,
+        expected_revision: Option<u64>,
+    ) -> Result<bool, Status> {
+        let store = Arc::clone(&self.store);
+        let id = file_id.to_string();
+        let removed = tokio::task::spawn_blocking(move || store.delete(&id, expected_revision))
+            .await
+            .map_err(|e| Status::internal(format!("Failed to delete file: {}", e)))??;
+        Ok(removed)
+    }
 
-            // FileメッセージをProtobufバイナリにシリアライズ
-            let mut buffer = Vec::new();
-            file.encode(&mut buffer)?;
+    // ファイルの指定した版（0の場合は現在の版）を取得
+    fn load_revision(
+        &self,
+        file_id: &str,
+        revision: u64,
+    ) -> store::StoreResult<Option<StoredRevision>> {
+        if revision == 0 {
+            self.store.get_current(file_id)
+        } else {
+            self.store.get_revision(file_id, revision)
+        }
+    }
 
-            tokio::fs::write(&file_path, buffer).await?;
+    // 保存で変更されるクラスのうち、他のセッションがロックしているものを確認する
+    // 拒否する場合はErr、警告として返すロックがある場合はSomeを返す
+    fn check_locks(
+        &self,
+        file_id: &str,
+        session_id: Option<&str>,
+        current: &File,
+        file: &File,
+    ) -> Result<Option<LockConflicts>, LockConflicts> {
+        let changed: Vec<String> = crate::diff::diff(current, file)
+            .classes
+            .into_iter()
+            .map(|class| class.class_id)
+            .collect();
+        if changed.is_empty() {
+            return Ok(None);
         }
 
-        Ok(())
+        let locks = self
+            .presence
+            .locked_by_others(file_id, session_id, &changed);
+        if locks.is_empty() {
+            return Ok(None);
+        }
+        match self.lock_mode {
+            LockMode::Reject => Err(LockConflicts { locks }),
+            LockMode::Warn => {
+                eprintln!(
+                    "Saving {} with {} classes locked by other sessions",
+                    file_id,
+                    locks.len()
+                );
+                Ok(Some(LockConflicts { locks }))
+            }
+        }
+    }
+
+    // リクエストメタデータのsession-idのうち、呼び出した利用者のセッション
+    // 他の利用者のセッションを指定した場合はセッション無しとして扱う
+    fn caller_session<T>(&self, request: &Request<T>) -> Option<String> {
+        let owner = principal_name(request);
+        session_id(request).filter(|session_id| self.presence.owns(session_id, owner.as_deref()))
     }
 
-    // ディスクからファイルを読み込み
-    pub async fn load_from_disk(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let persistence_dir = std::path::Path::new(&self.persistence_dir);
-
-        if !persistence_dir.exists() {
-            println!("Persistence directory does not exist, starting with empty storage");
-            return Ok(());
-        }
-
-        let snapshot_path = format!("{}/snapshot.bin", self.persistence_dir);
-        let snapshot_file = std::path::Path::new(&snapshot_path);
-
-        if !snapshot_file.exists() {
-            println!("Snapshot file does not exist, starting with empty storage");
-            return Ok(());
-        }
-
-        let file_content = fs::read(snapshot_file).await?;
-        let mut cursor = std::io::Cursor::new(file_content);
-        
-        // ファイル数を読み取り
-        let mut file_count_bytes = [0u8; 4];
-        cursor.read_exact(&mut file_count_bytes)?;
-        let file_count = u32::from_be_bytes(file_count_bytes);
-        
-        let mut files = self.files.lock().map_err(|_| "Failed to acquire lock")?;
-        
-        // 各ファイルをデコード
-        for _ in 0..file_count {
-            // ファイルIDの長さを読み取り
-            let mut file_id_len_bytes = [0u8; 4];
-            cursor.read_exact(&mut file_id_len_bytes)?;
-            let file_id_len = u32::from_be_bytes(file_id_len_bytes) as usize;
-            
-            // ファイルIDを読み取り
-            let mut file_id_bytes = vec![0u8; file_id_len];
-            cursor.read_exact(&mut file_id_bytes)?;
-            let file_id = String::from_utf8(file_id_bytes)?;
-            
-            // ファイルデータの長さを読み取り
-            let mut file_data_len_bytes = [0u8; 4];
-            cursor.read_exact(&mut file_data_len_bytes)?;
-            let file_data_len = u32::from_be_bytes(file_data_len_bytes) as usize;
-            
-            // ファイルデータを読み取り
-            let mut file_data_bytes = vec![0u8; file_data_len];
-            cursor.read_exact(&mut file_data_bytes)?;
-            
-            // ファイルデータをデコード
-            let file = File::decode(&file_data_bytes[..])?;
-            files.insert(file_id, file);
-        }
-
-        println!("Loaded {} files from disk", files.len());
+    // ファイルのCRDTを保存されている現在の版に合わせる
+    // 通常の保存で変更された内容はサーバのレプリカの操作として取り込む
+    fn current_crdt(
+        &self,
+        cached: &CrdtDiagram,
+        file_id: &str,
+    ) -> store::StoreResult<(CrdtDiagram, Option<StoredRevision>)> {
+        let Some(current) = self.store.get_current(file_id)? else {
+            // 削除されたファイルのCRDTは使わない
+            return Ok((CrdtDiagram::default(), None));
+        };
+        let mut diagram = cached.clone();
+        if diagram.base.as_ref() != Some(&current.file) {
+            diagram.update_from(&current.file);
+            diagram.base = Some(current.file.clone());
+        }
+        diagram.revision = current.revision;
+        Ok((diagram, Some(current)))
+    }
+
+    // ストアにバッファされている情報をディスクにダンプ
+    pub async fn save_to_disk(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || store.flush()).await??;
         Ok(())
     }
 
     // 定期的な保存タスクを開始
     pub fn start_periodic_save(&self, interval_minutes: u64) {
-        let files = Arc::clone(&self.files);
-        let persistence_dir = self.persistence_dir.clone();
+        let service = self.clone();
 
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(interval_minutes * 60));
@@ -165,12 +278,6 @@ impl DiagramServiceImpl {
             loop {
                 interval.tick().await;
 
-                // DiagramServiceImplのインスタンスを作成してsave_to_diskを呼び出し
-                let service = DiagramServiceImpl {
-                    files: Arc::clone(&files),
-                    persistence_dir: persistence_dir.clone(),
-                };
-
                 if let Err(e) = service.save_to_disk().await {
                     eprintln!("Failed to save files to disk: {}", e);
                 } else {
@@ -187,24 +294,43 @@ impl DiagramService for DiagramServiceImpl {
         &self,
         request: Request<File>,
     ) -> Result<Response<ProtoResult>, Status> {
+        let expected_revision = expected_revision(&request).map_err(Status::invalid_argument)?;
+        let session_id = self.caller_session(&request);
         let file = request.into_inner();
 
         // ファイルIDが存在するかチェック
-        if let Some(file_id) = &file.file_id {
-            let mut files = self
-                .files
-                .lock()
-                .map_err(|_| Status::internal("Failed to acquire lock"))?;
+        if let Some(file_id) = file.file_id.clone() {
+            let warnings = self.validate_for_save(&file).map_err(validation_error)?;
+            let (locked, checked_revision) = match self.store.get_current(&file_id.id)? {
+                Some(current) => (
+                    self.check_locks(&file_id.id, session_id.as_deref(), &current.file, &file)
+                        .map_err(lock_error)?,
+                    Some(current.revision),
+                ),
+                None => (None, None),
+            };
 
-            // ファイルを保存
-            files.insert(file_id.id.clone(), file);
+            // ファイルを保存（If-Matchが指定されている場合は版が一致する時のみ）
+            // If-Matchが無い場合も、ロックを確認した版の後に他の保存があった場合は失敗させる
+            let revision = self
+                .save_file(&file_id.id, file, expected_revision.or(checked_revision))
+                .await?;
 
             let result = ProtoResult {
                 value: true,
                 message: Some("Class diagram saved successfully".to_string()),
             };
 
-            Ok(Response::new(result))
+            let mut response = Response::new(result);
+            insert_etag(response.metadata_mut(), revision);
+            if let Some(warnings) = warnings {
+                response.metadata_mut().insert_bin(
+                    "validation-report-bin",
+                    MetadataValue::from_bytes(&warnings.encode_to_vec()),
+                );
+            }
+            insert_lock_conflicts(response.metadata_mut(), locked);
+            Ok(response)
         } else {
             let result = ProtoResult {
                 value: false,
@@ -217,13 +343,10 @@ impl DiagramService for DiagramServiceImpl {
     async fn get_class_diagram(&self, request: Request<FileId>) -> Result<Response<File>, Status> {
         let file_id = request.into_inner();
 
-        let files = self
-            .files
-            .lock()
-            .map_err(|_| Status::internal("Failed to acquire lock"))?;
-
-        if let Some(file) = files.get(&file_id.id) {
-            Ok(Response::new(file.clone()))
+        if let Some(stored) = self.store.get_current(&file_id.id)? {
+            let mut response = Response::new(stored.file);
+            insert_etag(response.metadata_mut(), stored.revision);
+            Ok(response)
         } else {
             Err(Status::not_found("File not found"))
         }
@@ -235,12 +358,7 @@ impl DiagramService for DiagramServiceImpl {
     ) -> Result<Response<ProtoResult>, Status> {
         let file_id = request.into_inner();
 
-        let files = self
-            .files
-            .lock()
-            .map_err(|_| Status::internal("Failed to acquire lock"))?;
-
-        let exists = files.contains_key(&file_id.id);
+        let exists = self.store.exists(&file_id.id)?;
 
         let result = ProtoResult {
             value: exists,
@@ -258,14 +376,35 @@ impl DiagramService for DiagramServiceImpl {
         &self,
         request: Request<FileId>,
     ) -> Result<Response<ProtoResult>, Status> {
+        let expected_revision = expected_revision(&request).map_err(Status::invalid_argument)?;
+        let session_id = self.caller_session(&request);
         let file_id = request.into_inner();
 
-        let mut files = self
-            .files
-            .lock()
-            .map_err(|_| Status::internal("Failed to acquire lock"))?;
+        // 削除は全てのクラスの変更として他のセッションのロックを確認する
+        let (locked, checked_revision) = match self.store.get_current(&file_id.id)? {
+            Some(current) => (
+                self.check_locks(
+                    &file_id.id,
+                    session_id.as_deref(),
+                    &current.file,
+                    &File::default(),
+                )
+                .map_err(lock_error)?,
+                Some(current.revision),
+            ),
+            None => (None, None),
+        };
 
-        let removed = files.remove(&file_id.id).is_some();
+        // If-Matchが指定されている場合は版が一致する時のみ削除する
+        // If-Matchが無い場合も、ロックを確認した版の後に他の保存があった場合は失敗させる
+        let removed = self
+            .delete_file(&file_id.id, expected_revision.or(checked_revision))
+            .await?;
+        if removed {
+            self.crdt.remove(&file_id.id);
+            self.search.remove(&file_id.id);
+            self.watchers.deleted(&file_id.id);
+        }
 
         let result = ProtoResult {
             value: removed,
@@ -276,17 +415,1047 @@ impl DiagramService for DiagramServiceImpl {
             },
         };
 
-        Ok(Response::new(result))
+        let mut response = Response::new(result);
+        insert_lock_conflicts(response.metadata_mut(), locked);
+        Ok(response)
+    }
+}
+
+// リクエストメタデータのif-matchから期待する版番号を取得
+fn expected_revision<T>(request: &Request<T>) -> Result<Option<u64>, String> {
+    let Some(value) = request.metadata().get("if-match") else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| "Invalid if-match metadata".to_string())?;
+
+    parse_etag(value)
+        .map(Some)
+        .ok_or_else(|| format!("Invalid if-match metadata: {}", value))
+}
+
+// リクエストメタデータのsession-idから保存するセッションを取得
+fn session_id<T>(request: &Request<T>) -> Option<String> {
+    request
+        .metadata()
+        .get("session-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+// インターセプタが認証した利用者の名前（資格情報の無い呼び出しではNone）
+fn principal_name<T>(request: &Request<T>) -> Option<String> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .map(|principal| principal.user_name.clone())
+}
+
+// 他のセッションがロックしているクラスを変更する保存を拒否した場合のエラー
+// （detailsにLockConflictsを入れる）
+fn lock_error(conflicts: LockConflicts) -> Status {
+    let holders: Vec<String> = conflicts
+        .locks
+        .iter()
+        .map(|lock| format!("{} ({})", lock.class_id, lock.user_name))
+        .collect();
+    Status::with_details(
+        tonic::Code::FailedPrecondition,
+        format!(
+            "Classes are locked by other sessions: {}",
+            holders.join(", ")
+        ),
+        conflicts.encode_to_vec().into(),
+    )
+}
+
+// 警告として保存した場合、ロックしているセッションをメタデータで返す
+fn insert_lock_conflicts(metadata: &mut MetadataMap, conflicts: Option<LockConflicts>) {
+    if let Some(conflicts) = conflicts {
+        metadata.insert_bin(
+            "lock-conflicts-bin",
+            MetadataValue::from_bytes(&conflicts.encode_to_vec()),
+        );
     }
 }
 
-pub async fn start_server(addr: SocketAddr) -> Result<Arc<DiagramServiceImpl>, String> {
-    let diagram_service = Arc::new(DiagramServiceImpl::new());
+// 検証で拒否した場合のエラー（detailsにValidationReportを入れる）
+fn validation_error(report: ValidationReport) -> Status {
+    Status::with_details(
+        tonic::Code::InvalidArgument,
+        format!(
+            "Class diagram has {} validation errors",
+            report.violations.len()
+        ),
+        report.encode_to_vec().into(),
+    )
+}
 
-    // 起動時にディスクからファイルを読み込み
-    if let Err(e) = diagram_service.load_from_disk().await {
-        return Err(format!("Failed to load files from disk: {}", e));
+// インポートで解釈できなかった場合のエラー（detailsにParseErrorを入れる）
+fn parse_error(error: ImportError) -> Status {
+    let details = ParseError {
+        line: error.line as u32,
+        column: error.column as u32,
+        message: error.message.clone(),
+    };
+    Status::with_details(
+        tonic::Code::InvalidArgument,
+        format!("Failed to parse diagram: {}", error),
+        details.encode_to_vec().into(),
+    )
+}
+
+// 版の情報をprotoのメッセージに変換
+fn revision_info(stored: &StoredRevision, is_current: bool) -> RevisionInfo {
+    RevisionInfo {
+        revision: stored.revision,
+        saved_at: stored.saved_at,
+        name: stored.file.name.clone(),
+        class_count: stored.file.classes.len() as u32,
+        is_current,
     }
+}
+
+// 一覧の並び替え（同じ値の場合はファイルIDで順序を固定し、ページ間で重複・欠落しないようにする）
+fn sort_summaries(summaries: &mut [StoredSummary], order_by: SortField, order: SortOrder) {
+    let descending = match order {
+        SortOrder::Ascending => false,
+        SortOrder::Descending => true,
+        SortOrder::Unspecified => order_by == SortField::LastModified,
+    };
+
+    summaries.sort_by(|a, b| {
+        let ordering = match order_by {
+            SortField::LastModified => a.last_modified.cmp(&b.last_modified),
+            SortField::Name => a.name.cmp(&b.name),
+        };
+        let ordering = ordering.then_with(|| a.file_id.cmp(&b.file_id));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+// ページトークンは次のページの先頭位置
+fn parse_page_token(token: &str) -> Result<usize, String> {
+    if token.is_empty() {
+        return Ok(0);
+    }
+    token
+        .parse()
+        .map_err(|_| format!("Invalid page token: {}", token))
+}
+
+fn summary_to_proto(summary: StoredSummary) -> DiagramSummary {
+    DiagramSummary {
+        file_id: Some(FileId {
+            id: summary.file_id,
+        }),
+        name: summary.name,
+        created_at: summary.created_at,
+        last_modified: summary.last_modified,
+        class_count: summary.class_count,
+        revision: summary.revision,
+    }
+}
+
+#[tonic::async_trait]
+impl DiagramExtService for DiagramServiceImpl {
+    type WatchClassDiagramStream = ReceiverStream<Result<DiagramEvent, Status>>;
+
+    async fn list_class_diagrams(
+        &self,
+        request: Request<ListClassDiagramsRequest>,
+    ) -> Result<Response<ListClassDiagramsResponse>, Status> {
+        let request = request.into_inner();
+        let order_by = SortField::try_from(request.order_by)
+            .map_err(|_| Status::invalid_argument("Unknown sort field"))?;
+        let order = SortOrder::try_from(request.order)
+            .map_err(|_| Status::invalid_argument("Unknown sort order"))?;
+        let offset = parse_page_token(&request.page_token).map_err(Status::invalid_argument)?;
+        let page_size = match request.page_size as usize {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+
+        let mut summaries = self.store.list()?;
+        sort_summaries(&mut summaries, order_by, order);
+
+        let total_count = summaries.len();
+        let end = offset.saturating_add(page_size).min(total_count);
+        let next_page_token = if end < total_count {
+            end.to_string()
+        } else {
+            String::new()
+        };
+
+        let diagrams = summaries
+            .into_iter()
+            .skip(offset)
+            .take(page_size)
+            .map(summary_to_proto)
+            .collect();
+
+        Ok(Response::new(ListClassDiagramsResponse {
+            diagrams,
+            next_page_token,
+            total_count: total_count as u32,
+        }))
+    }
+
+    async fn export_class_diagram(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<ExportedDiagram>, Status> {
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+        let format = ExportFormat::try_from(request.format)
+            .map_err(|_| Status::invalid_argument("Unknown export format"))?;
+
+        let stored = self
+            .store
+            .get_current(&file_id.id)?
+            .ok_or_else(|| Status::not_found("File not found"))?;
+
+        let mut response = Response::new(crate::export::export(&stored.file, format));
+        insert_etag(response.metadata_mut(), stored.revision);
+        Ok(response)
+    }
+
+    async fn import_class_diagram(
+        &self,
+        request: Request<ImportRequest>,
+    ) -> Result<Response<File>, Status> {
+        let (metadata, extensions, request) = request.into_parts();
+        let format = ImportFormat::try_from(request.format)
+            .map_err(|_| Status::invalid_argument("Unknown import format"))?;
+
+        let mut file = crate::import::import(&request.source, format).map_err(parse_error)?;
+
+        let file_id = request
+            .file_id
+            .filter(|file_id| !file_id.id.is_empty())
+            .unwrap_or_else(|| FileId {
+                id: crate::import::new_id(),
+            });
+        if !request.name.is_empty() {
+            file.name = request.name;
+        } else if file.name.is_empty() {
+            file.name = "Imported diagram".to_string();
+        }
+        let now = chrono::Utc::now().timestamp() as i32;
+        file.file_id = Some(file_id);
+        file.created_at = now;
+        file.last_modified = now;
+
+        // 通常の保存と同じ処理（検証・If-Match・検索インデックス）を通す
+        let saved = self
+            .save_class_diagram(Request::from_parts(metadata, extensions, file.clone()))
+            .await?;
+        let (metadata, result, extensions) = saved.into_parts();
+        if !result.value {
+            return Err(Status::internal(result.message.unwrap_or_default()));
+        }
+
+        Ok(Response::from_parts(metadata, file, extensions))
+    }
+
+    async fn validate_class_diagram(
+        &self,
+        request: Request<File>,
+    ) -> Result<Response<ValidationReport>, Status> {
+        let file = request.into_inner();
+        Ok(Response::new(crate::validation::validate(&file)))
+    }
+
+    async fn search_diagrams(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        let request = request.into_inner();
+        let limit = match request.limit as usize {
+            0 => DEFAULT_SEARCH_LIMIT,
+            limit => limit.min(MAX_SEARCH_LIMIT),
+        };
+
+        let hits = self
+            .search
+            .search(&request.query, limit)
+            .map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(SearchResponse { hits }))
+    }
+
+    async fn list_revisions(
+        &self,
+        request: Request<FileId>,
+    ) -> Result<Response<RevisionList>, Status> {
+        let file_id = request.into_inner();
+
+        let revisions = self.store.revisions(&file_id.id)?;
+        if revisions.is_empty() {
+            return Err(Status::not_found("File not found"));
+        }
+
+        // 先頭が現在の版
+        let revisions = revisions
+            .iter()
+            .enumerate()
+            .map(|(index, stored)| revision_info(stored, index == 0))
+            .collect();
+
+        Ok(Response::new(RevisionList { revisions }))
+    }
+
+    async fn get_revision(
+        &self,
+        request: Request<RevisionRequest>,
+    ) -> Result<Response<Revision>, Status> {
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let stored = self
+            .store
+            .get_revision(&file_id.id, request.revision)?
+            .ok_or_else(|| Status::not_found("Revision not found"))?;
+        let current = self.store.current_revision(&file_id.id)?;
+
+        Ok(Response::new(Revision {
+            info: Some(revision_info(&stored, current == Some(stored.revision))),
+            file: Some(stored.file),
+        }))
+    }
+
+    async fn restore_revision(
+        &self,
+        request: Request<RevisionRequest>,
+    ) -> Result<Response<ProtoResult>, Status> {
+        let expected_revision = expected_revision(&request).map_err(Status::invalid_argument)?;
+        let session_id = self.caller_session(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let stored = self
+            .store
+            .get_revision(&file_id.id, request.revision)?
+            .ok_or_else(|| Status::not_found("Revision not found"))?;
+        let (locked, checked_revision) = match self.store.get_current(&file_id.id)? {
+            Some(current) => (
+                self.check_locks(
+                    &file_id.id,
+                    session_id.as_deref(),
+                    &current.file,
+                    &stored.file,
+                )
+                .map_err(lock_error)?,
+                Some(current.revision),
+            ),
+            None => (None, None),
+        };
+
+        // 復元も新しい版として保存するため、復元前の内容も履歴に残る
+        // If-Matchが無い場合も、ロックを確認した版の後に他の保存があった場合は失敗させる
+        let revision = self
+            .save_file(
+                &file_id.id,
+                stored.file,
+                expected_revision.or(checked_revision),
+            )
+            .await?;
+
+        let result = ProtoResult {
+            value: true,
+            message: Some(format!(
+                "Revision {} restored as revision {}",
+                request.revision, revision
+            )),
+        };
+
+        let mut response = Response::new(result);
+        insert_etag(response.metadata_mut(), revision);
+        insert_lock_conflicts(response.metadata_mut(), locked);
+        Ok(response)
+    }
+
+    async fn generate_code(
+        &self,
+        request: Request<GenerateCodeRequest>,
+    ) -> Result<Response<GeneratedCode>, Status> {
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+        let language = CodeLanguage::try_from(request.language)
+            .map_err(|_| Status::invalid_argument("Unknown code language"))?;
+
+        let stored = self
+            .store
+            .get_current(&file_id.id)?
+            .ok_or_else(|| Status::not_found("File not found"))?;
+
+        let files = crate::codegen::generate(&stored.file, language)
+            .into_iter()
+            .map(|source| GeneratedFile {
+                path: source.path,
+                content: source.content,
+            })
+            .collect();
+        let code = GeneratedCode {
+            files,
+            archive_name: crate::codegen::archive_name(&stored.file, language),
+        };
+
+        let mut response = Response::new(code);
+        insert_etag(response.metadata_mut(), stored.revision);
+        Ok(response)
+    }
+
+    async fn reverse_engineer(
+        &self,
+        request: Request<ReverseEngineerRequest>,
+    ) -> Result<Response<ReverseEngineerResponse>, Status> {
+        let (metadata, extensions, request) = request.into_parts();
+        let language = SourceLanguage::try_from(request.language)
+            .map_err(|_| Status::invalid_argument("Unknown source language"))?;
+
+        let reversed = crate::reverse::reverse_engineer(&request.archive, language)
+            .map_err(Status::invalid_argument)?;
+        let mut file = reversed.file;
+        println!(
+            "Reverse engineered {} classes ({} files skipped)",
+            file.classes.len(),
+            reversed.skipped.len()
+        );
+
+        let file_id = request
+            .file_id
+            .filter(|file_id| !file_id.id.is_empty())
+            .unwrap_or_else(|| FileId {
+                id: crate::import::new_id(),
+            });
+        if !request.name.is_empty() {
+            file.name = request.name;
+        }
+        let now = chrono::Utc::now().timestamp() as i32;
+        file.file_id = Some(file_id);
+        file.created_at = now;
+        file.last_modified = now;
+
+        // 通常の保存と同じ処理（検証・If-Match・検索インデックス）を通す
+        let saved = self
+            .save_class_diagram(Request::from_parts(metadata, extensions, file.clone()))
+            .await?;
+        let (metadata, result, extensions) = saved.into_parts();
+        if !result.value {
+            return Err(Status::internal(result.message.unwrap_or_default()));
+        }
+
+        let skipped = reversed
+            .skipped
+            .into_iter()
+            .map(|skipped| SkippedSource {
+                path: skipped.path,
+                line: skipped.line as u32,
+                message: skipped.message,
+            })
+            .collect();
+        let response = ReverseEngineerResponse {
+            file: Some(file),
+            skipped,
+        };
+        Ok(Response::from_parts(metadata, response, extensions))
+    }
+
+    async fn diff_class_diagrams(
+        &self,
+        request: Request<DiffRequest>,
+    ) -> Result<Response<DiagramDiff>, Status> {
+        let request = request.into_inner();
+        let mut loaded = Vec::new();
+        for (side, reference) in [("Base", request.base), ("Target", request.target)] {
+            let reference = reference
+                .ok_or_else(|| Status::invalid_argument(format!("{} is required", side)))?;
+            let file_id = reference
+                .file_id
+                .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+            let stored = self
+                .load_revision(&file_id.id, reference.revision)?
+                .ok_or_else(|| {
+                    Status::not_found(format!(
+                        "{} not found: {} (revision {})",
+                        side, file_id.id, reference.revision
+                    ))
+                })?;
+            loaded.push(stored);
+        }
+        let (base, target) = (&loaded[0], &loaded[1]);
+
+        let mut diff = crate::diff::diff(&base.file, &target.file);
+        diff.base_revision = base.revision;
+        diff.target_revision = target.revision;
+        Ok(Response::new(diff))
+    }
+
+    async fn merge_class_diagrams(
+        &self,
+        request: Request<MergeRequest>,
+    ) -> Result<Response<MergeResult>, Status> {
+        let request = request.into_inner();
+        let (Some(base), Some(ours), Some(theirs)) = (request.base, request.ours, request.theirs)
+        else {
+            return Err(Status::invalid_argument(
+                "Base, ours and theirs are required",
+            ));
+        };
+
+        Ok(Response::new(crate::merge::merge(&base, &ours, &theirs)))
+    }
+
+    async fn save_merged_class_diagram(
+        &self,
+        request: Request<MergeSaveRequest>,
+    ) -> Result<Response<MergeResult>, Status> {
+        let session_id = self.caller_session(&request);
+        let request = request.into_inner();
+        let file = request
+            .file
+            .ok_or_else(|| Status::invalid_argument("File is required"))?;
+        let file_id = file
+            .file_id
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let base = self
+            .store
+            .get_revision(&file_id.id, request.base_revision)?
+            .ok_or_else(|| Status::not_found("Revision not found"))?;
+        let current = self
+            .store
+            .get_current(&file_id.id)?
+            .ok_or_else(|| Status::not_found("File not found"))?;
+
+        // クライアントの変更をours、編集中に保存された変更をtheirsとしてマージする
+        let mut merged = crate::merge::merge(&base.file, &file, &current.file);
+        if !merged.conflicts.is_empty() {
+            // 衝突がある場合は保存せず、クライアントが解決した後で改めて保存する
+            println!(
+                "Merge of {} has {} conflicts (base revision {}, current revision {})",
+                file_id.id,
+                merged.conflicts.len(),
+                base.revision,
+                current.revision
+            );
+            let mut response = Response::new(merged);
+            insert_etag(response.metadata_mut(), current.revision);
+            return Ok(response);
+        }
+
+        let mut file = merged.file.take().unwrap_or_default();
+        file.file_id = Some(file_id.clone());
+        file.created_at = current.file.created_at;
+        let warnings = self.validate_for_save(&file).map_err(validation_error)?;
+        let locked = self
+            .check_locks(&file_id.id, session_id.as_deref(), &current.file, &file)
+            .map_err(lock_error)?;
+
+        // マージの間に他の保存があった場合は失敗させる
+        let revision = self
+            .save_file(&file_id.id, file.clone(), Some(current.revision))
+            .await?;
+        println!(
+            "Merged {} (base revision {}, current revision {}) as revision {}",
+            file_id.id, base.revision, current.revision, revision
+        );
+        merged.file = Some(file);
+        merged.revision = revision;
+
+        let mut response = Response::new(merged);
+        insert_etag(response.metadata_mut(), revision);
+        if let Some(warnings) = warnings {
+            response.metadata_mut().insert_bin(
+                "validation-report-bin",
+                MetadataValue::from_bytes(&warnings.encode_to_vec()),
+            );
+        }
+        insert_lock_conflicts(response.metadata_mut(), locked);
+        Ok(response)
+    }
+
+    async fn watch_class_diagram(
+        &self,
+        request: Request<FileId>,
+    ) -> Result<Response<Self::WatchClassDiagramStream>, Status> {
+        let file_id = request.into_inner().id;
+        if file_id.is_empty() {
+            return Err(Status::invalid_argument("File ID is required"));
+        }
+
+        // 購読してから現在の内容を取得し、その間に保存された版を取りこぼさないようにする
+        let mut receiver = self.watchers.subscribe(&file_id);
+        let current = self
+            .store
+            .get_current(&file_id)?
+            .ok_or_else(|| Status::not_found("File not found"))?;
+        println!("Watching file_id: {}", file_id);
+
+        let (sender, stream) = mpsc::channel(16);
+        let store = Arc::clone(&self.store);
+        tokio::spawn(async move {
+            let mut revision = current.revision;
+            let snapshot = watch::event(
+                DiagramEventKind::Snapshot,
+                &file_id,
+                current.revision,
+                Some(current.file),
+            );
+            if sender.send(Ok(snapshot)).await.is_err() {
+                return;
+            }
+
+            loop {
+                let received = tokio::select! {
+                    received = receiver.recv() => received,
+                    // クライアントが切断した
+                    _ = sender.closed() => break,
+                };
+                let event = match received {
+                    Ok(event) => event,
+                    // 通知に追いつけなかった場合は現在の内容を送り直す
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Watcher of {} skipped {} events", file_id, skipped);
+                        match store.get_current(&file_id) {
+                            Ok(Some(stored)) => watch::event(
+                                DiagramEventKind::Snapshot,
+                                &file_id,
+                                stored.revision,
+                                Some(stored.file),
+                            ),
+                            Ok(None) => watch::event(DiagramEventKind::Deleted, &file_id, 0, None),
+                            Err(e) => {
+                                let _ = sender.send(Err(e.into())).await;
+                                break;
+                            }
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                // 版番号は削除して作り直した後も増え続けるため、既に送った版以下の保存は
+                // 最初に送った内容や送り直した内容に含まれている
+                if event.kind == DiagramEventKind::Saved as i32 && event.revision <= revision {
+                    continue;
+                }
+                revision = event.revision;
+                if sender.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+            println!("Stopped watching file_id: {}", file_id);
+        });
+
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+
+    async fn apply_diagram_operations(
+        &self,
+        request: Request<ApplyOperationsRequest>,
+    ) -> Result<Response<ApplyOperationsResponse>, Status> {
+        let expected_revision = expected_revision(&request).map_err(Status::invalid_argument)?;
+        let session_id = self.caller_session(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let current = self
+            .store
+            .get_current(&file_id.id)?
+            .ok_or_else(|| Status::not_found("File not found"))?;
+
+        // 複製に全ての操作を適用し、全て成功した場合だけ保存する
+        let mut file = current.file.clone();
+        crate::operations::apply(&mut file, &request.operations)
+            .map_err(Status::invalid_argument)?;
+        file.last_modified = chrono::Utc::now().timestamp() as i32;
+        let warnings = self.validate_for_save(&file).map_err(validation_error)?;
+        let locked = self
+            .check_locks(&file_id.id, session_id.as_deref(), &current.file, &file)
+            .map_err(lock_error)?;
+
+        // If-Matchが無い場合も、読み込んだ版の後に他の保存があった場合は失敗させる
+        let revision = self
+            .save_file(
+                &file_id.id,
+                file.clone(),
+                Some(expected_revision.unwrap_or(current.revision)),
+            )
+            .await?;
+        println!(
+            "Applied {} operations to {} as revision {}",
+            request.operations.len(),
+            file_id.id,
+            revision
+        );
+
+        let mut response = Response::new(ApplyOperationsResponse {
+            revision,
+            file: Some(file),
+        });
+        insert_etag(response.metadata_mut(), revision);
+        if let Some(warnings) = warnings {
+            response.metadata_mut().insert_bin(
+                "validation-report-bin",
+                MetadataValue::from_bytes(&warnings.encode_to_vec()),
+            );
+        }
+        insert_lock_conflicts(response.metadata_mut(), locked);
+        Ok(response)
+    }
+
+    async fn get_crdt_state_vector(
+        &self,
+        request: Request<FileId>,
+    ) -> Result<Response<CrdtStateVector>, Status> {
+        let file_id = request.into_inner();
+
+        let entry = self.crdt.get(&file_id.id);
+        let mut cached = entry.lock().await;
+        let (diagram, current) = self.current_crdt(&cached, &file_id.id)?;
+        let current = current.ok_or_else(|| Status::not_found("File not found"))?;
+        let state_vector = diagram.state_vector();
+        *cached = diagram;
+
+        let mut response = Response::new(state_vector);
+        insert_etag(response.metadata_mut(), current.revision);
+        Ok(response)
+    }
+
+    async fn sync_crdt(
+        &self,
+        request: Request<CrdtSyncRequest>,
+    ) -> Result<Response<CrdtSyncResponse>, Status> {
+        let session_id = self.caller_session(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+        for (index, operation) in request.operations.iter().enumerate() {
+            crate::crdt::validate(operation)
+                .map_err(|e| Status::invalid_argument(format!("Operation {}: {}", index, e)))?;
+        }
+
+        // 複製に操作を反映し、保存できた場合だけ置き換える
+        let entry = self.crdt.get(&file_id.id);
+        let mut cached = entry.lock().await;
+        let (mut diagram, current) = self.current_crdt(&cached, &file_id.id)?;
+        let received = request.operations.len();
+        let applied = request
+            .operations
+            .into_iter()
+            .filter(|operation| diagram.apply(operation.clone()))
+            .count();
+
+        let mut warnings = None;
+        let mut locked = None;
+        if applied > 0 {
+            // まだ保存されていないファイルはCRDTの内容から作成する
+            let now = chrono::Utc::now().timestamp() as i32;
+            let mut file = diagram.to_file();
+            file.file_id = Some(file_id.clone());
+            file.created_at = current
+                .as_ref()
+                .map_or(now, |current| current.file.created_at);
+            file.last_modified = now;
+            warnings = self.validate_for_save(&file).map_err(validation_error)?;
+            if let Some(current) = &current {
+                locked = self
+                    .check_locks(&file_id.id, session_id.as_deref(), &current.file, &file)
+                    .map_err(lock_error)?;
+            }
+
+            // 同期の間に通常の保存があった場合は失敗させる（クライアントは同期をやり直す）
+            let expected_revision = current.as_ref().map(|current| current.revision);
+            diagram.revision = self
+                .save_file(&file_id.id, file.clone(), expected_revision)
+                .await?;
+            diagram.base = Some(file);
+            println!(
+                "Synced {} CRDT operations ({} new) for {} as revision {}",
+                received, applied, file_id.id, diagram.revision
+            );
+        } else if current.is_none() {
+            return Err(Status::not_found("File not found"));
+        }
+
+        let revision = diagram.revision;
+        let mut response = Response::new(CrdtSyncResponse {
+            operations: diagram.delta(&request.state_vector.unwrap_or_default()),
+            state_vector: Some(diagram.state_vector()),
+            revision,
+        });
+        *cached = diagram;
+
+        insert_etag(response.metadata_mut(), revision);
+        if let Some(warnings) = warnings {
+            response.metadata_mut().insert_bin(
+                "validation-report-bin",
+                MetadataValue::from_bytes(&warnings.encode_to_vec()),
+            );
+        }
+        insert_lock_conflicts(response.metadata_mut(), locked);
+        Ok(response)
+    }
+
+    async fn join_session(
+        &self,
+        request: Request<JoinSessionRequest>,
+    ) -> Result<Response<SessionState>, Status> {
+        let owner = principal_name(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+        if !self.store.exists(&file_id.id)? {
+            return Err(Status::not_found("File not found"));
+        }
+
+        // 認証された呼び出しでは利用者名を使う（user_nameは認証しない設定の場合だけ）
+        let user_name = owner.as_deref().unwrap_or(&request.user_name);
+        let state = self
+            .presence
+            .join(
+                &file_id.id,
+                &request.session_id,
+                owner.as_deref(),
+                user_name,
+            )
+            .ok_or_else(|| Status::permission_denied("Session belongs to another user"))?;
+        if request.session_id.is_empty() {
+            println!(
+                "Session {} ({}) joined {}",
+                state.session_id, user_name, file_id.id
+            );
+        }
+        Ok(Response::new(state))
+    }
+
+    async fn leave_session(
+        &self,
+        request: Request<SessionRequest>,
+    ) -> Result<Response<ProtoResult>, Status> {
+        let owner = principal_name(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let left = self
+            .presence
+            .leave(&file_id.id, &request.session_id, owner.as_deref());
+        if left {
+            println!("Session {} left {}", request.session_id, file_id.id);
+        }
+        Ok(Response::new(ProtoResult {
+            value: left,
+            message: Some(if left {
+                "Session closed successfully".to_string()
+            } else {
+                "Session not found".to_string()
+            }),
+        }))
+    }
+
+    async fn get_sessions(
+        &self,
+        request: Request<FileId>,
+    ) -> Result<Response<SessionState>, Status> {
+        let file_id = request.into_inner();
+        Ok(Response::new(self.presence.state(&file_id.id)))
+    }
+
+    async fn lock_classes(
+        &self,
+        request: Request<LockRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        let owner = principal_name(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+        if request.class_ids.is_empty() {
+            return Err(Status::invalid_argument("Class IDs are required"));
+        }
+
+        let response = self
+            .presence
+            .lock(
+                &file_id.id,
+                &request.session_id,
+                owner.as_deref(),
+                &request.class_ids,
+                request.ttl_seconds,
+            )
+            .map_err(Status::not_found)?;
+        Ok(Response::new(response))
+    }
+
+    async fn unlock_classes(
+        &self,
+        request: Request<UnlockRequest>,
+    ) -> Result<Response<ProtoResult>, Status> {
+        let owner = principal_name(&request);
+        let request = request.into_inner();
+        let file_id = request
+            .file_id
+            .ok_or_else(|| Status::invalid_argument("File ID is required"))?;
+
+        let released = self.presence.unlock(
+            &file_id.id,
+            &request.session_id,
+            owner.as_deref(),
+            &request.class_ids,
+        );
+        Ok(Response::new(ProtoResult {
+            value: released > 0,
+            message: Some(format!("{} locks released", released)),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl AuthService for DiagramServiceImpl {
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<AuthToken>, Status> {
+        let request = request.into_inner();
+        let auth = Arc::clone(&self.auth);
+        let user_name = request.user_name.clone();
+        // Argon2の照合はランタイムのスレッドをブロックしないように別スレッドで行う
+        let (token, expires_at, principal) =
+            tokio::task::spawn_blocking(move || auth.login(&request.user_name, &request.password))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to log in: {}", e)))?
+                .inspect_err(|_| eprintln!("Failed login for {}", user_name))?;
+        println!("User {} logged in", principal.user_name);
+
+        Ok(Response::new(AuthToken {
+            token,
+            expires_at,
+            user: Some(proto_user(&principal)),
+        }))
+    }
+
+    async fn authenticate(
+        &self,
+        request: Request<AuthenticateRequest>,
+    ) -> Result<Response<User>, Status> {
+        let principal = self.auth.require(request.metadata())?;
+        Ok(Response::new(proto_user(&principal)))
+    }
+
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<User>, Status> {
+        let principal = self.auth.require(request.metadata())?;
+        let request = request.into_inner();
+        let auth = Arc::clone(&self.auth);
+        let user = tokio::task::spawn_blocking(move || {
+            auth.create_user(
+                &principal,
+                &request.user_name,
+                &request.password,
+                request.admin,
+            )
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Failed to create user: {}", e)))??;
+        Ok(Response::new(proto_user(&user)))
+    }
+
+    async fn create_api_key(
+        &self,
+        request: Request<CreateApiKeyRequest>,
+    ) -> Result<Response<CreatedApiKey>, Status> {
+        let principal = self.auth.require(request.metadata())?;
+        let request = request.into_inner();
+        let (record, key) = self.auth.create_api_key(&principal, &request.name)?;
+        Ok(Response::new(CreatedApiKey {
+            info: Some(proto_api_key(&record)),
+            key,
+        }))
+    }
+
+    async fn list_api_keys(
+        &self,
+        request: Request<ListApiKeysRequest>,
+    ) -> Result<Response<ApiKeyList>, Status> {
+        let principal = self.auth.require(request.metadata())?;
+        let keys = self
+            .auth
+            .api_keys(&principal)?
+            .iter()
+            .map(proto_api_key)
+            .collect();
+        Ok(Response::new(ApiKeyList { keys }))
+    }
+
+    async fn revoke_api_key(
+        &self,
+        request: Request<RevokeApiKeyRequest>,
+    ) -> Result<Response<ProtoResult>, Status> {
+        let principal = self.auth.require(request.metadata())?;
+        let request = request.into_inner();
+        self.auth.revoke_api_key(&principal, &request.key_id)?;
+        Ok(Response::new(ProtoResult {
+            value: true,
+            message: Some("API key revoked successfully".to_string()),
+        }))
+    }
+}
+
+fn proto_user(principal: &Principal) -> User {
+    User {
+        user_name: principal.user_name.clone(),
+        admin: principal.admin,
+        created_at: principal.created_at,
+    }
+}
+
+fn proto_api_key(record: &ApiKeyRecord) -> ApiKey {
+    ApiKey {
+        key_id: record.key_id.clone(),
+        name: record.name.clone(),
+        created_at: record.created_at,
+    }
+}
+
+pub async fn start_server(
+    addr: SocketAddr,
+    config: ServerConfig,
+) -> Result<Arc<DiagramServiceImpl>, String> {
+    // 起動時に設定されたストアを開き、ディスクからファイルを読み込み
+    let store = store::open_store(&config)
+        .map_err(|e| format!("Failed to load files from disk: {}", e))?;
+    let accounts = store::open_account_store(&config)
+        .map_err(|e| format!("Failed to open account store: {}", e))?;
+    let auth = Arc::new(
+        Authenticator::open(accounts, &config)
+            .map_err(|e| format!("Failed to initialize authentication: {}", e))?,
+    );
+    let diagram_service = Arc::new(DiagramServiceImpl::new(store, Arc::clone(&auth), &config));
+    let indexed = diagram_service
+        .rebuild_search_index()
+        .map_err(|e| format!("Failed to build search index: {}", e))?;
+    println!("Indexed {} files for search", indexed);
 
     // n分間隔で定期的にファイルを保存
     diagram_service.start_periodic_save(1);
@@ -308,7 +1477,17 @@ pub async fn start_server(addr: SocketAddr) -> Result<Arc<DiagramServiceImpl>, S
             .accept_http1(true)
             .layer(GrpcWebLayer::new())
             .layer(cors)
-            .add_service(DiagramServiceServer::new((*service_clone).clone()))
+            // ダイアグラムのサービスは全ての呼び出しを認証する（AuthServiceはLogin以外を各RPCで認証する）
+            .add_service(DiagramServiceServer::with_interceptor(
+                (*service_clone).clone(),
+                AuthInterceptor::new(Arc::clone(&auth)),
+            ))
+            .add_service(InterceptedService::new(
+                DiagramExtServiceServer::new((*service_clone).clone())
+                    .max_decoding_message_size(MAX_MESSAGE_SIZE),
+                AuthInterceptor::new(auth),
+            ))
+            .add_service(AuthServiceServer::new((*service_clone).clone()))
             .serve(addr)
             .await
         {
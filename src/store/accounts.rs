@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use super::snapshot::write_private;
+use super::{StoreError, StoreResult};
+
+// 利用者（パスワードはハッシュだけを保存する）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRecord {
+    pub user_name: String,
+    // PHC形式のArgon2ハッシュ
+    pub password_hash: String,
+    pub admin: bool,
+    // 作成日時（UNIX秒）
+    pub created_at: i64,
+}
+
+// APIキー（キーの値はハッシュだけを保存する）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub key_id: String,
+    // キーを作成した利用者（キーはこの利用者として認証される）
+    pub user_name: String,
+    pub name: String,
+    // キーの秘密部分のSHA-256
+    pub secret_hash: String,
+    // 作成日時（UNIX秒）
+    pub created_at: i64,
+}
+
+// 利用者・APIキー・トークンの署名鍵の保存先を抽象化するトレイト
+pub trait AccountStore: Send + Sync + fmt::Debug {
+    fn get_user(&self, user_name: &str) -> StoreResult<Option<UserRecord>>;
+
+    // 利用者を作成し、作成したかどうか（既に存在する場合はfalse）を返す
+    fn create_user(&self, user: UserRecord) -> StoreResult<bool>;
+
+    fn user_count(&self) -> StoreResult<usize>;
+
+    fn get_api_key(&self, key_id: &str) -> StoreResult<Option<ApiKeyRecord>>;
+
+    // 利用者のAPIキーを作成順に取得
+    fn api_keys(&self, user_name: &str) -> StoreResult<Vec<ApiKeyRecord>>;
+
+    fn put_api_key(&self, key: ApiKeyRecord) -> StoreResult<()>;
+
+    // 利用者のAPIキーを削除し、削除されたかどうかを返す
+    fn delete_api_key(&self, user_name: &str, key_id: &str) -> StoreResult<bool>;
+
+    // 保存されているトークンの署名鍵（Base64）
+    fn signing_key(&self) -> StoreResult<Option<String>>;
+
+    fn set_signing_key(&self, key: &str) -> StoreResult<()>;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Accounts {
+    users: Vec<UserRecord>,
+    api_keys: Vec<ApiKeyRecord>,
+    signing_key: Option<String>,
+}
+
+// メモリ上のストアと組み合わせて使う、永続化ディレクトリのJSONファイルに保存するストア
+// 利用者・APIキーの変更は少ないため、変更のたびにファイル全体を書き直す
+#[derive(Debug)]
+pub struct JsonAccountStore {
+    accounts: Mutex<Accounts>,
+    path: PathBuf,
+}
+
+impl JsonAccountStore {
+    pub fn open(persistence_dir: &str) -> StoreResult<Self> {
+        let path = Path::new(persistence_dir).join("accounts.json");
+        let accounts = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)?
+        } else {
+            Accounts::default()
+        };
+        Ok(Self {
+            accounts: Mutex::new(accounts),
+            path,
+        })
+    }
+
+    // 変更を加えてファイルに書き出し、変更したかどうかを返す
+    // changeがfalseを返した場合と書き出せなかった場合は変更しない
+    fn update(&self, change: impl FnOnce(&mut Accounts) -> bool) -> StoreResult<bool> {
+        let mut accounts = self.lock()?;
+        let mut updated = accounts.clone();
+        if !change(&mut updated) {
+            return Ok(false);
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // 署名鍵を含むため所有者だけが読めるようにする
+        write_private(&self.path, &serde_json::to_vec_pretty(&updated)?)?;
+        *accounts = updated;
+        Ok(true)
+    }
+
+    fn lock(&self) -> StoreResult<MutexGuard<'_, Accounts>> {
+        self.accounts.lock().map_err(|_| StoreError::Lock)
+    }
+}
+
+impl AccountStore for JsonAccountStore {
+    fn get_user(&self, user_name: &str) -> StoreResult<Option<UserRecord>> {
+        Ok(self
+            .lock()?
+            .users
+            .iter()
+            .find(|user| user.user_name == user_name)
+            .cloned())
+    }
+
+    fn create_user(&self, user: UserRecord) -> StoreResult<bool> {
+        self.update(|accounts| {
+            if accounts
+                .users
+                .iter()
+                .any(|other| other.user_name == user.user_name)
+            {
+                return false;
+            }
+            accounts.users.push(user);
+            true
+        })
+    }
+
+    fn user_count(&self) -> StoreResult<usize> {
+        Ok(self.lock()?.users.len())
+    }
+
+    fn get_api_key(&self, key_id: &str) -> StoreResult<Option<ApiKeyRecord>> {
+        Ok(self
+            .lock()?
+            .api_keys
+            .iter()
+            .find(|key| key.key_id == key_id)
+            .cloned())
+    }
+
+    fn api_keys(&self, user_name: &str) -> StoreResult<Vec<ApiKeyRecord>> {
+        Ok(self
+            .lock()?
+            .api_keys
+            .iter()
+            .filter(|key| key.user_name == user_name)
+            .cloned()
+            .collect())
+    }
+
+    fn put_api_key(&self, key: ApiKeyRecord) -> StoreResult<()> {
+        self.update(|accounts| {
+            accounts.api_keys.retain(|other| other.key_id != key.key_id);
+            accounts.api_keys.push(key);
+            true
+        })?;
+        Ok(())
+    }
+
+    fn delete_api_key(&self, user_name: &str, key_id: &str) -> StoreResult<bool> {
+        self.update(|accounts| {
+            let before = accounts.api_keys.len();
+            accounts
+                .api_keys
+                .retain(|key| !(key.key_id == key_id && key.user_name == user_name));
+            accounts.api_keys.len() < before
+        })
+    }
+
+    fn signing_key(&self) -> StoreResult<Option<String>> {
+        Ok(self.lock()?.signing_key.clone())
+    }
+
+    fn set_signing_key(&self, key: &str) -> StoreResult<()> {
+        self.update(|accounts| {
+            accounts.signing_key = Some(key.to_string());
+            true
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use super::snapshot::{decode_snapshot, encode_snapshot, write_atomic};
+use super::wal::{WalRecord, WriteAheadLog};
+use super::{
+    check_revision, DiagramStore, DiagramSummary, StoreError, StoreResult, StoredRevision,
+};
+use crate::server::class::File;
+
+// ファイルIDごとの版（古い順、末尾が現在の版）
+type Revisions = HashMap<String, VecDeque<StoredRevision>>;
+
+// 全ファイルをメモリに保持し、スナップショットとWALで永続化するストア
+#[derive(Debug)]
+pub struct MemoryStore {
+    // ファイルをメモリ内に保存するためのストレージ
+    files: Mutex<Revisions>,
+    // 削除したファイルの最後の版番号（同じIDで作り直した時は続きの番号を割り当てる）
+    // filesのロック中にだけロックする
+    deleted: Mutex<HashMap<String, u64>>,
+    // スナップショットファイルのパス
+    snapshot_path: PathBuf,
+    // スナップショット以降の保存・削除操作のログ
+    wal: WriteAheadLog,
+    // ファイルごとに保持する以前の版の数
+    history_limit: usize,
+    // 定期保存と終了時の保存が同じ一時ファイルやWALを同時に書き換えないようにする
+    flush_lock: Mutex<()>,
+}
+
+impl MemoryStore {
+    // スナップショットとWALからファイルを読み込んでストアを開く
+    pub fn open(persistence_dir: &str, history_limit: usize) -> StoreResult<Self> {
+        let store = Self {
+            files: Mutex::new(HashMap::new()),
+            deleted: Mutex::new(HashMap::new()),
+            snapshot_path: Path::new(persistence_dir).join("snapshot.bin"),
+            wal: WriteAheadLog::new(persistence_dir),
+            history_limit,
+            flush_lock: Mutex::new(()),
+        };
+        store.load_from_disk(persistence_dir)?;
+        Ok(store)
+    }
+
+    // ディスクからファイルを読み込み
+    fn load_from_disk(&self, persistence_dir: &str) -> StoreResult<()> {
+        if !Path::new(persistence_dir).exists() {
+            println!("Persistence directory does not exist, starting with empty storage");
+            return Ok(());
+        }
+
+        let mut files = self.lock()?;
+        let mut deleted = self.lock_deleted()?;
+
+        if self.snapshot_path.exists() {
+            let file_content = std::fs::read(&self.snapshot_path)?;
+
+            // 破損している場合は該当レコードを含むエラーを返す
+            let snapshot = decode_snapshot(&file_content)?;
+            for (file_id, revisions) in snapshot.files {
+                files.insert(file_id, revisions.into());
+            }
+            deleted.extend(snapshot.deleted);
+        } else {
+            println!("Snapshot file does not exist, starting with empty storage");
+        }
+
+        // スナップショット以降の操作をWALから再適用
+        let records = self.wal.replay()?;
+        let replayed = records.len();
+        for record in records {
+            match record {
+                // スナップショットの書き込み後、WALを切り詰める前に終了した場合は
+                // スナップショットに含まれている操作も残っているため、版番号で読み飛ばす
+                WalRecord::Save { file_id, revision } => {
+                    let current = current_revision(&files, &file_id)
+                        .or_else(|| deleted.get(&file_id).copied())
+                        .unwrap_or(0);
+                    if revision.revision > current {
+                        deleted.remove(&file_id);
+                        let revisions = files.entry(file_id).or_default();
+                        self.push_revision(revisions, revision);
+                    }
+                }
+                WalRecord::Delete { file_id, revision } => {
+                    match current_revision(&files, &file_id) {
+                        // 削除の後に作り直した版が既にある
+                        Some(current) if current > revision => {}
+                        Some(_) => {
+                            files.remove(&file_id);
+                            deleted.insert(file_id, revision);
+                        }
+                        None => {
+                            let last = deleted.entry(file_id).or_insert(revision);
+                            *last = (*last).max(revision);
+                        }
+                    }
+                }
+            }
+        }
+        if replayed > 0 {
+            println!("Replayed {} operations from write-ahead log", replayed);
+        }
+
+        println!("Loaded {} files from disk", files.len());
+        Ok(())
+    }
+
+    // 新しい版を追加し、上限を超えた古い版を破棄
+    fn push_revision(&self, revisions: &mut VecDeque<StoredRevision>, revision: StoredRevision) {
+        revisions.push_back(revision);
+        while revisions.len() > self.history_limit + 1 {
+            revisions.pop_front();
+        }
+    }
+
+    fn lock(&self) -> StoreResult<MutexGuard<'_, Revisions>> {
+        self.files.lock().map_err(|_| StoreError::Lock)
+    }
+
+    fn lock_deleted(&self) -> StoreResult<MutexGuard<'_, HashMap<String, u64>>> {
+        self.deleted.lock().map_err(|_| StoreError::Lock)
+    }
+}
+
+// ファイルの現在の版番号
+fn current_revision(files: &Revisions, file_id: &str) -> Option<u64> {
+    files
+        .get(file_id)
+        .and_then(|revisions| revisions.back())
+        .map(|stored| stored.revision)
+}
+
+impl DiagramStore for MemoryStore {
+    fn put(&self, file_id: &str, file: File, expected_revision: Option<u64>) -> StoreResult<u64> {
+        let mut files = self.lock()?;
+        let mut deleted = self.lock_deleted()?;
+
+        let current = files
+            .get(file_id)
+            .and_then(|revisions| revisions.back())
+            .map(|stored| stored.revision);
+        check_revision(expected_revision, current)?;
+
+        // 削除したファイルを作り直す場合は削除前の版の続きの番号にする
+        let previous = current.or_else(|| deleted.get(file_id).copied());
+        let revision = StoredRevision {
+            revision: previous.unwrap_or(0) + 1,
+            saved_at: chrono::Utc::now().timestamp(),
+            file,
+        };
+
+        // 応答を返す前にWALへ記録
+        self.wal.append_save(file_id, &revision)?;
+
+        deleted.remove(file_id);
+        let number = revision.revision;
+        let revisions = files.entry(file_id.to_string()).or_default();
+        self.push_revision(revisions, revision);
+        Ok(number)
+    }
+
+    fn delete(&self, file_id: &str, expected_revision: Option<u64>) -> StoreResult<bool> {
+        let mut files = self.lock()?;
+
+        let current = current_revision(&files, file_id);
+        check_revision(expected_revision, current)?;
+        let Some(current) = current else {
+            return Ok(false);
+        };
+
+        // 応答を返す前にWALへ記録
+        self.wal.append_delete(file_id, current)?;
+
+        // 古いETagが作り直したファイルに一致しないように最後の版番号を残す
+        files.remove(file_id);
+        self.lock_deleted()?.insert(file_id.to_string(), current);
+        Ok(true)
+    }
+
+    fn list(&self) -> StoreResult<Vec<DiagramSummary>> {
+        Ok(self
+            .lock()?
+            .iter()
+            .filter_map(|(file_id, revisions)| {
+                revisions
+                    .back()
+                    .map(|stored| DiagramSummary::new(file_id, stored.revision, &stored.file))
+            })
+            .collect())
+    }
+
+    fn exists(&self, file_id: &str) -> StoreResult<bool> {
+        Ok(self.lock()?.contains_key(file_id))
+    }
+
+    fn get_current(&self, file_id: &str) -> StoreResult<Option<StoredRevision>> {
+        Ok(self
+            .lock()?
+            .get(file_id)
+            .and_then(|revisions| revisions.back())
+            .cloned())
+    }
+
+    fn current_revision(&self, file_id: &str) -> StoreResult<Option<u64>> {
+        Ok(self
+            .lock()?
+            .get(file_id)
+            .and_then(|revisions| revisions.back())
+            .map(|stored| stored.revision))
+    }
+
+    fn revisions(&self, file_id: &str) -> StoreResult<Vec<StoredRevision>> {
+        Ok(self
+            .lock()?
+            .get(file_id)
+            .map(|revisions| revisions.iter().rev().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    // インメモリ情報をスナップショットとしてディスクにダンプ
+    fn flush(&self) -> StoreResult<()> {
+        // 状態の取得からWALの切り詰めまでを1回の保存として扱う
+        let _flushing = self.flush_lock.lock().map_err(|_| StoreError::Lock)?;
+
+        let (files, deleted, wal_offset) = {
+            let files_guard = self.lock()?;
+            let deleted = self.lock_deleted()?.clone();
+            // WALへの追記はファイルのロック中に行われるため、この時点の末尾がスナップショットと一致する
+            let wal_offset = self.wal.end_offset()?;
+            (files_guard.clone(), deleted, wal_offset) // Mutexからデータをクローンしてロックを解放
+        };
+
+        // ディレクトリが存在しない場合は作成
+        if let Some(parent) = self.snapshot_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // HashMapをバイナリとしてシリアライズ
+        let buffer = encode_snapshot(&files, &deleted)?;
+
+        // 書き込み途中でクラッシュしても既存のスナップショットが壊れないように置き換える
+        write_atomic(&self.snapshot_path, &buffer)?;
+
+        // スナップショットに含まれた操作をWALから取り除く
+        self.wal.truncate_prefix(wal_offset)?;
+
+        println!("Saved {} files snapshot to disk", files.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("edea-memory-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn replay_after_snapshot_before_wal_truncation_keeps_recreated_file() {
+        let dir = temp_dir();
+        let dir_str = dir.to_str().unwrap();
+        let store = MemoryStore::open(dir_str, 10).unwrap();
+        store.put("f", File::default(), None).unwrap();
+        store.delete("f", None).unwrap();
+        store.put("f", File::default(), None).unwrap();
+
+        // スナップショットの書き込み後、WALを切り詰める前に終了した状態を再現する
+        let wal = std::fs::read(dir.join("wal.log")).unwrap();
+        store.flush().unwrap();
+        std::fs::write(dir.join("wal.log"), wal).unwrap();
+        drop(store);
+
+        let store = MemoryStore::open(dir_str, 10).unwrap();
+        assert!(store.exists("f").unwrap());
+        assert_eq!(store.get_current("f").unwrap().unwrap().revision, 2);
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.put("f", File::default(), Some(2)).unwrap(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replay_of_delete_keeps_revision_counter() {
+        let dir = temp_dir();
+        let dir_str = dir.to_str().unwrap();
+        let store = MemoryStore::open(dir_str, 10).unwrap();
+        store.put("f", File::default(), None).unwrap();
+        store.put("f", File::default(), None).unwrap();
+        store.delete("f", None).unwrap();
+        drop(store);
+
+        let store = MemoryStore::open(dir_str, 10).unwrap();
+        assert!(!store.exists("f").unwrap());
+        assert_eq!(store.put("f", File::default(), None).unwrap(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
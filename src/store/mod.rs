@@ -0,0 +1,490 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::{ServerConfig, StorageBackend};
+use crate::server::class::File;
+
+pub mod accounts;
+pub mod memory;
+pub mod snapshot;
+pub mod sqlite;
+pub mod wal;
+
+pub use accounts::{AccountStore, ApiKeyRecord, JsonAccountStore, UserRecord};
+pub use memory::MemoryStore;
+pub use snapshot::SnapshotError;
+pub use sqlite::{SqliteAccountStore, SqliteStore};
+
+// ストレージ操作のエラー
+#[derive(Debug)]
+pub enum StoreError {
+    Io(std::io::Error),
+    Encode(prost::EncodeError),
+    Decode(prost::DecodeError),
+    Snapshot(SnapshotError),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+    Lock,
+    // 期待した版と現在の版が一致しない
+    Conflict {
+        expected: u64,
+        current: Option<u64>,
+    },
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "I/O error: {}", e),
+            StoreError::Encode(e) => write!(f, "Failed to encode file: {}", e),
+            StoreError::Decode(e) => write!(f, "Failed to decode file: {}", e),
+            StoreError::Snapshot(e) => write!(f, "{}", e),
+            StoreError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            StoreError::Json(e) => write!(f, "Failed to read or write accounts: {}", e),
+            StoreError::Lock => write!(f, "Failed to acquire lock"),
+            StoreError::Conflict {
+                expected,
+                current: Some(current),
+            } => write!(
+                f,
+                "Revision mismatch: expected {}, current revision is {}",
+                expected, current
+            ),
+            StoreError::Conflict {
+                expected,
+                current: None,
+            } => write!(
+                f,
+                "Revision mismatch: expected {}, but the file does not exist",
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<prost::EncodeError> for StoreError {
+    fn from(e: prost::EncodeError) -> Self {
+        StoreError::Encode(e)
+    }
+}
+
+impl From<prost::DecodeError> for StoreError {
+    fn from(e: prost::DecodeError) -> Self {
+        StoreError::Decode(e)
+    }
+}
+
+impl From<SnapshotError> for StoreError {
+    fn from(e: SnapshotError) -> Self {
+        StoreError::Snapshot(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+impl From<StoreError> for tonic::Status {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::Conflict { current, .. } => {
+                // 現在の版をETagとして返し、クライアントが再取得できるようにする
+                let mut metadata = tonic::metadata::MetadataMap::new();
+                if let Some(current) = current {
+                    insert_etag(&mut metadata, current);
+                }
+                tonic::Status::with_metadata(
+                    tonic::Code::FailedPrecondition,
+                    e.to_string(),
+                    metadata,
+                )
+            }
+            _ => tonic::Status::internal(format!("Storage error: {}", e)),
+        }
+    }
+}
+
+// 版番号をETag形式（"<版番号>"）に変換
+pub fn etag(revision: u64) -> String {
+    format!("\"{}\"", revision)
+}
+
+// ETag形式の文字列から版番号を取得（弱いETagと引用符無しも受け付ける）
+pub fn parse_etag(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.trim_matches('"').parse().ok()
+}
+
+// メタデータにETagを設定
+pub fn insert_etag(metadata: &mut tonic::metadata::MetadataMap, revision: u64) {
+    if let Ok(value) = etag(revision).parse() {
+        metadata.insert("etag", value);
+    }
+}
+
+// 保存されたファイルの版
+#[derive(Debug, Clone)]
+pub struct StoredRevision {
+    // ファイルごとに1から増加する版番号（削除して作り直したファイルは削除前の続きから）
+    pub revision: u64,
+    // 保存日時（UNIX秒）
+    pub saved_at: i64,
+    pub file: File,
+}
+
+// 一覧表示用のファイルの概要
+#[derive(Debug, Clone)]
+pub struct DiagramSummary {
+    pub file_id: String,
+    pub name: String,
+    pub created_at: i32,
+    pub last_modified: i32,
+    pub class_count: u32,
+    // 現在の版番号
+    pub revision: u64,
+}
+
+impl DiagramSummary {
+    pub fn new(file_id: &str, revision: u64, file: &File) -> Self {
+        Self {
+            file_id: file_id.to_string(),
+            name: file.name.clone(),
+            created_at: file.created_at,
+            last_modified: file.last_modified,
+            class_count: file.classes.len() as u32,
+            revision,
+        }
+    }
+}
+
+// ダイアグラムの保存先を抽象化するトレイト
+pub trait DiagramStore: Send + Sync + fmt::Debug {
+    // ファイルを新しい版として保存し、割り当てた版番号を返す
+    // 以前の版は履歴の上限まで保持する
+    // expected_revisionを指定した場合、現在の版と一致しなければConflictを返す
+    fn put(&self, file_id: &str, file: File, expected_revision: Option<u64>) -> StoreResult<u64>;
+
+    // ファイルを履歴ごと削除し、削除されたかどうかを返す
+    // expected_revisionが指定されている場合、現在の版と一致しなければConflictを返す
+    // 最後の版番号は残し、同じIDで作り直したファイルの版番号は続きから割り当てる
+    fn delete(&self, file_id: &str, expected_revision: Option<u64>) -> StoreResult<bool>;
+
+    // 保存されている全ファイルの概要を取得（順序は不定）
+    fn list(&self) -> StoreResult<Vec<DiagramSummary>>;
+
+    // ファイルが存在するかチェック
+    fn exists(&self, file_id: &str) -> StoreResult<bool>;
+
+    // 現在の版を版番号付きで取得
+    fn get_current(&self, file_id: &str) -> StoreResult<Option<StoredRevision>>;
+
+    // 現在の版番号を取得
+    fn current_revision(&self, file_id: &str) -> StoreResult<Option<u64>>;
+
+    // 保持している版を新しい順に取得（現在の版を含む）
+    fn revisions(&self, file_id: &str) -> StoreResult<Vec<StoredRevision>>;
+
+    // 指定した版を取得
+    fn get_revision(&self, file_id: &str, revision: u64) -> StoreResult<Option<StoredRevision>> {
+        Ok(self
+            .revisions(file_id)?
+            .into_iter()
+            .find(|stored| stored.revision == revision))
+    }
+
+    // バッファされている状態をディスクに書き出す
+    fn flush(&self) -> StoreResult<()> {
+        Ok(())
+    }
+}
+
+// 期待した版と現在の版を比較
+fn check_revision(expected: Option<u64>, current: Option<u64>) -> StoreResult<()> {
+    match expected {
+        Some(expected) if current != Some(expected) => {
+            Err(StoreError::Conflict { expected, current })
+        }
+        _ => Ok(()),
+    }
+}
+
+// 設定に応じたストレージを開く
+pub fn open_store(config: &ServerConfig) -> StoreResult<Arc<dyn DiagramStore>> {
+    match config.storage_backend {
+        StorageBackend::Memory => Ok(Arc::new(MemoryStore::open(
+            &config.persistence_dir,
+            config.history_limit,
+        )?)),
+        StorageBackend::Sqlite => Ok(Arc::new(SqliteStore::open(
+            &config.persistence_dir,
+            config.history_limit,
+        )?)),
+    }
+}
+
+// 設定に応じた利用者・APIキーの保存先を開く
+// メモリ上のストアの場合は永続化ディレクトリのJSONファイル、SQLiteの場合は同じデータベースに保存する
+pub fn open_account_store(config: &ServerConfig) -> StoreResult<Arc<dyn AccountStore>> {
+    match config.storage_backend {
+        StorageBackend::Memory => Ok(Arc::new(JsonAccountStore::open(&config.persistence_dir)?)),
+        StorageBackend::Sqlite => Ok(Arc::new(SqliteAccountStore::open(&config.persistence_dir)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // テスト用の一時ディレクトリに開くバックエンド
+    struct Backend {
+        kind: StorageBackend,
+        dir: PathBuf,
+        history_limit: usize,
+    }
+
+    impl Backend {
+        fn open(&self) -> Box<dyn DiagramStore> {
+            let dir = self.dir.to_str().unwrap();
+            match self.kind {
+                StorageBackend::Memory => {
+                    Box::new(MemoryStore::open(dir, self.history_limit).unwrap())
+                }
+                StorageBackend::Sqlite => {
+                    Box::new(SqliteStore::open(dir, self.history_limit).unwrap())
+                }
+            }
+        }
+    }
+
+    // 両方のバックエンドで同じ確認を行う
+    fn for_each_backend(history_limit: usize, check: impl Fn(&Backend)) {
+        for kind in [StorageBackend::Memory, StorageBackend::Sqlite] {
+            let backend = Backend {
+                kind,
+                dir: std::env::temp_dir().join(format!("edea-store-{}", uuid::Uuid::new_v4())),
+                history_limit,
+            };
+            println!("Checking {:?} backend", kind);
+            check(&backend);
+            let _ = std::fs::remove_dir_all(&backend.dir);
+        }
+    }
+
+    fn named(name: &str) -> File {
+        File {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn put_get_list_and_delete() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            assert!(!store.exists("a").unwrap());
+            assert!(store.get_current("a").unwrap().is_none());
+
+            assert_eq!(store.put("a", named("first"), None).unwrap(), 1);
+            assert_eq!(store.put("a", named("second"), None).unwrap(), 2);
+            assert_eq!(store.put("b", named("other"), None).unwrap(), 1);
+
+            assert!(store.exists("a").unwrap());
+            let current = store.get_current("a").unwrap().unwrap();
+            assert_eq!(current.revision, 2);
+            assert_eq!(current.file.name, "second");
+            assert_eq!(store.current_revision("a").unwrap(), Some(2));
+
+            let mut summaries = store.list().unwrap();
+            summaries.sort_by(|a, b| a.file_id.cmp(&b.file_id));
+            let listed: Vec<(&str, &str, u64)> = summaries
+                .iter()
+                .map(|summary| {
+                    (
+                        summary.file_id.as_str(),
+                        summary.name.as_str(),
+                        summary.revision,
+                    )
+                })
+                .collect();
+            assert_eq!(listed, vec![("a", "second", 2), ("b", "other", 1)]);
+
+            assert!(store.delete("a", None).unwrap());
+            assert!(!store.delete("a", None).unwrap());
+            assert!(!store.exists("a").unwrap());
+            assert!(store.get_current("a").unwrap().is_none());
+            assert_eq!(store.list().unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn recreated_file_continues_revision_numbers() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.put("a", named("second"), None).unwrap();
+            store.delete("a", None).unwrap();
+
+            assert_eq!(store.put("a", named("recreated"), None).unwrap(), 3);
+            assert_eq!(store.revisions("a").unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn state_survives_reopening() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.put("a", named("second"), None).unwrap();
+            store.put("b", named("deleted"), None).unwrap();
+            store.delete("b", None).unwrap();
+            store.flush().unwrap();
+            store.put("a", named("after flush"), None).unwrap();
+            drop(store);
+
+            let store = backend.open();
+            let current = store.get_current("a").unwrap().unwrap();
+            assert_eq!(current.revision, 3);
+            assert_eq!(current.file.name, "after flush");
+            assert_eq!(store.revisions("a").unwrap().len(), 3);
+            assert!(!store.exists("b").unwrap());
+            assert_eq!(store.put("b", named("recreated"), None).unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn history_keeps_limit_earlier_revisions() {
+        for_each_backend(2, |backend| {
+            let store = backend.open();
+            for index in 1..=5 {
+                store.put("a", named(&format!("v{}", index)), None).unwrap();
+            }
+
+            // 現在の版と、それ以前の2つの版を新しい順に保持する
+            let revisions: Vec<u64> = store
+                .revisions("a")
+                .unwrap()
+                .iter()
+                .map(|stored| stored.revision)
+                .collect();
+            assert_eq!(revisions, vec![5, 4, 3]);
+            assert_eq!(store.get_revision("a", 3).unwrap().unwrap().file.name, "v3");
+            assert!(store.get_revision("a", 2).unwrap().is_none());
+            assert!(store.get_revision("a", 6).unwrap().is_none());
+            drop(store);
+
+            // 開き直しても上限を超えた版は戻らない
+            let store = backend.open();
+            assert_eq!(store.revisions("a").unwrap().len(), 3);
+            assert!(store.get_revision("a", 2).unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn restoring_an_earlier_revision_saves_it_as_a_new_revision() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.put("a", named("second"), None).unwrap();
+
+            let earlier = store.get_revision("a", 1).unwrap().unwrap();
+            assert_eq!(store.put("a", earlier.file, Some(2)).unwrap(), 3);
+
+            let names: Vec<String> = store
+                .revisions("a")
+                .unwrap()
+                .into_iter()
+                .map(|stored| stored.file.name)
+                .collect();
+            assert_eq!(names, vec!["first", "second", "first"]);
+        });
+    }
+
+    #[test]
+    fn deleted_file_has_no_revisions() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.put("a", named("second"), None).unwrap();
+            store.delete("a", None).unwrap();
+
+            assert!(store.revisions("a").unwrap().is_empty());
+            assert!(store.get_revision("a", 1).unwrap().is_none());
+        });
+    }
+
+    fn assert_conflict(result: StoreResult<impl fmt::Debug>, expected: u64, current: Option<u64>) {
+        match result {
+            Err(StoreError::Conflict {
+                expected: actual_expected,
+                current: actual_current,
+            }) => {
+                assert_eq!(actual_expected, expected);
+                assert_eq!(actual_current, current);
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn put_rejects_stale_expected_revision() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            assert_conflict(store.put("a", named("missing"), Some(1)), 1, None);
+
+            store.put("a", named("first"), None).unwrap();
+            assert_eq!(store.put("a", named("second"), Some(1)).unwrap(), 2);
+            assert_conflict(store.put("a", named("stale"), Some(1)), 1, Some(2));
+
+            // 拒否された保存は版を作らない
+            let current = store.get_current("a").unwrap().unwrap();
+            assert_eq!(current.revision, 2);
+            assert_eq!(current.file.name, "second");
+        });
+    }
+
+    #[test]
+    fn delete_rejects_stale_expected_revision() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.put("a", named("second"), None).unwrap();
+
+            assert_conflict(store.delete("a", Some(1)), 1, Some(2));
+            assert!(store.exists("a").unwrap());
+            assert!(store.delete("a", Some(2)).unwrap());
+            assert_conflict(store.delete("a", Some(2)), 2, None);
+        });
+    }
+
+    #[test]
+    fn etag_from_before_delete_does_not_match_recreated_file() {
+        for_each_backend(10, |backend| {
+            let store = backend.open();
+            store.put("a", named("first"), None).unwrap();
+            store.delete("a", None).unwrap();
+            store.put("a", named("recreated"), None).unwrap();
+
+            assert_conflict(store.put("a", named("stale"), Some(1)), 1, Some(2));
+        });
+    }
+}
@@ -0,0 +1,467 @@
+use prost::Message;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::wal::sync_dir;
+use super::StoredRevision;
+use crate::server::class::File;
+
+// スナップショットファイルの識別子とフォーマットバージョン
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"EDEASNAP";
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+// スナップショットの破損内容
+#[derive(Debug)]
+pub struct SnapshotError {
+    // 問題のあるレコードの番号（0始まり、ヘッダの場合はNone）
+    pub record: Option<u32>,
+    // 読み取れた場合のファイルID
+    pub file_id: Option<String>,
+    // 問題を検出したファイル内の位置
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.record, &self.file_id) {
+            (Some(record), Some(file_id)) => write!(
+                f,
+                "Corrupted snapshot record #{} (file_id \"{}\") at offset {}: {}",
+                record, file_id, self.offset, self.reason
+            ),
+            (Some(record), None) => write!(
+                f,
+                "Corrupted snapshot record #{} at offset {}: {}",
+                record, self.offset, self.reason
+            ),
+            _ => write!(
+                f,
+                "Corrupted snapshot header at offset {}: {}",
+                self.offset, self.reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+// スナップショットの内容
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    // 各ファイルの版（古い順）
+    pub files: Vec<(String, Vec<StoredRevision>)>,
+    // 削除したファイルの最後の版番号
+    pub deleted: Vec<(String, u64)>,
+}
+
+// 各ファイルの保持している版と、削除したファイルの最後の版番号をスナップショット形式にエンコード
+// ヘッダ: マジック(8) | バージョン(u16) | レコード数(u32)
+// レコード: ID長(u32) | ID | 版数(u32) | 版... | CRC32(u32)
+// 版: 版番号(u64) | 保存日時(i64) | データ長(u32) | データ
+// 削除したファイルのレコード: ID長(u32) | ID | 0(u32) | 最後の版番号(u64) | CRC32(u32)
+pub fn encode_snapshot(
+    files: &HashMap<String, VecDeque<StoredRevision>>,
+    deleted: &HashMap<String, u64>,
+) -> Result<Vec<u8>, prost::EncodeError> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(SNAPSHOT_MAGIC);
+    buffer.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+    buffer.extend_from_slice(&((files.len() + deleted.len()) as u32).to_be_bytes());
+
+    for (file_id, revisions) in files.iter() {
+        let record_start = buffer.len();
+
+        let file_id_bytes = file_id.as_bytes();
+        buffer.extend_from_slice(&(file_id_bytes.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(file_id_bytes);
+
+        buffer.extend_from_slice(&(revisions.len() as u32).to_be_bytes());
+        for revision in revisions {
+            buffer.extend_from_slice(&revision.revision.to_be_bytes());
+            buffer.extend_from_slice(&revision.saved_at.to_be_bytes());
+
+            let mut file_buffer = Vec::new();
+            revision.file.encode(&mut file_buffer)?;
+            buffer.extend_from_slice(&(file_buffer.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(&file_buffer);
+        }
+
+        // レコード全体のチェックサム
+        let crc = crc32fast::hash(&buffer[record_start..]);
+        buffer.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    for (file_id, revision) in deleted.iter() {
+        let record_start = buffer.len();
+
+        let file_id_bytes = file_id.as_bytes();
+        buffer.extend_from_slice(&(file_id_bytes.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(file_id_bytes);
+        buffer.extend_from_slice(&0u32.to_be_bytes());
+        buffer.extend_from_slice(&revision.to_be_bytes());
+
+        let crc = crc32fast::hash(&buffer[record_start..]);
+        buffer.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    Ok(buffer)
+}
+
+// スナップショットをデコード（各ファイルの版は古い順）
+// マジックが無い場合はバージョン導入前の形式として読み込む
+pub fn decode_snapshot(content: &[u8]) -> Result<Snapshot, SnapshotError> {
+    let mut reader = SnapshotReader {
+        content,
+        offset: 0,
+        record: None,
+        file_id: None,
+    };
+
+    if !content.starts_with(SNAPSHOT_MAGIC) {
+        return decode_legacy(reader);
+    }
+    reader.offset = SNAPSHOT_MAGIC.len();
+
+    let version = u16::from_be_bytes(reader.take_array("format version")?);
+    if version != SNAPSHOT_VERSION {
+        return Err(reader.error(format!("Unsupported format version {}", version)));
+    }
+    let file_count = reader.read_u32("record count")?;
+
+    let mut snapshot = Snapshot::default();
+    for index in 0..file_count {
+        reader.record = Some(index);
+        reader.file_id = None;
+        let record_start = reader.offset;
+
+        let file_id = reader.read_file_id()?;
+
+        // 版数0のレコードは削除したファイル
+        let mut encoded = Vec::new();
+        let mut deleted = None;
+        let revision_count = reader.read_u32("revision count")?;
+        if revision_count == 0 {
+            deleted = Some(u64::from_be_bytes(
+                reader.take_array("deleted revision number")?,
+            ));
+        }
+        for _ in 0..revision_count {
+            let revision = u64::from_be_bytes(reader.take_array("revision number")?);
+            let saved_at = i64::from_be_bytes(reader.take_array("revision timestamp")?);
+            let data_len = reader.read_u32("data length")? as usize;
+            encoded.push((revision, saved_at, reader.take(data_len, "file data")?));
+        }
+        let record_end = reader.offset;
+
+        let expected = reader.read_u32("checksum")?;
+        let actual = crc32fast::hash(&content[record_start..record_end]);
+        if expected != actual {
+            reader.offset = record_start;
+            return Err(reader.error(format!(
+                "Checksum mismatch (expected {:08x}, actual {:08x})",
+                expected, actual
+            )));
+        }
+
+        if let Some(revision) = deleted {
+            snapshot.deleted.push((file_id, revision));
+            continue;
+        }
+
+        let mut revisions = Vec::with_capacity(encoded.len());
+        for (revision, saved_at, data) in encoded {
+            let file = reader.decode_file(data, record_start)?;
+            revisions.push(StoredRevision {
+                revision,
+                saved_at,
+                file,
+            });
+        }
+        snapshot.files.push((file_id, revisions));
+    }
+
+    if reader.offset != content.len() {
+        reader.record = None;
+        reader.file_id = None;
+        return Err(reader.error(format!(
+            "{} unexpected trailing bytes after {} records",
+            content.len() - reader.offset,
+            file_count
+        )));
+    }
+
+    Ok(snapshot)
+}
+
+// バージョン導入前の形式（ヘッダ・チェックサム無し）
+fn decode_legacy(mut reader: SnapshotReader<'_>) -> Result<Snapshot, SnapshotError> {
+    let file_count = reader.read_u32("record count")?;
+
+    let mut files = Vec::with_capacity(file_count as usize);
+    for index in 0..file_count {
+        reader.record = Some(index);
+        reader.file_id = None;
+        let record_start = reader.offset;
+
+        let file_id = reader.read_file_id()?;
+        let data_len = reader.read_u32("data length")? as usize;
+        let data = reader.take(data_len, "file data")?;
+
+        let file = reader.decode_file(data, record_start)?;
+        let revision = StoredRevision {
+            revision: 1,
+            saved_at: file.last_modified as i64,
+            file,
+        };
+        files.push((file_id, vec![revision]));
+    }
+
+    Ok(Snapshot {
+        files,
+        deleted: Vec::new(),
+    })
+}
+
+struct SnapshotReader<'a> {
+    content: &'a [u8],
+    offset: usize,
+    record: Option<u32>,
+    file_id: Option<String>,
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], SnapshotError> {
+        let remaining = self.content.len() - self.offset;
+        if len > remaining {
+            return Err(self.error(format!(
+                "Truncated while reading {} (need {} bytes, {} remaining)",
+                what, len, remaining
+            )));
+        }
+        let bytes = &self.content[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self, what: &str) -> Result<[u8; N], SnapshotError> {
+        let bytes = self.take(N, what)?;
+        let mut array = [0u8; N];
+        array.copy_from_slice(bytes);
+        Ok(array)
+    }
+
+    fn read_u32(&mut self, what: &str) -> Result<u32, SnapshotError> {
+        Ok(u32::from_be_bytes(self.take_array(what)?))
+    }
+
+    fn read_file_id(&mut self) -> Result<String, SnapshotError> {
+        let file_id_len = self.read_u32("file ID length")? as usize;
+        let file_id_bytes = self.take(file_id_len, "file ID")?;
+        let file_id = String::from_utf8(file_id_bytes.to_vec())
+            .map_err(|e| self.error(format!("File ID is not valid UTF-8: {}", e)))?;
+        self.file_id = Some(file_id.clone());
+        Ok(file_id)
+    }
+
+    fn decode_file(&mut self, data: &[u8], record_start: usize) -> Result<File, SnapshotError> {
+        File::decode(data).map_err(|e| {
+            self.offset = record_start;
+            self.error(format!("Failed to decode file data: {}", e))
+        })
+    }
+
+    fn error(&self, reason: String) -> SnapshotError {
+        SnapshotError {
+            record: self.record,
+            file_id: self.file_id.clone(),
+            offset: self.offset,
+            reason,
+        }
+    }
+}
+
+// 一時ファイルに書き込んでfsyncし、リネームで置き換える
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    replace_file(path, data, OpenOptions::new())
+}
+
+// write_atomicと同様に置き換え、所有者だけが読み書きできるファイルにする（署名鍵を含むファイルなど）
+pub fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    #[allow(unused_mut)]
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    replace_file(path, data, options)
+}
+
+// 一時ファイル（<ファイル名>.tmp）に書き込んでfsyncし、リネームで置き換える
+fn replace_file(path: &Path, data: &[u8], mut options: OpenOptions) -> io::Result<()> {
+    let mut tmp_name = path.file_name().map(OsString::from).unwrap_or_default();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // 前回の書き込みで残った一時ファイルは権限が異なる可能性があるため作り直す
+    match std::fs::remove_file(&tmp_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    {
+        let mut tmp = options.write(true).create_new(true).open(&tmp_path)?;
+        tmp.write_all(data)?;
+        tmp.sync_all()?;
+    }
+
+    std::fs::rename(&tmp_path, path)?;
+    sync_dir(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn stored(revision: u64, name: &str) -> StoredRevision {
+        StoredRevision {
+            revision,
+            saved_at: 1_700_000_000 + revision as i64,
+            file: File {
+                name: name.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut files = HashMap::new();
+        files.insert(
+            "a".to_string(),
+            VecDeque::from(vec![stored(2, "older"), stored(3, "current")]),
+        );
+        let mut deleted = HashMap::new();
+        deleted.insert("b".to_string(), 7);
+        encode_snapshot(&files, &deleted).unwrap()
+    }
+
+    #[test]
+    fn round_trips_files_and_deleted_revisions() {
+        let snapshot = decode_snapshot(&sample()).unwrap();
+
+        assert_eq!(snapshot.files.len(), 1);
+        let (file_id, revisions) = &snapshot.files[0];
+        assert_eq!(file_id, "a");
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].revision, 2);
+        assert_eq!(revisions[1].revision, 3);
+        assert_eq!(revisions[1].saved_at, 1_700_000_003);
+        assert_eq!(revisions[1].file.name, "current");
+        assert_eq!(snapshot.deleted, vec![("b".to_string(), 7)]);
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let mut content = sample();
+        let version = SNAPSHOT_MAGIC.len();
+        content[version..version + 2].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_be_bytes());
+
+        let error = decode_snapshot(&content).unwrap_err();
+        assert_eq!(error.record, None);
+        assert!(error.reason.contains("Unsupported format version"));
+    }
+
+    #[test]
+    fn reports_checksum_mismatch_with_record_and_file_id() {
+        let mut content = sample();
+        // 最後のレコード（削除したファイル）の版番号の1バイトを書き換える
+        let last_revision = content.len() - 5;
+        content[last_revision] ^= 0xff;
+
+        let error = decode_snapshot(&content).unwrap_err();
+        assert_eq!(error.record, Some(1));
+        assert_eq!(error.file_id.as_deref(), Some("b"));
+        assert!(error.reason.contains("Checksum mismatch"));
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_bytes() {
+        let content = sample();
+
+        let error = decode_snapshot(&content[..content.len() - 2]).unwrap_err();
+        assert!(error.reason.contains("Truncated"));
+
+        let mut trailing = content.clone();
+        trailing.push(0);
+        let error = decode_snapshot(&trailing).unwrap_err();
+        assert!(error.reason.contains("trailing bytes"));
+    }
+
+    #[test]
+    fn reads_snapshot_without_header() {
+        // バージョン導入前の形式: ファイル数 | ID長 | ID | データ長 | データ
+        let file = File {
+            name: "legacy".to_string(),
+            last_modified: 1_600_000_000,
+            ..Default::default()
+        };
+        let data = file.encode_to_vec();
+        let mut content = Vec::new();
+        content.extend_from_slice(&1u32.to_be_bytes());
+        content.extend_from_slice(&1u32.to_be_bytes());
+        content.push(b'a');
+        content.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        content.extend_from_slice(&data);
+
+        let snapshot = decode_snapshot(&content).unwrap();
+        let (file_id, revisions) = &snapshot.files[0];
+        assert_eq!(file_id, "a");
+        assert_eq!(revisions[0].revision, 1);
+        assert_eq!(revisions[0].saved_at, 1_600_000_000);
+        assert_eq!(revisions[0].file.name, "legacy");
+    }
+
+    #[test]
+    fn write_atomic_replaces_existing_file() {
+        let dir = temp_dir();
+        let path = dir.join("snapshot.bin");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!dir.join("snapshot.bin.tmp").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("edea-snapshot-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_private_creates_owner_only_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir();
+        let path = dir.join("accounts.json");
+        // 前回の書き込みで残った一時ファイルの権限を引き継がない
+        std::fs::write(dir.join("accounts.json.tmp"), b"stale").unwrap();
+        write_private(&path, b"{}").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read(&path).unwrap(), b"{}");
+        assert!(!dir.join("accounts.json.tmp").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,444 @@
+use prost::Message;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use super::{
+    check_revision, AccountStore, ApiKeyRecord, DiagramStore, DiagramSummary, StoreError,
+    StoreResult, StoredRevision, UserRecord,
+};
+use crate::server::class::File;
+
+// スキーマのバージョン（PRAGMA user_versionで管理）
+const SCHEMA_VERSION: i64 = 1;
+
+// 組み込みSQLiteにファイルを保存するストア
+// ファイルは必要な時だけ読み込むため、全ダイアグラムをメモリに保持しない
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    // ファイルごとに保持する以前の版の数
+    history_limit: usize,
+}
+
+impl SqliteStore {
+    pub fn open(persistence_dir: &str, history_limit: usize) -> StoreResult<Self> {
+        std::fs::create_dir_all(persistence_dir)?;
+        let path = Path::new(persistence_dir).join("diagrams.sqlite3");
+        let mut conn = Connection::open(&path)?;
+
+        // 書き込みは応答を返す前にディスクへ同期する
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "FULL")?;
+        create_schema(&mut conn)?;
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM diagrams", [], |row| row.get(0))?;
+        println!("Opened SQLite store at {} ({} files)", path.display(), count);
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            history_limit,
+        })
+    }
+
+    fn lock(&self) -> StoreResult<MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| StoreError::Lock)
+    }
+}
+
+// スキーマを作成（作成済みの場合は何もしない）
+fn create_schema(conn: &mut Connection) -> StoreResult<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    // ダイアグラムの現在の版と一覧表示用の概要、保持している版、
+    // 削除したファイルの最後の版番号（同じIDで作り直した時は続きの番号を割り当てる）
+    // 利用者・APIキーと、トークンの署名鍵などの設定
+    tx.execute_batch(
+        "CREATE TABLE diagrams (
+            file_id TEXT PRIMARY KEY NOT NULL,
+            data BLOB NOT NULL,
+            revision INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_modified INTEGER NOT NULL,
+            class_count INTEGER NOT NULL
+         );
+         CREATE TABLE revisions (
+            file_id TEXT NOT NULL,
+            revision INTEGER NOT NULL,
+            saved_at INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            PRIMARY KEY (file_id, revision)
+         );
+         CREATE TABLE deleted_diagrams (
+            file_id TEXT PRIMARY KEY NOT NULL,
+            revision INTEGER NOT NULL
+         );
+         CREATE TABLE users (
+            user_name TEXT PRIMARY KEY NOT NULL,
+            password_hash TEXT NOT NULL,
+            admin INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+         );
+         CREATE TABLE api_keys (
+            key_id TEXT PRIMARY KEY NOT NULL,
+            user_name TEXT NOT NULL,
+            name TEXT NOT NULL,
+            secret_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+         );
+         CREATE INDEX api_keys_user_name ON api_keys (user_name);
+         CREATE TABLE settings (
+            name TEXT PRIMARY KEY NOT NULL,
+            value TEXT NOT NULL
+         );",
+    )?;
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn decode_revision(revision: i64, saved_at: i64, data: Vec<u8>) -> StoreResult<StoredRevision> {
+    Ok(StoredRevision {
+        revision: revision as u64,
+        saved_at,
+        file: File::decode(&data[..])?,
+    })
+}
+
+impl DiagramStore for SqliteStore {
+    fn put(&self, file_id: &str, file: File, expected_revision: Option<u64>) -> StoreResult<u64> {
+        let mut data = Vec::new();
+        file.encode(&mut data)?;
+        let saved_at = chrono::Utc::now().timestamp();
+
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+
+        let current: Option<i64> = tx
+            .query_row(
+                "SELECT revision FROM diagrams WHERE file_id = ?1",
+                params![file_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        check_revision(expected_revision, current.map(|current| current as u64))?;
+
+        // 削除したファイルを作り直す場合は削除前の版の続きの番号にする
+        let previous = match current {
+            Some(current) => Some(current),
+            None => tx
+                .query_row(
+                    "SELECT revision FROM deleted_diagrams WHERE file_id = ?1",
+                    params![file_id],
+                    |row| row.get(0),
+                )
+                .optional()?,
+        };
+        let revision = previous.unwrap_or(0) + 1;
+
+        tx.execute(
+            "INSERT INTO diagrams
+                (file_id, data, revision, name, created_at, last_modified, class_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(file_id) DO UPDATE SET
+                data = excluded.data,
+                revision = excluded.revision,
+                name = excluded.name,
+                created_at = excluded.created_at,
+                last_modified = excluded.last_modified,
+                class_count = excluded.class_count",
+            params![
+                file_id,
+                data,
+                revision,
+                file.name,
+                file.created_at,
+                file.last_modified,
+                file.classes.len() as i64
+            ],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO revisions (file_id, revision, saved_at, data)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![file_id, revision, saved_at, data],
+        )?;
+        tx.execute(
+            "DELETE FROM deleted_diagrams WHERE file_id = ?1",
+            params![file_id],
+        )?;
+
+        // 上限を超えた古い版を破棄
+        tx.execute(
+            "DELETE FROM revisions WHERE file_id = ?1 AND revision < ?2",
+            params![file_id, revision - self.history_limit as i64],
+        )?;
+
+        tx.commit()?;
+        Ok(revision as u64)
+    }
+
+    fn delete(&self, file_id: &str, expected_revision: Option<u64>) -> StoreResult<bool> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+
+        let current: Option<i64> = tx
+            .query_row(
+                "SELECT revision FROM diagrams WHERE file_id = ?1",
+                params![file_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        check_revision(expected_revision, current.map(|current| current as u64))?;
+
+        // 古いETagが作り直したファイルに一致しないように最後の版番号を残す
+        tx.execute(
+            "INSERT OR REPLACE INTO deleted_diagrams (file_id, revision)
+             SELECT file_id, revision FROM diagrams WHERE file_id = ?1",
+            params![file_id],
+        )?;
+        let removed = tx.execute("DELETE FROM diagrams WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM revisions WHERE file_id = ?1", params![file_id])?;
+        tx.commit()?;
+        Ok(removed > 0)
+    }
+
+    fn list(&self) -> StoreResult<Vec<DiagramSummary>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT file_id, name, created_at, last_modified, class_count, revision
+             FROM diagrams",
+        )?;
+        let summaries = stmt
+            .query_map([], |row| {
+                Ok(DiagramSummary {
+                    file_id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                    last_modified: row.get(3)?,
+                    class_count: row.get::<_, i64>(4)? as u32,
+                    revision: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(summaries)
+    }
+
+    fn exists(&self, file_id: &str) -> StoreResult<bool> {
+        let conn = self.lock()?;
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM diagrams WHERE file_id = ?1)",
+            params![file_id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    fn get_current(&self, file_id: &str) -> StoreResult<Option<StoredRevision>> {
+        let conn = self.lock()?;
+        let row: Option<(i64, i64, Vec<u8>)> = conn
+            .query_row(
+                "SELECT r.revision, r.saved_at, r.data FROM diagrams d
+                 JOIN revisions r ON r.file_id = d.file_id AND r.revision = d.revision
+                 WHERE d.file_id = ?1",
+                params![file_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        row.map(|(revision, saved_at, data)| decode_revision(revision, saved_at, data))
+            .transpose()
+    }
+
+    fn current_revision(&self, file_id: &str) -> StoreResult<Option<u64>> {
+        let conn = self.lock()?;
+        let revision: Option<i64> = conn
+            .query_row(
+                "SELECT revision FROM diagrams WHERE file_id = ?1",
+                params![file_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(revision.map(|revision| revision as u64))
+    }
+
+    fn revisions(&self, file_id: &str) -> StoreResult<Vec<StoredRevision>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT revision, saved_at, data FROM revisions
+             WHERE file_id = ?1 ORDER BY revision DESC",
+        )?;
+        let rows = stmt
+            .query_map(params![file_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<Vec<(i64, i64, Vec<u8>)>, _>>()?;
+
+        rows.into_iter()
+            .map(|(revision, saved_at, data)| decode_revision(revision, saved_at, data))
+            .collect()
+    }
+
+    fn get_revision(&self, file_id: &str, revision: u64) -> StoreResult<Option<StoredRevision>> {
+        let conn = self.lock()?;
+        let row: Option<(i64, Vec<u8>)> = conn
+            .query_row(
+                "SELECT saved_at, data FROM revisions WHERE file_id = ?1 AND revision = ?2",
+                params![file_id, revision as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        row.map(|(saved_at, data)| decode_revision(revision as i64, saved_at, data))
+            .transpose()
+    }
+}
+
+// 利用者・APIキーをダイアグラムと同じSQLiteデータベースに保存するストア
+#[derive(Debug)]
+pub struct SqliteAccountStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteAccountStore {
+    pub fn open(persistence_dir: &str) -> StoreResult<Self> {
+        std::fs::create_dir_all(persistence_dir)?;
+        let mut conn = Connection::open(Path::new(persistence_dir).join("diagrams.sqlite3"))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "FULL")?;
+        create_schema(&mut conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn lock(&self) -> StoreResult<MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| StoreError::Lock)
+    }
+}
+
+fn api_key_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ApiKeyRecord> {
+    Ok(ApiKeyRecord {
+        key_id: row.get(0)?,
+        user_name: row.get(1)?,
+        name: row.get(2)?,
+        secret_hash: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+impl AccountStore for SqliteAccountStore {
+    fn get_user(&self, user_name: &str) -> StoreResult<Option<UserRecord>> {
+        let conn = self.lock()?;
+        Ok(conn
+            .query_row(
+                "SELECT user_name, password_hash, admin, created_at FROM users
+                 WHERE user_name = ?1",
+                params![user_name],
+                |row| {
+                    Ok(UserRecord {
+                        user_name: row.get(0)?,
+                        password_hash: row.get(1)?,
+                        admin: row.get(2)?,
+                        created_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn create_user(&self, user: UserRecord) -> StoreResult<bool> {
+        let conn = self.lock()?;
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO users (user_name, password_hash, admin, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                user.user_name,
+                user.password_hash,
+                user.admin,
+                user.created_at
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    fn user_count(&self) -> StoreResult<usize> {
+        let conn = self.lock()?;
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn get_api_key(&self, key_id: &str) -> StoreResult<Option<ApiKeyRecord>> {
+        let conn = self.lock()?;
+        Ok(conn
+            .query_row(
+                "SELECT key_id, user_name, name, secret_hash, created_at FROM api_keys
+                 WHERE key_id = ?1",
+                params![key_id],
+                api_key_from_row,
+            )
+            .optional()?)
+    }
+
+    fn api_keys(&self, user_name: &str) -> StoreResult<Vec<ApiKeyRecord>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT key_id, user_name, name, secret_hash, created_at FROM api_keys
+             WHERE user_name = ?1 ORDER BY created_at, rowid",
+        )?;
+        let keys = stmt
+            .query_map(params![user_name], api_key_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(keys)
+    }
+
+    fn put_api_key(&self, key: ApiKeyRecord) -> StoreResult<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO api_keys (key_id, user_name, name, secret_hash, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                key.key_id,
+                key.user_name,
+                key.name,
+                key.secret_hash,
+                key.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_api_key(&self, user_name: &str, key_id: &str) -> StoreResult<bool> {
+        let conn = self.lock()?;
+        let removed = conn.execute(
+            "DELETE FROM api_keys WHERE key_id = ?1 AND user_name = ?2",
+            params![key_id, user_name],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn signing_key(&self) -> StoreResult<Option<String>> {
+        let conn = self.lock()?;
+        Ok(conn
+            .query_row(
+                "SELECT value FROM settings WHERE name = 'signing_key'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn set_signing_key(&self, key: &str) -> StoreResult<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (name, value) VALUES ('signing_key', ?1)",
+            params![key],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use prost::Message;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::StoredRevision;
+use crate::server::class::File;
+
+// レコード種別
+const OP_SAVE: u8 = 1;
+const OP_DELETE: u8 = 2;
+
+// WALに記録される操作
+#[derive(Debug, Clone)]
+pub enum WalRecord {
+    Save {
+        file_id: String,
+        revision: StoredRevision,
+    },
+    // 削除した時点の版番号（再適用時に、削除の後に作り直した版を消さないため）
+    Delete {
+        file_id: String,
+        revision: u64,
+    },
+}
+
+// スナップショット間の保存・削除操作を記録する追記専用ログ
+#[derive(Debug, Default)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    // 最初の書き込み時に開く
+    file: Mutex<Option<fs::File>>,
+}
+
+impl WriteAheadLog {
+    pub fn new(persistence_dir: &str) -> Self {
+        Self {
+            path: Path::new(persistence_dir).join("wal.log"),
+            file: Mutex::new(None),
+        }
+    }
+
+    // 保存操作を追記してfsyncする
+    pub fn append_save(&self, file_id: &str, revision: &StoredRevision) -> io::Result<()> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&revision.revision.to_be_bytes());
+        data.extend_from_slice(&revision.saved_at.to_be_bytes());
+        revision
+            .file
+            .encode(&mut data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.append(OP_SAVE, file_id, &data)
+    }
+
+    // 削除操作を削除した時点の版番号とともに追記してfsyncする
+    pub fn append_delete(&self, file_id: &str, revision: u64) -> io::Result<()> {
+        self.append(OP_DELETE, file_id, &revision.to_be_bytes())
+    }
+
+    fn append(&self, op: u8, file_id: &str, data: &[u8]) -> io::Result<()> {
+        let mut buffer = Vec::with_capacity(9 + file_id.len() + data.len());
+        buffer.push(op);
+        buffer.extend_from_slice(&(file_id.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(file_id.as_bytes());
+        buffer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(data);
+
+        let mut guard = self.lock()?;
+        if guard.is_none() {
+            *guard = Some(self.open()?);
+        }
+        let file = guard.as_mut().expect("WAL file is opened above");
+
+        // レコード全体を一度に書き込み、RPCが返る前にディスクへ同期
+        let start = file.metadata()?.len();
+        if let Err(e) = file.write_all(&buffer).and_then(|_| file.sync_data()) {
+            // 中途半端なレコードを残さないように書き込み前の長さへ戻す
+            let _ = file.set_len(start);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // 現在のログ末尾の位置（バイト数）
+    pub fn end_offset(&self) -> io::Result<u64> {
+        let _guard = self.lock()?;
+        match fs::metadata(&self.path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    // スナップショットに含まれた先頭offsetバイトを切り捨てる
+    // スナップショット取得後に追記されたレコードは一時ファイル経由で残す
+    pub fn truncate_prefix(&self, offset: u64) -> io::Result<()> {
+        let mut guard = self.lock()?;
+
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let offset = (offset as usize).min(content.len());
+
+        let tmp_path = self.path.with_extension("log.tmp");
+        {
+            let mut tmp = fs::File::create(&tmp_path)?;
+            tmp.write_all(&content[offset..])?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        sync_dir(&self.path)?;
+
+        // 置き換えたファイルを次回の書き込みで開き直す
+        *guard = None;
+        Ok(())
+    }
+
+    // ログに記録された操作を順番に読み出す
+    pub fn replay(&self) -> io::Result<Vec<WalRecord>> {
+        let mut guard = self.lock()?;
+
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let total = content.len() as u64;
+        let mut cursor = io::Cursor::new(content);
+        let mut records = Vec::new();
+
+        while cursor.position() < total {
+            let start = cursor.position();
+            match read_record(&mut cursor) {
+                Ok(record) => records.push(record),
+                // 書き込み途中でクラッシュした末尾のレコードは応答を返していないので破棄する
+                // 以降の追記が壊れたレコードの後ろに続かないようにファイルも切り詰める
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    eprintln!(
+                        "Discarding incomplete WAL record at offset {} ({} bytes)",
+                        start,
+                        total - start
+                    );
+                    let file = OpenOptions::new().write(true).open(&self.path)?;
+                    file.set_len(start)?;
+                    file.sync_all()?;
+                    *guard = None;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(records)
+    }
+
+    fn open(&self) -> io::Result<fs::File> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, Option<fs::File>>> {
+        self.file
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire WAL lock"))
+    }
+}
+
+fn read_record(cursor: &mut io::Cursor<Vec<u8>>) -> io::Result<WalRecord> {
+    let mut op = [0u8; 1];
+    cursor.read_exact(&mut op)?;
+
+    let file_id_len = read_u32(cursor)? as usize;
+    let mut file_id_bytes = vec![0u8; file_id_len];
+    cursor.read_exact(&mut file_id_bytes)?;
+    let file_id = String::from_utf8(file_id_bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let data_len = read_u32(cursor)? as usize;
+    let mut data = vec![0u8; data_len];
+    cursor.read_exact(&mut data)?;
+
+    match op[0] {
+        OP_SAVE => {
+            if data.len() < 16 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "WAL save record is too short",
+                ));
+            }
+            let (header, file_data) = data.split_at(16);
+            let mut revision_bytes = [0u8; 8];
+            revision_bytes.copy_from_slice(&header[..8]);
+            let mut saved_at_bytes = [0u8; 8];
+            saved_at_bytes.copy_from_slice(&header[8..]);
+
+            let revision = StoredRevision {
+                revision: u64::from_be_bytes(revision_bytes),
+                saved_at: i64::from_be_bytes(saved_at_bytes),
+                file: decode_file(file_data)?,
+            };
+            Ok(WalRecord::Save { file_id, revision })
+        }
+        OP_DELETE => {
+            let revision_bytes: [u8; 8] = data.as_slice().try_into().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "WAL delete record has no revision",
+                )
+            })?;
+            Ok(WalRecord::Delete {
+                file_id,
+                revision: u64::from_be_bytes(revision_bytes),
+            })
+        }
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown WAL record type: {}", other),
+        )),
+    }
+}
+
+fn decode_file(data: &[u8]) -> io::Result<File> {
+    File::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_u32(cursor: &mut io::Cursor<Vec<u8>>) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    cursor.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+// リネームを永続化するためにディレクトリをfsyncする
+pub fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_wal() -> (PathBuf, WriteAheadLog) {
+        let dir = std::env::temp_dir().join(format!("edea-wal-{}", uuid::Uuid::new_v4()));
+        let wal = WriteAheadLog::new(dir.to_str().unwrap());
+        (dir, wal)
+    }
+
+    fn stored(revision: u64, name: &str) -> StoredRevision {
+        StoredRevision {
+            revision,
+            saved_at: 1_700_000_000 + revision as i64,
+            file: File {
+                name: name.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn replays_records_in_order() {
+        let (dir, wal) = temp_wal();
+        wal.append_save("a", &stored(1, "first")).unwrap();
+        wal.append_save("a", &stored(2, "second")).unwrap();
+        wal.append_delete("a", 2).unwrap();
+
+        let records = wal.replay().unwrap();
+        assert_eq!(records.len(), 3);
+        match &records[1] {
+            WalRecord::Save { file_id, revision } => {
+                assert_eq!(file_id, "a");
+                assert_eq!(revision.revision, 2);
+                assert_eq!(revision.saved_at, 1_700_000_002);
+                assert_eq!(revision.file.name, "second");
+            }
+            other => panic!("unexpected record: {:?}", other),
+        }
+        match &records[2] {
+            WalRecord::Delete { file_id, revision } => {
+                assert_eq!(file_id, "a");
+                assert_eq!(*revision, 2);
+            }
+            other => panic!("unexpected record: {:?}", other),
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn discards_torn_tail_and_keeps_appending_after_it() {
+        let (dir, wal) = temp_wal();
+        wal.append_save("a", &stored(1, "kept")).unwrap();
+        let complete = wal.end_offset().unwrap();
+        wal.append_save("a", &stored(2, "torn")).unwrap();
+
+        // 2つ目のレコードの書き込み途中で終了した状態
+        let path = dir.join("wal.log");
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 3]).unwrap();
+
+        let wal = WriteAheadLog::new(dir.to_str().unwrap());
+        let records = wal.replay().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete);
+
+        // 切り詰めた後の追記は壊れたレコードの後ろに続かない
+        wal.append_save("a", &stored(2, "retried")).unwrap();
+        let records = wal.replay().unwrap();
+        assert_eq!(records.len(), 2);
+        match &records[1] {
+            WalRecord::Save { revision, .. } => assert_eq!(revision.file.name, "retried"),
+            other => panic!("unexpected record: {:?}", other),
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn discards_tail_torn_inside_the_header() {
+        let (dir, wal) = temp_wal();
+        wal.append_delete("a", 1).unwrap();
+        let complete = wal.end_offset().unwrap();
+
+        // 操作の種別とIDの長さの一部だけが書き込まれた状態
+        let path = dir.join("wal.log");
+        let mut content = fs::read(&path).unwrap();
+        content.extend_from_slice(&[OP_SAVE, 0, 0]);
+        fs::write(&path, content).unwrap();
+
+        let records = wal.replay().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupted_records() {
+        let (dir, wal) = temp_wal();
+        wal.append_save("a", &stored(1, "a")).unwrap();
+
+        // 完全なレコードの破損は書き込み途中の終了と区別して読み込みを失敗させる
+        let path = dir.join("wal.log");
+        let mut content = fs::read(&path).unwrap();
+        content[0] = 9;
+        fs::write(&path, &content).unwrap();
+        let error = wal.replay().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // 版番号の無い削除レコード
+        let mut record = vec![OP_DELETE];
+        record.extend_from_slice(&1u32.to_be_bytes());
+        record.push(b'a');
+        record.extend_from_slice(&0u32.to_be_bytes());
+        fs::write(&path, record).unwrap();
+        let error = wal.replay().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncate_prefix_keeps_records_appended_after_the_offset() {
+        let (dir, wal) = temp_wal();
+        wal.append_save("a", &stored(1, "in snapshot")).unwrap();
+        let offset = wal.end_offset().unwrap();
+        wal.append_save("b", &stored(1, "after snapshot")).unwrap();
+
+        wal.truncate_prefix(offset).unwrap();
+        let records = wal.replay().unwrap();
+        assert_eq!(records.len(), 1);
+        match &records[0] {
+            WalRecord::Save { file_id, .. } => assert_eq!(file_id, "b"),
+            other => panic!("unexpected record: {:?}", other),
+        }
+
+        // 置き換えたファイルに追記できる
+        wal.append_delete("b", 1).unwrap();
+        assert_eq!(wal.replay().unwrap().len(), 2);
+        assert!(!dir.join("wal.log.tmp").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_log_replays_nothing() {
+        let (_dir, wal) = temp_wal();
+        assert!(wal.replay().unwrap().is_empty());
+        assert_eq!(wal.end_offset().unwrap(), 0);
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::server::class::{Class, File, Multiplicity};
+use crate::server::diagram_ext::{ValidationReport, Violation};
+
+// ファイルの意味的な整合性を検証し、見つかった問題を返す
+// - 関係の対象（target_class_id）がファイル内のクラスを指しているか
+// - Class.id が重複していないか
+// - 多重度の下限が上限を超えていないか
+// - クラス名が空でないか
+pub fn validate(file: &File) -> ValidationReport {
+    let mut violations = Vec::new();
+
+    // クラスIDと最初に現れた位置
+    let mut class_ids: HashMap<&str, usize> = HashMap::new();
+    for (index, class) in file.classes.iter().enumerate() {
+        let path = class_path(index, class);
+
+        if class.name.trim().is_empty() {
+            violations.push(violation(
+                "empty_class_name",
+                path.clone(),
+                class,
+                "Class name is empty".to_string(),
+            ));
+        }
+
+        if let Some(first) = class_ids.get(class.id.as_str()) {
+            violations.push(violation(
+                "duplicate_class_id",
+                path,
+                class,
+                format!(
+                    "Class id \"{}\" is already used by {}",
+                    class.id,
+                    class_path(*first, &file.classes[*first])
+                ),
+            ));
+        } else {
+            class_ids.insert(&class.id, index);
+        }
+    }
+
+    for (index, class) in file.classes.iter().enumerate() {
+        let Some(relations) = &class.relations else {
+            continue;
+        };
+
+        for (relation_index, relation) in relations.relation_infos.iter().enumerate() {
+            let path = format!("{}.relations[{}]", class_path(index, class), relation_index);
+
+            if !class_ids.contains_key(relation.target_class_id.as_str()) {
+                violations.push(violation(
+                    "dangling_relation",
+                    path.clone(),
+                    class,
+                    format!(
+                        "Relation target \"{}\" does not match any class in the file",
+                        relation.target_class_id
+                    ),
+                ));
+            }
+
+            for (name, multiplicity) in [
+                ("multiplicity_p", &relation.multiplicity_p),
+                ("multiplicity_c", &relation.multiplicity_c),
+            ] {
+                if let Some(Multiplicity {
+                    lower,
+                    upper: Some(upper),
+                }) = multiplicity
+                {
+                    if lower > upper {
+                        violations.push(violation(
+                            "invalid_multiplicity",
+                            format!("{}.{}", path, name),
+                            class,
+                            format!(
+                                "Lower bound {} is greater than upper bound {}",
+                                lower, upper
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    ValidationReport { violations }
+}
+
+// 名前が無いクラスは位置でパスを表す
+fn class_path(index: usize, class: &Class) -> String {
+    if class.name.trim().is_empty() {
+        format!("classes[{}]", index)
+    } else {
+        class.name.clone()
+    }
+}
+
+fn violation(code: &str, path: String, class: &Class, message: String) -> Violation {
+    Violation {
+        code: code.to_string(),
+        path,
+        class_id: class.id.clone(),
+        message,
+    }
+}
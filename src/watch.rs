@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::broadcast;
+
+use crate::server::class::{File, FileId};
+use crate::server::diagram_ext::{DiagramEvent, DiagramEventKind};
+
+// 購読者ごとに溜めておける未送信の変更の数（超えた場合は現在の内容を送り直す）
+const CHANNEL_CAPACITY: usize = 64;
+
+// ファイルIDごとの変更の配信先
+// 購読者がいないファイルのチャネルは次の配信時に取り除く
+#[derive(Debug, Default)]
+pub struct WatchHub {
+    channels: Mutex<HashMap<String, broadcast::Sender<DiagramEvent>>>,
+}
+
+impl WatchHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // ファイルの変更を購読
+    pub fn subscribe(&self, file_id: &str) -> broadcast::Receiver<DiagramEvent> {
+        let mut channels = self
+            .channels
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        channels
+            .entry(file_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    // 新しい版が保存されたことを購読者に送る
+    pub fn saved(&self, file_id: &str, revision: u64, file: &File) {
+        self.publish(
+            file_id,
+            event(
+                DiagramEventKind::Saved,
+                file_id,
+                revision,
+                Some(file.clone()),
+            ),
+        );
+    }
+
+    // ファイルが削除されたことを購読者に送る
+    pub fn deleted(&self, file_id: &str) {
+        self.publish(file_id, event(DiagramEventKind::Deleted, file_id, 0, None));
+    }
+
+    fn publish(&self, file_id: &str, event: DiagramEvent) {
+        let mut channels = self
+            .channels
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(sender) = channels.get(file_id) else {
+            return;
+        };
+        // 送信先が無い（全員が購読をやめた）場合はチャネルを取り除く
+        if sender.send(event).is_err() {
+            channels.remove(file_id);
+        }
+    }
+}
+
+pub fn event(
+    kind: DiagramEventKind,
+    file_id: &str,
+    revision: u64,
+    file: Option<File>,
+) -> DiagramEvent {
+    DiagramEvent {
+        kind: kind as i32,
+        file_id: Some(FileId {
+            id: file_id.to_string(),
+        }),
+        revision,
+        file,
+    }
+}